@@ -38,7 +38,9 @@ fn python_interpreter_startup_teardown_packed_resources(packed_resources: &[u8])
 
 pub fn bench_embedded_interpreter(c: &mut Criterion) {
     let (packed_resources, _) =
-        resolve_packed_resources().expect("failed to resolve packed resources");
+        resolve_packed_resources(false).expect("failed to resolve packed resources");
+    let (packed_resources_name_index, _) =
+        resolve_packed_resources(true).expect("failed to resolve packed resources");
 
     c.bench_function("pyembed.new_interpreter_plain", |b| {
         b.iter(|| python_interpreter_startup_teardown_plain().expect("Python interpreter run"))
@@ -50,6 +52,13 @@ pub fn bench_embedded_interpreter(c: &mut Criterion) {
                 .expect("Python interpreter run")
         })
     });
+
+    c.bench_function("pyembed.new_interpreter_packed_resources_name_index", |b| {
+        b.iter(|| {
+            python_interpreter_startup_teardown_packed_resources(&packed_resources_name_index)
+                .expect("Python interpreter run")
+        })
+    });
 }
 
 criterion_group!(benches, bench_embedded_interpreter);
@@ -38,6 +38,7 @@ fn python_resources_state_resolve_modules(
     for name in modules {
         state
             .resolve_importable_module(name, BytecodeOptimizationLevel::Zero)
+            .map_err(|e| anyhow!("error resolving module {}: {}", name, e))?
             .expect("failed to retrieve module");
     }
 
@@ -65,7 +66,9 @@ fn python_interpreter_import_all_modules(
 
 pub fn bench_oxidized_finder(c: &mut Criterion) {
     let (packed_resources, names) =
-        resolve_packed_resources().expect("failed to resolve packed resources");
+        resolve_packed_resources(false).expect("failed to resolve packed resources");
+    let (packed_resources_name_index, _) =
+        resolve_packed_resources(true).expect("failed to resolve packed resources");
     let importable_modules = filter_module_names(&names);
     println!(
         "{} bytes packed resources data for {} modules; {} importable",
@@ -80,6 +83,12 @@ pub fn bench_oxidized_finder(c: &mut Criterion) {
         .index_data(&packed_resources)
         .expect("failed to index resources data");
 
+    let mut resources_state_name_index =
+        PythonResourcesState::new_from_env().expect("failed to create resources state");
+    resources_state_name_index
+        .index_data(&packed_resources_name_index)
+        .expect("failed to index resources data");
+
     c.bench_function("python-packed-resources.parse", |b| {
         b.iter(|| {
             parse_packed_resources(&packed_resources).expect("failed to parse packed resources")
@@ -90,6 +99,16 @@ pub fn bench_oxidized_finder(c: &mut Criterion) {
         b.iter(|| python_resources_state_index(&packed_resources).expect("failed to index data"))
     });
 
+    c.bench_function(
+        "oxidized_importer.PythonResourcesState.index_data.name_index",
+        |b| {
+            b.iter(|| {
+                python_resources_state_index(&packed_resources_name_index)
+                    .expect("failed to index data")
+            })
+        },
+    );
+
     c.bench_function(
         "oxidized_importer.PythonResourcesState.resolve_modules",
         |b| {
@@ -100,6 +119,16 @@ pub fn bench_oxidized_finder(c: &mut Criterion) {
         },
     );
 
+    c.bench_function(
+        "oxidized_importer.PythonResourcesState.resolve_modules.name_index",
+        |b| {
+            b.iter(|| {
+                python_resources_state_resolve_modules(&resources_state_name_index, &names)
+                    .expect("failed to resolve modules")
+            })
+        },
+    );
+
     c.bench_function(
         "oxidized_importer.OxidizedFinder.in_memory.find_spec.all_modules",
         |b| {
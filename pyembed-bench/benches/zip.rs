@@ -58,7 +58,7 @@ pub fn bench_zip(c: &mut Criterion) {
         .expect("failed to create temp directory");
 
     let (packed_resources, names) =
-        resolve_packed_resources().expect("failed to resolve packed resources");
+        resolve_packed_resources(false).expect("failed to resolve packed resources");
     let importable_modules = filter_module_names(&names);
     println!(
         "{} bytes packed resources data for {} modules; {} importable",
@@ -134,7 +134,10 @@ pub fn get_interpreter_and_oxidized_finder<'interpreter, 'resources>(
     Ok((interp, finder))
 }
 
-pub fn resolve_packed_resources() -> Result<(Vec<u8>, Vec<String>)> {
+/// Resolve packed resources data for the standard library.
+///
+/// `name_index` controls whether the data contains a name index.
+pub fn resolve_packed_resources(name_index: bool) -> Result<(Vec<u8>, Vec<String>)> {
     let dist = get_python_distribution()?;
 
     let mut collector = PythonResourceCollector::new(
@@ -163,7 +166,8 @@ pub fn resolve_packed_resources() -> Result<(Vec<u8>, Vec<String>)> {
         .tempdir()?;
     let mut compiler = BytecodeCompiler::new(dist.python_exe_path(), temp_dir.path())?;
 
-    let compiled = collector.compile_resources(&mut compiler)?;
+    let mut compiled = collector.compile_resources(&mut compiler)?;
+    compiled.name_index = name_index;
 
     let mut buffer = Vec::<u8>::new();
     compiled.write_packed_resources(&mut buffer)?;
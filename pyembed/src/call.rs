@@ -0,0 +1,134 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Call Python functions from Rust.
+
+use pyo3::{
+    exceptions::{PySystemExit, PyValueError},
+    prelude::*,
+};
+
+#[cfg(feature = "serialization")]
+use {
+    crate::{
+        error::CallError,
+        serde_conversion::{from_python, to_python},
+    },
+    pyo3::types::{PyDict, PyList, PyString, PyTuple},
+    serde::{de::DeserializeOwned, Serialize},
+};
+
+/// Resolve a `module:function` entry point string to a Python object.
+///
+/// The function part can reference nested attributes. e.g. `pkg.mod:App.main`.
+pub(crate) fn resolve_entry_point<'p>(py: Python<'p>, target: &str) -> PyResult<&'p PyAny> {
+    let (module, attrs) = match target.split_once(':') {
+        Some((module, attrs)) if !module.trim().is_empty() && !attrs.trim().is_empty() => {
+            (module.trim(), attrs.trim())
+        }
+        _ => {
+            return Err(PyValueError::new_err(format!(
+                "entry point {} is not of the form module:function",
+                target
+            )))
+        }
+    };
+
+    let mut value: &PyAny = py.import(module)?;
+
+    for attr in attrs.split('.') {
+        value = value.getattr(attr)?;
+    }
+
+    Ok(value)
+}
+
+/// Convert a value to a process exit code, like `sys.exit()` does.
+fn exit_code(value: &PyAny) -> i32 {
+    if value.is_none() {
+        0
+    } else if let Ok(code) = value.extract::<i32>() {
+        code
+    } else {
+        eprintln!("{}", value);
+        1
+    }
+}
+
+/// Call an entry point without arguments and return a process exit code.
+///
+/// Uncaught exceptions are printed and result in exit code 1.
+pub(crate) fn run_entry_point(py: Python, target: &str) -> i32 {
+    match resolve_entry_point(py, target).and_then(|function| function.call0()) {
+        Ok(value) => exit_code(value),
+        Err(err) if err.is_instance_of::<PySystemExit>(py) => match err.value(py).getattr("code") {
+            Ok(code) => exit_code(code),
+            Err(_) => 1,
+        },
+        Err(err) => {
+            err.print(py);
+            1
+        }
+    }
+}
+
+/// Call a Python function with arguments converted from Rust values.
+///
+/// Values are converted between `serde` values and Python objects directly.
+/// See [crate::serde_conversion] for how types are mapped.
+#[cfg(feature = "serialization")]
+pub(crate) fn call_function<A, K, R>(
+    py: Python,
+    target: &str,
+    args: &A,
+    kwargs: Option<&K>,
+) -> Result<R, CallError>
+where
+    A: Serialize + ?Sized,
+    K: Serialize + ?Sized,
+    R: DeserializeOwned,
+{
+    let function = resolve_entry_point(py, target)?;
+
+    let args = to_python(py, args)
+        .map_err(|e| CallError::Serialize(e.to_string()))?
+        .into_ref(py);
+    let args = if let Ok(args) = args.cast_as::<PyTuple>() {
+        args
+    } else if let Ok(args) = args.cast_as::<PyList>() {
+        PyTuple::new(py, args.iter().collect::<Vec<_>>())
+    } else {
+        return Err(CallError::Serialize(
+            "arguments must serialize to a sequence".to_string(),
+        ));
+    };
+
+    let kwargs = if let Some(kwargs) = kwargs {
+        let kwargs = to_python(py, kwargs)
+            .map_err(|e| CallError::Serialize(e.to_string()))?
+            .into_ref(py);
+
+        match kwargs.cast_as::<PyDict>() {
+            Ok(kwargs)
+                if kwargs
+                    .keys()
+                    .iter()
+                    .all(|k| k.is_instance_of::<PyString>().unwrap_or(false)) =>
+            {
+                Some(kwargs)
+            }
+            _ => {
+                return Err(CallError::Serialize(
+                    "keyword arguments must serialize to a map having string keys".to_string(),
+                ))
+            }
+        }
+    } else {
+        None
+    };
+
+    let result = function.call(args, kwargs)?;
+
+    from_python(result).map_err(|e| CallError::Deserialize(e.to_string()))
+}
@@ -5,7 +5,7 @@
 //! Data structures for configuring a Python interpreter.
 
 use {
-    crate::NewInterpreterError,
+    crate::{pyalloc::DEFAULT_ALLOCATION_TRACE_CAPACITY, NewInterpreterError},
     oxidized_importer::{PackedResourcesSource, PythonResourcesState},
     pyo3::ffi as pyffi,
     python_packaging::interpreter::{
@@ -110,6 +110,31 @@ pub struct OxidizedPythonInterpreterConfig<'a> {
     /// `$ORIGIN` expanded to the resolved value of [Self::origin].
     pub interpreter_config: PythonInterpreterConfig,
 
+    /// Directory to write crash reports to.
+    ///
+    /// This complements [PythonInterpreterConfig::fault_handler], which prints
+    /// Python tracebacks to stderr when the process crashes. Crash reports also
+    /// capture the Python traceback of every thread. In addition, they record
+    /// the signal, the registers of the crashing thread, the threads of the
+    /// process, raw stack memory of each thread, and loaded modules. Stack
+    /// memory of threads other than the crashing one is only available for
+    /// threads that are blocked in a system call.
+    ///
+    /// Only supported on Linux. Has no effect on other platforms.
+    ///
+    /// Default value: [None]
+    ///
+    /// [Self::resolve()] behavior: the token `$ORIGIN` is expanded to the
+    /// resolved value of [Self::origin].
+    ///
+    /// Interpreter initialization behavior: if set, the directory is created
+    /// and a handler for fatal signals is installed after the interpreter is
+    /// initialized. When a fatal signal is received, the handler writes a
+    /// `crash-<pid>-<tid>-<time>.txt` file to the directory then defers to the
+    /// previously installed handler. e.g. `faulthandler`'s. The handler remains
+    /// installed for the lifetime of the process.
+    pub crash_report_dir: Option<PathBuf>,
+
     /// Memory allocator backend to use.
     ///
     /// Default value: [MemoryAllocatorBackend::Default].
@@ -121,6 +146,10 @@ pub struct OxidizedPythonInterpreterConfig<'a> {
     /// [Self::allocator_obj], and [Self::allocator_pymalloc_arena]. If a custom
     /// allocator backend is defined but all the `allocator_*` flags are [false],
     /// the allocator won't be used.
+    ///
+    /// If [MemoryAllocatorBackend::Tracing], the allocator installed wraps
+    /// [Self::allocator_tracing_backend] and records allocation events. See
+    /// [crate::pyalloc::PythonMemoryAllocator::tracing()].
     pub allocator_backend: MemoryAllocatorBackend,
 
     /// Whether to install the custom allocator for the `raw` memory domain.
@@ -189,6 +218,52 @@ pub struct OxidizedPythonInterpreterConfig<'a> {
     /// (see other `allocator_*` fields).
     pub allocator_debug: bool,
 
+    /// Whether to record statistics about memory allocator usage.
+    ///
+    /// Default value: [false]
+    ///
+    /// Interpreter initialization behavior: after custom allocators are installed
+    /// and before debug hooks are set up, the allocators of the `raw`, `mem`, and
+    /// `obj` domains are wrapped by an allocator counting bytes allocated and
+    /// freed, peak usage, and live allocations. Statistics can be obtained via
+    /// `oxidized_importer.allocator_stats()` from Python and
+    /// [crate::allocator_stats()] from Rust.
+    ///
+    /// This works with any memory allocator backend, including the default.
+    /// Recording statistics adds a small amount of overhead to each allocation.
+    pub allocator_stats: bool,
+
+    /// Whether to print memory allocator statistics when the interpreter is finalized.
+    ///
+    /// Default value: [false]
+    ///
+    /// Interpreter initialization behavior: implies [Self::allocator_stats]. A
+    /// report is printed to stderr at the end of interpreter finalization.
+    /// Allocations that are still live at that point were leaked by the
+    /// interpreter or by extension modules.
+    pub allocator_stats_report: bool,
+
+    /// Memory allocator backend wrapped by the tracing memory allocator.
+    ///
+    /// Default value: [MemoryAllocatorBackend::Default].
+    ///
+    /// Interpreter initialization behavior: if [Self::allocator_backend] is
+    /// [MemoryAllocatorBackend::Tracing], allocation requests are served by this
+    /// backend. [MemoryAllocatorBackend::Default] serves requests with Python's
+    /// default allocators. [MemoryAllocatorBackend::Tracing] is not allowed.
+    pub allocator_tracing_backend: MemoryAllocatorBackend,
+
+    /// Maximum number of allocation events retained by the tracing memory allocator.
+    ///
+    /// Default value: `65536`
+    ///
+    /// Interpreter initialization behavior: if [Self::allocator_backend] is
+    /// [MemoryAllocatorBackend::Tracing], allocation events are recorded in a
+    /// ring buffer of this size. When it is full, the oldest events are discarded.
+    /// Events can be obtained via `oxidized_importer.allocation_trace()` from
+    /// Python and [crate::allocation_trace()] from Rust.
+    pub allocator_tracing_capacity: usize,
+
     /// Whether to automatically set missing "path configuration" fields.
     ///
     /// If `true`, various path configuration
@@ -280,16 +355,64 @@ pub struct OxidizedPythonInterpreterConfig<'a> {
     /// current working directory following the operating system's standard
     /// path expansion behavior.
     ///
+    /// Sources are layered in order. A resource is merged into resources having
+    /// the same name from earlier sources, its set fields taking precedence.
+    /// Tombstone resources hide resources having the same name from earlier
+    /// sources. This allows e.g. an overlay file loaded via
+    /// [PackedResourcesSource::OptionalMemoryMappedPath] to patch resources
+    /// embedded in the binary.
+    ///
     /// Default value: `vec![]`
     ///
-    /// [Self::resolve()] behavior: [PackedResourcesSource::MemoryMappedPath] members
-    /// have the special string `$ORIGIN` expanded to the string value that
-    /// [Self::origin] resolves to.
+    /// [Self::resolve()] behavior: [PackedResourcesSource::MemoryMappedPath] and
+    /// [PackedResourcesSource::OptionalMemoryMappedPath] members have the special
+    /// string `$ORIGIN` expanded to the string value that [Self::origin] resolves to.
     ///
     /// This field is ignored during serialization.
     #[cfg_attr(feature = "serialization", serde(skip))]
     pub packed_resources: Vec<PackedResourcesSource<'a>>,
 
+    /// Ed25519 public key that packed resources data must be signed with.
+    ///
+    /// The value is the raw 32 byte public key.
+    ///
+    /// When set, every entry in [Self::packed_resources] must have an integrity
+    /// trailer carrying SHA-256 digests of its content and an Ed25519 signature
+    /// over those digests made with the corresponding private key. See the
+    /// ``python-packed-resources`` crate for how to produce such data.
+    ///
+    /// Default value: [None]
+    ///
+    /// Interpreter initialization behavior: if set, packed resources data is
+    /// verified before it is indexed. Interpreter initialization fails if
+    /// data is unsigned, if the signature is invalid, or if the data doesn't
+    /// match its digests.
+    pub packed_resources_public_key: Option<Vec<u8>>,
+
+    /// Directory to cache bytecode of modules loaded from filesystem source.
+    ///
+    /// Applies to modules indexed by `oxidized_importer` whose source is
+    /// loaded from a path relative to [Self::origin] and which don't have
+    /// bytecode. Without a cache, the source of these modules is compiled on
+    /// every import.
+    ///
+    /// Cached bytecode is stored in hash-based `.pyc` files (see PEP 552) at
+    /// paths mirroring the relative path of the module source. Entries are
+    /// validated against the hash of the current source and are rewritten
+    /// if stale. Like CPython, nothing is written if `sys.dont_write_bytecode`
+    /// is set and errors writing cache files are ignored.
+    ///
+    /// Default value: [None]
+    ///
+    /// [Self::resolve()] behavior: the token `$ORIGIN` is expanded to the
+    /// resolved value of [Self::origin].
+    ///
+    /// Interpreter initialization behavior: if set, bytecode for modules
+    /// loaded from filesystem source is read from and written to this
+    /// directory. This setting is ignored if [Self::oxidized_importer] is
+    /// `false`.
+    pub bytecode_cache_dir: Option<PathBuf>,
+
     /// Extra extension modules to make available to the interpreter.
     ///
     /// The values will effectively be passed to ``PyImport_ExtendInitTab()``.
@@ -421,6 +544,37 @@ pub struct OxidizedPythonInterpreterConfig<'a> {
     ///
     /// Default value: [None]
     pub write_modules_directory_env: Option<String>,
+
+    /// Environment variable holding the directory to write an import trace file.
+    ///
+    /// If this value is set and the environment it refers to is set, import
+    /// tracing is enabled on the `OxidizedFinder` meta path importer and on
+    /// interpreter shutdown, we will write an `import-trace-<random>` file to
+    /// the directory specified. The file records which packed resources source
+    /// served each module, which package resources and distribution resources
+    /// were read, and which module lookups failed.
+    ///
+    /// Traces from multiple runs can be combined with `pyoxidizer packed-resources-usage`
+    /// to find packaged resources that are never used.
+    ///
+    /// Has no effect unless `oxidized_importer` is enabled.
+    ///
+    /// Default value: [None]
+    pub write_import_trace_directory_env: Option<String>,
+
+    /// Python function to call when the interpreter is run.
+    ///
+    /// The value has the form `module:function`, as used by `console_scripts`
+    /// entry points. e.g. `pkg.mod:main`. The function may be an attribute of
+    /// an object in the module. e.g. `pkg.mod:App.main`.
+    ///
+    /// If set, [crate::MainPythonInterpreter::run()] imports the module and calls
+    /// the function without arguments instead of evaluating what is configured by
+    /// `run_command`, `run_module`, or `run_filename`. The return value is
+    /// converted to an exit code the same way `sys.exit()` would.
+    ///
+    /// Default value: [None]
+    pub entry_point: Option<String>,
 }
 
 impl<'a> Default for OxidizedPythonInterpreterConfig<'a> {
@@ -432,6 +586,7 @@ impl<'a> Default for OxidizedPythonInterpreterConfig<'a> {
                 profile: PythonInterpreterProfile::Python,
                 ..PythonInterpreterConfig::default()
             },
+            crash_report_dir: None,
             allocator_backend: MemoryAllocatorBackend::Default,
             // We set to true by default so any installed custom backend
             // takes effect.
@@ -440,10 +595,16 @@ impl<'a> Default for OxidizedPythonInterpreterConfig<'a> {
             allocator_obj: false,
             allocator_pymalloc_arena: false,
             allocator_debug: false,
+            allocator_stats: false,
+            allocator_stats_report: false,
+            allocator_tracing_backend: MemoryAllocatorBackend::Default,
+            allocator_tracing_capacity: DEFAULT_ALLOCATION_TRACE_CAPACITY,
             set_missing_path_configuration: true,
             oxidized_importer: false,
             filesystem_importer: true,
             packed_resources: vec![],
+            packed_resources_public_key: None,
+            bytecode_cache_dir: None,
             extra_extension_modules: None,
             argv: None,
             argvb: false,
@@ -454,6 +615,8 @@ impl<'a> Default for OxidizedPythonInterpreterConfig<'a> {
             terminfo_resolution: TerminfoResolution::Dynamic,
             tcl_library: None,
             write_modules_directory_env: None,
+            write_import_trace_directory_env: None,
+            entry_point: None,
         }
     }
 }
@@ -506,6 +669,11 @@ impl<'a> OxidizedPythonInterpreterConfig<'a> {
                         p.display().to_string().replace("$ORIGIN", &origin_string),
                     ))
                 }
+                PackedResourcesSource::OptionalMemoryMappedPath(p) => {
+                    PackedResourcesSource::OptionalMemoryMappedPath(PathBuf::from(
+                        p.display().to_string().replace("$ORIGIN", &origin_string),
+                    ))
+                }
             })
             .collect::<Vec<_>>();
 
@@ -526,6 +694,16 @@ impl<'a> OxidizedPythonInterpreterConfig<'a> {
             .as_ref()
             .map(|x| PathBuf::from(x.display().to_string().replace("$ORIGIN", &origin_string)));
 
+        let bytecode_cache_dir = self
+            .bytecode_cache_dir
+            .as_ref()
+            .map(|x| PathBuf::from(x.display().to_string().replace("$ORIGIN", &origin_string)));
+
+        let crash_report_dir = self
+            .crash_report_dir
+            .as_ref()
+            .map(|x| PathBuf::from(x.display().to_string().replace("$ORIGIN", &origin_string)));
+
         Ok(ResolvedOxidizedPythonInterpreterConfig {
             inner: Self {
                 exe: Some(exe),
@@ -534,8 +712,10 @@ impl<'a> OxidizedPythonInterpreterConfig<'a> {
                     module_search_paths,
                     ..self.interpreter_config
                 },
+                crash_report_dir,
                 argv,
                 packed_resources,
+                bytecode_cache_dir,
                 tcl_library,
                 ..self
             },
@@ -614,6 +794,14 @@ impl<'a, 'config: 'a> TryFrom<&ResolvedOxidizedPythonInterpreterConfig<'config>>
         let mut state = Self::default();
         state.set_current_exe(config.exe().to_path_buf());
         state.set_origin(config.origin().to_path_buf());
+        state.set_packed_resources_public_key(config.packed_resources_public_key.clone());
+        state.set_bytecode_cache_dir(config.bytecode_cache_dir.clone());
+
+        if let Some(key) = &config.write_import_trace_directory_env {
+            if std::env::var_os(key).is_some() {
+                state.set_import_trace_enabled(true);
+            }
+        }
 
         for source in &config.packed_resources {
             match source {
@@ -627,6 +815,13 @@ impl<'a, 'config: 'a> TryFrom<&ResolvedOxidizedPythonInterpreterConfig<'config>>
                         .index_path_memory_mapped(path)
                         .map_err(NewInterpreterError::Dynamic)?;
                 }
+                PackedResourcesSource::OptionalMemoryMappedPath(path) => {
+                    if path.exists() {
+                        state
+                            .index_path_memory_mapped(path)
+                            .map_err(NewInterpreterError::Dynamic)?;
+                    }
+                }
             }
         }
 
@@ -0,0 +1,709 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Write reports when the process crashes.
+
+A handler is installed for the fatal signals `faulthandler` handles. When one
+is received, a plain text report is written to a file in a configured
+directory. The report contains:
+
+* The signal and the faulting address.
+* The registers of the crashing thread.
+* The Python traceback of every thread, as written by `faulthandler`.
+* The threads of the process.
+* Raw memory from the stack of every thread.
+* The memory mappings of the process, including loaded libraries.
+
+The stack pointer of the crashing thread comes from the signal context. Those
+of other threads are read from `/proc/self/task/<tid>/syscall`, which only
+has them for threads blocked in the kernel, e.g. waiting on a lock or for
+I/O. Other threads aren't stopped while the report is written, so their
+stack memory may be changing as it is read.
+
+The previous signal handler is then restored and the signal raised again. So
+`faulthandler`, if enabled, and the default action (e.g. dumping core) still
+run.
+
+Code in the handler is restricted to async-signal-safe functions. So files
+are written with raw system calls and no memory is allocated.
+*/
+
+use {
+    core::ffi::c_void,
+    once_cell::sync::OnceCell,
+    pyo3::ffi as pyffi,
+    std::{
+        os::{raw::c_char, unix::ffi::OsStrExt},
+        path::Path,
+    },
+};
+
+extern "C" {
+    /// Write the traceback of all Python threads to a file descriptor.
+    ///
+    /// This is what `faulthandler` uses. It is exported by libpython but not
+    /// declared in a public header.
+    fn _Py_DumpTracebackThreads(
+        fd: libc::c_int,
+        interp: *mut pyffi::PyInterpreterState,
+        current_tstate: *mut pyffi::PyThreadState,
+    ) -> *const c_char;
+}
+
+/// Signals reports are written for. Same as those handled by `faulthandler`.
+const FATAL_SIGNALS: [(libc::c_int, &str); 5] = [
+    (libc::SIGBUS, "SIGBUS"),
+    (libc::SIGILL, "SIGILL"),
+    (libc::SIGFPE, "SIGFPE"),
+    (libc::SIGABRT, "SIGABRT"),
+    (libc::SIGSEGV, "SIGSEGV"),
+];
+
+/// Number of bytes of each thread's stack written to a report.
+const STACK_DUMP_SIZE: usize = 4096;
+
+/// Maximum length of a report path.
+const MAX_PATH: usize = 4096;
+
+/// Size of the alternate signal stack, so stack overflows can be reported.
+const ALTERNATE_STACK_SIZE: usize = 65536;
+
+struct CrashHandler {
+    /// Directory reports are written to.
+    directory: Vec<u8>,
+
+    /// Signal actions in place before ours were installed.
+    previous: Vec<(libc::c_int, libc::sigaction)>,
+}
+
+static CRASH_HANDLER: OnceCell<CrashHandler> = OnceCell::new();
+
+/// Writes a report to a file descriptor.
+///
+/// Errors are ignored: there is nothing useful to do about them when crashing.
+struct ReportWriter {
+    fd: libc::c_int,
+}
+
+impl ReportWriter {
+    fn write(&self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let res = unsafe { libc::write(self.fd, data.as_ptr() as *const c_void, data.len()) };
+
+            if res > 0 {
+                data = &data[res as usize..];
+            } else if res < 0 && std::io::Error::last_os_error().raw_os_error() == Some(libc::EINTR)
+            {
+                continue;
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn write_str(&self, s: &str) {
+        self.write(s.as_bytes())
+    }
+
+    fn write_decimal(&self, value: u64) {
+        let mut buf = [0u8; 20];
+        self.write(format_decimal(&mut buf, value));
+    }
+
+    fn write_hex(&self, value: u64) {
+        let mut buf = *b"0x0000000000000000";
+
+        for i in 0..16 {
+            buf[17 - i] = b"0123456789abcdef"[((value >> (i * 4)) & 0xf) as usize];
+        }
+
+        self.write(&buf);
+    }
+
+    /// Copy the content of a file.
+    fn copy_file(&self, path: &[u8]) {
+        let fd = unsafe {
+            libc::open(
+                path.as_ptr() as *const c_char,
+                libc::O_RDONLY | libc::O_CLOEXEC,
+            )
+        };
+        if fd < 0 {
+            self.write_str("<unavailable>\n");
+            return;
+        }
+
+        let mut buf = [0u8; 4096];
+        loop {
+            let res = unsafe { libc::read(fd, buf.as_mut_ptr() as *mut c_void, buf.len()) };
+            if res <= 0 {
+                break;
+            }
+
+            self.write(&buf[0..res as usize]);
+        }
+
+        unsafe { libc::close(fd) };
+    }
+}
+
+/// Write registers of the crashing thread. Returns the stack pointer.
+#[cfg(target_arch = "x86_64")]
+unsafe fn write_registers(w: &ReportWriter, context: *mut c_void) -> Option<u64> {
+    let context = &*(context as *const libc::ucontext_t);
+    let gregs = &context.uc_mcontext.gregs;
+
+    for (name, index) in [
+        ("rax", libc::REG_RAX),
+        ("rbx", libc::REG_RBX),
+        ("rcx", libc::REG_RCX),
+        ("rdx", libc::REG_RDX),
+        ("rsi", libc::REG_RSI),
+        ("rdi", libc::REG_RDI),
+        ("rbp", libc::REG_RBP),
+        ("rsp", libc::REG_RSP),
+        ("r8", libc::REG_R8),
+        ("r9", libc::REG_R9),
+        ("r10", libc::REG_R10),
+        ("r11", libc::REG_R11),
+        ("r12", libc::REG_R12),
+        ("r13", libc::REG_R13),
+        ("r14", libc::REG_R14),
+        ("r15", libc::REG_R15),
+        ("rip", libc::REG_RIP),
+        ("eflags", libc::REG_EFL),
+    ] {
+        w.write_str(name);
+        w.write_str(": ");
+        w.write_hex(gregs[index as usize] as u64);
+        w.write_str("\n");
+    }
+
+    Some(gregs[libc::REG_RSP as usize] as u64)
+}
+
+/// Write registers of the crashing thread. Returns the stack pointer.
+#[cfg(target_arch = "aarch64")]
+unsafe fn write_registers(w: &ReportWriter, context: *mut c_void) -> Option<u64> {
+    let context = &*(context as *const libc::ucontext_t);
+    let mcontext = &context.uc_mcontext;
+
+    for (i, value) in mcontext.regs.iter().enumerate() {
+        w.write_str("x");
+        w.write_decimal(i as u64);
+        w.write_str(": ");
+        w.write_hex(*value);
+        w.write_str("\n");
+    }
+
+    for (name, value) in [
+        ("sp", mcontext.sp),
+        ("pc", mcontext.pc),
+        ("pstate", mcontext.pstate),
+    ] {
+        w.write_str(name);
+        w.write_str(": ");
+        w.write_hex(value);
+        w.write_str("\n");
+    }
+
+    Some(mcontext.sp)
+}
+
+/// Write registers of the crashing thread. Returns the stack pointer.
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+unsafe fn write_registers(w: &ReportWriter, _context: *mut c_void) -> Option<u64> {
+    w.write_str("<unavailable on this architecture>\n");
+
+    None
+}
+
+/// Call a function with the id of each thread in the process.
+///
+/// Returns false if threads couldn't be enumerated.
+fn for_each_thread(mut f: impl FnMut(&[u8])) -> bool {
+    let fd = unsafe {
+        libc::open(
+            c"/proc/self/task".as_ptr(),
+            libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC,
+        )
+    };
+    if fd < 0 {
+        return false;
+    }
+
+    // opendir() allocates. So directory entries are read via getdents64, which
+    // fills the buffer with `struct linux_dirent64` records.
+    let mut buf = [0u8; 4096];
+    loop {
+        let res = unsafe { libc::syscall(libc::SYS_getdents64, fd, buf.as_mut_ptr(), buf.len()) };
+        if res <= 0 {
+            break;
+        }
+
+        let mut offset = 0;
+        while offset < res as usize {
+            let record_length = u16::from_ne_bytes([buf[offset + 16], buf[offset + 17]]) as usize;
+            let name = &buf[offset + 19..offset + record_length];
+            let name = &name[0..name.iter().position(|c| *c == 0).unwrap_or(name.len())];
+
+            if !name.is_empty() && name[0] != b'.' {
+                f(name);
+            }
+
+            offset += record_length;
+        }
+    }
+
+    unsafe { libc::close(fd) };
+
+    true
+}
+
+/// Build the NUL terminated path of a file in a thread's `/proc` directory.
+fn task_path<'a>(path: &'a mut [u8; 64], tid: &[u8], file: &[u8]) -> &'a [u8] {
+    let mut len = 0;
+    for part in [&b"/proc/self/task/"[..], tid, &b"/"[..], file, &b"\0"[..]] {
+        if len + part.len() > path.len() {
+            break;
+        }
+        path[len..len + part.len()].copy_from_slice(part);
+        len += part.len();
+    }
+
+    &path[0..len]
+}
+
+/// Write the id and name of each thread in the process.
+fn write_threads(w: &ReportWriter) {
+    let found = for_each_thread(|tid| {
+        let mut path = [0u8; 64];
+
+        w.write(tid);
+        w.write_str(" ");
+        w.copy_file(task_path(&mut path, tid, b"comm"));
+    });
+
+    if !found {
+        w.write_str("<unavailable>\n");
+    }
+}
+
+/// Obtain the stack pointer of a thread that isn't the crashing one.
+///
+/// `/proc/self/task/<tid>/syscall` ends with the stack pointer and program
+/// counter, unless it is `running` because the thread is in user space.
+fn thread_stack_pointer(tid: &[u8]) -> Option<u64> {
+    let mut path = [0u8; 64];
+    let fd = unsafe {
+        libc::open(
+            task_path(&mut path, tid, b"syscall").as_ptr() as *const c_char,
+            libc::O_RDONLY | libc::O_CLOEXEC,
+        )
+    };
+    if fd < 0 {
+        return None;
+    }
+
+    let mut buf = [0u8; 256];
+    let res = unsafe { libc::read(fd, buf.as_mut_ptr() as *mut c_void, buf.len()) };
+    unsafe { libc::close(fd) };
+
+    if res <= 0 {
+        return None;
+    }
+
+    let mut fields = buf[0..res as usize]
+        .split(|c| c.is_ascii_whitespace())
+        .filter(|field| !field.is_empty())
+        .rev();
+
+    let _pc = fields.next()?;
+    let sp = fields.next()?.strip_prefix(b"0x")?;
+
+    let mut value = 0u64;
+    for c in sp {
+        let digit = (*c as char).to_digit(16)?;
+        value = value.checked_mul(16)?.checked_add(digit as u64)?;
+    }
+
+    Some(value)
+}
+
+/// Write memory from the stack of each thread.
+///
+/// `crashing_sp` is the stack pointer of the crashing thread, from its
+/// registers.
+fn write_stacks(w: &ReportWriter, crashing_tid: u64, crashing_sp: Option<u64>) {
+    let mut crashing = [0u8; 20];
+    let crashing = format_decimal(&mut crashing, crashing_tid);
+
+    let found = for_each_thread(|tid| {
+        w.write_str("Thread ");
+        w.write(tid);
+        w.write_str(":\n");
+
+        let sp = if tid == crashing {
+            crashing_sp
+        } else {
+            thread_stack_pointer(tid)
+        };
+
+        match sp {
+            Some(sp) => write_stack(w, sp),
+            None => w.write_str("<unavailable>\n"),
+        }
+    });
+
+    if !found {
+        w.write_str("<unavailable>\n");
+    }
+}
+
+/// Write memory from a thread's stack.
+fn write_stack(w: &ReportWriter, sp: u64) {
+    let mut buf = [0u8; STACK_DUMP_SIZE];
+
+    // The stack may end before the requested range does. process_vm_readv()
+    // reads what it can instead of faulting.
+    let local = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut c_void,
+        iov_len: buf.len(),
+    };
+    let remote = libc::iovec {
+        iov_base: sp as *mut c_void,
+        iov_len: buf.len(),
+    };
+
+    let res = unsafe { libc::process_vm_readv(libc::getpid(), &local, 1, &remote, 1, 0) };
+    if res <= 0 {
+        w.write_str("<unavailable>\n");
+        return;
+    }
+
+    for (i, line) in buf[0..res as usize].chunks(32).enumerate() {
+        w.write_hex(sp + i as u64 * 32);
+        w.write_str(":");
+
+        for word in line.chunks(8) {
+            let mut value = [0u8; 8];
+            value[0..word.len()].copy_from_slice(word);
+
+            w.write_str(" ");
+            w.write_hex(u64::from_ne_bytes(value));
+        }
+
+        w.write_str("\n");
+    }
+}
+
+/// Maximum number of suffixes tried when a report file name is taken.
+const MAX_REPORT_SUFFIX: u64 = 100;
+
+/// Open a new report file in the report directory.
+///
+/// The file is named after the process, the crashing thread and the time. If
+/// that name is taken, e.g. by another thread crashing within the same second,
+/// a numeric suffix is appended.
+///
+/// Returns the file descriptor and the length of the path written to `path`.
+fn open_report(directory: &[u8], path: &mut [u8; MAX_PATH]) -> Option<(libc::c_int, usize)> {
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_REALTIME, &mut now) };
+
+    let mut pid = [0u8; 20];
+    let pid = format_decimal(&mut pid, unsafe { libc::getpid() } as u64);
+    let mut tid = [0u8; 20];
+    let tid = format_decimal(&mut tid, unsafe { libc::syscall(libc::SYS_gettid) } as u64);
+    let mut time = [0u8; 20];
+    let time = format_decimal(&mut time, now.tv_sec as u64);
+
+    for attempt in 0..MAX_REPORT_SUFFIX {
+        let mut suffix = [0u8; 20];
+        let suffix = format_decimal(&mut suffix, attempt);
+
+        let mut len = 0;
+        for part in [
+            directory,
+            &b"/crash-"[..],
+            pid,
+            &b"-"[..],
+            tid,
+            &b"-"[..],
+            time,
+            if attempt == 0 { &b""[..] } else { &b"-"[..] },
+            if attempt == 0 { &b""[..] } else { suffix },
+            &b".txt\0"[..],
+        ] {
+            if len + part.len() > path.len() {
+                return None;
+            }
+            path[len..len + part.len()].copy_from_slice(part);
+            len += part.len();
+        }
+
+        let fd = unsafe {
+            libc::open(
+                path.as_ptr() as *const c_char,
+                libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL | libc::O_CLOEXEC,
+                0o600,
+            )
+        };
+
+        if fd >= 0 {
+            return Some((fd, len - 1));
+        } else if std::io::Error::last_os_error().raw_os_error() != Some(libc::EEXIST) {
+            return None;
+        }
+    }
+
+    None
+}
+
+/// Format an integer without allocating.
+fn format_decimal(buf: &mut [u8; 20], mut value: u64) -> &[u8] {
+    let mut pos = buf.len();
+
+    loop {
+        pos -= 1;
+        buf[pos] = b'0' + (value % 10) as u8;
+        value /= 10;
+
+        if value == 0 {
+            break;
+        }
+    }
+
+    &buf[pos..]
+}
+
+unsafe fn write_report(
+    handler: &CrashHandler,
+    signal: libc::c_int,
+    info: *mut libc::siginfo_t,
+    context: *mut c_void,
+) {
+    let mut path = [0u8; MAX_PATH];
+    let (fd, path_len) = match open_report(&handler.directory, &mut path) {
+        Some(res) => res,
+        None => return,
+    };
+
+    let w = ReportWriter { fd };
+
+    w.write_str("Fatal signal: ");
+    w.write_decimal(signal as u64);
+    if let Some((_, name)) = FATAL_SIGNALS.iter().find(|(s, _)| *s == signal) {
+        w.write_str(" (");
+        w.write_str(name);
+        w.write_str(")");
+    }
+    w.write_str("\n");
+
+    if !info.is_null() {
+        w.write_str("Signal code: ");
+        w.write_decimal((*info).si_code as u64);
+        w.write_str("\nFault address: ");
+        w.write_hex((*info).si_addr() as u64);
+        w.write_str("\n");
+    }
+
+    let tid = libc::syscall(libc::SYS_gettid) as u64;
+
+    w.write_str("Process: ");
+    w.write_decimal(libc::getpid() as u64);
+    w.write_str("\nThread: ");
+    w.write_decimal(tid);
+    w.write_str("\n\nRegisters:\n");
+
+    let sp = if context.is_null() {
+        w.write_str("<unavailable>\n");
+        None
+    } else {
+        write_registers(&w, context)
+    };
+
+    w.write_str("\nPython tracebacks:\n");
+    let err = _Py_DumpTracebackThreads(
+        fd,
+        std::ptr::null_mut(),
+        pyffi::PyGILState_GetThisThreadState(),
+    );
+    if !err.is_null() {
+        w.write(std::ffi::CStr::from_ptr(err).to_bytes());
+        w.write_str("\n");
+    }
+
+    w.write_str("\nThreads:\n");
+    write_threads(&w);
+
+    w.write_str("\nStacks:\n");
+    write_stacks(&w, tid, sp);
+
+    w.write_str("\nLoaded modules:\n");
+    w.copy_file(b"/proc/self/maps\0");
+
+    libc::close(fd);
+
+    let stderr = ReportWriter {
+        fd: libc::STDERR_FILENO,
+    };
+    stderr.write_str("Crash report written to ");
+    stderr.write(&path[0..path_len]);
+    stderr.write_str("\n");
+}
+
+extern "C" fn handle_fatal_signal(
+    signal: libc::c_int,
+    info: *mut libc::siginfo_t,
+    context: *mut c_void,
+) {
+    if let Some(handler) = CRASH_HANDLER.get() {
+        // Restore the previous handlers first. If writing the report crashes,
+        // the process dies like it would have without us.
+        for (signal, action) in &handler.previous {
+            unsafe { libc::sigaction(*signal, action, std::ptr::null_mut()) };
+        }
+
+        unsafe { write_report(handler, signal, info, context) };
+    }
+
+    // The signal is blocked while we run. So it is delivered to the previous
+    // handler when we return. Signals caused by a fault would be raised again
+    // anyway. But signals sent by kill() wouldn't.
+    unsafe { libc::raise(signal) };
+}
+
+/// Install an alternate signal stack for the current thread, unless it has a
+/// large enough one.
+///
+/// Without one, the handler can't run when the stack overflowed. The Rust
+/// runtime installs one for its own stack overflow detection, but it is too
+/// small to write a report.
+fn install_alternate_stack() {
+    unsafe {
+        let mut current = std::mem::zeroed::<libc::stack_t>();
+        if libc::sigaltstack(std::ptr::null(), &mut current) != 0
+            || (current.ss_flags & libc::SS_DISABLE == 0 && current.ss_size >= ALTERNATE_STACK_SIZE)
+        {
+            return;
+        }
+
+        // The stack must outlive the thread. So it is leaked.
+        let stack = Box::leak(vec![0u8; ALTERNATE_STACK_SIZE].into_boxed_slice());
+
+        let stack = libc::stack_t {
+            ss_sp: stack.as_mut_ptr() as *mut c_void,
+            ss_flags: 0,
+            ss_size: stack.len(),
+        };
+
+        libc::sigaltstack(&stack, std::ptr::null_mut());
+    }
+}
+
+/// Install a handler writing crash reports to files in a directory.
+///
+/// The directory is created if it doesn't exist. The handler should be
+/// installed after Python's `faulthandler` is enabled, so it runs first and
+/// `faulthandler` runs afterwards.
+///
+/// The handler can only be installed once per process. Subsequent calls have
+/// no effect.
+pub(crate) fn install_crash_handler(directory: &Path) -> Result<(), String> {
+    if CRASH_HANDLER.get().is_some() {
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(directory).map_err(|e| {
+        format!(
+            "unable to create crash report directory {}: {}",
+            directory.display(),
+            e
+        )
+    })?;
+
+    let directory = directory.as_os_str().as_bytes().to_vec();
+
+    // Leave room for the file name.
+    if directory.len() > MAX_PATH - 64 {
+        return Err("crash report directory path is too long".to_string());
+    }
+
+    let mut previous = vec![];
+    for (signal, _) in FATAL_SIGNALS {
+        let mut action = unsafe { std::mem::zeroed::<libc::sigaction>() };
+        unsafe { libc::sigaction(signal, std::ptr::null(), &mut action) };
+        previous.push((signal, action));
+    }
+
+    // Handlers must be able to access state as soon as they are installed.
+    let handler = CRASH_HANDLER.get_or_init(|| CrashHandler {
+        directory,
+        previous,
+    });
+
+    install_alternate_stack();
+
+    for (signal, _) in &handler.previous {
+        let mut action = unsafe { std::mem::zeroed::<libc::sigaction>() };
+        action.sa_sigaction = handle_fatal_signal as *const () as usize;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+        unsafe { libc::sigemptyset(&mut action.sa_mask) };
+
+        if unsafe { libc::sigaction(*signal, &action, std::ptr::null_mut()) } != 0 {
+            return Err(format!(
+                "unable to install crash handler: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_report_unique() {
+        let dir = std::env::temp_dir().join(format!("pyembed-open-report-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let directory = dir.as_os_str().as_bytes();
+        let mut paths = vec![];
+
+        // Reports opened by the same thread within the same second get distinct names.
+        for _ in 0..3 {
+            let mut path = [0u8; MAX_PATH];
+            let (fd, len) = open_report(directory, &mut path).unwrap();
+            unsafe { libc::close(fd) };
+            paths.push(String::from_utf8(path[directory.len() + 1..len].to_vec()).unwrap());
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let prefix = format!("crash-{}-{}-", std::process::id(), unsafe {
+            libc::syscall(libc::SYS_gettid)
+        });
+        assert!(
+            paths
+                .iter()
+                .all(|path| path.starts_with(&prefix) && path.ends_with(".txt")),
+            "{:?}",
+            paths
+        );
+        assert_eq!(
+            paths
+                .iter()
+                .collect::<std::collections::BTreeSet<_>>()
+                .len(),
+            3
+        );
+    }
+}
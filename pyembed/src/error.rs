@@ -86,3 +86,34 @@ impl NewInterpreterError {
         }
     }
 }
+
+/// Represents an error encountered when calling a Python function from Rust.
+#[derive(Debug)]
+pub enum CallError {
+    /// Converting Rust values to Python arguments failed.
+    Serialize(String),
+    /// Python raised an exception.
+    Python(String),
+    /// Converting the Python return value to a Rust value failed.
+    Deserialize(String),
+}
+
+impl From<PyErr> for CallError {
+    fn from(err: PyErr) -> Self {
+        CallError::Python(err.to_string())
+    }
+}
+
+impl Display for CallError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            CallError::Serialize(value) => write!(f, "error converting arguments: {}", value),
+            CallError::Python(value) => value.fmt(f),
+            CallError::Deserialize(value) => {
+                write!(f, "error converting return value: {}", value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CallError {}
@@ -4,25 +4,36 @@
 
 //! Manage an embedded Python interpreter.
 
+#[cfg(target_os = "linux")]
+use crate::crash::install_crash_handler;
+#[cfg(feature = "serialization")]
+use {
+    crate::{call::call_function, error::CallError},
+    serde::{de::DeserializeOwned, Serialize},
+};
 use {
     crate::{
+        call::run_entry_point,
         config::{OxidizedPythonInterpreterConfig, ResolvedOxidizedPythonInterpreterConfig},
         conversion::osstring_to_bytes,
         error::NewInterpreterError,
         osutils::resolve_terminfo_dirs,
-        pyalloc::PythonMemoryAllocator,
+        pyalloc::{print_allocator_stats_report, PythonMemoryAllocator},
+        sub_interpreter::{ActiveInterpreterGuard, SubInterpreter},
     },
     once_cell::sync::Lazy,
     oxidized_importer::{
-        install_path_hook, remove_external_importers, replace_meta_path_importers, ImporterState,
-        OxidizedFinder, PyInit_oxidized_importer, PythonResourcesState, OXIDIZED_IMPORTER_NAME,
-        OXIDIZED_IMPORTER_NAME_STR,
+        install_allocator_stats, install_path_hook, remove_external_importers,
+        replace_meta_path_importers, ImporterState, OxidizedFinder, PyInit_oxidized_importer,
+        PythonResourcesState, OXIDIZED_IMPORTER_NAME, OXIDIZED_IMPORTER_NAME_STR,
     },
     pyo3::{
         exceptions::PyRuntimeError, ffi as pyffi, prelude::*, types::PyDict, PyTypeInfo,
         ToBorrowedObject,
     },
-    python_packaging::interpreter::{MultiprocessingStartMethod, TerminfoResolution},
+    python_packaging::interpreter::{
+        MemoryAllocatorBackend, MultiprocessingStartMethod, TerminfoResolution,
+    },
     std::{
         collections::BTreeSet,
         env, fs,
@@ -92,6 +103,8 @@ pub struct MainPythonInterpreter<'interpreter, 'resources: 'interpreter> {
     pub(crate) allocator: Option<PythonMemoryAllocator>,
     /// File to write containing list of modules when the interpreter finalizes.
     write_modules_path: Option<PathBuf>,
+    /// File to write containing the import trace when the interpreter finalizes.
+    write_import_trace_path: Option<PathBuf>,
 }
 
 impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
@@ -120,6 +133,7 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
             interpreter_guard: None,
             allocator: None,
             write_modules_path: None,
+            write_import_trace_path: None,
         };
 
         res.init()?;
@@ -166,7 +180,13 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
         };
 
         // Set the memory allocator domains if they are configured.
-        self.allocator = PythonMemoryAllocator::from_backend(self.config.allocator_backend);
+        self.allocator = match self.config.allocator_backend {
+            MemoryAllocatorBackend::Tracing => Some(PythonMemoryAllocator::tracing(
+                self.config.allocator_tracing_backend,
+                self.config.allocator_tracing_capacity,
+            )),
+            backend => PythonMemoryAllocator::from_backend(backend),
+        };
 
         if let Some(allocator) = &self.allocator {
             if self.config.allocator_raw {
@@ -190,6 +210,12 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
             }
         }
 
+        // Statistics wrap whichever allocators are installed. Debug hooks are
+        // installed afterwards so they see the memory handed out to Python.
+        if self.config.allocator_stats || self.config.allocator_stats_report {
+            install_allocator_stats().map_err(NewInterpreterError::Simple)?;
+        }
+
         // Debug hooks apply to all allocator domains and work with or without
         // custom domain allocators.
         if self.config.allocator_debug {
@@ -212,6 +238,12 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
             ));
         }
 
+        if self.config.allocator_stats_report {
+            unsafe {
+                pyffi::Py_AtExit(Some(print_allocator_stats_report));
+            }
+        }
+
         // The GIL is held.
         debug_assert_eq!(unsafe { pyffi::PyGILState_Check() }, 1);
 
@@ -240,6 +272,15 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
         // The GIL is held after finishing initialization.
         debug_assert_eq!(unsafe { pyffi::PyGILState_Check() }, 1);
 
+        // faulthandler is enabled during main initialization. Installing our
+        // handler afterwards makes it run first and defer to faulthandler's.
+        #[cfg(target_os = "linux")]
+        {
+            if let Some(path) = &self.config.crash_report_dir {
+                install_crash_handler(path).map_err(NewInterpreterError::Dynamic)?;
+            }
+        }
+
         // We release the GIL so we can have pyo3's GIL handling take over from
         // an "empty" state. This mirrors what pyo3's prepare_freethreaded_python() does.
         unsafe {
@@ -249,6 +290,16 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
         self.write_modules_path =
             self.with_gil(|py| self.init_post_main(py, oxidized_finder_loaded))?;
 
+        if oxidized_finder_loaded {
+            self.write_import_trace_path = self.with_gil(|py| {
+                self.resolve_write_path(
+                    py,
+                    self.config.write_import_trace_directory_env.as_ref(),
+                    "import-trace",
+                )
+            })?;
+        }
+
         debug_assert_eq!(unsafe { pyffi::PyGILState_Check() }, 0);
 
         Ok(())
@@ -272,8 +323,32 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
             NewInterpreterError::new_from_pyerr(py, err, "import of oxidized importer module")
         })?;
 
-        let cb = |importer_state: &mut ImporterState| match self.config.multiprocessing_start_method
-        {
+        // Ownership of the resources state is transferred into the importer, where the Box
+        // is summarily leaked. However, the importer tracks a pointer to the resources state
+        // and will constitute the struct for dropping when it itself is dropped. We could
+        // potentially encounter a use-after-free if the importer is used after self.config
+        // is dropped. However, that would require self to be dropped. And if self is dropped,
+        // there should no longer be a Python interpreter around. So it follows that the
+        // importer state cannot be dropped after self.
+
+        replace_meta_path_importers(
+            py,
+            oxidized_importer,
+            resources_state,
+            Some(|importer_state: &mut ImporterState| {
+                self.configure_importer_state(importer_state)
+            }),
+        )
+        .map_err(|err| {
+            NewInterpreterError::new_from_pyerr(py, err, "initialization of oxidized importer")
+        })?;
+
+        Ok(true)
+    }
+
+    /// Apply settings from the config to the state of an `OxidizedFinder`.
+    fn configure_importer_state(&self, importer_state: &mut ImporterState) {
+        match self.config.multiprocessing_start_method {
             MultiprocessingStartMethod::None => {}
             MultiprocessingStartMethod::Fork
             | MultiprocessingStartMethod::ForkServer
@@ -296,23 +371,7 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
 
                 importer_state.set_multiprocessing_set_start_method(Some(method.to_string()));
             }
-        };
-
-        // Ownership of the resources state is transferred into the importer, where the Box
-        // is summarily leaked. However, the importer tracks a pointer to the resources state
-        // and will constitute the struct for dropping when it itself is dropped. We could
-        // potentially encounter a use-after-free if the importer is used after self.config
-        // is dropped. However, that would require self to be dropped. And if self is dropped,
-        // there should no longer be a Python interpreter around. So it follows that the
-        // importer state cannot be dropped after self.
-
-        replace_meta_path_importers(py, oxidized_importer, resources_state, Some(cb)).map_err(
-            |err| {
-                NewInterpreterError::new_from_pyerr(py, err, "initialization of oxidized importer")
-            },
-        )?;
-
-        Ok(true)
+        }
     }
 
     /// Performs interpreter configuration after main interpreter initialization.
@@ -380,6 +439,104 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
             })?;
         }
 
+        self.init_sys_attributes(py)?;
+
+        self.resolve_write_path(
+            py,
+            self.config.write_modules_directory_env.as_ref(),
+            "modules",
+        )
+    }
+
+    /// Performs configuration of a newly created sub-interpreter.
+    ///
+    /// Sub-interpreter initialization installs the default importers, so
+    /// modules imported during initialization come from them. Here, we put
+    /// a new `OxidizedFinder` in front of them, indexing the same packed
+    /// resources as the main interpreter, and apply the same `sys` settings.
+    pub(crate) fn init_sub_interpreter(&self, py: Python) -> Result<(), NewInterpreterError> {
+        let sys_module = py
+            .import("sys")
+            .map_err(|e| NewInterpreterError::new_from_pyerr(py, e, "obtaining sys module"))?;
+
+        let finder = if self.config.oxidized_importer {
+            let meta_path = sys_module
+                .getattr("meta_path")
+                .and_then(|meta_path| meta_path.iter()?.collect::<PyResult<Vec<_>>>())
+                .map_err(|err| {
+                    NewInterpreterError::new_from_pyerr(py, err, "obtaining sys.meta_path")
+                })?;
+
+            let resources_state = Box::new(PythonResourcesState::try_from(&self.config)?);
+
+            let oxidized_importer = py.import(OXIDIZED_IMPORTER_NAME_STR).map_err(|err| {
+                NewInterpreterError::new_from_pyerr(py, err, "import of oxidized importer module")
+            })?;
+
+            // As with the main interpreter, the resources state is owned by the
+            // importer. The sub-interpreter holds a reference to self, so self
+            // outlives the importer.
+            let finder = replace_meta_path_importers(
+                py,
+                oxidized_importer,
+                resources_state,
+                Some(|importer_state: &mut ImporterState| {
+                    self.configure_importer_state(importer_state)
+                }),
+            )
+            .map_err(|err| {
+                NewInterpreterError::new_from_pyerr(py, err, "initialization of oxidized importer")
+            })?;
+
+            // replace_meta_path_importers() removed every existing meta path
+            // importer. The main interpreter retains the "external" importers
+            // installed after OxidizedFinder. So restore them.
+            let external_importers = meta_path
+                .into_iter()
+                .filter(|importer| {
+                    importer
+                        .getattr("__module__")
+                        .and_then(|module| module.extract::<String>())
+                        .map(|module| module != "_frozen_importlib")
+                        .unwrap_or(true)
+                })
+                .collect::<Vec<_>>();
+
+            for importer in external_importers {
+                sys_module
+                    .getattr("meta_path")
+                    .and_then(|meta_path| meta_path.call_method1("append", (importer,)))
+                    .map_err(|err| {
+                        NewInterpreterError::new_from_pyerr(py, err, "restoring sys.meta_path")
+                    })?;
+            }
+
+            Some(finder)
+        } else {
+            None
+        };
+
+        if !self.config.filesystem_importer {
+            remove_external_importers(sys_module).map_err(|err| {
+                NewInterpreterError::new_from_pyerr(py, err, "removing external importers")
+            })?;
+        }
+
+        if let Some(finder) = finder {
+            install_path_hook(finder, sys_module).map_err(|err| {
+                NewInterpreterError::new_from_pyerr(
+                    py,
+                    err,
+                    "installing OxidizedFinder in sys.path_hooks",
+                )
+            })?;
+        }
+
+        self.init_sys_attributes(py)
+    }
+
+    /// Set custom attributes on the `sys` module.
+    fn init_sys_attributes(&self, py: Python) -> Result<(), NewInterpreterError> {
         if self.config.argvb {
             let args_objs = self
                 .config
@@ -437,14 +594,28 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
             }
         }
 
-        let write_modules_path = if let Some(key) = &self.config.write_modules_directory_env {
+        Ok(())
+    }
+
+    /// Resolve the path of a file to write to on interpreter finalization.
+    ///
+    /// `directory_env` names an environment variable holding the directory to
+    /// write to. If it is set, the directory is created and a path to a
+    /// `<prefix>-<UUID>` file within it is returned.
+    fn resolve_write_path(
+        &self,
+        py: Python,
+        directory_env: Option<&String>,
+        prefix: &str,
+    ) -> Result<Option<PathBuf>, NewInterpreterError> {
+        let path = if let Some(key) = directory_env {
             if let Ok(path) = std::env::var(key) {
                 let path = PathBuf::from(path);
 
                 std::fs::create_dir_all(&path).map_err(|e| {
                     NewInterpreterError::Dynamic(format!(
-                        "error creating directory for loaded modules files: {}",
-                        e
+                        "error creating directory for {} files: {}",
+                        prefix, e
                     ))
                 })?;
 
@@ -466,7 +637,7 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
                     })?
                     .to_string();
 
-                Some(path.join(format!("modules-{}", uuid_str)))
+                Some(path.join(format!("{}-{}", prefix, uuid_str)))
             } else {
                 None
             }
@@ -474,21 +645,112 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
             None
         };
 
-        Ok(write_modules_path)
+        Ok(path)
     }
 
     /// Proxy for [Python::with_gil()].
     ///
     /// This allows running Python code via the PyO3 Rust APIs. Alternatively,
     /// this can be used to run code when the Python GIL is held.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from within [SubInterpreter::with_gil()].
     #[inline]
     pub fn with_gil<F, R>(&self, f: F) -> R
     where
         F: for<'py> FnOnce(Python<'py>) -> R,
     {
+        let _guard = ActiveInterpreterGuard::main();
+
         Python::with_gil(f)
     }
 
+    /// Call a Python function with arguments converted from Rust values.
+    ///
+    /// `target` has the form `module:function`. e.g. `pkg.mod:main`. The module
+    /// is imported and the function is called with the values in `args` as
+    /// positional arguments. `args` must serialize to a sequence, such as a
+    /// tuple or `Vec`. The return value is deserialized into `R`.
+    ///
+    /// Values are converted between `serde` values and Python objects
+    /// directly. e.g. tuples become `tuple`, byte arrays become `bytes` and maps
+    /// become `dict`. Python `set` and `bytes` can be read as sequences.
+    #[cfg(feature = "serialization")]
+    pub fn call<A, R>(&self, target: &str, args: &A) -> Result<R, CallError>
+    where
+        A: Serialize + ?Sized,
+        R: DeserializeOwned,
+    {
+        self.with_gil(|py| call_function(py, target, args, None::<&()>))
+    }
+
+    /// Call a Python function with positional and keyword arguments.
+    ///
+    /// This is like [Self::call()] except `kwargs` is passed as keyword
+    /// arguments. `kwargs` must serialize to a map having string keys.
+    #[cfg(feature = "serialization")]
+    pub fn call_with_kwargs<A, K, R>(
+        &self,
+        target: &str,
+        args: &A,
+        kwargs: &K,
+    ) -> Result<R, CallError>
+    where
+        A: Serialize + ?Sized,
+        K: Serialize + ?Sized,
+        R: DeserializeOwned,
+    {
+        self.with_gil(|py| call_function(py, target, args, Some(kwargs)))
+    }
+
+    /// Create a new sub-interpreter.
+    ///
+    /// The sub-interpreter has its own modules and interpreter state. If the
+    /// `OxidizedFinder` importer is enabled, the sub-interpreter gets its own
+    /// instance indexing the same packed resources as this interpreter.
+    ///
+    /// See [SubInterpreter] for caveats.
+    pub fn new_sub_interpreter(
+        &self,
+    ) -> Result<SubInterpreter<'_, 'interpreter, 'resources>, NewInterpreterError> {
+        SubInterpreter::new(self)
+    }
+
+    /// Replace resources indexed from a memory mapped packed resources file.
+    ///
+    /// This allows a long-running process to pick up new Python code without
+    /// restarting. `path` is a file that was indexed from a
+    /// [crate::PackedResourcesSource::MemoryMappedPath] source. The string `$ORIGIN`
+    /// is expanded like it is in the config. The `OxidizedFinder` on `sys.meta_path`
+    /// re-indexes its packed resources with the current content of the file and
+    /// calls `importlib.invalidate_caches()`.
+    ///
+    /// Modules that are already imported are not affected: they need to be
+    /// reloaded with `importlib.reload()`. Sub-interpreters have their own
+    /// importer and aren't affected either.
+    ///
+    /// The file should be replaced by renaming a new file into place. Modifying
+    /// a file that is memory mapped can crash the process.
+    pub fn reload_packed_resources(&self, path: impl AsRef<Path>) -> PyResult<()> {
+        let path = PathBuf::from(
+            path.as_ref()
+                .display()
+                .to_string()
+                .replace("$ORIGIN", &self.config.origin().display().to_string()),
+        );
+
+        self.with_gil(|py| {
+            let finder = find_oxidized_finder(py)?.ok_or_else(|| {
+                PyRuntimeError::new_err("OxidizedFinder not found on sys.meta_path")
+            })?;
+
+            finder.call_method1("reload_file_memory_mapped", (path.as_os_str(),))?;
+
+            Ok(())
+        })
+    }
+
     /// Runs `Py_RunMain()` and finalizes the interpreter.
     ///
     /// This will execute whatever is configured by the Python interpreter config
@@ -583,6 +845,9 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
     /// current process invocation appears to be a spawned multiprocessing worker
     /// and dispatch to multiprocessing accordingly.
     ///
+    /// If an entry point is configured, it is called and its return value is
+    /// converted to an exit code.
+    ///
     /// Otherwise, this delegates to [Self::py_runmain].
     pub fn run(self) -> i32 {
         if self.config.multiprocessing_auto_dispatch && self.is_multiprocessing() {
@@ -596,6 +861,8 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
                     1
                 }
             }
+        } else if let Some(entry_point) = &self.config.entry_point {
+            self.with_gil(|py| run_entry_point(py, entry_point))
         } else {
             self.py_runmain()
         }
@@ -712,6 +979,31 @@ fn write_modules_to_path(py: Python, path: &Path) -> Result<(), &'static str> {
     Ok(())
 }
 
+/// Find the `OxidizedFinder` instance on `sys.meta_path`.
+fn find_oxidized_finder(py: Python) -> PyResult<Option<&PyAny>> {
+    for finder in py.import("sys")?.getattr("meta_path")?.iter()? {
+        let finder = finder?;
+
+        if finder.cast_as::<PyCell<OxidizedFinder>>().is_ok() {
+            return Ok(Some(finder));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Write the import trace of the `OxidizedFinder` on `sys.meta_path` to a file.
+fn write_import_trace_to_path(py: Python, path: &Path) -> Result<(), String> {
+    let finder = find_oxidized_finder(py)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "OxidizedFinder not found on sys.meta_path".to_string())?;
+
+    finder
+        .call_method1("write_import_trace", (path.as_os_str(),))
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
 impl<'interpreter, 'resources> Drop for MainPythonInterpreter<'interpreter, 'resources> {
     fn drop(&mut self) {
         // Interpreter may have been finalized already. Possibly through our invocation
@@ -731,6 +1023,15 @@ impl<'interpreter, 'resources> Drop for MainPythonInterpreter<'interpreter, 'res
             }
         }
 
+        if let Some(path) = self.write_import_trace_path.as_ref() {
+            match self.with_gil(|py| write_import_trace_to_path(py, path)) {
+                Ok(_) => {}
+                Err(msg) => {
+                    eprintln!("error writing import trace file: {}", msg);
+                }
+            }
+        }
+
         unsafe {
             pyffi::PyGILState_Ensure();
             pyffi::Py_FinalizeEx();
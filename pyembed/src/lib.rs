@@ -23,6 +23,8 @@ The most important types in this crate are
 [OxidizedPythonInterpreterConfig] defines how a Python interpreter is to
 behave. A [MainPythonInterpreter] creates and manages that interpreter and
 serves as a high-level interface for running code in the interpreter.
+[SubInterpreter] instances can be created from a [MainPythonInterpreter] to run
+code in isolated Python sub-interpreters.
 
 # Dependencies
 
@@ -50,17 +52,25 @@ The feature behaves similarly to `jemalloc`, which is documented above.
 
 The optional `serialization` feature controls whether configuration types
 (such as [OxidizedPythonInterpreterConfig]) implement `Serialize` and
-`Deserialize`.
+`Deserialize`. It also enables [MainPythonInterpreter::call()] and
+[SubInterpreter::call()] for calling Python functions with arguments and
+return values converted from and to `serde` values.
 */
 
+mod call;
 #[allow(unused)]
 mod config;
 mod conversion;
+#[cfg(target_os = "linux")]
+mod crash;
 mod error;
 mod interpreter;
 mod interpreter_config;
 mod osutils;
 mod pyalloc;
+#[cfg(feature = "serialization")]
+mod serde_conversion;
+mod sub_interpreter;
 pub mod technotes;
 #[cfg(test)]
 mod test;
@@ -72,11 +82,15 @@ pub use {
             ExtensionModule, OxidizedPythonInterpreterConfig,
             ResolvedOxidizedPythonInterpreterConfig,
         },
-        error::NewInterpreterError,
+        error::{CallError, NewInterpreterError},
         interpreter::MainPythonInterpreter,
-        pyalloc::PythonMemoryAllocator,
+        pyalloc::{PythonMemoryAllocator, DEFAULT_ALLOCATION_TRACE_CAPACITY},
+        sub_interpreter::SubInterpreter,
+    },
+    oxidized_importer::{
+        allocation_trace, allocator_stats, AllocationEvent, AllocationEventKind,
+        AllocatorDomainStats, AllocatorStats, PackedResourcesSource, PythonResourcesState,
     },
-    oxidized_importer::{PackedResourcesSource, PythonResourcesState},
     python_packaging::{
         interpreter::{
             Allocator, BytesWarning, CheckHashPycsMode, CoerceCLocale, MemoryAllocatorBackend,
@@ -75,6 +75,11 @@ to.)
 We support `jemalloc`, `mimalloc`, `snmalloc`, and Rust's global allocator as
 custom Python allocators.
 
+We also support a _tracing_ allocator, which wraps one of the aforementioned
+allocators (or Python's default allocators) and records allocation events and
+the Python stack frames responsible for them. This is a debugging aid and is
+slow.
+
 Rust's global allocator can independently also be set to one of the aforementioned
 custom allocators via external Rust code.
 
@@ -90,6 +95,7 @@ to the allocator will be more efficient.
 
 use {
     core::ffi::c_void,
+    oxidized_importer::{allocator_stats, record_allocation_event, AllocationEventKind},
     pyo3::ffi as pyffi,
     python_packaging::interpreter::MemoryAllocatorBackend,
     std::{
@@ -102,6 +108,9 @@ use {
 
 const MIN_ALIGN: usize = 16;
 
+/// Default number of allocation events retained by the tracing allocator.
+pub const DEFAULT_ALLOCATION_TRACE_CAPACITY: usize = 65536;
+
 /// Tracks allocations from an allocator.
 ///
 /// Some allocators need to pass the original allocation size and alignment
@@ -451,6 +460,112 @@ extern "C" fn snmalloc_arena_free(_ctx: *mut c_void, ptr: *mut c_void, _size: us
     unsafe { snmalloc_sys::sn_free(ptr as *mut _) }
 }
 
+/// A memory allocator domain whose allocations are being recorded.
+///
+/// A pointer to an instance is the context of the installed `PyMemAllocatorEx`.
+struct TracingDomain {
+    /// The allocator requests are forwarded to.
+    inner: pyffi::PyMemAllocatorEx,
+
+    /// Name of the domain, as recorded in allocation events.
+    name: &'static str,
+}
+
+/// Represents an allocator recording allocations served by another allocator.
+pub(crate) struct TracingAllocator {
+    /// The wrapped allocator. `None` wraps Python's default allocators.
+    inner: Option<Box<PythonMemoryAllocator>>,
+
+    /// Allocators to install for the `raw`, `mem`, and `obj` domains.
+    allocators: [pyffi::PyMemAllocatorEx; 3],
+
+    _domains: Box<[TracingDomain; 3]>,
+}
+
+/// Resolve the index of a domain in [TracingAllocator] arrays.
+fn domain_index(domain: pyffi::PyMemAllocatorDomain) -> usize {
+    match domain {
+        pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_RAW => 0,
+        pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_MEM => 1,
+        pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_OBJ => 2,
+    }
+}
+
+unsafe fn tracing_domain<'a>(ctx: *mut c_void) -> &'a TracingDomain {
+    &*(ctx as *const TracingDomain)
+}
+
+extern "C" fn tracing_malloc(ctx: *mut c_void, size: usize) -> *mut c_void {
+    let domain = unsafe { tracing_domain(ctx) };
+
+    let malloc = domain.inner.malloc.expect("wrapped allocator has malloc");
+    let res = unsafe { malloc(domain.inner.ctx, size) };
+
+    if !res.is_null() {
+        record_allocation_event(
+            AllocationEventKind::Allocate,
+            domain.name,
+            res as usize,
+            Some(size),
+        );
+    }
+
+    res
+}
+
+extern "C" fn tracing_calloc(ctx: *mut c_void, nelem: usize, elsize: usize) -> *mut c_void {
+    let domain = unsafe { tracing_domain(ctx) };
+
+    let calloc = domain.inner.calloc.expect("wrapped allocator has calloc");
+    let res = unsafe { calloc(domain.inner.ctx, nelem, elsize) };
+
+    if !res.is_null() {
+        record_allocation_event(
+            AllocationEventKind::Allocate,
+            domain.name,
+            res as usize,
+            Some(nelem.saturating_mul(elsize)),
+        );
+    }
+
+    res
+}
+
+extern "C" fn tracing_realloc(ctx: *mut c_void, ptr: *mut c_void, new_size: usize) -> *mut c_void {
+    let domain = unsafe { tracing_domain(ctx) };
+
+    let realloc = domain.inner.realloc.expect("wrapped allocator has realloc");
+    let res = unsafe { realloc(domain.inner.ctx, ptr, new_size) };
+
+    if !res.is_null() {
+        record_allocation_event(
+            if ptr.is_null() {
+                AllocationEventKind::Allocate
+            } else {
+                AllocationEventKind::Reallocate
+            },
+            domain.name,
+            res as usize,
+            Some(new_size),
+        );
+    }
+
+    res
+}
+
+extern "C" fn tracing_free(ctx: *mut c_void, ptr: *mut c_void) {
+    if ptr.is_null() {
+        return;
+    }
+
+    let domain = unsafe { tracing_domain(ctx) };
+
+    record_allocation_event(AllocationEventKind::Free, domain.name, ptr as usize, None);
+
+    let free = domain.inner.free.expect("wrapped allocator has free");
+    unsafe { free(domain.inner.ctx, ptr) };
+}
+
 /// Represents a `PyMemAllocatorEx` that can be installed as a memory allocator.
 enum AllocatorInstance {
     /// Backed by a `PyMemAllocatorEx` struct.
@@ -459,6 +574,9 @@ enum AllocatorInstance {
 
     /// Backed by a custom wrapper type.
     Tracking(TrackingAllocator),
+
+    /// Records allocations served by another allocator.
+    Tracing(TracingAllocator),
 }
 
 /// Represents a custom memory allocator that can be registered with Python.
@@ -481,6 +599,10 @@ impl PythonMemoryAllocator {
             MemoryAllocatorBackend::Mimalloc => Some(Self::mimalloc()),
             MemoryAllocatorBackend::Snmalloc => Some(Self::snmalloc()),
             MemoryAllocatorBackend::Rust => Some(Self::rust()),
+            MemoryAllocatorBackend::Tracing => Some(Self::tracing(
+                MemoryAllocatorBackend::Default,
+                DEFAULT_ALLOCATION_TRACE_CAPACITY,
+            )),
         }
     }
 
@@ -593,6 +715,81 @@ impl PythonMemoryAllocator {
         panic!("snmalloc allocator requested but it isn't compiled into this build configuration; try `cargo build --features allocator-snmalloc`");
     }
 
+    /// Construct a new instance recording allocations served by another backend.
+    ///
+    /// `inner` is the backend serving allocations. If it is
+    /// [MemoryAllocatorBackend::Default], Python's default allocators are wrapped.
+    ///
+    /// `capacity` is the maximum number of allocation events retained. Events
+    /// are recorded process wide and can be obtained via
+    /// [oxidized_importer::allocation_trace()].
+    ///
+    /// Python stack frames are only recorded for allocations made while the
+    /// GIL is held. So allocations in the `raw` domain are often recorded
+    /// without frames. Frames aren't recorded on Python 3.11+, where reading
+    /// them allocates memory.
+    pub fn tracing(inner: MemoryAllocatorBackend, capacity: usize) -> Self {
+        if inner == MemoryAllocatorBackend::Tracing {
+            panic!("tracing allocator cannot wrap another tracing allocator");
+        }
+
+        let inner = Self::from_backend(inner).map(Box::new);
+
+        let domains = Box::new(
+            [
+                (pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_RAW, "raw"),
+                (pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_MEM, "mem"),
+                (pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_OBJ, "obj"),
+            ]
+            .map(|(domain, name)| {
+                let inner = if let Some(inner) = &inner {
+                    let allocator = unsafe { &*inner.as_memory_allocator(domain) };
+
+                    pyffi::PyMemAllocatorEx {
+                        ctx: allocator.ctx,
+                        malloc: allocator.malloc,
+                        calloc: allocator.calloc,
+                        realloc: allocator.realloc,
+                        free: allocator.free,
+                    }
+                } else {
+                    let mut allocator = pyffi::PyMemAllocatorEx {
+                        ctx: std::ptr::null_mut(),
+                        malloc: None,
+                        calloc: None,
+                        realloc: None,
+                        free: None,
+                    };
+
+                    unsafe { pyffi::PyMem_GetAllocator(domain, &mut allocator) };
+
+                    allocator
+                };
+
+                TracingDomain { inner, name }
+            }),
+        );
+
+        let allocators = [0, 1, 2].map(|i| pyffi::PyMemAllocatorEx {
+            ctx: &domains[i] as *const _ as *mut c_void,
+            malloc: Some(tracing_malloc),
+            calloc: Some(tracing_calloc),
+            realloc: Some(tracing_realloc),
+            free: Some(tracing_free),
+        });
+
+        oxidized_importer::enable_allocation_trace(capacity);
+
+        Self {
+            backend: MemoryAllocatorBackend::Tracing,
+            instance: AllocatorInstance::Tracing(TracingAllocator {
+                inner,
+                allocators,
+                _domains: domains,
+            }),
+        }
+    }
+
     /// Obtain the backend used for this instance.
     #[allow(unused)]
     pub fn backend(&self) -> MemoryAllocatorBackend {
@@ -604,7 +801,7 @@ impl PythonMemoryAllocator {
     /// This should be called before `Py_Initialize*()`.
     pub fn set_allocator(&self, domain: pyffi::PyMemAllocatorDomain) {
         unsafe {
-            pyffi::PyMem_SetAllocator(domain, self.as_memory_allocator() as *mut _);
+            pyffi::PyMem_SetAllocator(domain, self.as_memory_allocator(domain) as *mut _);
         }
     }
 
@@ -612,24 +809,49 @@ impl PythonMemoryAllocator {
     ///
     /// This only has an effect if the `pymalloc` allocator is registered to the
     /// `mem` or `object` allocator domains.
+    ///
+    /// Arena allocations aren't recorded by the tracing allocator. It installs
+    /// the arena allocator of the backend it wraps, if any.
     #[allow(dead_code)]
     pub fn set_arena_allocator(&self) {
-        unsafe { pyffi::PyObject_SetArenaAllocator(self.as_arena_allocator()) }
+        if let Some(arena) = self.as_arena_allocator() {
+            unsafe { pyffi::PyObject_SetArenaAllocator(arena) }
+        }
     }
 
     /// Obtain the pointer to the `PyMemAllocatorEx` for this allocator.
-    fn as_memory_allocator(&self) -> *const pyffi::PyMemAllocatorEx {
+    fn as_memory_allocator(
+        &self,
+        domain: pyffi::PyMemAllocatorDomain,
+    ) -> *const pyffi::PyMemAllocatorEx {
         match &self.instance {
             AllocatorInstance::Simple(alloc, _) => alloc as *const _,
             AllocatorInstance::Tracking(alloc) => &alloc.allocator as *const _,
+            AllocatorInstance::Tracing(alloc) => {
+                &alloc.allocators[domain_index(domain)] as *const _
+            }
         }
     }
 
     #[allow(dead_code)]
-    fn as_arena_allocator(&self) -> *mut pyffi::PyObjectArenaAllocator {
+    fn as_arena_allocator(&self) -> Option<*mut pyffi::PyObjectArenaAllocator> {
         match &self.instance {
-            AllocatorInstance::Simple(_, arena) => arena as *const _ as *mut _,
-            AllocatorInstance::Tracking(alloc) => &alloc.arena as *const _ as *mut _,
+            AllocatorInstance::Simple(_, arena) => Some(arena as *const _ as *mut _),
+            AllocatorInstance::Tracking(alloc) => Some(&alloc.arena as *const _ as *mut _),
+            AllocatorInstance::Tracing(alloc) => alloc
+                .inner
+                .as_ref()
+                .and_then(|inner| inner.as_arena_allocator()),
         }
     }
 }
+
+/// Print memory allocator statistics to stderr.
+///
+/// This is registered via `Py_AtExit()` and called at the end of interpreter
+/// finalization.
+pub(crate) extern "C" fn print_allocator_stats_report() {
+    if let Some(stats) = allocator_stats() {
+        eprint!("{}", stats);
+    }
+}
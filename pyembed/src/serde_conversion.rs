@@ -0,0 +1,690 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Convert between `serde` values and Python objects.
+
+Values are mapped to Python types as follows:
+
+* Unit, unit structs and `None` become `None`.
+* Booleans, integers, floats and strings become `bool`, `int`, `float` and
+  `str`. Characters become single character `str`.
+* Byte arrays become `bytes`.
+* Sequences become `list`. Tuples and tuple structs become `tuple`.
+* Maps and structs become `dict`.
+* Unit enum variants become the variant name as a `str`. Other variants
+  become a `dict` with the variant name as its only key.
+
+When converting Python objects to Rust values, `list`, `tuple`, `set`,
+`frozenset`, `bytes` and `bytearray` can be read as sequences.
+*/
+
+use {
+    pyo3::{
+        prelude::*,
+        types::{
+            PyBool, PyByteArray, PyBytes, PyDict, PyFloat, PyFrozenSet, PyList, PyLong, PySet,
+            PyString, PyTuple,
+        },
+    },
+    serde::{
+        de::{self, DeserializeSeed, IntoDeserializer, Visitor},
+        ser::{self, Serialize},
+    },
+    std::fmt::{Display, Formatter},
+};
+
+/// An error converting between `serde` values and Python objects.
+#[derive(Debug)]
+pub(crate) struct ConversionError(String);
+
+impl Display for ConversionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+impl ser::Error for ConversionError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+impl de::Error for ConversionError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+impl From<PyErr> for ConversionError {
+    fn from(err: PyErr) -> Self {
+        Self(err.to_string())
+    }
+}
+
+type Result<T> = std::result::Result<T, ConversionError>;
+
+/// Convert a value implementing `Serialize` to a Python object.
+pub(crate) fn to_python<T: Serialize + ?Sized>(py: Python, value: &T) -> Result<PyObject> {
+    value.serialize(Serializer { py })
+}
+
+/// Convert a Python object to a value implementing `Deserialize`.
+pub(crate) fn from_python<'de, T: de::Deserialize<'de>>(value: &PyAny) -> Result<T> {
+    T::deserialize(Deserializer { value })
+}
+
+/// A `serde` serializer producing Python objects.
+struct Serializer<'p> {
+    py: Python<'p>,
+}
+
+/// Wrap a value in a `dict` keyed by an enum variant name.
+fn variant_dict(py: Python, variant: &str, value: PyObject) -> Result<PyObject> {
+    let dict = PyDict::new(py);
+    dict.set_item(variant, value)?;
+
+    Ok(dict.into())
+}
+
+impl<'p> ser::Serializer for Serializer<'p> {
+    type Ok = PyObject;
+    type Error = ConversionError;
+    type SerializeSeq = SerializeSeq<'p>;
+    type SerializeTuple = SerializeSeq<'p>;
+    type SerializeTupleStruct = SerializeSeq<'p>;
+    type SerializeTupleVariant = SerializeSeq<'p>;
+    type SerializeMap = SerializeMap<'p>;
+    type SerializeStruct = SerializeMap<'p>;
+    type SerializeStructVariant = SerializeMap<'p>;
+
+    fn serialize_bool(self, v: bool) -> Result<PyObject> {
+        Ok(v.into_py(self.py))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<PyObject> {
+        Ok(v.into_py(self.py))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<PyObject> {
+        Ok(v.into_py(self.py))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<PyObject> {
+        Ok(v.into_py(self.py))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<PyObject> {
+        Ok(v.into_py(self.py))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<PyObject> {
+        Ok(v.into_py(self.py))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<PyObject> {
+        Ok(v.into_py(self.py))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<PyObject> {
+        Ok(v.into_py(self.py))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<PyObject> {
+        Ok(v.into_py(self.py))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<PyObject> {
+        Ok(v.into_py(self.py))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<PyObject> {
+        Ok(v.into_py(self.py))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<PyObject> {
+        Ok(v.into_py(self.py))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<PyObject> {
+        Ok(v.into_py(self.py))
+    }
+
+    fn serialize_char(self, v: char) -> Result<PyObject> {
+        Ok(v.into_py(self.py))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<PyObject> {
+        Ok(v.into_py(self.py))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<PyObject> {
+        Ok(PyBytes::new(self.py, v).into())
+    }
+
+    fn serialize_none(self) -> Result<PyObject> {
+        Ok(self.py.None())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<PyObject> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<PyObject> {
+        Ok(self.py.None())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<PyObject> {
+        Ok(self.py.None())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<PyObject> {
+        Ok(variant.into_py(self.py))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<PyObject> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<PyObject> {
+        let py = self.py;
+        variant_dict(py, variant, value.serialize(self)?)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeSeq<'p>> {
+        Ok(SerializeSeq::new(self.py, len, false, None))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeSeq<'p>> {
+        Ok(SerializeSeq::new(self.py, Some(len), true, None))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SerializeSeq<'p>> {
+        Ok(SerializeSeq::new(self.py, Some(len), true, None))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeSeq<'p>> {
+        Ok(SerializeSeq::new(self.py, Some(len), true, Some(variant)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeMap<'p>> {
+        Ok(SerializeMap::new(self.py, None))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<SerializeMap<'p>> {
+        Ok(SerializeMap::new(self.py, None))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<SerializeMap<'p>> {
+        Ok(SerializeMap::new(self.py, Some(variant)))
+    }
+}
+
+/// Collects sequence and tuple elements into a `list` or `tuple`.
+struct SerializeSeq<'p> {
+    py: Python<'p>,
+    items: Vec<PyObject>,
+    tuple: bool,
+    variant: Option<&'static str>,
+}
+
+impl<'p> SerializeSeq<'p> {
+    fn new(py: Python<'p>, len: Option<usize>, tuple: bool, variant: Option<&'static str>) -> Self {
+        Self {
+            py,
+            items: Vec::with_capacity(len.unwrap_or_default()),
+            tuple,
+            variant,
+        }
+    }
+
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.items.push(to_python(self.py, value)?);
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<PyObject> {
+        let value: PyObject = if self.tuple {
+            PyTuple::new(self.py, self.items).into()
+        } else {
+            PyList::new(self.py, self.items).into()
+        };
+
+        match self.variant {
+            Some(variant) => variant_dict(self.py, variant, value),
+            None => Ok(value),
+        }
+    }
+}
+
+impl<'p> ser::SerializeSeq for SerializeSeq<'p> {
+    type Ok = PyObject;
+    type Error = ConversionError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<PyObject> {
+        self.finish()
+    }
+}
+
+impl<'p> ser::SerializeTuple for SerializeSeq<'p> {
+    type Ok = PyObject;
+    type Error = ConversionError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<PyObject> {
+        self.finish()
+    }
+}
+
+impl<'p> ser::SerializeTupleStruct for SerializeSeq<'p> {
+    type Ok = PyObject;
+    type Error = ConversionError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<PyObject> {
+        self.finish()
+    }
+}
+
+impl<'p> ser::SerializeTupleVariant for SerializeSeq<'p> {
+    type Ok = PyObject;
+    type Error = ConversionError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<PyObject> {
+        self.finish()
+    }
+}
+
+/// Collects map entries and struct fields into a `dict`.
+struct SerializeMap<'p> {
+    py: Python<'p>,
+    dict: &'p PyDict,
+    key: Option<PyObject>,
+    variant: Option<&'static str>,
+}
+
+impl<'p> SerializeMap<'p> {
+    fn new(py: Python<'p>, variant: Option<&'static str>) -> Self {
+        Self {
+            py,
+            dict: PyDict::new(py),
+            key: None,
+            variant,
+        }
+    }
+
+    fn finish(self) -> Result<PyObject> {
+        match self.variant {
+            Some(variant) => variant_dict(self.py, variant, self.dict.into()),
+            None => Ok(self.dict.into()),
+        }
+    }
+}
+
+impl<'p> ser::SerializeMap for SerializeMap<'p> {
+    type Ok = PyObject;
+    type Error = ConversionError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.key = Some(to_python(self.py, key)?);
+
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ConversionError("map value serialized before its key".to_string()))?;
+
+        self.dict.set_item(key, to_python(self.py, value)?)?;
+
+        Ok(())
+    }
+
+    fn end(self) -> Result<PyObject> {
+        self.finish()
+    }
+}
+
+impl<'p> ser::SerializeStruct for SerializeMap<'p> {
+    type Ok = PyObject;
+    type Error = ConversionError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.dict.set_item(key, to_python(self.py, value)?)?;
+
+        Ok(())
+    }
+
+    fn end(self) -> Result<PyObject> {
+        self.finish()
+    }
+}
+
+impl<'p> ser::SerializeStructVariant for SerializeMap<'p> {
+    type Ok = PyObject;
+    type Error = ConversionError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.dict.set_item(key, to_python(self.py, value)?)?;
+
+        Ok(())
+    }
+
+    fn end(self) -> Result<PyObject> {
+        self.finish()
+    }
+}
+
+/// A `serde` deserializer reading a Python object.
+struct Deserializer<'p> {
+    value: &'p PyAny,
+}
+
+impl<'p> Deserializer<'p> {
+    /// Obtain the elements of a Python object that can be read as a sequence.
+    fn sequence(&self) -> Result<Option<Vec<&'p PyAny>>> {
+        let value = self.value;
+        let py = value.py();
+
+        if let Ok(data) = value.cast_as::<PyBytes>() {
+            Ok(Some(
+                data.as_bytes()
+                    .iter()
+                    .map(|b| b.into_py(py).into_ref(py))
+                    .collect(),
+            ))
+        } else if let Ok(data) = value.cast_as::<PyByteArray>() {
+            Ok(Some(
+                data.to_vec()
+                    .into_iter()
+                    .map(|b| b.into_py(py).into_ref(py))
+                    .collect(),
+            ))
+        } else if value.is_instance_of::<PyList>()?
+            || value.is_instance_of::<PyTuple>()?
+            || value.is_instance_of::<PySet>()?
+            || value.is_instance_of::<PyFrozenSet>()?
+        {
+            Ok(Some(value.iter()?.collect::<PyResult<Vec<_>>>()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn unsupported(&self) -> ConversionError {
+        let name = self
+            .value
+            .get_type()
+            .name()
+            .unwrap_or("<unknown>")
+            .to_string();
+
+        ConversionError(format!("unsupported Python type {}", name))
+    }
+}
+
+impl<'de, 'p> de::Deserializer<'de> for Deserializer<'p> {
+    type Error = ConversionError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let value = self.value;
+
+        if value.is_none() {
+            visitor.visit_unit()
+        } else if let Ok(v) = value.cast_as::<PyBool>() {
+            visitor.visit_bool(v.is_true())
+        } else if value.is_instance_of::<PyLong>()? {
+            if let Ok(v) = value.extract::<i64>() {
+                visitor.visit_i64(v)
+            } else if let Ok(v) = value.extract::<u64>() {
+                visitor.visit_u64(v)
+            } else {
+                visitor.visit_i128(value.extract::<i128>()?)
+            }
+        } else if let Ok(v) = value.cast_as::<PyFloat>() {
+            visitor.visit_f64(v.value())
+        } else if let Ok(v) = value.cast_as::<PyString>() {
+            visitor.visit_string(v.to_str()?.to_string())
+        } else if let Ok(v) = value.cast_as::<PyBytes>() {
+            visitor.visit_byte_buf(v.as_bytes().to_vec())
+        } else if let Ok(v) = value.cast_as::<PyByteArray>() {
+            visitor.visit_byte_buf(v.to_vec())
+        } else if let Ok(v) = value.cast_as::<PyDict>() {
+            visitor.visit_map(MapAccess::new(v))
+        } else if let Some(items) = self.sequence()? {
+            visitor.visit_seq(SeqAccess::new(items))
+        } else {
+            Err(self.unsupported())
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.value.is_none() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.sequence()? {
+            Some(items) => visitor.visit_seq(SeqAccess::new(items)),
+            None => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        if let Ok(variant) = self.value.cast_as::<PyString>() {
+            return visitor.visit_enum(variant.to_str()?.to_string().into_deserializer());
+        }
+
+        match self.value.cast_as::<PyDict>() {
+            Ok(dict) if dict.len() == 1 => {
+                let (variant, value) = dict.items().get_item(0)?.extract::<(&PyAny, &PyAny)>()?;
+
+                visitor.visit_enum(EnumAccess { variant, value })
+            }
+            _ => Err(ConversionError(
+                "enums must be a str or a dict with a single key".to_string(),
+            )),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct map struct identifier ignored_any
+    }
+}
+
+/// Reads elements of a Python sequence.
+struct SeqAccess<'p> {
+    items: std::vec::IntoIter<&'p PyAny>,
+}
+
+impl<'p> SeqAccess<'p> {
+    fn new(items: Vec<&'p PyAny>) -> Self {
+        Self {
+            items: items.into_iter(),
+        }
+    }
+}
+
+impl<'de, 'p> de::SeqAccess<'de> for SeqAccess<'p> {
+    type Error = ConversionError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        self.items
+            .next()
+            .map(|value| seed.deserialize(Deserializer { value }))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+/// Reads entries of a Python `dict`.
+struct MapAccess<'p> {
+    items: std::vec::IntoIter<(&'p PyAny, &'p PyAny)>,
+    value: Option<&'p PyAny>,
+}
+
+impl<'p> MapAccess<'p> {
+    fn new(dict: &'p PyDict) -> Self {
+        Self {
+            items: dict.iter().collect::<Vec<_>>().into_iter(),
+            value: None,
+        }
+    }
+}
+
+impl<'de, 'p> de::MapAccess<'de> for MapAccess<'p> {
+    type Error = ConversionError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match self.items.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(Deserializer { value: key }).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| ConversionError("map value read before its key".to_string()))?;
+
+        seed.deserialize(Deserializer { value })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+/// Reads an enum variant stored as a single entry `dict`.
+struct EnumAccess<'p> {
+    variant: &'p PyAny,
+    value: &'p PyAny,
+}
+
+impl<'de, 'p> de::EnumAccess<'de> for EnumAccess<'p> {
+    type Error = ConversionError;
+    type Variant = Deserializer<'p>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self::Variant)> {
+        let variant = seed.deserialize(Deserializer {
+            value: self.variant,
+        })?;
+
+        Ok((variant, Deserializer { value: self.value }))
+    }
+}
+
+impl<'de, 'p> de::VariantAccess<'de> for Deserializer<'p> {
+    type Error = ConversionError;
+
+    fn unit_variant(self) -> Result<()> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_any(self, visitor)
+    }
+}
@@ -0,0 +1,245 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Manage Python sub-interpreters.
+
+#[cfg(feature = "serialization")]
+use {
+    crate::{call::call_function, error::CallError},
+    serde::{de::DeserializeOwned, Serialize},
+};
+use {
+    crate::{error::NewInterpreterError, interpreter::MainPythonInterpreter},
+    pyo3::{ffi as pyffi, prelude::*, GILPool},
+    std::cell::Cell,
+};
+
+/// Kinds of interpreters whose `with_gil()` can be running on a thread.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ActiveInterpreter {
+    None,
+    Main,
+    Sub,
+}
+
+thread_local! {
+    /// The interpreter whose `with_gil()` is running on this thread.
+    static ACTIVE_INTERPRETER: Cell<ActiveInterpreter> = const { Cell::new(ActiveInterpreter::None) };
+}
+
+/// Marks an interpreter as active on the current thread until dropped.
+pub(crate) struct ActiveInterpreterGuard {
+    previous: ActiveInterpreter,
+}
+
+impl ActiveInterpreterGuard {
+    /// Mark the main interpreter as active.
+    ///
+    /// Panics if a sub-interpreter is active on this thread: its thread state
+    /// is current, so Python code would run in the sub-interpreter.
+    pub(crate) fn main() -> Self {
+        let previous = ACTIVE_INTERPRETER.with(|active| active.replace(ActiveInterpreter::Main));
+
+        if previous == ActiveInterpreter::Sub {
+            ACTIVE_INTERPRETER.with(|active| active.set(previous));
+            panic!("MainPythonInterpreter::with_gil() called within SubInterpreter::with_gil()");
+        }
+
+        Self { previous }
+    }
+
+    /// Mark a sub-interpreter as active.
+    ///
+    /// Panics if any interpreter is already active on this thread. Acquiring
+    /// the GIL again would deadlock and swapping thread states would leave
+    /// objects of the active interpreter in use by another interpreter.
+    fn sub() -> Self {
+        let previous = ACTIVE_INTERPRETER.with(|active| active.replace(ActiveInterpreter::Sub));
+
+        if previous != ActiveInterpreter::None {
+            ACTIVE_INTERPRETER.with(|active| active.set(previous));
+            panic!(
+                "SubInterpreter::with_gil() called while an interpreter is active on this thread"
+            );
+        }
+
+        Self { previous }
+    }
+}
+
+impl Drop for ActiveInterpreterGuard {
+    fn drop(&mut self) {
+        ACTIVE_INTERPRETER.with(|active| active.set(self.previous));
+    }
+}
+
+/// Holds the GIL with a sub-interpreter's thread state current until dropped.
+struct ThreadStateGuard {
+    _active: ActiveInterpreterGuard,
+}
+
+impl ThreadStateGuard {
+    unsafe fn acquire(thread_state: *mut pyffi::PyThreadState) -> Self {
+        let active = ActiveInterpreterGuard::sub();
+
+        // Acquires the GIL and makes the thread state current.
+        pyffi::PyEval_RestoreThread(thread_state);
+
+        Self { _active: active }
+    }
+}
+
+impl Drop for ThreadStateGuard {
+    fn drop(&mut self) {
+        // Releases the GIL. No thread state is current afterwards.
+        unsafe {
+            pyffi::PyEval_SaveThread();
+        }
+    }
+}
+
+/// A Python sub-interpreter.
+///
+/// Instances are obtained from [MainPythonInterpreter::new_sub_interpreter()].
+/// Each sub-interpreter has its own `sys.modules`, `sys.meta_path`, and other
+/// interpreter state. So modules imported in one interpreter are not shared
+/// with other interpreters.
+///
+/// Sub-interpreters are created with `Py_NewInterpreter()`, which initializes
+/// the sub-interpreter using the configuration of the main interpreter.
+/// CPython installs its default importers during this initialization and
+/// `OxidizedFinder` is only installed afterwards. So the modules imported during
+/// initialization (e.g. `encodings`) must be importable by the default importers.
+///
+/// The sub-interpreter is destroyed via `Py_EndInterpreter()` when this instance
+/// is dropped. A sub-interpreter borrows the main interpreter, so it can't outlive it.
+///
+/// Each sub-interpreter owns the thread state `Py_NewInterpreter()` created for
+/// it. The GIL is acquired with that thread state rather than through the
+/// `PyGILState` APIs, which only know about the main interpreter. A thread state
+/// belongs to the thread that created it, so instances are neither `Send` nor
+/// `Sync` and must be used on the thread that created them.
+///
+/// Python objects belong to the interpreter that created them. Objects must not
+/// be moved between interpreters, e.g. by returning a [PyObject] from
+/// [SubInterpreter::with_gil()] and using it in [MainPythonInterpreter::with_gil()].
+/// The interpreters share the GIL and allocator, so this isn't detected, but such
+/// objects reference the modules and types of their interpreter and using them
+/// elsewhere can crash once their interpreter is destroyed.
+///
+/// Python extension modules not supporting multiple interpreters may misbehave
+/// when imported in sub-interpreters.
+pub struct SubInterpreter<'main, 'interpreter, 'resources: 'interpreter> {
+    main: &'main MainPythonInterpreter<'interpreter, 'resources>,
+    thread_state: *mut pyffi::PyThreadState,
+}
+
+impl<'main, 'interpreter, 'resources> SubInterpreter<'main, 'interpreter, 'resources> {
+    /// Create a new sub-interpreter from a main interpreter.
+    pub(crate) fn new(
+        main: &'main MainPythonInterpreter<'interpreter, 'resources>,
+    ) -> Result<Self, NewInterpreterError> {
+        // Py_NewInterpreter() and Py_EndInterpreter() need the GIL, which is
+        // obtained through the main interpreter. The new thread state is swapped
+        // in while the GIL is held and the main interpreter's thread state is
+        // swapped back before the GIL is released.
+        let thread_state = main.with_gil(|_| unsafe {
+            let main_thread_state = pyffi::PyThreadState_Swap(std::ptr::null_mut());
+
+            // This makes the new interpreter's thread state current.
+            let thread_state = pyffi::Py_NewInterpreter();
+
+            let res = if thread_state.is_null() {
+                Err(NewInterpreterError::Simple(
+                    "unable to create sub-interpreter",
+                ))
+            } else {
+                let res = {
+                    let pool = GILPool::new();
+                    main.init_sub_interpreter(pool.python())
+                };
+
+                match res {
+                    Ok(()) => Ok(thread_state),
+                    Err(err) => {
+                        pyffi::Py_EndInterpreter(thread_state);
+                        Err(err)
+                    }
+                }
+            };
+
+            pyffi::PyThreadState_Swap(main_thread_state);
+
+            res
+        })?;
+
+        Ok(Self { main, thread_state })
+    }
+
+    /// Run code in the sub-interpreter.
+    ///
+    /// The GIL is acquired and the sub-interpreter's thread state is made
+    /// current for the duration of the function. The [Python] token and any
+    /// objects obtained through it must not be used outside the function.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from within [SubInterpreter::with_gil()] or
+    /// [MainPythonInterpreter::with_gil()] of any interpreter, as the GIL is
+    /// already held. Calling [MainPythonInterpreter::with_gil()] from within the
+    /// function panics as well.
+    pub fn with_gil<F, R>(&self, f: F) -> R
+    where
+        F: for<'py> FnOnce(Python<'py>) -> R,
+    {
+        let _guard = unsafe { ThreadStateGuard::acquire(self.thread_state) };
+
+        // Objects created in the function must be released before the
+        // sub-interpreter's thread state stops being current.
+        let pool = unsafe { GILPool::new() };
+        f(pool.python())
+    }
+
+    /// Call a Python function in the sub-interpreter.
+    ///
+    /// See [MainPythonInterpreter::call()].
+    #[cfg(feature = "serialization")]
+    pub fn call<A, R>(&self, target: &str, args: &A) -> Result<R, CallError>
+    where
+        A: Serialize + ?Sized,
+        R: DeserializeOwned,
+    {
+        self.with_gil(|py| call_function(py, target, args, None::<&()>))
+    }
+
+    /// Call a Python function in the sub-interpreter with keyword arguments.
+    ///
+    /// See [MainPythonInterpreter::call_with_kwargs()].
+    #[cfg(feature = "serialization")]
+    pub fn call_with_kwargs<A, K, R>(
+        &self,
+        target: &str,
+        args: &A,
+        kwargs: &K,
+    ) -> Result<R, CallError>
+    where
+        A: Serialize + ?Sized,
+        K: Serialize + ?Sized,
+        R: DeserializeOwned,
+    {
+        self.with_gil(|py| call_function(py, target, args, Some(kwargs)))
+    }
+}
+
+impl<'main, 'interpreter, 'resources> Drop for SubInterpreter<'main, 'interpreter, 'resources> {
+    fn drop(&mut self) {
+        // Py_EndInterpreter() needs the sub-interpreter's thread state current
+        // and leaves no thread state current.
+        self.main.with_gil(|_| unsafe {
+            let main_thread_state = pyffi::PyThreadState_Swap(self.thread_state);
+            pyffi::Py_EndInterpreter(self.thread_state);
+            pyffi::PyThreadState_Swap(main_thread_state);
+        });
+    }
+}
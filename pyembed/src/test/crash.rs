@@ -0,0 +1,75 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    super::default_interpreter_config,
+    crate::MainPythonInterpreter,
+    pyo3::types::PyDict,
+    rusty_fork::rusty_fork_test,
+};
+
+/// Crashes a forked child process and returns whether it died from SIGSEGV.
+///
+/// The child has a second thread, blocked in the kernel when it crashes.
+const CRASH_CHILD: &str = "import ctypes, os, signal, threading
+pid = os.fork()
+if pid == 0:
+    event = threading.Event()
+    thread = threading.Thread(target=event.wait)
+    thread.start()
+    while open(f'/proc/self/task/{thread.native_id}/syscall').read().startswith('running'):
+        pass
+    ctypes.string_at(0)
+    os._exit(0)
+_, status = os.waitpid(pid, 0)
+crashed = os.WIFSIGNALED(status) and os.WTERMSIG(status) == signal.SIGSEGV
+";
+
+rusty_fork_test! {
+    #[test]
+    fn crash_report_written() {
+        let dir = std::env::temp_dir().join(format!("pyembed-crash-reports-{}", std::process::id()));
+
+        let mut config = default_interpreter_config();
+        config.crash_report_dir = Some(dir.clone());
+
+        let interp = MainPythonInterpreter::new(config).unwrap();
+        assert!(dir.is_dir());
+
+        interp.with_gil(|py| {
+            let locals = PyDict::new(py);
+            py.run(CRASH_CHILD, None, Some(locals)).unwrap();
+
+            assert!(locals.get_item("crashed").unwrap().extract::<bool>().unwrap());
+        });
+
+        let reports = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        assert_eq!(reports.len(), 1);
+
+        let report = std::fs::read_to_string(&reports[0]).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(report.starts_with("Fatal signal: 11 (SIGSEGV)\n"));
+        for section in [
+            "\nRegisters:\n",
+            "\nPython tracebacks:\n",
+            "\nThreads:\n",
+            "\nStacks:\n",
+            "\nLoaded modules:\n",
+        ] {
+            assert!(report.contains(section), "report contains {:?}", section);
+        }
+
+        // The traceback of the thread that crashed points at the ctypes.string_at() call.
+        assert!(report.contains("File \"<string>\", line 9 in <module>"));
+
+        // Stack memory is written for both threads.
+        let stacks = &report[report.find("\nStacks:\n").unwrap()..report.find("\nLoaded modules:\n").unwrap()];
+        assert_eq!(stacks.matches("\nThread ").count(), 2);
+        assert!(!stacks.contains("<unavailable>"), "{}", stacks);
+    }
+}
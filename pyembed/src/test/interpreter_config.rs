@@ -4,7 +4,7 @@
 
 use {
     super::{default_interpreter_config, set_sys_paths, PYTHON_INTERPRETER_PATH},
-    crate::{MainPythonInterpreter, OxidizedPythonInterpreterConfig},
+    crate::{MainPythonInterpreter, NewInterpreterError, OxidizedPythonInterpreterConfig},
     pyo3::{
         ffi as pyffi,
         prelude::*,
@@ -321,6 +321,163 @@ rusty_fork_test! {
         MainPythonInterpreter::new(config).unwrap();
     }
 
+    #[test]
+    fn test_allocator_stats_disabled() {
+        let mut config = default_interpreter_config();
+        config.oxidized_importer = true;
+
+        let interp = MainPythonInterpreter::new(config).unwrap();
+
+        assert!(crate::allocator_stats().is_none());
+
+        interp.with_gil(|py| {
+            let stats = py
+                .import("oxidized_importer")
+                .unwrap()
+                .call_method0("allocator_stats")
+                .unwrap();
+            assert!(stats.is_none());
+        });
+    }
+
+    #[test]
+    fn test_allocator_stats() {
+        let mut config = default_interpreter_config();
+        config.oxidized_importer = true;
+        config.allocator_stats = true;
+
+        let interp = MainPythonInterpreter::new(config).unwrap();
+
+        let stats = crate::allocator_stats().unwrap();
+        for domain in [&stats.raw, &stats.mem, &stats.obj] {
+            assert!(domain.allocations > 0);
+            assert!(domain.live_allocations > 0);
+            assert!(domain.peak_bytes >= domain.current_bytes());
+        }
+
+        interp.with_gil(|py| {
+            let before = crate::allocator_stats().unwrap().obj.allocations;
+            let _data = PyList::new(py, (0..1000).map(|i| i.to_string()));
+            assert!(crate::allocator_stats().unwrap().obj.allocations > before);
+
+            let stats = py
+                .import("oxidized_importer")
+                .unwrap()
+                .call_method0("allocator_stats")
+                .unwrap();
+
+            let obj = stats.get_item("obj").unwrap();
+            for key in ["allocated_bytes", "peak_bytes", "live_allocations"] {
+                assert!(obj.get_item(key).unwrap().extract::<u64>().unwrap() > 0);
+            }
+        });
+    }
+
+    #[test]
+    fn test_allocator_stats_custom_backend() {
+        let mut config = default_interpreter_config();
+
+        config.allocator_backend = MemoryAllocatorBackend::Rust;
+        config.allocator_raw = true;
+        config.allocator_debug = true;
+        config.allocator_stats_report = true;
+
+        MainPythonInterpreter::new(config).unwrap();
+
+        assert!(crate::allocator_stats().unwrap().raw.allocations > 0);
+    }
+
+    #[test]
+    fn test_allocator_stats_reinstall() {
+        let mut config = default_interpreter_config();
+        config.allocator_stats = true;
+
+        // Statistics keep being recorded for a later interpreter using the same allocators.
+        std::mem::drop(MainPythonInterpreter::new(config.clone()).unwrap());
+        std::mem::drop(MainPythonInterpreter::new(config.clone()).unwrap());
+
+        // But they can't be recorded for different allocators.
+        config.allocator_backend = MemoryAllocatorBackend::Rust;
+        config.allocator_raw = true;
+
+        match MainPythonInterpreter::new(config) {
+            Err(NewInterpreterError::Simple(msg)) => assert!(msg.contains("once per process")),
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+
+    #[test]
+    fn test_allocator_tracing() {
+        let mut config = default_interpreter_config();
+
+        config.oxidized_importer = true;
+        config.allocator_backend = MemoryAllocatorBackend::Tracing;
+        config.allocator_raw = true;
+        config.allocator_mem = true;
+        config.allocator_obj = true;
+        config.allocator_tracing_capacity = 1024;
+
+        let interp = MainPythonInterpreter::new(config).unwrap();
+
+        assert_eq!(interp.allocator.as_ref().unwrap().backend(), MemoryAllocatorBackend::Tracing);
+
+        interp.with_gil(|py| {
+            py.run(
+                "def make():\n    return [str(i) for i in range(1000)]\ndata = make()",
+                None,
+                None,
+            )
+            .unwrap();
+
+            let events = crate::allocation_trace().unwrap();
+            assert_eq!(events.len(), 1024);
+            if py.version_info() >= (3, 11) {
+                assert!(events.iter().all(|event| event.frames.is_empty()));
+            } else {
+                assert!(events.iter().any(|event| {
+                    event.domain == "obj"
+                        && matches!(event.frames.first(), Some((filename, _)) if filename == "<string>")
+                }));
+                // Calling frames are walked too.
+                assert!(events.iter().any(|event| {
+                    event.frames.len() >= 2
+                        && event.frames.iter().all(|(filename, _)| filename == "<string>")
+                }));
+            }
+
+            let trace = py
+                .import("oxidized_importer")
+                .unwrap()
+                .call_method0("allocation_trace")
+                .unwrap()
+                .cast_as::<PyList>()
+                .unwrap();
+            assert!(!trace.is_empty());
+        });
+    }
+
+    #[test]
+    fn test_allocator_tracing_custom_backend() {
+        let mut config = default_interpreter_config();
+
+        config.allocator_backend = MemoryAllocatorBackend::Tracing;
+        config.allocator_tracing_backend = MemoryAllocatorBackend::Rust;
+        config.allocator_raw = true;
+        config.allocator_pymalloc_arena = true;
+
+        let interp = MainPythonInterpreter::new(config).unwrap();
+
+        interp.with_gil(|py| {
+            py.run("import json; json.dumps(list(range(100)))", None, None).unwrap();
+
+            let events = crate::allocation_trace().unwrap();
+            assert!(events.iter().any(|event| {
+                event.domain == "raw" && event.kind == crate::AllocationEventKind::Allocate
+            }));
+        });
+    }
+
     #[test]
     fn test_sys_paths_origin() {
         let mut config = OxidizedPythonInterpreterConfig::default();
@@ -555,6 +712,22 @@ rusty_fork_test! {
         assert_eq!(config.tcl_library, Some(origin.join("lib").join("tcl8.6")));
     }
 
+    #[test]
+    fn test_bytecode_cache_dir_origin() {
+        let mut config = default_interpreter_config();
+        config.bytecode_cache_dir = Some(PathBuf::from("$ORIGIN").join("pycache"));
+
+        let config = config.resolve().unwrap();
+
+        let origin = std::env::current_exe()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .to_path_buf();
+
+        assert_eq!(config.bytecode_cache_dir, Some(origin.join("pycache")));
+    }
+
     #[test]
     fn test_dev_mode() {
         let mut config = default_interpreter_config();
@@ -2,6 +2,8 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+#[cfg(feature = "serialization")]
+use crate::CallError;
 use {
     super::{default_interpreter_config, run_py_test},
     crate::MainPythonInterpreter,
@@ -35,8 +37,153 @@ rusty_fork_test! {
         std::mem::drop(interp);
     }
 
+    #[test]
+    fn entry_point_none() {
+        let mut config = default_interpreter_config();
+        config.entry_point = Some("sys:exit".to_string());
+
+        assert_eq!(MainPythonInterpreter::new(config).unwrap().run(), 0);
+    }
+
+    #[test]
+    fn entry_point_exit_code() {
+        let mut config = default_interpreter_config();
+        config.entry_point = Some("os:getppid".to_string());
+
+        let expected = unsafe { libc::getppid() };
+
+        assert_eq!(MainPythonInterpreter::new(config).unwrap().run(), expected);
+    }
+
+    #[test]
+    fn entry_point_nested_attribute() {
+        let mut config = default_interpreter_config();
+        config.entry_point = Some("string:digits.__len__".to_string());
+
+        assert_eq!(MainPythonInterpreter::new(config).unwrap().run(), 10);
+    }
+
+    #[test]
+    fn entry_point_errors() {
+        for entry_point in ["sys", "sys:", ":exit", "missing_module:main", "sys:missing"] {
+            let mut config = default_interpreter_config();
+            config.entry_point = Some(entry_point.to_string());
+
+            assert_eq!(MainPythonInterpreter::new(config).unwrap().run(), 1);
+        }
+    }
+
     #[test]
     fn multiprocessing_py() {
         run_py_test("test_multiprocessing.py").unwrap()
     }
 }
+
+#[cfg(feature = "serialization")]
+rusty_fork_test! {
+    #[test]
+    fn call_function() {
+        let interp = MainPythonInterpreter::new(default_interpreter_config()).unwrap();
+
+        let value: String = interp.call("json:dumps", &(vec![1, 2],)).unwrap();
+        assert_eq!(value, "[1, 2]");
+
+        let value: i64 = interp.call("builtins:max", &[1, 5, 3]).unwrap();
+        assert_eq!(value, 5);
+
+        let value: Vec<i64> = interp
+            .call_with_kwargs(
+                "builtins:sorted",
+                &(vec![3, 1, 2],),
+                &[("reverse", true)].into_iter().collect::<std::collections::BTreeMap<_, _>>(),
+            )
+            .unwrap();
+        assert_eq!(value, vec![3, 2, 1]);
+
+        let value: Option<String> = interp.call("os:environ.get", &("PYEMBED_MISSING",)).unwrap();
+        assert_eq!(value, None);
+
+        // Values are converted without an intermediate JSON representation.
+        let value: (f64, f64) = interp.call("builtins:divmod", &(7.5, 2)).unwrap();
+        assert_eq!(value, (3.0, 1.5));
+
+        let value: String = interp.call("builtins:repr", &((1, "a"),)).unwrap();
+        assert_eq!(value, "(1, 'a')");
+
+        let value: std::collections::BTreeSet<i64> = interp.call("builtins:set", &(vec![1, 1, 2],)).unwrap();
+        assert_eq!(value, [1, 2].into_iter().collect());
+
+        let value: Vec<u8> = interp.call("builtins:bytes", &(vec![0u8, 255],)).unwrap();
+        assert_eq!(value, vec![0, 255]);
+
+        let value: std::collections::BTreeMap<i64, String> = interp
+            .call("builtins:dict", &(vec![(1, "a")],))
+            .unwrap();
+        assert_eq!(value, [(1, "a".to_string())].into_iter().collect());
+
+        #[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+        struct Point {
+            x: i64,
+            y: Option<i64>,
+        }
+
+        #[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+        enum Shape {
+            Empty,
+            Circle(f64),
+            Line(Point, Point),
+        }
+
+        let point = Point { x: 1, y: None };
+        let value: String = interp.call("builtins:repr", &(&point,)).unwrap();
+        assert_eq!(value, "{'x': 1, 'y': None}");
+        let value: Point = interp.call("copy:copy", &(&point,)).unwrap();
+        assert_eq!(value, point);
+
+        for shape in [
+            Shape::Empty,
+            Shape::Circle(1.5),
+            Shape::Line(Point { x: 0, y: Some(0) }, Point { x: 1, y: Some(1) }),
+        ] {
+            let value: Shape = interp.call("copy:copy", &(&shape,)).unwrap();
+            assert_eq!(value, shape);
+        }
+    }
+
+    #[test]
+    fn call_function_errors() {
+        let interp = MainPythonInterpreter::new(default_interpreter_config()).unwrap();
+        let no_args: Vec<()> = vec![];
+
+        match interp.call::<_, i64>("builtins:int", &("x",)) {
+            Err(CallError::Python(msg)) => assert!(msg.starts_with("ValueError")),
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        match interp.call::<_, i64>("builtins", &no_args) {
+            Err(CallError::Python(msg)) => assert!(msg.contains("module:function")),
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        match interp.call::<_, i64>("builtins:int", &42) {
+            Err(CallError::Serialize(_)) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        let kwargs = [(1, true)].into_iter().collect::<std::collections::BTreeMap<_, _>>();
+        match interp.call_with_kwargs::<_, _, i64>("builtins:int", &no_args, &kwargs) {
+            Err(CallError::Serialize(_)) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        match interp.call::<_, i64>("builtins:object", &no_args) {
+            Err(CallError::Deserialize(_)) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        match interp.call::<_, i64>("builtins:str", &(42,)) {
+            Err(CallError::Deserialize(_)) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+}
@@ -8,10 +8,13 @@ use {
     std::path::PathBuf,
 };
 
+#[cfg(target_os = "linux")]
+mod crash;
 mod importer;
 mod interpreter_config;
 mod main_python_interpreter;
 mod python_resources;
+mod sub_interpreter;
 
 pub const PYTHON_INTERPRETER_PATH: &str = env!("PYTHON_INTERPRETER_PATH");
 
@@ -3,10 +3,11 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
+    super::default_interpreter_config,
     crate::OxidizedPythonInterpreterConfig,
     anyhow::{anyhow, Result},
     oxidized_importer::{PackedResourcesSource, PyTempDir, PythonResourcesState},
-    python_packed_resources::Resource,
+    python_packed_resources::{CompressionPolicy, Resource},
     rusty_fork::rusty_fork_test,
 };
 
@@ -40,8 +41,158 @@ fn multiple_resource_blobs() -> Result<()> {
     resources.index_data(&data0).unwrap();
     resources.index_data(&data1).unwrap();
 
-    assert!(resources.has_resource("foo"));
-    assert!(resources.has_resource("bar"));
+    assert!(resources.has_resource("foo").unwrap());
+    assert!(resources.has_resource("bar").unwrap());
+
+    Ok(())
+}
+
+#[test]
+fn name_index_resources() -> Result<()> {
+    let resources0 = vec![
+        Resource {
+            name: "foo".into(),
+            is_python_module: true,
+            in_memory_source: Some(vec![42].into()),
+            ..Default::default()
+        },
+        Resource {
+            name: "bar".into(),
+            is_python_module: true,
+            in_memory_bytecode: Some(vec![42, 42].into()),
+            ..Default::default()
+        },
+    ];
+    let mut data0 = vec![];
+    python_packed_resources::write_packed_resources_v4(
+        &resources0,
+        &mut data0,
+        None,
+        CompressionPolicy::None,
+        true,
+    )?;
+
+    let mut state1 = PythonResourcesState::default();
+    state1
+        .add_resource(Resource {
+            name: "foo".into(),
+            is_python_module: true,
+            in_memory_bytecode: Some(vec![1].into()),
+            ..Default::default()
+        })
+        .unwrap();
+    state1
+        .add_resource(Resource {
+            name: "baz".into(),
+            is_python_module: true,
+            in_memory_source: Some(vec![2].into()),
+            ..Default::default()
+        })
+        .unwrap();
+    let data1 = state1.serialize_resources(true, true)?;
+
+    let mut resources = PythonResourcesState::default();
+    resources.index_data(&data0).unwrap();
+    resources.index_data(&data1).unwrap();
+
+    assert!(resources.has_resource("foo").unwrap());
+    assert!(resources.has_resource("bar").unwrap());
+    assert!(resources.has_resource("baz").unwrap());
+    assert!(!resources.has_resource("missing").unwrap());
+
+    // Data from the second blob is merged into the lazily parsed resource.
+    let data = resources.serialize_resources(true, true)?;
+    let foo = python_packed_resources::load_resources(&data)
+        .unwrap()
+        .map(|r| r.unwrap())
+        .find(|r| r.name == "foo")
+        .unwrap();
+    assert_eq!(foo.in_memory_source, Some(vec![42].into()));
+    assert_eq!(foo.in_memory_bytecode, Some(vec![1].into()));
+
+    assert_eq!(resources.resource_layer("foo"), Some(1));
+    assert_eq!(resources.resource_layer("bar"), Some(0));
+    assert_eq!(resources.resource_layer("baz"), Some(1));
+
+    Ok(())
+}
+
+#[test]
+fn layered_resources() -> Result<()> {
+    let mut state0 = PythonResourcesState::default();
+    for name in ["foo", "bar"] {
+        state0
+            .add_resource(Resource {
+                name: name.into(),
+                is_python_module: true,
+                in_memory_source: Some(vec![42].into()),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    let data0 = state0.serialize_resources(true, true)?;
+
+    let mut state1 = PythonResourcesState::default();
+    state1
+        .add_resource(Resource {
+            name: "foo".into(),
+            is_python_module: true,
+            in_memory_bytecode: Some(vec![1].into()),
+            ..Default::default()
+        })
+        .unwrap();
+    state1
+        .add_resource(Resource {
+            name: "bar".into(),
+            is_tombstone: true,
+            ..Default::default()
+        })
+        .unwrap();
+    let data1 = state1.serialize_resources(true, true)?;
+
+    let current_dir = std::env::current_exe()?
+        .parent()
+        .ok_or_else(|| anyhow!("unable to find current exe parent"))?
+        .to_path_buf();
+    let resources_dir = current_dir.join("resources");
+    if !resources_dir.exists() {
+        std::fs::create_dir(&resources_dir)?;
+    }
+    let overlay_path = resources_dir.join("test_layered_resources");
+    std::fs::write(&overlay_path, &data1)?;
+
+    let mut config = OxidizedPythonInterpreterConfig::default();
+    config
+        .packed_resources
+        .push(PackedResourcesSource::Memory(&data0));
+    config
+        .packed_resources
+        .push(PackedResourcesSource::OptionalMemoryMappedPath(
+            overlay_path,
+        ));
+    config
+        .packed_resources
+        .push(PackedResourcesSource::OptionalMemoryMappedPath(
+            resources_dir.join("missing"),
+        ));
+
+    let resources = PythonResourcesState::try_from(&config.resolve()?)?;
+
+    assert!(resources.has_resource("foo").unwrap());
+    assert!(!resources.has_resource("bar").unwrap());
+    assert_eq!(resources.resource_layer("foo"), Some(1));
+
+    let data = resources.serialize_resources(true, true)?;
+    let loaded = python_packed_resources::load_resources(&data)
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(loaded.len(), 2);
+    assert_eq!(loaded[0].name, "bar");
+    assert!(loaded[0].is_tombstone);
+    assert_eq!(loaded[1].name, "foo");
+    assert_eq!(loaded[1].in_memory_source, Some(vec![42].into()));
+    assert_eq!(loaded[1].in_memory_bytecode, Some(vec![1].into()));
 
     Ok(())
 }
@@ -83,7 +234,7 @@ fn test_memory_mapped_file_resources() -> Result<()> {
     let resolved = config.clone().resolve()?;
     let resources = PythonResourcesState::try_from(&resolved)?;
 
-    assert!(resources.has_resource("foo"));
+    assert!(resources.has_resource("foo").unwrap());
 
     // Now let's try with relative paths.
     let relative_path = pathdiff::diff_paths(&resources_path, std::env::current_dir()?).unwrap();
@@ -94,7 +245,212 @@ fn test_memory_mapped_file_resources() -> Result<()> {
 
     let resolved = config.resolve()?;
     let resources = PythonResourcesState::try_from(&resolved)?;
-    assert!(resources.has_resource("foo"));
+    assert!(resources.has_resource("foo").unwrap());
+
+    Ok(())
+}
+
+#[test]
+fn reload_memory_mapped_file_resources() -> Result<()> {
+    let mut state0 = PythonResourcesState::default();
+    for name in ["foo", "bar"] {
+        state0
+            .add_resource(Resource {
+                name: name.into(),
+                is_python_module: true,
+                in_memory_source: Some(vec![42].into()),
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    let data0 = state0.serialize_resources(true, true)?;
+
+    let mut state1 = PythonResourcesState::default();
+    state1
+        .add_resource(Resource {
+            name: "baz".into(),
+            is_python_module: true,
+            in_memory_source: Some(vec![1].into()),
+            ..Default::default()
+        })
+        .unwrap();
+    let data1 = state1.serialize_resources(true, true)?;
+
+    let mut state2 = PythonResourcesState::default();
+    state2
+        .add_resource(Resource {
+            name: "foo".into(),
+            is_tombstone: true,
+            ..Default::default()
+        })
+        .unwrap();
+    state2
+        .add_resource(Resource {
+            name: "qux".into(),
+            is_python_module: true,
+            in_memory_source: Some(vec![2].into()),
+            ..Default::default()
+        })
+        .unwrap();
+    let data2 = state2.serialize_resources(true, true)?;
+
+    let current_dir = std::env::current_exe()?
+        .parent()
+        .ok_or_else(|| anyhow!("unable to find current exe parent"))?
+        .to_path_buf();
+    let resources_dir = current_dir.join("resources");
+    if !resources_dir.exists() {
+        std::fs::create_dir(&resources_dir)?;
+    }
+    let resources_path = resources_dir.join("test_reload_memory_mapped_file_resources");
+    let new_path = resources_dir.join("test_reload_memory_mapped_file_resources.new");
+    std::fs::write(&resources_path, &data1)?;
+
+    let mut config = OxidizedPythonInterpreterConfig::default();
+    config
+        .packed_resources
+        .push(PackedResourcesSource::Memory(&data0));
+    config
+        .packed_resources
+        .push(PackedResourcesSource::MemoryMappedPath(
+            resources_path.clone(),
+        ));
+
+    let mut resources = PythonResourcesState::try_from(&config.resolve()?)?;
+    resources
+        .add_resource(Resource {
+            name: "added".into(),
+            is_python_module: true,
+            in_memory_source: Some(vec![3].into()),
+            ..Default::default()
+        })
+        .unwrap();
+
+    assert!(resources.has_resource("baz").unwrap());
+    assert!(!resources.has_resource("qux").unwrap());
+
+    assert!(resources
+        .reload_path_memory_mapped(resources_dir.join("missing"))
+        .is_err());
+
+    // Invalid data is rejected and existing resources remain.
+    std::fs::write(&new_path, b"foo")?;
+    std::fs::rename(&new_path, &resources_path)?;
+    assert!(resources
+        .reload_path_memory_mapped(&resources_path)
+        .is_err());
+    assert!(resources.has_resource("baz").unwrap());
+
+    std::fs::write(&new_path, &data2)?;
+    std::fs::rename(&new_path, &resources_path)?;
+    resources
+        .reload_path_memory_mapped(&resources_path)
+        .map_err(|e| anyhow!(e))?;
+
+    assert!(!resources.has_resource("foo").unwrap());
+    assert!(resources.has_resource("bar").unwrap());
+    assert!(!resources.has_resource("baz").unwrap());
+    assert!(resources.has_resource("qux").unwrap());
+    assert_eq!(resources.resource_layer("qux"), Some(1));
+    assert!(resources.has_resource("added").unwrap());
+    assert_eq!(resources.resource_layer("added"), None);
+    assert!(resources.has_resource("sys").unwrap());
+
+    Ok(())
+}
+
+#[test]
+fn reload_memory_mapped_file_resources_failure() -> Result<()> {
+    // The first layer is parsed lazily and the name index entry of one resource
+    // is corrupt. This goes unnoticed until an overlay touches the resource.
+    let resources0 = vec![
+        Resource {
+            name: "corrupt_module".into(),
+            is_python_module: true,
+            in_memory_source: Some(vec![42].into()),
+            ..Default::default()
+        },
+        Resource {
+            name: "foo".into(),
+            is_python_module: true,
+            in_memory_source: Some(vec![42].into()),
+            ..Default::default()
+        },
+    ];
+    let mut data0 = vec![];
+    python_packed_resources::write_packed_resources_v4(
+        &resources0,
+        &mut data0,
+        None,
+        CompressionPolicy::None,
+        true,
+    )?;
+
+    // The entry's resources index offset is followed by the name length.
+    let entry = [&0u32.to_le_bytes()[..], &14u16.to_le_bytes()[..]].concat();
+    let positions = data0
+        .windows(entry.len())
+        .enumerate()
+        .filter(|(_, window)| *window == entry.as_slice())
+        .map(|(position, _)| position)
+        .collect::<Vec<_>>();
+    assert_eq!(positions.len(), 1);
+    data0[positions[0]..positions[0] + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+    let mut state1 = PythonResourcesState::default();
+    state1
+        .add_resource(Resource {
+            name: "baz".into(),
+            is_python_module: true,
+            in_memory_source: Some(vec![1].into()),
+            ..Default::default()
+        })
+        .unwrap();
+    let data1 = state1.serialize_resources(true, true)?;
+
+    let mut state2 = PythonResourcesState::default();
+    state2
+        .add_resource(Resource {
+            name: "corrupt_module".into(),
+            is_python_module: true,
+            in_memory_bytecode: Some(vec![2].into()),
+            ..Default::default()
+        })
+        .unwrap();
+    let data2 = state2.serialize_resources(true, true)?;
+
+    let current_dir = std::env::current_exe()?
+        .parent()
+        .ok_or_else(|| anyhow!("unable to find current exe parent"))?
+        .to_path_buf();
+    let resources_dir = current_dir.join("resources");
+    if !resources_dir.exists() {
+        std::fs::create_dir(&resources_dir)?;
+    }
+    let resources_path = resources_dir.join("test_reload_memory_mapped_file_resources_failure");
+    let new_path = resources_dir.join("test_reload_memory_mapped_file_resources_failure.new");
+    std::fs::write(&resources_path, &data1)?;
+
+    let mut resources = PythonResourcesState::default();
+    resources.index_data(&data0).unwrap();
+    resources
+        .index_path_memory_mapped(&resources_path)
+        .map_err(|e| anyhow!(e))?;
+
+    assert!(resources.has_resource("foo").unwrap());
+    assert!(resources.has_resource("baz").unwrap());
+
+    std::fs::write(&new_path, &data2)?;
+    std::fs::rename(&new_path, &resources_path)?;
+    assert_eq!(
+        resources.reload_path_memory_mapped(&resources_path),
+        Err("name index entry out of bounds".to_string())
+    );
+
+    // The failed reload left the previously indexed resources in place.
+    assert!(resources.has_resource("foo").unwrap());
+    assert!(resources.has_resource("baz").unwrap());
+    assert_eq!(resources.resource_layer("baz"), Some(1));
 
     Ok(())
 }
@@ -123,4 +479,69 @@ rusty_fork_test! {
         };
         assert!(!path.is_dir());
     }
+
+    #[test]
+    fn reload_packed_resources() {
+        let serialize_module = |source: &[u8]| {
+            let mut state = PythonResourcesState::default();
+            state
+                .add_resource(Resource {
+                    name: "hot_module".into(),
+                    is_python_module: true,
+                    in_memory_source: Some(source.to_vec().into()),
+                    ..Default::default()
+                })
+                .unwrap();
+            state.serialize_resources(true, true).unwrap()
+        };
+
+        let resources_dir = std::env::current_exe()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("resources");
+        if !resources_dir.exists() {
+            std::fs::create_dir(&resources_dir).unwrap();
+        }
+        let resources_path = resources_dir.join("test_reload_packed_resources");
+        let new_path = resources_dir.join("test_reload_packed_resources.new");
+        std::fs::write(&resources_path, serialize_module(b"VALUE = 1\n")).unwrap();
+
+        let mut config = default_interpreter_config();
+        config.oxidized_importer = true;
+        config
+            .packed_resources
+            .push(PackedResourcesSource::MemoryMappedPath(
+                resources_path.clone(),
+            ));
+        let interp = crate::MainPythonInterpreter::new(config).unwrap();
+
+        let value = |module: &pyo3::PyAny| -> i64 {
+            module.getattr("VALUE").unwrap().extract().unwrap()
+        };
+
+        interp.with_gil(|py| {
+            assert_eq!(value(py.import("hot_module").unwrap()), 1);
+        });
+
+        std::fs::write(&new_path, serialize_module(b"VALUE = 2\n")).unwrap();
+        std::fs::rename(&new_path, &resources_path).unwrap();
+
+        interp.reload_packed_resources(&resources_path).unwrap();
+        assert!(interp
+            .reload_packed_resources(resources_dir.join("missing"))
+            .is_err());
+
+        interp.with_gil(|py| {
+            let module = py.import("hot_module").unwrap();
+            assert_eq!(value(module), 1);
+
+            let module = py
+                .import("importlib")
+                .unwrap()
+                .call_method1("reload", (module,))
+                .unwrap();
+            assert_eq!(value(module), 2);
+        });
+    }
 }
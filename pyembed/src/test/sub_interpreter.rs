@@ -0,0 +1,181 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    super::default_interpreter_config,
+    crate::MainPythonInterpreter,
+    pyo3::{ffi as pyffi, prelude::*, AsPyPointer},
+    rusty_fork::rusty_fork_test,
+};
+
+fn new_interpreter<'interpreter, 'resources>() -> MainPythonInterpreter<'interpreter, 'resources> {
+    let mut config = default_interpreter_config();
+    config.oxidized_importer = true;
+
+    MainPythonInterpreter::new(config).unwrap()
+}
+
+/// Obtain the id() of the OxidizedFinder on sys.meta_path.
+fn finder_id(py: Python) -> usize {
+    let sys = py.import("sys").unwrap();
+    let meta_path = sys.getattr("meta_path").unwrap();
+
+    let finder = meta_path.get_item(0).unwrap();
+    assert_eq!(finder.get_type().name().unwrap(), "OxidizedFinder");
+
+    finder.as_ptr() as usize
+}
+
+/// Whether another thread can acquire the GIL.
+///
+/// `PyGILState_Check()` always returns 1 once sub-interpreters exist, so it
+/// can't be used to tell whether the GIL was released.
+fn gil_is_released() -> bool {
+    let (tx, rx) = std::sync::mpsc::channel();
+
+    std::thread::spawn(move || {
+        Python::with_gil(|_| tx.send(()).unwrap());
+    });
+
+    rx.recv_timeout(std::time::Duration::from_secs(10)).is_ok()
+}
+
+rusty_fork_test! {
+    #[test]
+    fn sub_interpreter_gil_state() {
+        let interp = new_interpreter();
+        let sub = interp.new_sub_interpreter().unwrap();
+
+        assert!(gil_is_released());
+
+        sub.with_gil(|py| {
+            py.import("sys").unwrap();
+        });
+
+        assert!(gil_is_released());
+
+        std::mem::drop(sub);
+
+        assert!(gil_is_released());
+
+        interp.with_gil(|py| {
+            py.import("sys").unwrap();
+        });
+    }
+
+    #[test]
+    fn sub_interpreter_thread_state() {
+        let interp = new_interpreter();
+        let sub = interp.new_sub_interpreter().unwrap();
+
+        let main_state = interp.with_gil(|_| unsafe { pyffi::PyThreadState_Get() } as usize);
+        let sub_state = sub.with_gil(|_| unsafe { pyffi::PyThreadState_Get() } as usize);
+
+        // The sub-interpreter's own thread state is used every time.
+        assert_ne!(main_state, sub_state);
+        assert_eq!(
+            sub.with_gil(|_| unsafe { pyffi::PyThreadState_Get() } as usize),
+            sub_state
+        );
+    }
+
+    #[test]
+    fn sub_interpreter_nested_with_gil() {
+        let interp = new_interpreter();
+        let sub = interp.new_sub_interpreter().unwrap();
+
+        let nested = |f: &dyn Fn()| {
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).is_err()
+        };
+
+        assert!(nested(&|| sub.with_gil(|_| sub.with_gil(|_| ()))));
+        assert!(nested(&|| interp.with_gil(|_| sub.with_gil(|_| ()))));
+        assert!(nested(&|| sub.with_gil(|_| interp.with_gil(|_| ()))));
+
+        // The GIL is released when unwinding, so both interpreters remain usable.
+        sub.with_gil(|py| {
+            py.import("sys").unwrap();
+        });
+        interp.with_gil(|py| {
+            py.import("sys").unwrap();
+        });
+    }
+
+    #[test]
+    fn sub_interpreter_importer() {
+        let interp = new_interpreter();
+        let a = interp.new_sub_interpreter().unwrap();
+        let b = interp.new_sub_interpreter().unwrap();
+
+        let main_id = interp.with_gil(finder_id);
+        let a_id = a.with_gil(finder_id);
+        let b_id = b.with_gil(finder_id);
+
+        assert_ne!(main_id, a_id);
+        assert_ne!(main_id, b_id);
+        assert_ne!(a_id, b_id);
+
+        a.with_gil(|py| {
+            let sys = py.import("sys").unwrap();
+            assert!(sys.getattr("oxidized").unwrap().extract::<bool>().unwrap());
+
+            // Standard library modules are importable.
+            let json = py.import("json").unwrap();
+            assert_eq!(
+                json.call_method1("dumps", (42,))
+                    .unwrap()
+                    .extract::<String>()
+                    .unwrap(),
+                "42"
+            );
+        });
+    }
+
+    #[test]
+    fn sub_interpreter_module_state() {
+        let interp = new_interpreter();
+        let a = interp.new_sub_interpreter().unwrap();
+        let b = interp.new_sub_interpreter().unwrap();
+
+        a.with_gil(|py| {
+            py.import("json").unwrap().setattr("marker", "a").unwrap();
+        });
+
+        b.with_gil(|py| {
+            let json = py.import("json").unwrap();
+            assert!(!json.hasattr("marker").unwrap());
+            json.setattr("marker", "b").unwrap();
+        });
+
+        interp.with_gil(|py| {
+            let json = py.import("json").unwrap();
+            assert!(!json.hasattr("marker").unwrap());
+        });
+
+        a.with_gil(|py| {
+            let json = py.import("json").unwrap();
+            assert_eq!(
+                json.getattr("marker").unwrap().extract::<String>().unwrap(),
+                "a"
+            );
+        });
+
+        // A new sub-interpreter starts with fresh state after another is destroyed.
+        std::mem::drop(a);
+        let c = interp.new_sub_interpreter().unwrap();
+
+        c.with_gil(|py| {
+            let json = py.import("json").unwrap();
+            assert!(!json.hasattr("marker").unwrap());
+        });
+
+        b.with_gil(|py| {
+            let json = py.import("json").unwrap();
+            assert_eq!(
+                json.getattr("marker").unwrap().extract::<String>().unwrap(),
+                "b"
+            );
+        });
+    }
+}
@@ -58,6 +58,17 @@ class TestImporterConstruction(unittest.TestCase):
         f = OxidizedFinder(relative_path_origin="/path/to/origin")
         self.assertEqual(f.origin, "/path/to/origin")
 
+    def test_bytecode_cache_dir(self):
+        f = OxidizedFinder()
+        self.assertIsNone(f.bytecode_cache_dir)
+
+        f = OxidizedFinder(bytecode_cache_dir="/path/to/cache")
+        self.assertEqual(f.bytecode_cache_dir, "/path/to/cache")
+
+    def test_bytecode_cache_dir_bad_value(self):
+        with self.assertRaises(TypeError):
+            OxidizedFinder(bytecode_cache_dir=True)
+
 
 if __name__ == "__main__":
     unittest.main()
@@ -20,7 +20,9 @@ SYMBOL_ATTRIBUTES = {
     "OxidizedFinder": {
         "add_resource",
         "add_resources",
+        "bytecode_cache_dir",
         "create_module",
+        "enable_import_trace",
         "exec_module",
         "find_distributions",
         "find_module",
@@ -30,6 +32,7 @@ SYMBOL_ATTRIBUTES = {
         "get_filename",
         "get_resource_reader",
         "get_source",
+        "import_trace",
         "index_bytes",
         "index_file_memory_mapped",
         "index_interpreter_builtins",
@@ -43,7 +46,9 @@ SYMBOL_ATTRIBUTES = {
         "path_hook",
         "path_hook_base_str",
         "pkg_resources_import_auto_register",
+        "reload_file_memory_mapped",
         "serialize_indexed_resources",
+        "write_import_trace",
     },
     "OxidizedPathEntryFinder": {
         "_package",
@@ -81,6 +86,8 @@ SYMBOL_ATTRIBUTES = {
         "is_namespace_package",
         "is_package",
         "is_shared_library",
+        "is_tombstone",
+        "layer",
         "name",
         "relative_path_distribution_resources",
         "relative_path_extension_module_shared_library",
@@ -99,22 +106,55 @@ SYMBOL_ATTRIBUTES = {
     },
     "OxidizedResourceReader": {
         "contents",
+        "files",
         "is_resource",
         "open_resource",
         "resource_path",
     },
+    "OxidizedTraversable": {
+        "__rtruediv__",
+        "__truediv__",
+        "is_dir",
+        "is_file",
+        "iterdir",
+        "joinpath",
+        "name",
+        "open",
+        "read_bytes",
+        "read_text",
+    },
+    "OxidizedZipDistribution": {
+        "_normalized_name",
+        "entry_points",
+        "files",
+        "locate_file",
+        "metadata",
+        "name",
+        "read_text",
+        "requires",
+        "version",
+    },
     "OxidizedZipFinder": {
         "create_module",
         "exec_module",
+        "find_distributions",
         "find_module",
         "find_spec",
         "from_path",
         "from_zip_data",
         "get_code",
+        "get_data",
+        "get_resource_reader",
         "get_source",
         "invalidate_caches",
         "is_package",
     },
+    "OxidizedZipResourceReader": {
+        "contents",
+        "is_resource",
+        "open_resource",
+        "resource_path",
+    },
     "PythonExtensionModule": {"name"},
     "PythonModuleBytecode": {
         "bytecode",
@@ -125,6 +165,8 @@ SYMBOL_ATTRIBUTES = {
     "PythonModuleSource": {"is_package", "module", "source"},
     "PythonPackageDistributionResource": {"data", "name", "package", "version"},
     "PythonPackageResource": {"data", "name", "package"},
+    "allocation_trace": set(),
+    "allocator_stats": set(),
     "decode_source": set(),
     "find_resources_in_path": set(),
     "pkg_resources_find_distributions": set(),
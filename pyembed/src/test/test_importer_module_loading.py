@@ -14,6 +14,7 @@ import unittest
 
 from oxidized_importer import (
     OxidizedFinder,
+    OxidizedResource,
     OxidizedResourceCollector,
     find_resources_in_path,
 )
@@ -182,6 +183,221 @@ class TestImporterModuleLoading(unittest.TestCase):
         self.assertIn("dotinit.bar", sys.modules)
         self.assertNotIn("dotinit.__init__", sys.modules)
 
+    def _filesystem_source_finder(self, source, **kwargs):
+        origin = self.td / "origin"
+        origin.mkdir()
+
+        with (origin / "cached_module.py").open("wb") as fh:
+            fh.write(source)
+
+        resource = OxidizedResource()
+        resource.is_module = True
+        resource.name = "cached_module"
+        resource.relative_path_module_source = "cached_module.py"
+
+        f = OxidizedFinder(relative_path_origin=origin, **kwargs)
+        f.add_resource(resource)
+
+        return f
+
+    def test_bytecode_cache_disabled(self):
+        f = self._filesystem_source_finder(b"value = 42\n")
+
+        self.assertIsNone(f.bytecode_cache_dir)
+
+        spec = f.find_spec("cached_module", None)
+        self.assertIsNone(spec.cached)
+
+        m = importlib.util.module_from_spec(spec)
+        f.exec_module(m)
+        self.assertEqual(m.value, 42)
+
+    def test_bytecode_cache(self):
+        cache_dir = self.td / "cache"
+        source = b"value = 42\n"
+
+        f = self._filesystem_source_finder(source, bytecode_cache_dir=cache_dir)
+
+        cache_path = cache_dir / (
+            "cached_module.%s%s.pyc"
+            % (
+                sys.implementation.cache_tag,
+                ".opt-%d" % sys.flags.optimize if sys.flags.optimize else "",
+            )
+        )
+
+        spec = f.find_spec("cached_module", None)
+        self.assertEqual(spec.cached, str(cache_path))
+        self.assertFalse(cache_path.exists())
+
+        m = importlib.util.module_from_spec(spec)
+        self.assertEqual(m.__cached__, str(cache_path))
+        f.exec_module(m)
+        self.assertEqual(m.value, 42)
+
+        # Importing populates the cache with a hash-based .pyc.
+        data = cache_path.read_bytes()
+        self.assertEqual(data[0:4], importlib.util.MAGIC_NUMBER)
+        self.assertEqual(int.from_bytes(data[4:8], "little"), 0b11)
+        self.assertEqual(data[8:16], importlib.util.source_hash(source))
+        self.assertEqual(
+            marshal.loads(data[16:]),
+            compile(source, str(self.td / "origin" / "cached_module.py"), "exec"),
+        )
+
+        # Valid cache entries are used instead of compiling source.
+        code = compile("value = 'cached'", "cached_module", "exec")
+        cache_path.write_bytes(data[0:16] + marshal.dumps(code))
+
+        m = importlib.util.module_from_spec(spec)
+        f.exec_module(m)
+        self.assertEqual(m.value, "cached")
+
+    def test_bytecode_cache_stale(self):
+        cache_dir = self.td / "cache"
+
+        f = self._filesystem_source_finder(
+            b"value = 42\n", bytecode_cache_dir=cache_dir
+        )
+
+        spec = f.find_spec("cached_module", None)
+        m = importlib.util.module_from_spec(spec)
+        f.exec_module(m)
+        self.assertEqual(m.value, 42)
+
+        # Changing the source invalidates the cached bytecode.
+        source = b"value = 43\n"
+        (self.td / "origin" / "cached_module.py").write_bytes(source)
+
+        m = importlib.util.module_from_spec(spec)
+        f.exec_module(m)
+        self.assertEqual(m.value, 43)
+
+        data = pathlib.Path(spec.cached).read_bytes()
+        self.assertEqual(data[8:16], importlib.util.source_hash(source))
+
+    def test_import_trace_disabled(self):
+        f = OxidizedFinder()
+
+        self.assertIsNone(f.import_trace())
+
+        with self.assertRaisesRegex(ValueError, "import tracing is not enabled"):
+            f.write_import_trace(self.td / "trace")
+
+    def test_import_trace(self):
+        p = self._make_package("my_package")
+
+        with (p / "data.txt").open("wb") as fh:
+            fh.write(b"data")
+
+        f = self._finder_from_td()
+        f.enable_import_trace()
+        self.assertEqual(f.import_trace(), [])
+
+        self.assertIsNone(f.find_spec("missing_package", None))
+        self.assertIsNotNone(f.find_spec("my_package", None))
+        self.assertEqual(
+            f.get_resource_reader("my_package").open_resource("data.txt").read(),
+            b"data",
+        )
+
+        self.assertEqual(
+            f.import_trace(),
+            [
+                ("module", "my_package", None, "source-bytecode"),
+                ("package-resource", "my_package", "data.txt"),
+                ("missing", "missing_package"),
+            ],
+        )
+
+        trace_path = self.td / "trace"
+        f.write_import_trace(trace_path)
+
+        self.assertEqual(
+            trace_path.read_text("utf-8").splitlines(),
+            [
+                "# oxidized_importer import trace v1",
+                "module\tmy_package\t-\tsource-bytecode",
+                "package-resource\tmy_package\tdata.txt",
+                "missing\tmissing_package",
+            ],
+        )
+
+    def test_reload_file_memory_mapped(self):
+        module_path = self.td / "hot_module.py"
+        module_path.write_bytes(b"VALUE = 1\n")
+
+        with tempfile.TemporaryDirectory(prefix="oxidized_importer-test-") as td:
+            resources_path = pathlib.Path(td) / "resources"
+            resources_path.write_bytes(
+                self._finder_from_td().serialize_indexed_resources()
+            )
+
+            f = OxidizedFinder()
+            f.index_file_memory_mapped(resources_path)
+            sys.meta_path.insert(0, f)
+
+            class RecordingFinder:
+                invalidated = 0
+
+                def find_spec(self, *args):
+                    return None
+
+                def invalidate_caches(self):
+                    self.invalidated += 1
+
+            recorder = RecordingFinder()
+            sys.meta_path.append(recorder)
+
+            try:
+                import hot_module
+
+                self.assertEqual(hot_module.VALUE, 1)
+
+                module_path.write_bytes(b"VALUE = 2\n")
+                new_path = pathlib.Path(td) / "resources.new"
+                new_path.write_bytes(
+                    self._finder_from_td().serialize_indexed_resources()
+                )
+                os.replace(new_path, resources_path)
+
+                # Already imported modules aren't affected.
+                f.reload_file_memory_mapped(resources_path)
+                self.assertEqual(hot_module.VALUE, 1)
+                self.assertEqual(recorder.invalidated, 1)
+
+                importlib.reload(hot_module)
+                self.assertEqual(hot_module.VALUE, 2)
+            finally:
+                sys.modules.pop("hot_module", None)
+
+    def test_reload_file_memory_mapped_not_indexed(self):
+        f = OxidizedFinder()
+
+        with self.assertRaises(ValueError):
+            f.reload_file_memory_mapped(self.td / "resources")
+
+    def test_reload_file_memory_mapped_bad_data(self):
+        (self.td / "hot_module.py").write_bytes(b"VALUE = 1\n")
+
+        with tempfile.TemporaryDirectory(prefix="oxidized_importer-test-") as td:
+            resources_path = pathlib.Path(td) / "resources"
+            resources_path.write_bytes(
+                self._finder_from_td().serialize_indexed_resources()
+            )
+
+            f = OxidizedFinder()
+            f.index_file_memory_mapped(resources_path)
+
+            new_path = pathlib.Path(td) / "resources.new"
+            new_path.write_bytes(b"foo")
+            os.replace(new_path, resources_path)
+
+            with self.assertRaises(ValueError):
+                f.reload_file_memory_mapped(resources_path)
+
+            self.assertIsNotNone(f.find_spec("hot_module", None))
+
 
 if __name__ == "__main__":
     unittest.main()
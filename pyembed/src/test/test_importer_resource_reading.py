@@ -2,9 +2,11 @@
 # License, v. 2.0. If a copy of the MPL was not distributed with this
 # file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+import importlib.resources
 import io
 import os
 import pathlib
+import sys
 import tempfile
 import unittest
 
@@ -12,6 +14,7 @@ from oxidized_importer import (
     OxidizedFinder,
     OxidizedResourceCollector,
     OxidizedResourceReader,
+    OxidizedTraversable,
     find_resources_in_path,
 )
 
@@ -22,8 +25,15 @@ class TestImporterResourceReading(unittest.TestCase):
             prefix="oxidized_importer-test-"
         )
         self.td = pathlib.Path(self.raw_temp_dir.name)
+        self.old_meta_path = list(sys.meta_path)
 
     def tearDown(self):
+        sys.meta_path[:] = self.old_meta_path
+
+        for name in list(sys.modules):
+            if name == "my_package" or name.startswith("my_package."):
+                del sys.modules[name]
+
         self.raw_temp_dir.cleanup()
         del self.raw_temp_dir
         del self.td
@@ -125,6 +135,112 @@ class TestImporterResourceReading(unittest.TestCase):
         self.assertEqual(r.open_resource("child0/a.txt").getvalue(), b"a")
         self.assertEqual(r.open_resource("child1/b.txt").getvalue(), b"b")
 
+    def _make_resources_package(self):
+        p = self._make_package("my_package")
+
+        (p / "data").mkdir()
+        (p / "data" / "nested").mkdir()
+
+        with (p / "resource.txt").open("wb") as fh:
+            fh.write(b"my resource")
+        with (p / "data" / "x.json").open("wb") as fh:
+            fh.write(b'{"foo": "bar"}')
+        with (p / "data" / "nested" / "y.txt").open("wb") as fh:
+            fh.write(b"y\xc3\xa9")
+
+        return self._finder_from_td()
+
+    def test_files(self):
+        f = self._make_resources_package()
+        r = f.get_resource_reader("my_package")
+
+        root = r.files()
+        self.assertIsInstance(root, OxidizedTraversable)
+        self.assertEqual(root.name, "my_package")
+        self.assertTrue(root.is_dir())
+        self.assertFalse(root.is_file())
+
+        self.assertEqual(
+            [(t.name, t.is_dir()) for t in root.iterdir()],
+            [("data", True), ("resource.txt", False)],
+        )
+
+        data = root / "data"
+        self.assertIsInstance(data, OxidizedTraversable)
+        self.assertEqual(data.name, "data")
+        self.assertTrue(data.is_dir())
+        self.assertFalse(data.is_file())
+        self.assertEqual(
+            [t.name for t in data.iterdir()],
+            ["nested", "x.json"],
+        )
+
+        with self.assertRaises(IsADirectoryError):
+            data.read_bytes()
+
+        x = data / "x.json"
+        self.assertEqual(x.name, "x.json")
+        self.assertTrue(x.is_file())
+        self.assertFalse(x.is_dir())
+        self.assertEqual(x.read_bytes(), b'{"foo": "bar"}')
+        self.assertEqual(x.read_text(), '{"foo": "bar"}')
+
+        with self.assertRaises(NotADirectoryError):
+            x.iterdir()
+
+        y = root.joinpath("data", "nested/y.txt")
+        self.assertTrue(y.is_file())
+        self.assertEqual(y.read_text(encoding="utf-8"), "y\xe9")
+        self.assertEqual(y.read_text(encoding="latin-1"), "y\xc3\xa9")
+
+        with y.open("rb") as fh:
+            self.assertEqual(fh.read(), b"y\xc3\xa9")
+
+        with y.open("r", encoding="utf-8") as fh:
+            self.assertEqual(fh.read(), "y\xe9")
+
+        with y.open(encoding="utf-8") as fh:
+            self.assertEqual(fh.read(), "y\xe9")
+
+        with self.assertRaises(ValueError):
+            y.open("w")
+
+        self.assertEqual(
+            (data / "nested" / ".." / "x.json").read_bytes(), x.read_bytes()
+        )
+
+        missing = root / "missing.txt"
+        self.assertFalse(missing.is_file())
+        self.assertFalse(missing.is_dir())
+
+        with self.assertRaises(FileNotFoundError):
+            missing.read_bytes()
+
+        with self.assertRaises(FileNotFoundError):
+            missing.open("rb")
+
+    @unittest.skipIf(
+        sys.version_info < (3, 9), "importlib.resources.files() requires Python 3.9+"
+    )
+    def test_importlib_resources_files(self):
+        f = self._make_resources_package()
+        sys.meta_path.insert(0, f)
+
+        root = importlib.resources.files("my_package")
+        self.assertIsInstance(root, OxidizedTraversable)
+
+        self.assertEqual(
+            (root / "data" / "x.json").read_bytes(),
+            b'{"foo": "bar"}',
+        )
+        self.assertEqual(
+            root.joinpath("resource.txt").read_text(),
+            "my resource",
+        )
+
+        with importlib.resources.as_file(root / "resource.txt") as path:
+            self.assertEqual(path.read_bytes(), b"my resource")
+
 
 if __name__ == "__main__":
     unittest.main()
@@ -72,6 +72,8 @@ class TestImporterResources(unittest.TestCase):
         self.assertFalse(resource.is_frozen_module)
         self.assertFalse(resource.is_extension_module)
         self.assertFalse(resource.is_shared_library)
+        self.assertFalse(resource.is_tombstone)
+        self.assertIsNone(resource.layer)
         self.assertEqual(resource.name, "")
 
     def test_resource_set_is_module(self):
@@ -139,6 +141,25 @@ class TestImporterResources(unittest.TestCase):
         with self.assertRaises(TypeError):
             resource.is_shared_library = None
 
+    def test_resource_set_is_tombstone(self):
+        resource = OxidizedResource()
+        resource.is_tombstone = True
+        self.assertTrue(resource.is_tombstone)
+        resource.is_tombstone = False
+        self.assertFalse(resource.is_tombstone)
+
+        with self.assertRaises(AttributeError):
+            del resource.is_tombstone
+
+        with self.assertRaises(TypeError):
+            resource.is_tombstone = None
+
+    def test_resource_layer_read_only(self):
+        resource = OxidizedResource()
+
+        with self.assertRaises(AttributeError):
+            resource.layer = 1
+
     def test_resource_set_name(self):
         resource = OxidizedResource()
 
@@ -666,6 +687,141 @@ class TestImporterResources(unittest.TestCase):
         self.assertEqual(modules["my_module"].in_memory_source, b"import io")
         self.assertEqual(modules["module_b"].in_memory_bytecode, b"dummy bytecode")
 
+    def test_index_layers(self):
+        base = OxidizedFinder()
+
+        for name in ("foo", "bar", "baz"):
+            m = OxidizedResource()
+            m.is_module = True
+            m.name = name
+            m.in_memory_source = b"import io"
+            base.add_resource(m)
+
+        overlay = OxidizedFinder()
+
+        m = OxidizedResource()
+        m.is_module = True
+        m.name = "foo"
+        m.in_memory_bytecode = b"dummy bytecode"
+        overlay.add_resource(m)
+
+        m = OxidizedResource()
+        m.name = "bar"
+        m.is_tombstone = True
+        overlay.add_resource(m)
+
+        f = OxidizedFinder()
+        f.index_bytes(base.serialize_indexed_resources())
+        f.index_bytes(overlay.serialize_indexed_resources())
+
+        resources = {r.name: r for r in f.indexed_resources() if r.is_module}
+        self.assertEqual(set(resources), {"foo", "baz"})
+
+        # Resources from later data are merged into resources from earlier data.
+        self.assertEqual(resources["foo"].layer, 1)
+        self.assertEqual(resources["foo"].in_memory_source, b"import io")
+        self.assertEqual(resources["foo"].in_memory_bytecode, b"dummy bytecode")
+        self.assertEqual(resources["baz"].layer, 0)
+
+        self.assertIsNone(f.find_spec("bar", None))
+        self.assertIsNotNone(f.find_spec("baz", None))
+
+    def _query_finder(self):
+        f = OxidizedFinder()
+        f.index_interpreter_builtin_extension_modules()
+
+        m = OxidizedResource()
+        m.is_module = True
+        m.name = "foo"
+        m.in_memory_source = b"import io"
+        m.in_memory_bytecode = marshal.dumps(compile("import io", "foo", "exec"))
+        m.in_memory_package_resources = {"a.txt": b"a", "b.txt": b"bb"}
+        f.add_resource(m)
+
+        m = OxidizedResource()
+        m.is_module = True
+        m.is_package = True
+        m.name = "foo.bar"
+        m.in_memory_source = b"value = 42"
+        f.add_resource(m)
+
+        return f
+
+    def test_find_resources(self):
+        f = self._query_finder()
+
+        resources = f.find_resources(name="foo*")
+        self.assertEqual([r.name for r in resources], ["foo", "foo.bar"])
+        self.assertIsInstance(resources[0], OxidizedResource)
+
+        resources = f.find_resources(name="foo.*")
+        self.assertEqual([r.name for r in resources], ["foo.bar"])
+
+        resources = f.find_resources(flags=["is_package"])
+        self.assertEqual([r.name for r in resources], ["foo.bar"])
+
+        resources = f.find_resources(name="f?o", flags=["is_module"])
+        self.assertEqual([r.name for r in resources], ["foo"])
+
+        resources = f.find_resources(flags=["is_builtin_extension_module"])
+        self.assertIn("_io", [r.name for r in resources])
+        self.assertNotIn("foo", [r.name for r in resources])
+
+        self.assertEqual(f.find_resources(name="[!f]oo"), [])
+        self.assertEqual(len(f.find_resources()), len(f.indexed_resources()))
+
+        with self.assertRaisesRegex(ValueError, "unknown resource flag: is_bogus"):
+            f.find_resources(flags=["is_bogus"])
+
+    def test_resource_field_sizes(self):
+        f = self._query_finder()
+
+        sizes = f.resource_field_sizes(name="foo*")
+        self.assertEqual(set(sizes), {"foo", "foo.bar"})
+        self.assertEqual(sizes["foo"]["in_memory_source"], 9)
+        self.assertEqual(
+            sizes["foo"]["in_memory_bytecode"],
+            len(marshal.dumps(compile("import io", "foo", "exec"))),
+        )
+        self.assertEqual(sizes["foo"]["in_memory_package_resources"], 3)
+        self.assertEqual(sizes["foo.bar"], {"in_memory_source": 10})
+
+        # Built-in extension modules have no data.
+        self.assertEqual(f.resource_field_sizes(name="_io"), {"_io": {}})
+
+    def test_export_resources(self):
+        f = self._query_finder()
+
+        data = f.export_resources(name="foo*")
+        self.assertIsInstance(data, bytes)
+
+        f2 = OxidizedFinder()
+        f2.index_bytes(data)
+
+        resources = {r.name: r for r in f2.indexed_resources()}
+        self.assertEqual(set(resources), {"foo", "foo.bar"})
+        self.assertEqual(resources["foo"].in_memory_source, b"import io")
+        self.assertEqual(
+            resources["foo"].in_memory_bytecode,
+            marshal.dumps(compile("import io", "foo", "exec")),
+        )
+        self.assertEqual(
+            resources["foo"].in_memory_package_resources,
+            {"a.txt": b"a", "b.txt": b"bb"},
+        )
+        self.assertTrue(resources["foo.bar"].is_package)
+        self.assertEqual(resources["foo.bar"].in_memory_source, b"value = 42")
+
+    def test_export_resources_ignores_builtins(self):
+        f = self._query_finder()
+
+        f2 = OxidizedFinder()
+        f2.index_bytes(f.export_resources())
+
+        self.assertEqual(
+            sorted(r.name for r in f2.indexed_resources()), ["foo", "foo.bar"]
+        )
+
 
 if __name__ == "__main__":
     unittest.main()
@@ -359,6 +359,199 @@ class TestImporter(unittest.TestCase):
         self.assertEqual(spec.origin, str(p / "foo.py"))
         self.assertIsNone(spec.submodule_search_locations)
 
+    def test_import_pycache_bytecode(self):
+        source = b"foo = 42\n"
+        code = compile(source.decode("ascii"), "foo.py", "exec")
+        bytecode = make_pyc(code, DEFAULT_MTIME, len(source))
+
+        tag = sys.implementation.cache_tag
+        if sys.flags.optimize:
+            tag = "%s.opt-%d" % (tag, sys.flags.optimize)
+
+        other_code = compile("foo = 0\n", "foo.py", "exec", optimize=2)
+        other_bytecode = make_pyc(other_code, DEFAULT_MTIME, len(source))
+        other_tag = (
+            "%s.opt-2" % sys.implementation.cache_tag
+            if sys.flags.optimize != 2
+            else sys.implementation.cache_tag
+        )
+
+        zip_data = make_zip(
+            {
+                "foo/__init__.py": (DEFAULT_MTIME, source),
+                "foo/__pycache__/__init__.%s.pyc" % tag: (DEFAULT_MTIME, bytecode),
+                "foo/__pycache__/__init__.%s.pyc"
+                % other_tag: (DEFAULT_MTIME, other_bytecode),
+            }
+        )
+
+        importer = OxidizedZipFinder.from_zip_data(zip_data)
+
+        spec = importer.find_spec("foo", None)
+        self.assertEqual(
+            spec.origin, os.path.join(sys.executable, "foo", "__init__.py")
+        )
+        self.assertEqual(
+            spec.submodule_search_locations, [os.path.join(sys.executable, "foo")]
+        )
+        self.assertTrue(importer.is_package("foo"))
+        self.assertEqual(importer.get_code("foo"), code)
+
+    def test_import_pycache_bytecode_only(self):
+        source = b"foo = 42\n"
+        code = compile(source.decode("ascii"), "foo.py", "exec")
+        bytecode = make_pyc(code, DEFAULT_MTIME, len(source))
+
+        tag = sys.implementation.cache_tag
+        if sys.flags.optimize:
+            tag = "%s.opt-%d" % (tag, sys.flags.optimize)
+
+        zip_data = make_zip(
+            {"foo/__pycache__/__init__.%s.pyc" % tag: (DEFAULT_MTIME, bytecode)}
+        )
+
+        importer = OxidizedZipFinder.from_zip_data(zip_data)
+
+        spec = importer.find_spec("foo", None)
+        self.assertEqual(
+            spec.origin,
+            os.path.join(
+                sys.executable, "foo", "__pycache__", "__init__.%s.pyc" % tag
+            ),
+        )
+        self.assertEqual(
+            spec.submodule_search_locations, [os.path.join(sys.executable, "foo")]
+        )
+        self.assertEqual(importer.get_code("foo"), code)
+        self.assertIsNone(importer.get_source("foo"))
+
+    def test_bad_magic_number(self):
+        source = b"foo = 42\n"
+        code = compile(source.decode("ascii"), "foo.py", "exec")
+        bytecode = make_pyc(code, DEFAULT_MTIME, len(source))
+        bytecode = b"\x00\x00\x00\x00" + bytecode[4:]
+
+        zip_data = make_zip({"foo.pyc": (DEFAULT_MTIME, bytecode)})
+        importer = OxidizedZipFinder.from_zip_data(zip_data)
+
+        with self.assertRaisesRegex(ImportError, "bad magic number"):
+            importer.get_code("foo")
+
+        # Source is used when bytecode can't be.
+        zip_data = make_zip(
+            {"foo.py": (DEFAULT_MTIME, source), "foo.pyc": (DEFAULT_MTIME, bytecode)}
+        )
+        importer = OxidizedZipFinder.from_zip_data(zip_data)
+
+        self.assertEqual(importer.get_code("foo"), code)
+
+    def test_get_data(self):
+        zip_data = make_zip(
+            {
+                "foo/__init__.py": (DEFAULT_MTIME, b""),
+                "foo/data.txt": (DEFAULT_MTIME, b"data"),
+            }
+        )
+        importer = OxidizedZipFinder.from_zip_data(zip_data)
+
+        self.assertEqual(importer.get_data("foo/data.txt"), b"data")
+        self.assertEqual(
+            importer.get_data(os.path.join(sys.executable, "foo", "data.txt")),
+            b"data",
+        )
+
+        with self.assertRaises(FileNotFoundError):
+            importer.get_data("foo/missing.txt")
+
+    def test_resource_reader(self):
+        zip_data = make_zip(
+            {
+                "foo/__init__.py": (DEFAULT_MTIME, b""),
+                "foo/bar.py": (DEFAULT_MTIME, b""),
+                "foo/data.txt": (DEFAULT_MTIME, b"data"),
+                "foo/sub/resource.txt": (DEFAULT_MTIME, b"resource"),
+            }
+        )
+        importer = OxidizedZipFinder.from_zip_data(zip_data)
+
+        self.assertIsNone(importer.get_resource_reader("missing"))
+        self.assertIsNone(importer.get_resource_reader("foo.bar"))
+
+        reader = importer.get_resource_reader("foo")
+        self.assertIsNotNone(reader)
+
+        self.assertEqual(
+            list(reader.contents()), ["__init__.py", "bar.py", "data.txt", "sub"]
+        )
+        self.assertTrue(reader.is_resource("data.txt"))
+        self.assertFalse(reader.is_resource("sub"))
+
+        with self.assertRaises(FileNotFoundError):
+            reader.is_resource("missing")
+
+        with reader.open_resource("data.txt") as fh:
+            self.assertEqual(fh.read(), b"data")
+
+        with self.assertRaises(FileNotFoundError):
+            reader.open_resource("missing")
+
+        with self.assertRaises(FileNotFoundError):
+            reader.resource_path("data.txt")
+
+        sys.meta_path.insert(0, importer)
+        importlib.import_module("foo")
+
+        import importlib.resources
+
+        self.assertEqual(importlib.resources.read_binary("foo", "data.txt"), b"data")
+
+    def test_find_distributions(self):
+        zip_data = make_zip(
+            {
+                "foo/__init__.py": (DEFAULT_MTIME, b""),
+                "my_package-1.0.dist-info/METADATA": (
+                    DEFAULT_MTIME,
+                    b"Name: my-package\nVersion: 1.0\nRequires-Dist: foo\n",
+                ),
+                "my_package-1.0.dist-info/entry_points.txt": (
+                    DEFAULT_MTIME,
+                    b"[console_scripts]\nfoo = foo:main\n",
+                ),
+                "other-2.0.egg-info/PKG-INFO": (
+                    DEFAULT_MTIME,
+                    b"Name: other\nVersion: 2.0\n",
+                ),
+            }
+        )
+        importer = OxidizedZipFinder.from_zip_data(zip_data)
+
+        dists = list(importer.find_distributions())
+        self.assertEqual(len(dists), 2)
+        self.assertEqual([d.name for d in dists], ["my-package", "other"])
+
+        from importlib.metadata import DistributionFinder
+
+        dists = list(
+            importer.find_distributions(DistributionFinder.Context(name="my-package"))
+        )
+        self.assertEqual(len(dists), 1)
+        dist = dists[0]
+
+        self.assertEqual(dist.version, "1.0")
+        self.assertEqual(dist._normalized_name, "my_package")
+        self.assertEqual(dist.requires, ["foo"])
+        self.assertIsNone(dist.read_text("missing"))
+        self.assertEqual(len(dist.entry_points), 1)
+
+        dists = importer.find_distributions(DistributionFinder.Context(name="missing"))
+        self.assertEqual(list(dists), [])
+
+        sys.meta_path.insert(0, importer)
+
+        import importlib.metadata
+
+        self.assertEqual(importlib.metadata.version("other"), "2.0")
+
 
 if __name__ == "__main__":
     unittest.main()
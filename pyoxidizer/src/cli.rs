@@ -5,7 +5,8 @@
 use {
     crate::{
         environment::{default_target_triple, PYOXIDIZER_VERSION},
-        project_building, projectmgmt,
+        packed_resources, project_building, projectmgmt,
+        sbom::SbomFormat,
     },
     anyhow::{anyhow, Context, Result},
     clap::{Arg, ArgMatches, Command},
@@ -23,6 +24,12 @@ existing PyOxidizer enabled project.
 
 This command will invoke Rust's build system tool (Cargo) to build
 the project.
+
+Python distributions resolved by `PythonExecutable.pip_download()`,
+`PythonExecutable.pip_install()`, and `PythonExecutable.install_wheels()`
+are recorded in a `pyoxidizer.lock` file next to the configuration file.
+With --locked, the lock file is not modified and the build fails if a
+resolved distribution differs from what the lock file records.
 ";
 
 const INIT_RUST_PROJECT_ABOUT: &str = "\
@@ -98,6 +105,35 @@ conversion is critical for properly packaging Python applications and
 bugs can result in incorrect install layouts, missing resources, etc.
 ";
 
+const PACKED_RESOURCES_ABOUT: &str = "\
+Inspect Python packed resources data.
+
+The PATH argument can be a standalone packed resources file, such as the
+`packed-resources` file written when building, or a built executable
+embedding packed resources data. When given an executable, the embedded
+packed resources data is located automatically.
+
+Compressed field data is decompressed before being dumped, extracted, or
+compared.
+";
+
+const PACKED_RESOURCES_USAGE_ABOUT: &str = "\
+Report packed resources never used according to import traces.
+
+Import traces are written by executables whose Python interpreter config
+has `write_import_trace_directory_env` set. Each TRACE_PATH argument can be
+a trace file or a directory containing `import-trace-*` files. Traces from
+all arguments are merged, so running a test suite multiple times and
+passing its trace directory here reports resources unused by all runs.
+
+The PATH argument can be a standalone packed resources file or a built
+executable embedding packed resources data.
+
+With --filter-file, the names of used resources are written to a file
+suitable for `PythonExecutable.filter_resources_from_files()`. Resources
+that aren't Python modules, such as shared libraries, are always included.
+";
+
 const VAR_HELP: &str = "\
 Defines a single string key to set in the VARS global dict.
 
@@ -128,6 +164,22 @@ be `None` instead of a `string`.
 If a Starlark variable is defined multiple times, an error occurs.
 ";
 
+const SBOM_ABOUT: &str = "\
+Write a software bill of materials for a previously built executable.
+
+The SBOM lists the components of the executable with their versions,
+licenses, and source locations. It also lists the SHA-256 digests of the
+executable, the files installed next to it, and the resources embedded in
+it.
+
+SBOMs are copied from those recorded by the last `pyoxidizer build` of the
+target and written next to the built executable. `--format` can be given
+multiple times to write SBOMs in several formats.
+
+If TARGET is not specified, the first resolved target producing a
+PythonExecutable is used.
+";
+
 fn add_env_args(app: Command) -> Command {
     app.arg(
         Arg::new("vars")
@@ -246,6 +298,11 @@ pub fn run_cli() -> Result<()> {
                     .long("release")
                     .help("Build a release binary"),
             )
+            .arg(
+                Arg::new("locked")
+                    .long("locked")
+                    .help("Require resolved Python distributions to match the lock file"),
+            )
             .arg(
                 Arg::new("path")
                     .long("path")
@@ -365,6 +422,106 @@ pub fn run_cli() -> Result<()> {
             ),
     );
 
+    let app = app.subcommand(
+        Command::new("packed-resources-diff")
+            .about("Show differences between resources in two packed resources files")
+            .long_about(PACKED_RESOURCES_ABOUT)
+            .arg(
+                Arg::new("a")
+                    .required(true)
+                    .value_name("PATH_A")
+                    .help("Path to first packed resources file or executable"),
+            )
+            .arg(
+                Arg::new("b")
+                    .required(true)
+                    .value_name("PATH_B")
+                    .help("Path to second packed resources file or executable"),
+            ),
+    );
+
+    let app = app.subcommand(
+        Command::new("packed-resources-dump")
+            .about("Write the data of a single resource field to stdout")
+            .long_about(PACKED_RESOURCES_ABOUT)
+            .arg(
+                Arg::new("field")
+                    .long("field")
+                    .takes_value(true)
+                    .default_value("source")
+                    .value_name("FIELD")
+                    .help("Name of resource field to dump (e.g. source, bytecode)"),
+            )
+            .arg(
+                Arg::new("path")
+                    .required(true)
+                    .value_name("PATH")
+                    .help("Path to packed resources file or executable"),
+            )
+            .arg(
+                Arg::new("name")
+                    .required(true)
+                    .value_name("NAME")
+                    .help("Name of resource to dump"),
+            ),
+    );
+
+    let app = app.subcommand(
+        Command::new("packed-resources-extract")
+            .about("Extract in-memory resource data to a directory")
+            .long_about(PACKED_RESOURCES_ABOUT)
+            .arg(
+                Arg::new("path")
+                    .required(true)
+                    .value_name("PATH")
+                    .help("Path to packed resources file or executable"),
+            )
+            .arg(
+                Arg::new("dest_path")
+                    .required(true)
+                    .value_name("DESTINATION_PATH")
+                    .help("Directory to write resource files to"),
+            ),
+    );
+
+    let app = app.subcommand(
+        Command::new("packed-resources-list")
+            .about("List resources in a packed resources file")
+            .long_about(PACKED_RESOURCES_ABOUT)
+            .arg(
+                Arg::new("path")
+                    .required(true)
+                    .value_name("PATH")
+                    .help("Path to packed resources file or executable"),
+            ),
+    );
+
+    let app = app.subcommand(
+        Command::new("packed-resources-usage")
+            .about("Report packed resources never used according to import traces")
+            .long_about(PACKED_RESOURCES_USAGE_ABOUT)
+            .arg(
+                Arg::new("filter_file")
+                    .long("filter-file")
+                    .takes_value(true)
+                    .value_name("FILTER_PATH")
+                    .help("Write names of used resources to this file"),
+            )
+            .arg(
+                Arg::new("path")
+                    .required(true)
+                    .value_name("PATH")
+                    .help("Path to packed resources file or executable"),
+            )
+            .arg(
+                Arg::new("traces")
+                    .required(true)
+                    .multiple_values(true)
+                    .value_name("TRACE_PATH")
+                    .help("Import trace files or directories containing them"),
+            ),
+    );
+
     let app = app.subcommand(
         Command::new("python-distribution-extract")
             .about("Extract a Python distribution archive to a directory")
@@ -488,6 +645,44 @@ pub fn run_cli() -> Result<()> {
             ),
     );
 
+    let app = app.subcommand(add_env_args(
+        Command::new("sbom")
+            .about("Build an executable and write a software bill of materials for it")
+            .long_about(SBOM_ABOUT)
+            .arg(
+                Arg::new("format")
+                    .long("format")
+                    .takes_value(true)
+                    .multiple_occurrences(true)
+                    .possible_values(&["spdx", "cyclonedx"])
+                    .default_value("spdx")
+                    .help("SBOM format to write"),
+            )
+            .arg(
+                Arg::new("target_triple")
+                    .long("target-triple")
+                    .takes_value(true)
+                    .help("Rust target triple to build for"),
+            )
+            .arg(
+                Arg::new("release")
+                    .long("release")
+                    .help("Build a release binary"),
+            )
+            .arg(
+                Arg::new("path")
+                    .long("path")
+                    .default_value(".")
+                    .value_name("PATH")
+                    .help("Directory containing project to build"),
+            )
+            .arg(
+                Arg::new("target")
+                    .value_name("TARGET")
+                    .help("Target producing the PythonExecutable to describe"),
+            ),
+    ));
+
     let matches = app.get_matches();
 
     let verbose = matches.is_present("verbose");
@@ -530,6 +725,7 @@ pub fn run_cli() -> Result<()> {
         "build" => {
             let starlark_vars = starlark_vars(args)?;
             let release = args.is_present("release");
+            let locked = args.is_present("locked");
             let target_triple = args.value_of("target_triple");
             let path = args.value_of("path").unwrap();
             let resolve_targets = args
@@ -543,6 +739,7 @@ pub fn run_cli() -> Result<()> {
                 resolve_targets,
                 starlark_vars,
                 release,
+                locked,
                 verbose,
             )
         }
@@ -618,6 +815,46 @@ pub fn run_cli() -> Result<()> {
             projectmgmt::init_rust_project(&env, project_path)
         }
 
+        "packed-resources-diff" => {
+            let a = args.value_of("a").unwrap();
+            let b = args.value_of("b").unwrap();
+
+            packed_resources::diff(Path::new(a), Path::new(b))
+        }
+
+        "packed-resources-dump" => {
+            let path = args.value_of("path").unwrap();
+            let name = args.value_of("name").unwrap();
+            let field = args.value_of("field").unwrap();
+
+            packed_resources::dump(Path::new(path), name, field)
+        }
+
+        "packed-resources-extract" => {
+            let path = args.value_of("path").unwrap();
+            let dest_path = args.value_of("dest_path").unwrap();
+
+            packed_resources::extract(Path::new(path), Path::new(dest_path))
+        }
+
+        "packed-resources-list" => {
+            let path = args.value_of("path").unwrap();
+
+            packed_resources::list(Path::new(path))
+        }
+
+        "packed-resources-usage" => {
+            let path = args.value_of("path").unwrap();
+            let traces = args
+                .values_of("traces")
+                .unwrap()
+                .map(Path::new)
+                .collect::<Vec<_>>();
+            let filter_file = args.value_of("filter_file").map(Path::new);
+
+            packed_resources::usage(Path::new(path), &traces, filter_file)
+        }
+
         "python-distribution-extract" => {
             let download_default = args.is_present("download-default");
             let archive_path = args.value_of("archive-path");
@@ -674,6 +911,30 @@ pub fn run_cli() -> Result<()> {
             )
         }
 
+        "sbom" => {
+            let starlark_vars = starlark_vars(args)?;
+            let formats = args
+                .values_of("format")
+                .unwrap()
+                .map(|x| SbomFormat::try_from(x).map_err(|e| anyhow!(e)))
+                .collect::<Result<Vec<_>>>()?;
+            let target_triple = args.value_of("target_triple");
+            let release = args.is_present("release");
+            let path = args.value_of("path").unwrap();
+            let target = args.value_of("target");
+
+            projectmgmt::sbom(
+                &env,
+                Path::new(path),
+                target_triple,
+                release,
+                target,
+                starlark_vars,
+                formats,
+                verbose,
+            )
+        }
+
         "rust-project-licensing" => {
             let project_path =
                 Path::new(args.value_of("project_path").expect("argument is required"));
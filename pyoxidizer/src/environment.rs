@@ -5,7 +5,10 @@
 //! Resolve details about the PyOxidizer execution environment.
 
 use {
-    crate::{project_layout::PyembedLocation, py_packaging::distribution::AppleSdkInfo},
+    crate::{
+        project_layout::PyembedLocation,
+        py_packaging::{bytecode_cache::BytecodeCache, distribution::AppleSdkInfo},
+    },
     anyhow::{anyhow, Context, Result},
     apple_sdk::{AppleSdk, ParsedSdk, SdkSearch, SdkSearchLocation, SdkSorting},
     log::{info, warn},
@@ -271,6 +274,11 @@ pub struct Environment {
     ///
     /// Cached because lookups may be expensive.
     rust_environment: Arc<RwLock<Option<RustEnvironment>>>,
+
+    /// Cache of compiled Python bytecode.
+    ///
+    /// Shared between clones so statistics cover the whole process.
+    bytecode_cache: Arc<BytecodeCache>,
 }
 
 impl Environment {
@@ -288,12 +296,15 @@ impl Environment {
 
         let managed_rust = std::env::var("PYOXIDIZER_SYSTEM_RUST").is_err();
 
+        let bytecode_cache = Arc::new(BytecodeCache::new(cache_dir.join("bytecode")));
+
         Ok(Self {
             pyoxidizer_source,
             cargo_target_directory: cargo_target_directory()?,
             cache_dir,
             managed_rust,
             rust_environment: Arc::new(RwLock::new(None)),
+            bytecode_cache,
         })
     }
 
@@ -314,6 +325,13 @@ impl Environment {
         self.cache_dir.join("rust")
     }
 
+    /// Cache of compiled Python bytecode.
+    ///
+    /// Entries are stored in the `bytecode` directory of the cache directory.
+    pub fn bytecode_cache(&self) -> &BytecodeCache {
+        &self.bytecode_cache
+    }
+
     /// Do not use a managed Rust.
     ///
     /// When called, [self.ensure_rust_toolchain()] will attempt to locate a
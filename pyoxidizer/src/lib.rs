@@ -14,11 +14,14 @@ This library exposes that functionality to other tools.
 mod default_python_distributions;
 pub mod environment;
 pub mod licensing;
+pub mod lock_file;
+pub mod packed_resources;
 pub mod project_building;
 pub mod project_layout;
 pub mod projectmgmt;
 pub mod py_packaging;
 pub mod python_distributions;
+pub mod sbom;
 pub mod starlark;
 
 #[cfg(test)]
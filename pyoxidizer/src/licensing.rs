@@ -18,10 +18,18 @@ use {
         MetadataCommand,
     },
     log::{info, warn},
-    python_packaging::licensing::{
-        ComponentFlavor, LicenseFlavor, LicensedComponent, LicensedComponents, SourceLocation,
+    python_packaging::{
+        licensing::{
+            ComponentFlavor, LicenseFlavor, LicensePolicy, LicensedComponent, LicensedComponents,
+            SourceLocation,
+        },
+        resource_collection::PrePackagedResource,
+    },
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        path::Path,
+        sync::Arc,
     },
-    std::{path::Path, sync::Arc},
 };
 
 /// Log a summary of licensing info.
@@ -44,6 +52,99 @@ pub fn log_licensing_info(components: &LicensedComponents) {
     info!("");
 }
 
+/// Describe a resource for the purpose of attributing a component to it.
+fn describe_resource(resource: &PrePackagedResource) -> String {
+    if resource.in_memory_distribution_resources.is_some()
+        || resource.relative_path_distribution_resources.is_some()
+    {
+        format!("package distribution metadata of {}", resource.name)
+    } else if resource.is_builtin_extension_module || resource.is_extension_module {
+        format!("extension module {}", resource.name)
+    } else if resource.is_module {
+        format!("module {}", resource.name)
+    } else if resource.is_shared_library {
+        format!("shared library {}", resource.name)
+    } else {
+        format!("resource {}", resource.name)
+    }
+}
+
+/// Describe what caused a licensed component to be part of a binary.
+fn component_origin(
+    component: &LicensedComponent,
+    resources: &BTreeMap<&str, &PrePackagedResource>,
+    library_links: &BTreeMap<String, BTreeSet<String>>,
+) -> String {
+    match component.flavor() {
+        ComponentFlavor::PythonDistribution(_) => "the Python distribution".to_string(),
+        ComponentFlavor::Library(name) => match library_links.get(name) {
+            Some(modules) if !modules.is_empty() => format!(
+                "extension module{} {}",
+                if modules.len() > 1 { "s" } else { "" },
+                modules.iter().cloned().collect::<Vec<_>>().join(", ")
+            ),
+            _ => "an unknown extension module".to_string(),
+        },
+        ComponentFlavor::RustCrate(_) => "the Rust project building the binary".to_string(),
+        flavor => {
+            let name = flavor.name();
+            let prefix = format!("{}.", name);
+
+            resources
+                .get(name)
+                .or_else(|| {
+                    resources
+                        .range(prefix.as_str()..)
+                        .next()
+                        .filter(|(k, _)| k.starts_with(&prefix))
+                        .map(|(_, v)| v)
+                })
+                .map(|resource| describe_resource(resource))
+                .unwrap_or_else(|| "an unknown resource".to_string())
+        }
+    }
+}
+
+/// Ensure licensed components satisfy a [LicensePolicy].
+///
+/// `resources` are the resources of the binary the components are part of and
+/// `library_links` maps library names to the extension modules linking them.
+/// They are used to report which resource pulled in a violating component.
+///
+/// Errors listing every violating component if the policy isn't satisfied.
+pub fn enforce_license_policy<'a>(
+    policy: &LicensePolicy,
+    components: &LicensedComponents,
+    resources: impl Iterator<Item = (&'a String, &'a PrePackagedResource)>,
+    library_links: &BTreeMap<String, BTreeSet<String>>,
+) -> Result<()> {
+    let violations = policy.violations(components);
+
+    if violations.is_empty() {
+        return Ok(());
+    }
+
+    let resources = resources
+        .map(|(k, v)| (k.as_str(), v))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut lines = vec![format!(
+        "{} components violate the license policy:",
+        violations.len()
+    )];
+
+    for (component, reason) in violations {
+        lines.push(format!(
+            "  {} (pulled in by {}): {}",
+            component.flavor(),
+            component_origin(component, &resources, library_links),
+            reason
+        ));
+    }
+
+    Err(anyhow!("{}", lines.join("\n")))
+}
+
 /// Resolve licenses from a cargo manifest.
 pub fn licenses_from_cargo_manifest<'a>(
     manifest_path: impl AsRef<Path>,
@@ -194,6 +295,8 @@ pub fn licenses_from_cargo_manifest<'a>(
             LicensedComponent::new(flavor, LicenseFlavor::None)
         };
 
+        component.set_version(package.version());
+
         for author in package.authors() {
             component.add_author(author);
         }
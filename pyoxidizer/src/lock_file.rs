@@ -0,0 +1,509 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Lock files recording resolved Python distributions.
+
+A lock file is a JSON document named `pyoxidizer.lock` residing next to the
+configuration file. For every target triple, it records the Python
+distributions resolved by `PythonExecutable.pip_download()`,
+`PythonExecutable.pip_install()`, and `PythonExecutable.install_wheels()`:
+their normalized names, versions, and, when a wheel was obtained, the wheel
+filename and its SHA-256.
+
+Builds normally rewrite the entries for the target triple being built. In
+locked mode, the lock file is left alone and resolving a distribution not
+recorded in it, or not resolving one that is, is an error.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    log::warn,
+    once_cell::sync::OnceCell,
+    python_packaging::{
+        requirement::normalize_project_name, resource::PythonResource,
+        wheel_resolver::WheelFilename,
+    },
+    serde::{Deserialize, Serialize},
+    sha2::{Digest, Sha256},
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        path::{Path, PathBuf},
+        sync::Mutex,
+    },
+};
+
+/// Filename of lock files.
+pub const LOCK_FILE_NAME: &str = "pyoxidizer.lock";
+
+/// Version of the lock file format.
+const LOCK_FILE_VERSION: u32 = 1;
+
+/// A Python distribution recorded in a lock file.
+#[derive(Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct LockedDistribution {
+    /// Normalized name of the distribution.
+    pub name: String,
+
+    /// Version of the distribution.
+    pub version: String,
+
+    /// Filename of the wheel the distribution was installed from.
+    ///
+    /// Not known for distributions installed by `pip install`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+
+    /// Hex encoded SHA-256 of the wheel.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+impl LockedDistribution {
+    /// Construct an instance from a wheel file.
+    pub fn from_wheel_path(path: &Path) -> Result<Self> {
+        let filename = path
+            .file_name()
+            .ok_or_else(|| anyhow!("{} has no filename", path.display()))?
+            .to_string_lossy()
+            .to_string();
+        let wheel = WheelFilename::parse(&filename)?;
+
+        let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+
+        Ok(Self {
+            name: wheel.name,
+            version: wheel.version,
+            filename: Some(filename),
+            sha256: Some(format!("{:x}", Sha256::digest(&data))),
+        })
+    }
+
+    /// Construct instances from the package distribution resources in a collection.
+    ///
+    /// Only names and versions are known.
+    pub fn from_resources<'a>(
+        resources: impl Iterator<Item = &'a PythonResource<'a>>,
+    ) -> BTreeSet<Self> {
+        resources
+            .filter_map(|r| match r {
+                PythonResource::PackageDistributionResource(r) => Some(Self {
+                    name: normalize_project_name(&r.package),
+                    version: r.version.clone(),
+                    filename: None,
+                    sha256: None,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Whether this resolved distribution is satisfied by a locked one.
+    ///
+    /// Wheel filenames and hashes are only compared when this instance has them.
+    fn satisfied_by(&self, locked: &Self) -> bool {
+        self.name == locked.name
+            && self.version == locked.version
+            && (self.filename.is_none() || self.filename == locked.filename)
+            && (self.sha256.is_none() || self.sha256 == locked.sha256)
+    }
+
+    fn describe(&self) -> String {
+        match (&self.filename, &self.sha256) {
+            (Some(filename), Some(sha256)) => {
+                format!("{} ({}, sha256 {})", self.version, filename, sha256)
+            }
+            (Some(filename), None) => format!("{} ({})", self.version, filename),
+            _ => self.version.clone(),
+        }
+    }
+}
+
+/// The content of a lock file.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct LockFile {
+    /// Version of the lock file format.
+    pub version: u32,
+
+    /// Locked distributions, keyed by target triple.
+    pub targets: BTreeMap<String, BTreeSet<LockedDistribution>>,
+}
+
+impl Default for LockFile {
+    fn default() -> Self {
+        Self {
+            version: LOCK_FILE_VERSION,
+            targets: BTreeMap::new(),
+        }
+    }
+}
+
+impl LockFile {
+    /// Read a lock file from a path.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+        let lock: Self = serde_json::from_slice(&data)
+            .with_context(|| format!("parsing lock file {}", path.display()))?;
+
+        if lock.version != LOCK_FILE_VERSION {
+            return Err(anyhow!(
+                "lock file {} has unsupported version {}; expected {}",
+                path.display(),
+                lock.version,
+                LOCK_FILE_VERSION
+            ));
+        }
+
+        Ok(lock)
+    }
+
+    /// Write the lock file to a path.
+    pub fn write_path(&self, path: &Path) -> Result<()> {
+        let mut data = serde_json::to_string_pretty(self)?;
+        data.push('\n');
+
+        std::fs::write(path, data).with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// Describe how resolved distributions differ from those locked for a target triple.
+    ///
+    /// Locked distributions that weren't resolved aren't reported, as they may
+    /// still be resolved later in the build. See [LockFile::unresolved].
+    pub fn drift<'a>(
+        &self,
+        target_triple: &str,
+        resolved: impl Iterator<Item = &'a LockedDistribution>,
+    ) -> Vec<String> {
+        let locked = self.targets.get(target_triple);
+
+        resolved
+            .filter_map(|dist| {
+                let candidates = locked
+                    .iter()
+                    .flat_map(|x| x.iter())
+                    .filter(|l| l.name == dist.name)
+                    .collect::<Vec<_>>();
+
+                if candidates.iter().any(|l| dist.satisfied_by(l)) {
+                    None
+                } else if candidates.is_empty() {
+                    Some(format!(
+                        "{} {} is not in the lock file",
+                        dist.name,
+                        dist.describe()
+                    ))
+                } else {
+                    Some(format!(
+                        "{} resolved to {} but is locked to {}",
+                        dist.name,
+                        dist.describe(),
+                        candidates
+                            .iter()
+                            .map(|l| l.describe())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ))
+                }
+            })
+            .collect()
+    }
+
+    /// Describe distributions locked for a target triple that weren't resolved.
+    pub fn unresolved<'a>(
+        &self,
+        target_triple: &str,
+        resolved: impl Iterator<Item = &'a LockedDistribution>,
+    ) -> Vec<String> {
+        let resolved = resolved
+            .map(|dist| dist.name.as_str())
+            .collect::<BTreeSet<_>>();
+
+        self.targets
+            .get(target_triple)
+            .iter()
+            .flat_map(|x| x.iter())
+            .filter(|l| !resolved.contains(l.name.as_str()))
+            .map(|l| format!("{} {} is locked but was not resolved", l.name, l.describe()))
+            .collect()
+    }
+}
+
+/// Records distributions resolved during a build and enforces a lock file.
+#[derive(Debug)]
+pub struct DistributionLock {
+    /// Path of the lock file.
+    path: PathBuf,
+
+    /// Target triple distributions are resolved for.
+    target_triple: String,
+
+    /// Whether resolved distributions must match the lock file.
+    locked: bool,
+
+    /// Content of the lock file, if it exists. Read when first needed.
+    existing: OnceCell<Option<LockFile>>,
+
+    /// Distributions resolved so far.
+    resolved: Mutex<BTreeSet<LockedDistribution>>,
+}
+
+impl DistributionLock {
+    /// Construct an instance for a lock file at a path.
+    ///
+    /// The lock file isn't read until a distribution is recorded in locked
+    /// mode or the lock file is written.
+    pub fn new(path: impl AsRef<Path>, target_triple: impl ToString, locked: bool) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            target_triple: target_triple.to_string(),
+            locked,
+            existing: OnceCell::new(),
+            resolved: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    /// Obtain the content of the lock file, reading it on first use.
+    ///
+    /// In locked mode, the lock file must exist.
+    fn existing(&self) -> Result<Option<&LockFile>> {
+        self.existing
+            .get_or_try_init(|| {
+                if self.path.exists() {
+                    Ok(Some(LockFile::from_path(&self.path)?))
+                } else if self.locked {
+                    Err(anyhow!(
+                        "lock file {} does not exist; build without --locked to create it",
+                        self.path.display()
+                    ))
+                } else {
+                    Ok(None)
+                }
+            })
+            .map(|x| x.as_ref())
+    }
+
+    /// Path of the lock file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether resolved distributions must match the lock file.
+    pub fn locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Record resolved distributions.
+    ///
+    /// In locked mode, errors if any distribution isn't in the lock file.
+    pub fn record(
+        &self,
+        distributions: impl IntoIterator<Item = LockedDistribution>,
+    ) -> Result<()> {
+        let distributions = distributions.into_iter().collect::<BTreeSet<_>>();
+
+        if self.locked {
+            let drift = self
+                .existing()?
+                .map(|lock| lock.drift(&self.target_triple, distributions.iter()))
+                .unwrap_or_default();
+
+            if !drift.is_empty() {
+                return Err(anyhow!(
+                    "resolved Python distributions for {} differ from lock file {}:\n  {}\n\
+                    build without --locked to update the lock file",
+                    self.target_triple,
+                    self.path.display(),
+                    drift.join("\n  ")
+                ));
+            }
+        }
+
+        self.resolved
+            .lock()
+            .map_err(|e| anyhow!("unable to lock resolved distributions: {}", e))?
+            .extend(distributions);
+
+        Ok(())
+    }
+
+    /// Write resolved distributions for the target triple to the lock file.
+    ///
+    /// Entries for other target triples are preserved. Nothing is written if
+    /// nothing was resolved and no lock file exists.
+    ///
+    /// In locked mode, nothing is written. Instead, errors if a distribution
+    /// locked for the target triple wasn't resolved.
+    pub fn write(&self) -> Result<()> {
+        let resolved = self
+            .resolved
+            .lock()
+            .map_err(|e| anyhow!("unable to lock resolved distributions: {}", e))?;
+
+        if self.locked {
+            let unresolved = self
+                .existing()?
+                .map(|lock| lock.unresolved(&self.target_triple, resolved.iter()))
+                .unwrap_or_default();
+
+            if !unresolved.is_empty() {
+                return Err(anyhow!(
+                    "resolved Python distributions for {} differ from lock file {}:\n  {}\n\
+                    build without --locked to update the lock file",
+                    self.target_triple,
+                    self.path.display(),
+                    unresolved.join("\n  ")
+                ));
+            }
+
+            return Ok(());
+        }
+
+        if resolved.is_empty() && !self.path.exists() {
+            return Ok(());
+        }
+
+        let existing = self.existing()?;
+        let mut lock = existing.cloned().unwrap_or_default();
+
+        if resolved.is_empty() {
+            lock.targets.remove(&self.target_triple);
+        } else {
+            lock.targets
+                .insert(self.target_triple.clone(), resolved.clone());
+        }
+
+        if Some(&lock) != existing {
+            warn!("writing lock file {}", self.path.display());
+            lock.write_path(&self.path)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, python_packaging::wheel_builder::WheelBuilder};
+
+    fn dist(name: &str, version: &str, sha256: Option<&str>) -> LockedDistribution {
+        LockedDistribution {
+            name: name.to_string(),
+            version: version.to_string(),
+            filename: sha256.map(|_| format!("{}-{}-py3-none-any.whl", name, version)),
+            sha256: sha256.map(|x| x.to_string()),
+        }
+    }
+
+    #[test]
+    fn from_wheel_path() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+
+        let mut builder = WheelBuilder::new("My.Package", "1.0");
+        builder.add_file("my_package/__init__.py", b"".to_vec())?;
+        let path = builder.write_wheel_into_directory(temp_dir.path())?;
+
+        let locked = LockedDistribution::from_wheel_path(&path)?;
+        assert_eq!(locked.name, "my-package");
+        assert_eq!(locked.version, "1.0");
+        assert_eq!(
+            locked.filename.as_deref(),
+            Some(path.file_name().unwrap().to_str().unwrap())
+        );
+        assert_eq!(
+            locked.sha256,
+            Some(format!("{:x}", Sha256::digest(&std::fs::read(&path)?)))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn drift() {
+        let mut lock = LockFile::default();
+        lock.targets.insert(
+            "x86_64-unknown-linux-gnu".to_string(),
+            [dist("foo", "1.0", Some("aa")), dist("bar", "2.0", None)]
+                .into_iter()
+                .collect(),
+        );
+
+        let drift = |resolved: &[LockedDistribution]| {
+            lock.drift("x86_64-unknown-linux-gnu", resolved.iter())
+        };
+
+        assert!(drift(&[dist("foo", "1.0", Some("aa"))]).is_empty());
+        assert!(drift(&[dist("foo", "1.0", None)]).is_empty());
+        assert!(drift(&[dist("bar", "2.0", None)]).is_empty());
+        assert_eq!(
+            drift(&[dist("foo", "1.0", Some("bb"))]),
+            vec!["foo resolved to 1.0 (foo-1.0-py3-none-any.whl, sha256 bb) but is locked to 1.0 (foo-1.0-py3-none-any.whl, sha256 aa)"]
+        );
+        assert_eq!(
+            drift(&[dist("bar", "2.1", None)]),
+            vec!["bar resolved to 2.1 but is locked to 2.0"]
+        );
+        assert_eq!(
+            drift(&[dist("baz", "1.0", None)]),
+            vec!["baz 1.0 is not in the lock file"]
+        );
+        assert_eq!(
+            lock.drift("aarch64-apple-darwin", [dist("foo", "1.0", None)].iter()),
+            vec!["foo 1.0 is not in the lock file"]
+        );
+
+        assert!(lock
+            .unresolved(
+                "x86_64-unknown-linux-gnu",
+                [dist("foo", "1.0", None), dist("bar", "2.0", None)].iter()
+            )
+            .is_empty());
+        assert_eq!(
+            lock.unresolved(
+                "x86_64-unknown-linux-gnu",
+                [dist("foo", "1.0", None)].iter()
+            ),
+            vec!["bar 2.0 is locked but was not resolved"]
+        );
+    }
+
+    #[test]
+    fn write_and_enforce() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let path = temp_dir.path().join(LOCK_FILE_NAME);
+
+        assert!(
+            DistributionLock::new(&path, "x86_64-unknown-linux-gnu", true)
+                .write()
+                .is_err()
+        );
+
+        let lock = DistributionLock::new(&path, "x86_64-unknown-linux-gnu", false);
+        lock.write()?;
+        assert!(!path.exists());
+
+        lock.record([dist("foo", "1.0", Some("aa"))])?;
+        lock.write()?;
+        assert_eq!(
+            LockFile::from_path(&path)?.targets["x86_64-unknown-linux-gnu"],
+            [dist("foo", "1.0", Some("aa"))].into_iter().collect()
+        );
+
+        let lock = DistributionLock::new(&path, "aarch64-apple-darwin", false);
+        lock.record([dist("foo", "1.1", Some("bb"))])?;
+        lock.write()?;
+        assert_eq!(LockFile::from_path(&path)?.targets.len(), 2);
+
+        let lock = DistributionLock::new(&path, "x86_64-unknown-linux-gnu", true);
+        lock.record([dist("foo", "1.0", Some("aa"))])?;
+        assert!(lock.record([dist("foo", "1.1", Some("bb"))]).is_err());
+
+        let before = std::fs::read(&path)?;
+        lock.write()?;
+        assert_eq!(std::fs::read(&path)?, before);
+
+        let lock = DistributionLock::new(&path, "x86_64-unknown-linux-gnu", true);
+        assert!(lock.write().is_err());
+
+        Ok(())
+    }
+}
@@ -31,11 +31,14 @@ mod cli;
 mod default_python_distributions;
 mod environment;
 mod licensing;
+mod lock_file;
+mod packed_resources;
 mod project_building;
 mod project_layout;
 mod projectmgmt;
 mod py_packaging;
 mod python_distributions;
+mod sbom;
 pub mod starlark;
 #[cfg(test)]
 mod testutil;
@@ -0,0 +1,1072 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Inspect packed resources data.
+
+use {
+    anyhow::{anyhow, Context, Result},
+    python_packaging::import_trace::ImportTrace,
+    python_packed_resources::{
+        load_resources, read_integrity_trailer, CompressionCodec, IntegritySignatureAlgorithm,
+        Resource, ResourceField, HEADER_V3, HEADER_V4,
+    },
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        io::Write,
+        path::{Component, Path, PathBuf},
+    },
+};
+
+/// Packed resources data loaded from a file.
+pub struct PackedResourcesFile {
+    /// Raw file content.
+    data: Vec<u8>,
+
+    /// Offset of packed resources data within the file.
+    ///
+    /// `None` if the file consists solely of packed resources data.
+    embedded_offset: Option<usize>,
+
+    /// Length of the packed resources data.
+    length: usize,
+}
+
+impl PackedResourcesFile {
+    /// Load packed resources data from a path.
+    ///
+    /// The path can be a standalone packed resources file or a file having
+    /// packed resources data embedded within it, such as a built executable.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+
+        Self::from_data(data)
+            .with_context(|| format!("resolving packed resources in {}", path.display()))
+    }
+
+    /// Resolve packed resources data from file content.
+    pub fn from_data(data: Vec<u8>) -> Result<Self> {
+        if has_packed_resources_header(&data) {
+            let length = data.len();
+
+            return Ok(Self {
+                data,
+                embedded_offset: None,
+                length,
+            });
+        }
+
+        let (offset, length) = find_embedded_packed_resources(&data)
+            .ok_or_else(|| anyhow!("unable to find packed resources data"))?;
+
+        Ok(Self {
+            data,
+            embedded_offset: Some(offset),
+            length,
+        })
+    }
+
+    /// The packed resources data.
+    pub fn data(&self) -> &[u8] {
+        let offset = self.embedded_offset.unwrap_or(0);
+
+        &self.data[offset..offset + self.length]
+    }
+
+    /// Parse resources, decompressing field data.
+    pub fn resources(&self) -> Result<Vec<Resource<'static, u8>>> {
+        load_resources(self.data())
+            .map_err(|e| anyhow!("error parsing packed resources: {}", e))?
+            .map(|resource| {
+                resource
+                    .and_then(|resource| resource.to_decompressed())
+                    .map_err(|e| anyhow!("error parsing packed resources: {}", e))
+            })
+            .collect()
+    }
+}
+
+fn has_packed_resources_header(data: &[u8]) -> bool {
+    data.starts_with(HEADER_V3) || data.starts_with(HEADER_V4)
+}
+
+/// Find packed resources data embedded in a larger file.
+///
+/// Executables contain packed resources header values in places other than
+/// the embedded resources data (e.g. in the code parsing that data). So we
+/// attempt to parse every candidate and take the first one that yields
+/// resources fitting within the file.
+///
+/// Returns the offset and length of the packed resources data. Integrity
+/// trailers aren't included.
+fn find_embedded_packed_resources(data: &[u8]) -> Option<(usize, usize)> {
+    let mut offset = 0;
+
+    while let Some(position) = data[offset..]
+        .windows(HEADER_V3.len())
+        .position(has_packed_resources_header)
+    {
+        let start = offset + position;
+
+        if let Some(length) = parsed_packed_resources_length(&data[start..]) {
+            return Some((start, length));
+        }
+
+        offset = start + 1;
+    }
+
+    None
+}
+
+fn parsed_packed_resources_length(data: &[u8]) -> Option<usize> {
+    let resources = load_resources(data).ok()?;
+    let length = resources.blob_end_offset();
+
+    if resources.expected_resources_count() == 0 || length > data.len() {
+        return None;
+    }
+
+    for resource in resources {
+        resource.ok()?;
+    }
+
+    Some(length)
+}
+
+/// A value of a resource field.
+#[derive(Clone, Debug, PartialEq)]
+enum FieldValue<'a> {
+    Data(&'a [u8]),
+    Path(&'a Path),
+    Names(Vec<&'a str>),
+}
+
+impl<'a> FieldValue<'a> {
+    fn describe(&self) -> String {
+        match self {
+            Self::Data(data) => format!("{} bytes", data.len()),
+            Self::Path(path) => format!("-> {}", path.display()),
+            Self::Names(names) => names.join(", "),
+        }
+    }
+}
+
+fn resource_flags(resource: &Resource<u8>) -> Vec<&'static str> {
+    [
+        (resource.is_python_module, "python-module"),
+        (resource.is_python_package, "python-package"),
+        (
+            resource.is_python_namespace_package,
+            "python-namespace-package",
+        ),
+        (
+            resource.is_python_builtin_extension_module,
+            "python-builtin-extension-module",
+        ),
+        (resource.is_python_frozen_module, "python-frozen-module"),
+        (
+            resource.is_python_extension_module,
+            "python-extension-module",
+        ),
+        (resource.is_shared_library, "shared-library"),
+        (resource.is_utf8_filename_data, "file"),
+        (resource.file_executable, "executable"),
+        (resource.is_tombstone, "tombstone"),
+    ]
+    .into_iter()
+    .filter_map(|(set, flag)| if set { Some(flag) } else { None })
+    .collect()
+}
+
+/// Resolve named values of all fields present on a resource.
+///
+/// Names are stable and are how users refer to fields on the command line.
+fn resource_fields<'r>(resource: &'r Resource<u8>) -> Vec<(String, ResourceField, FieldValue<'r>)> {
+    let mut fields = vec![];
+
+    for (name, field, value) in [
+        (
+            "source",
+            ResourceField::InMemorySource,
+            &resource.in_memory_source,
+        ),
+        (
+            "bytecode",
+            ResourceField::InMemoryBytecode,
+            &resource.in_memory_bytecode,
+        ),
+        (
+            "bytecode-opt1",
+            ResourceField::InMemoryBytecodeOpt1,
+            &resource.in_memory_bytecode_opt1,
+        ),
+        (
+            "bytecode-opt2",
+            ResourceField::InMemoryBytecodeOpt2,
+            &resource.in_memory_bytecode_opt2,
+        ),
+        (
+            "extension-module",
+            ResourceField::InMemoryExtensionModuleSharedLibrary,
+            &resource.in_memory_extension_module_shared_library,
+        ),
+        (
+            "shared-library",
+            ResourceField::InMemorySharedLibrary,
+            &resource.in_memory_shared_library,
+        ),
+        (
+            "file-data",
+            ResourceField::FileDataEmbedded,
+            &resource.file_data_embedded,
+        ),
+    ] {
+        if let Some(data) = value {
+            fields.push((name.to_string(), field, FieldValue::Data(data)));
+        }
+    }
+
+    for (prefix, field, value) in [
+        (
+            "package-resource",
+            ResourceField::InMemoryResourcesData,
+            &resource.in_memory_package_resources,
+        ),
+        (
+            "distribution-resource",
+            ResourceField::InMemoryDistributionResource,
+            &resource.in_memory_distribution_resources,
+        ),
+    ] {
+        if let Some(entries) = value {
+            for (key, data) in entries.iter().collect::<BTreeMap<_, _>>() {
+                fields.push((format!("{}:{}", prefix, key), field, FieldValue::Data(data)));
+            }
+        }
+    }
+
+    if let Some(names) = &resource.shared_library_dependency_names {
+        fields.push((
+            "shared-library-dependencies".to_string(),
+            ResourceField::SharedLibraryDependencyNames,
+            FieldValue::Names(names.iter().map(|x| x.as_ref()).collect()),
+        ));
+    }
+
+    for (name, field, value) in [
+        (
+            "relative-path-source",
+            ResourceField::RelativeFilesystemModuleSource,
+            &resource.relative_path_module_source,
+        ),
+        (
+            "relative-path-bytecode",
+            ResourceField::RelativeFilesystemModuleBytecode,
+            &resource.relative_path_module_bytecode,
+        ),
+        (
+            "relative-path-bytecode-opt1",
+            ResourceField::RelativeFilesystemModuleBytecodeOpt1,
+            &resource.relative_path_module_bytecode_opt1,
+        ),
+        (
+            "relative-path-bytecode-opt2",
+            ResourceField::RelativeFilesystemModuleBytecodeOpt2,
+            &resource.relative_path_module_bytecode_opt2,
+        ),
+        (
+            "relative-path-extension-module",
+            ResourceField::RelativeFilesystemExtensionModuleSharedLibrary,
+            &resource.relative_path_extension_module_shared_library,
+        ),
+    ] {
+        if let Some(path) = value {
+            fields.push((name.to_string(), field, FieldValue::Path(path)));
+        }
+    }
+
+    for (prefix, field, value) in [
+        (
+            "relative-path-package-resource",
+            ResourceField::RelativeFilesystemPackageResources,
+            &resource.relative_path_package_resources,
+        ),
+        (
+            "relative-path-distribution-resource",
+            ResourceField::RelativeFilesystemDistributionResource,
+            &resource.relative_path_distribution_resources,
+        ),
+    ] {
+        if let Some(entries) = value {
+            for (key, path) in entries.iter().collect::<BTreeMap<_, _>>() {
+                fields.push((format!("{}:{}", prefix, key), field, FieldValue::Path(path)));
+            }
+        }
+    }
+
+    if let Some(path) = &resource.file_data_utf8_relative_path {
+        fields.push((
+            "relative-path-file-data".to_string(),
+            ResourceField::FileDataUtf8RelativePath,
+            FieldValue::Path(Path::new(path.as_ref())),
+        ));
+    }
+
+    fields
+}
+
+fn codec_name(codec: CompressionCodec) -> &'static str {
+    match codec {
+        CompressionCodec::Zstd => "zstd",
+        CompressionCodec::Lz4 => "lz4",
+    }
+}
+
+/// Print a summary of packed resources data and its resources.
+pub fn list(path: &Path) -> Result<()> {
+    let file = PackedResourcesFile::from_path(path)?;
+    let data = file.data();
+
+    println!("Format version: {}", data[HEADER_V3.len() - 1]);
+    if let Some(offset) = file.embedded_offset {
+        println!("Embedded at offset: {}", offset);
+    } else {
+        let trailer = read_integrity_trailer(data)
+            .map_err(|e| anyhow!("error reading integrity trailer: {}", e))?;
+
+        println!(
+            "Integrity trailer: {}",
+            match trailer.map(|trailer| trailer.signature_algorithm) {
+                None => "none",
+                Some(IntegritySignatureAlgorithm::None) => "unsigned",
+                Some(IntegritySignatureAlgorithm::Ed25519) => "signed (Ed25519)",
+            }
+        );
+    }
+
+    // Parse without decompressing so stored sizes can be reported.
+    let parser =
+        load_resources(data).map_err(|e| anyhow!("error parsing packed resources: {}", e))?;
+
+    println!(
+        "Name index: {}",
+        if parser.name_index().is_some() {
+            "yes"
+        } else {
+            "no"
+        }
+    );
+
+    let resources = parser
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow!("error parsing packed resources: {}", e))?;
+
+    println!("Resources: {}", resources.len());
+
+    for resource in resources {
+        println!();
+        println!("{}", resource.name);
+        println!("  flags: {}", resource_flags(&resource).join(", "));
+
+        for (name, field, value) in resource_fields(&resource) {
+            match resource.field_compression(field) {
+                Some(codec) => println!(
+                    "  {}: {} ({} compressed)",
+                    name,
+                    value.describe(),
+                    codec_name(codec)
+                ),
+                None => println!("  {}: {}", name, value.describe()),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Write the data of a single resource field to stdout.
+pub fn dump(path: &Path, resource_name: &str, field_name: &str) -> Result<()> {
+    let file = PackedResourcesFile::from_path(path)?;
+
+    let resource = file
+        .resources()?
+        .into_iter()
+        .find(|resource| resource.name == resource_name)
+        .ok_or_else(|| anyhow!("resource {} not found", resource_name))?;
+
+    let fields = resource_fields(&resource);
+    let value = fields
+        .iter()
+        .find_map(|(name, _, value)| {
+            if name == field_name {
+                Some(value)
+            } else {
+                None
+            }
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "resource {} does not have field {}; available fields: {}",
+                resource_name,
+                field_name,
+                fields
+                    .iter()
+                    .map(|(name, _, _)| name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })?;
+
+    match value {
+        FieldValue::Data(data) => {
+            let mut stdout = std::io::stdout();
+            stdout.write_all(data)?;
+            stdout.flush()?;
+        }
+        value => println!("{}", value.describe()),
+    }
+
+    Ok(())
+}
+
+/// Join a relative path stored in packed resources to a destination directory.
+///
+/// Paths attempting to escape the destination directory are rejected.
+fn join_relative(dest: &Path, path: &str) -> Result<PathBuf> {
+    let path = Path::new(path);
+
+    if path
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return Err(anyhow!(
+            "refusing to write to unsafe path {}",
+            path.display()
+        ));
+    }
+
+    Ok(dest.join(path))
+}
+
+/// Resolve files to materialize for a resource.
+///
+/// Python modules are laid out as they would be on the filesystem. Bytecode
+/// is raw marshal data and not a valid `.pyc` file, so it is written with a
+/// `.bytecode` suffix. Filesystem-relative fields only hold paths and aren't
+/// materialized.
+fn resource_files<'r>(resource: &'r Resource<u8>) -> Vec<(String, &'r [u8])> {
+    let mut files = vec![];
+
+    let module_path = resource.name.replace('.', "/");
+    let (module_stem, package_dir) = if resource.is_python_package {
+        (format!("{}/__init__", module_path), module_path.clone())
+    } else {
+        let package_dir = match module_path.rfind('/') {
+            Some(index) => module_path[0..index].to_string(),
+            None => "".to_string(),
+        };
+
+        (module_path.clone(), package_dir)
+    };
+
+    for (suffix, value) in [
+        ("py", &resource.in_memory_source),
+        ("bytecode", &resource.in_memory_bytecode),
+        ("opt-1.bytecode", &resource.in_memory_bytecode_opt1),
+        ("opt-2.bytecode", &resource.in_memory_bytecode_opt2),
+        (
+            "extension-module",
+            &resource.in_memory_extension_module_shared_library,
+        ),
+    ] {
+        if let Some(data) = value {
+            files.push((format!("{}.{}", module_stem, suffix), data.as_ref()));
+        }
+    }
+
+    if let Some(entries) = &resource.in_memory_package_resources {
+        for (key, data) in entries {
+            let path = if resource.is_python_package {
+                format!("{}/{}", module_path, key)
+            } else if package_dir.is_empty() {
+                key.to_string()
+            } else {
+                format!("{}/{}", package_dir, key)
+            };
+
+            files.push((path, data.as_ref()));
+        }
+    }
+
+    if let Some(entries) = &resource.in_memory_distribution_resources {
+        for (key, data) in entries {
+            files.push((
+                format!("{}.dist-info/{}", resource.name, key),
+                data.as_ref(),
+            ));
+        }
+    }
+
+    for data in [
+        &resource.in_memory_shared_library,
+        &resource.file_data_embedded,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        files.push((resource.name.to_string(), data.as_ref()));
+    }
+
+    files
+}
+
+/// Extract all in-memory resource data to a directory tree.
+pub fn extract(path: &Path, dest_path: &Path) -> Result<()> {
+    let file = PackedResourcesFile::from_path(path)?;
+
+    for resource in file.resources()? {
+        for (relative_path, data) in resource_files(&resource) {
+            let dest = join_relative(dest_path, &relative_path)?;
+
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("creating {}", parent.display()))?;
+            }
+
+            println!("writing {}", dest.display());
+            std::fs::write(&dest, data).with_context(|| format!("writing {}", dest.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Describes how a resource differs between two sets of packed resources.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ResourceDifference {
+    /// Resource only exists in the second set.
+    Added(String),
+    /// Resource only exists in the first set.
+    Removed(String),
+    /// Resource exists in both sets with differing content.
+    ///
+    /// Holds the names of changed flags and fields.
+    Changed(String, Vec<String>),
+}
+
+/// Compute the differences between resources in two packed resources files.
+///
+/// Field data is compared after decompression, so compression settings alone
+/// don't constitute a difference.
+pub fn diff_resources(
+    a: &PackedResourcesFile,
+    b: &PackedResourcesFile,
+) -> Result<Vec<ResourceDifference>> {
+    let a = a
+        .resources()?
+        .into_iter()
+        .map(|resource| (resource.name.to_string(), resource))
+        .collect::<BTreeMap<_, _>>();
+    let b = b
+        .resources()?
+        .into_iter()
+        .map(|resource| (resource.name.to_string(), resource))
+        .collect::<BTreeMap<_, _>>();
+
+    let names = a.keys().chain(b.keys()).collect::<BTreeSet<_>>();
+
+    let mut differences = vec![];
+
+    for name in names {
+        let (a, b) = match (a.get(name), b.get(name)) {
+            (Some(a), Some(b)) => (a, b),
+            (None, Some(_)) => {
+                differences.push(ResourceDifference::Added(name.clone()));
+                continue;
+            }
+            (Some(_), None) => {
+                differences.push(ResourceDifference::Removed(name.clone()));
+                continue;
+            }
+            (None, None) => unreachable!(),
+        };
+
+        let a_flags = resource_flags(a);
+        let b_flags = resource_flags(b);
+        let mut changed = a_flags
+            .iter()
+            .chain(b_flags.iter())
+            .filter(|flag| a_flags.contains(flag) != b_flags.contains(flag))
+            .map(|flag| flag.to_string())
+            .collect::<BTreeSet<_>>();
+
+        let a_fields = resource_fields(a)
+            .into_iter()
+            .map(|(name, _, value)| (name, value))
+            .collect::<BTreeMap<_, _>>();
+        let b_fields = resource_fields(b)
+            .into_iter()
+            .map(|(name, _, value)| (name, value))
+            .collect::<BTreeMap<_, _>>();
+
+        for field in a_fields.keys().chain(b_fields.keys()) {
+            if a_fields.get(field) != b_fields.get(field) {
+                changed.insert(field.clone());
+            }
+        }
+
+        if !changed.is_empty() {
+            differences.push(ResourceDifference::Changed(
+                name.clone(),
+                changed.into_iter().collect(),
+            ));
+        }
+    }
+
+    Ok(differences)
+}
+
+/// Print the differences between resources in two packed resources files.
+pub fn diff(a_path: &Path, b_path: &Path) -> Result<()> {
+    let a = PackedResourcesFile::from_path(a_path)?;
+    let b = PackedResourcesFile::from_path(b_path)?;
+
+    for difference in diff_resources(&a, &b)? {
+        match difference {
+            ResourceDifference::Added(name) => println!("+ {}", name),
+            ResourceDifference::Removed(name) => println!("- {}", name),
+            ResourceDifference::Changed(name, fields) => {
+                println!("M {} ({})", name, fields.join(", "))
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Usage of resources in packed resources data according to import traces.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ResourcesUsage {
+    /// Names of resources that were used.
+    ///
+    /// Resources that aren't Python modules, such as shared libraries, aren't
+    /// recorded by import traces and are always considered used.
+    pub used: BTreeSet<String>,
+
+    /// Names of Python modules that were never imported.
+    pub unused_modules: BTreeSet<String>,
+
+    /// Package resources that were never read, as `(package, name)` tuples.
+    pub unused_package_resources: BTreeSet<(String, String)>,
+
+    /// Package distribution resources that were never read, as `(package, name)` tuples.
+    pub unused_distribution_resources: BTreeSet<(String, String)>,
+}
+
+/// Resolve which resources in packed resources data were used by import traces.
+pub fn resources_usage(file: &PackedResourcesFile, trace: &ImportTrace) -> Result<ResourcesUsage> {
+    let module_names = trace.module_names();
+    let package_resources = trace.package_resources();
+    let distribution_resources = trace.package_distribution_resources();
+
+    let mut usage = ResourcesUsage::default();
+
+    for resource in file.resources()? {
+        let name = resource.name.as_ref();
+
+        let is_module = resource.is_python_module
+            || resource.is_python_extension_module
+            || resource.is_python_builtin_extension_module
+            || resource.is_python_frozen_module;
+
+        let mut used = !is_module || module_names.contains(name);
+
+        let resource_names = resource
+            .in_memory_package_resources
+            .iter()
+            .flat_map(|resources| resources.keys())
+            .chain(
+                resource
+                    .relative_path_package_resources
+                    .iter()
+                    .flat_map(|resources| resources.keys()),
+            );
+
+        for resource_name in resource_names {
+            if package_resources.contains(&(name, resource_name.as_ref())) {
+                used = true;
+            } else {
+                usage
+                    .unused_package_resources
+                    .insert((name.to_string(), resource_name.to_string()));
+            }
+        }
+
+        let distribution_names = resource
+            .in_memory_distribution_resources
+            .iter()
+            .flat_map(|resources| resources.keys())
+            .chain(
+                resource
+                    .relative_path_distribution_resources
+                    .iter()
+                    .flat_map(|resources| resources.keys()),
+            );
+
+        for resource_name in distribution_names {
+            if distribution_resources.contains(&(name, resource_name.as_ref())) {
+                used = true;
+            } else {
+                usage
+                    .unused_distribution_resources
+                    .insert((name.to_string(), resource_name.to_string()));
+            }
+        }
+
+        if used {
+            usage.used.insert(name.to_string());
+        } else {
+            usage.unused_modules.insert(name.to_string());
+        }
+    }
+
+    Ok(usage)
+}
+
+/// Load and merge import traces from files or directories.
+///
+/// Directories are searched for `import-trace-*` files, as written by
+/// interpreters having `write_import_trace_directory_env` set.
+fn load_import_traces(paths: &[&Path]) -> Result<ImportTrace> {
+    let mut trace = ImportTrace::default();
+    let mut count = 0;
+
+    for path in paths {
+        if path.is_dir() {
+            for entry in
+                std::fs::read_dir(path).with_context(|| format!("reading {}", path.display()))?
+            {
+                let entry = entry?;
+
+                if entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with("import-trace-")
+                {
+                    trace.merge(&ImportTrace::from_path(entry.path())?);
+                    count += 1;
+                }
+            }
+        } else {
+            trace.merge(&ImportTrace::from_path(path)?);
+            count += 1;
+        }
+    }
+
+    if count == 0 {
+        return Err(anyhow!("no import trace files found"));
+    }
+
+    Ok(trace)
+}
+
+/// Write names of used resources to a file usable by `filter_resources_from_files()`.
+fn write_filter_file(path: &Path, usage: &ResourcesUsage) -> Result<()> {
+    let mut fh =
+        std::fs::File::create(path).with_context(|| format!("creating {}", path.display()))?;
+
+    writeln!(fh, "# Resources used according to import traces.")?;
+    for name in &usage.used {
+        writeln!(fh, "{}", name)?;
+    }
+
+    Ok(())
+}
+
+/// Print packaged resources that import traces never used.
+pub fn usage(path: &Path, trace_paths: &[&Path], filter_path: Option<&Path>) -> Result<()> {
+    let file = PackedResourcesFile::from_path(path)?;
+    let trace = load_import_traces(trace_paths)?;
+
+    let usage = resources_usage(&file, &trace)?;
+
+    println!("Unused modules: {}", usage.unused_modules.len());
+    for name in &usage.unused_modules {
+        println!("  {}", name);
+    }
+
+    println!(
+        "Unused package resources: {}",
+        usage.unused_package_resources.len()
+    );
+    for (package, name) in &usage.unused_package_resources {
+        println!("  {}: {}", package, name);
+    }
+
+    println!(
+        "Unused distribution resources: {}",
+        usage.unused_distribution_resources.len()
+    );
+    for (package, name) in &usage.unused_distribution_resources {
+        println!("  {}: {}", package, name);
+    }
+
+    let missing = trace.missing_names();
+    println!("Failed module lookups: {}", missing.len());
+    for name in missing {
+        println!("  {}", name);
+    }
+
+    if let Some(filter_path) = filter_path {
+        write_filter_file(filter_path, &usage)?;
+        println!("wrote filter file to {}", filter_path.display());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        python_packaging::import_trace::ImportTraceEntry,
+        python_packed_resources::{
+            write_packed_resources_v3, write_packed_resources_v4, CompressionPolicy,
+        },
+        std::borrow::Cow,
+    };
+
+    fn module(name: &str, source: &[u8]) -> Resource<'static, u8> {
+        Resource {
+            name: Cow::Owned(name.to_string()),
+            is_python_module: true,
+            in_memory_source: Some(Cow::Owned(source.to_vec())),
+            ..Resource::default()
+        }
+    }
+
+    fn packed(resources: &[Resource<u8>]) -> Result<Vec<u8>> {
+        let mut data = vec![];
+        write_packed_resources_v3(resources, &mut data, None)?;
+
+        Ok(data)
+    }
+
+    #[test]
+    fn test_standalone() -> Result<()> {
+        let data = packed(&[module("foo", b"import bar")])?;
+        let file = PackedResourcesFile::from_data(data.clone())?;
+
+        assert!(file.embedded_offset.is_none());
+        assert_eq!(file.data(), data.as_slice());
+        assert_eq!(file.resources()?, vec![module("foo", b"import bar")]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_embedded() -> Result<()> {
+        let data = packed(&[module("foo", b"import bar")])?;
+
+        // Decoy headers before the real data shouldn't confuse us.
+        let mut file_data = b"prefix".to_vec();
+        file_data.extend_from_slice(HEADER_V3);
+        file_data.extend_from_slice(HEADER_V4);
+        file_data.extend_from_slice(b"garbage");
+        let offset = file_data.len();
+        file_data.extend_from_slice(&data);
+        file_data.extend_from_slice(b"suffix");
+
+        let file = PackedResourcesFile::from_data(file_data)?;
+        assert_eq!(file.embedded_offset, Some(offset));
+        assert_eq!(file.data(), data.as_slice());
+
+        assert!(PackedResourcesFile::from_data(b"no resources here".to_vec()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resource_files() -> Result<()> {
+        let mut package = module("foo.bar", b"package");
+        package.is_python_package = true;
+        package.in_memory_bytecode = Some(Cow::Owned(b"bytecode".to_vec()));
+        package.in_memory_package_resources = Some(
+            [(
+                Cow::Owned("data/file.txt".to_string()),
+                Cow::Owned(b"data".to_vec()),
+            )]
+            .into_iter()
+            .collect(),
+        );
+
+        let mut files = resource_files(&package);
+        files.sort();
+
+        assert_eq!(
+            files,
+            vec![
+                (
+                    "foo/bar/__init__.bytecode".to_string(),
+                    b"bytecode".as_ref()
+                ),
+                ("foo/bar/__init__.py".to_string(), b"package".as_ref()),
+                ("foo/bar/data/file.txt".to_string(), b"data".as_ref()),
+            ]
+        );
+
+        assert_eq!(
+            resource_files(&module("foo.baz", b"module")),
+            vec![("foo/baz.py".to_string(), b"module".as_ref())]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_join_relative() -> Result<()> {
+        let dest = Path::new("dest");
+
+        assert_eq!(join_relative(dest, "foo/bar.py")?, dest.join("foo/bar.py"));
+        assert!(join_relative(dest, "../foo").is_err());
+        assert!(join_relative(dest, "/foo").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff() -> Result<()> {
+        let a = PackedResourcesFile::from_data(packed(&[
+            module("changed", b"old"),
+            module("removed", b""),
+            module("same", b"same"),
+        ])?)?;
+
+        let mut changed = module("changed", b"new");
+        changed.is_python_package = true;
+
+        let mut b_data = vec![];
+        write_packed_resources_v4(
+            &[changed, module("added", b""), module("same", b"same")],
+            &mut b_data,
+            None,
+            CompressionPolicy::Zstd,
+            true,
+        )?;
+        let b = PackedResourcesFile::from_data(b_data)?;
+
+        assert_eq!(
+            diff_resources(&a, &b)?,
+            vec![
+                ResourceDifference::Added("added".to_string()),
+                ResourceDifference::Changed(
+                    "changed".to_string(),
+                    vec!["python-package".to_string(), "source".to_string()]
+                ),
+                ResourceDifference::Removed("removed".to_string()),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resources_usage() -> Result<()> {
+        let mut package = module("foo", b"pass");
+        package.is_python_package = true;
+        package.in_memory_package_resources = Some(
+            [
+                (
+                    Cow::Owned("read.txt".to_string()),
+                    Cow::Owned(b"data".to_vec()),
+                ),
+                (
+                    Cow::Owned("unread.txt".to_string()),
+                    Cow::Owned(b"data".to_vec()),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        package.in_memory_distribution_resources = Some(
+            [(
+                Cow::Owned("METADATA".to_string()),
+                Cow::Owned(b"data".to_vec()),
+            )]
+            .into_iter()
+            .collect(),
+        );
+
+        let mut metadata_only = module("meta", b"pass");
+        metadata_only.in_memory_distribution_resources = Some(
+            [(
+                Cow::Owned("METADATA".to_string()),
+                Cow::Owned(b"data".to_vec()),
+            )]
+            .into_iter()
+            .collect(),
+        );
+
+        let library = Resource {
+            name: Cow::Borrowed("libfoo.so"),
+            is_shared_library: true,
+            ..Resource::default()
+        };
+
+        let file = PackedResourcesFile::from_data(packed(&[
+            package,
+            module("foo.used", b"pass"),
+            module("foo.unused", b"pass"),
+            metadata_only,
+            library,
+        ])?)?;
+
+        let mut trace = ImportTrace::default();
+        trace.record(ImportTraceEntry::Module {
+            name: "foo".to_string(),
+            layer: Some(0),
+            flavor: "source-bytecode".to_string(),
+        });
+        trace.record(ImportTraceEntry::Module {
+            name: "foo.used".to_string(),
+            layer: Some(0),
+            flavor: "source-bytecode".to_string(),
+        });
+        trace.record(ImportTraceEntry::PackageResource {
+            package: "foo".to_string(),
+            name: "read.txt".to_string(),
+        });
+        trace.record(ImportTraceEntry::PackageDistributionResource {
+            package: "meta".to_string(),
+            name: "METADATA".to_string(),
+        });
+
+        let usage = resources_usage(&file, &trace)?;
+
+        assert_eq!(
+            usage.used,
+            ["foo", "foo.used", "libfoo.so", "meta"]
+                .into_iter()
+                .map(|s| s.to_string())
+                .collect()
+        );
+        assert_eq!(
+            usage.unused_modules,
+            ["foo.unused".to_string()].into_iter().collect()
+        );
+        assert_eq!(
+            usage.unused_package_resources,
+            [("foo".to_string(), "unread.txt".to_string())]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(
+            usage.unused_distribution_resources,
+            [("foo".to_string(), "METADATA".to_string())]
+                .into_iter()
+                .collect()
+        );
+
+        Ok(())
+    }
+}
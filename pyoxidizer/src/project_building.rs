@@ -5,19 +5,20 @@
 use {
     crate::{
         environment::{canonicalize_path, Environment, RustEnvironment},
-        licensing::{licenses_from_cargo_manifest, log_licensing_info},
+        licensing::{enforce_license_policy, licenses_from_cargo_manifest, log_licensing_info},
         project_layout::initialize_project,
         py_packaging::{
             binary::{LibpythonLinkMode, PythonBinaryBuilder},
             distribution::AppleSdkInfo,
             embedding::{EmbeddedPythonContext, DEFAULT_PYTHON_CONFIG_FILENAME},
         },
+        sbom::{Sbom, SbomFile, SbomFormat},
         starlark::eval::{EvaluationContext, EvaluationContextBuilder},
     },
     anyhow::{anyhow, Context, Result},
     apple_sdk::AppleSdk,
     duct::cmd,
-    log::warn,
+    log::{info, warn},
     starlark_dialect_build_targets::ResolvedTarget,
     std::{
         collections::HashMap,
@@ -25,6 +26,7 @@ use {
         io::{BufRead, BufReader},
         path::{Path, PathBuf},
     },
+    tugger_file_manifest::FileEntry,
 };
 
 /// Find a pyoxidizer.toml configuration file by walking directory ancestry.
@@ -250,6 +252,35 @@ pub struct BuiltExecutable<'a> {
 
     /// Holds state generated from building.
     pub binary_data: EmbeddedPythonContext<'a>,
+
+    /// SBOM documents describing the build, in every supported format.
+    pub sbom_documents: Vec<(SbomFormat, Vec<u8>)>,
+}
+
+impl<'a> BuiltExecutable<'a> {
+    /// Describe the executable and the files installed next to it in an [Sbom].
+    pub fn sbom(&self) -> Result<Sbom> {
+        let mut sbom = Sbom::new(&self.exe_name, self.binary_data.licensing.clone())?;
+
+        sbom.add_file(SbomFile::from_data(&self.exe_name, &self.exe_data));
+        sbom.add_file_manifest(&self.binary_data.extra_files)?;
+
+        for (collection, path) in &self.binary_data.pending_resources {
+            sbom.add_embedded_resources(path, collection.resources.values());
+        }
+
+        Ok(sbom)
+    }
+
+    /// Generate SBOM documents in every supported format.
+    pub fn generate_sbom_documents(&self) -> Result<Vec<(SbomFormat, Vec<u8>)>> {
+        let sbom = self.sbom()?;
+
+        SbomFormat::ALL
+            .iter()
+            .map(|format| Ok((*format, sbom.to_json(*format)?)))
+            .collect()
+    }
 }
 
 /// Build an executable embedding Python using an existing Rust project.
@@ -289,6 +320,34 @@ pub fn build_executable_with_rust_project<'a>(
     )
     .context("resolving build environment")?;
 
+    // Construct unified licensing info by combining the Python licensing metadata
+    // with the dynamically derived licensing info for Rust crates from the Cargo manifest.
+    for component in licenses_from_cargo_manifest(
+        project_path.join("Cargo.toml"),
+        false,
+        cargo_features(exe),
+        Some(target_triple),
+        &build_env.rust_environment,
+        include_self_license,
+    )?
+    .into_components()
+    {
+        embedded_data.add_licensed_component(component)?;
+    }
+
+    // Inform user about licensing info.
+    log_licensing_info(embedded_data.licensing());
+
+    // Refuse to build binaries with unacceptable licensing.
+    if let Some(policy) = exe.license_policy() {
+        enforce_license_policy(
+            policy,
+            embedded_data.licensing(),
+            exe.iter_resources(),
+            exe.library_links(),
+        )?;
+    }
+
     warn!(
         "building with Rust {}",
         build_env.rust_environment.rust_version.semver
@@ -364,30 +423,30 @@ pub fn build_executable_with_rust_project<'a>(
         std::fs::read(&exe_path).with_context(|| format!("reading {}", exe_path.display()))?;
     let exe_name = exe_path.file_name().unwrap().to_string_lossy().to_string();
 
-    // Construct unified licensing info by combining the Python licensing metadata
-    // with the dynamically derived licensing info for Rust crates from the Cargo manifest.
-    for component in licenses_from_cargo_manifest(
-        project_path.join("Cargo.toml"),
-        false,
-        cargo_features(exe),
-        Some(target_triple),
-        &build_env.rust_environment,
-        include_self_license,
-    )?
-    .into_components()
-    {
-        embedded_data.add_licensed_component(component)?;
-    }
-
-    // Inform user about licensing info.
-    log_licensing_info(embedded_data.licensing());
-
-    Ok(BuiltExecutable {
+    let mut build = BuiltExecutable {
         exe_path: Some(exe_path),
         exe_name,
         exe_data,
         binary_data: embedded_data,
-    })
+        sbom_documents: vec![],
+    };
+
+    // SBOMs describe the other files installed next to the executable. So
+    // they are added last.
+    build.sbom_documents = build.generate_sbom_documents()?;
+
+    for (format, data) in &build.sbom_documents {
+        if build.binary_data.sbom_formats.contains(format) {
+            let filename = format.filename(&build.exe_name);
+            info!("generated SBOM {}", filename);
+            build.binary_data.extra_files.add_file_entry(
+                Path::new(&filename),
+                FileEntry::new_from_data(data.clone(), false),
+            )?;
+        }
+    }
+
+    Ok(build)
 }
 
 /// Build a Python executable using a temporary Rust project.
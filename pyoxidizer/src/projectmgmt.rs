@@ -19,9 +19,14 @@ use {
             standalone_distribution::StandaloneDistribution,
         },
         python_distributions::PYTHON_DISTRIBUTIONS,
-        starlark::eval::EvaluationContextBuilder,
+        sbom::{SbomFormat, RECORDED_SBOM_DIR},
+        starlark::{
+            eval::{EvaluationContext, EvaluationContextBuilder},
+            python_executable::PythonExecutableValue,
+        },
     },
     anyhow::{anyhow, Context, Result},
+    log::warn,
     python_packaging::licensing::LicenseFlavor,
     python_packaging::{
         filesystem_scanning::find_python_resources,
@@ -29,6 +34,7 @@ use {
         resource::PythonResource,
         wheel::WheelArchive,
     },
+    starlark_dialect_build_targets::ResolvedTarget,
     std::{
         collections::HashMap,
         fs::create_dir_all,
@@ -101,6 +107,27 @@ pub fn list_targets(env: &Environment, project_path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Build resolved targets.
+///
+/// Bytecode cache hits and misses are reported once all targets are built.
+fn build_targets(
+    env: &Environment,
+    context: &mut EvaluationContext,
+    targets: impl IntoIterator<Item = String>,
+) -> Result<Vec<ResolvedTarget>> {
+    let resolved = targets
+        .into_iter()
+        .map(|target| context.build_resolved_target(&target))
+        .collect::<Result<Vec<_>>>()?;
+
+    let stats = env.bytecode_cache().stats();
+    if stats.lookups() > 0 {
+        warn!("{}", stats);
+    }
+
+    Ok(resolved)
+}
+
 /// Build a PyOxidizer enabled project.
 ///
 /// This is a glorified wrapper around `cargo build`. Our goal is to get the
@@ -113,6 +140,7 @@ pub fn build(
     resolve_targets: Option<Vec<String>>,
     extra_vars: HashMap<String, Option<String>>,
     release: bool,
+    locked: bool,
     verbose: bool,
 ) -> Result<()> {
     let config_path = find_pyoxidizer_config_file_env(project_path).ok_or_else(|| {
@@ -128,13 +156,15 @@ pub fn build(
         .release(release)
         .verbose(verbose)
         .resolve_targets_optional(resolve_targets)
+        .locked(locked)
         .into_context()?;
 
     context.evaluate_file(&config_path)?;
 
-    for target in context.targets_to_resolve()? {
-        context.build_resolved_target(&target)?;
-    }
+    let targets = context.targets_to_resolve()?;
+    build_targets(env, &mut context, targets)?;
+
+    context.write_lock_file()?;
 
     Ok(())
 }
@@ -167,7 +197,110 @@ pub fn run(
 
     context.evaluate_file(&config_path)?;
 
-    context.run_target(target)
+    let target = match target {
+        Some(target) => target.to_string(),
+        None => context
+            .default_target()?
+            .ok_or_else(|| anyhow!("unable to determine target to run"))?,
+    };
+
+    for resolved in build_targets(env, &mut context, [target])? {
+        resolved.run()?;
+    }
+
+    Ok(())
+}
+
+/// Write SBOMs describing a previously built `PythonExecutable` target.
+///
+/// The documents are copied from those recorded by the last build of the
+/// target. If `target` is not specified, the first resolved target producing
+/// a `PythonExecutable` is used.
+#[allow(clippy::too_many_arguments)]
+pub fn sbom(
+    env: &Environment,
+    project_path: &Path,
+    target_triple: Option<&str>,
+    release: bool,
+    target: Option<&str>,
+    extra_vars: HashMap<String, Option<String>>,
+    formats: Vec<SbomFormat>,
+    verbose: bool,
+) -> Result<()> {
+    let config_path = find_pyoxidizer_config_file_env(project_path).ok_or_else(|| {
+        anyhow!(
+            "unable to find PyOxidizer config file at {}",
+            project_path.display()
+        )
+    })?;
+    let target_triple = resolve_target(target_triple)?;
+    let windows = target_triple.contains("pc-windows");
+
+    let mut context = EvaluationContextBuilder::new(env, config_path.clone(), target_triple)
+        .extra_vars(extra_vars)
+        .release(release)
+        .verbose(verbose)
+        .resolve_target_optional(target)
+        .into_context()?;
+
+    context.evaluate_file(&config_path)?;
+
+    let candidates = if let Some(target) = target {
+        vec![target.to_string()]
+    } else {
+        context.targets_to_resolve()?
+    };
+
+    let mut resolved = None;
+    for candidate in candidates {
+        if let Some(value) = context.target_resolved_value(&candidate)? {
+            if value.downcast_ref::<PythonExecutableValue>().is_some() {
+                resolved = Some((candidate, value));
+                break;
+            }
+        }
+    }
+
+    let (target, value) = resolved.ok_or_else(|| match target {
+        Some(target) => anyhow!("target {} does not produce a PythonExecutable", target),
+        None => anyhow!("unable to find a target producing a PythonExecutable"),
+    })?;
+
+    let exe_name = {
+        let exe_value = value.downcast_ref::<PythonExecutableValue>().unwrap();
+        let exe = exe_value.inner(&target).map_err(|e| anyhow!("{:?}", e))?;
+
+        // Matches the file name of the executable written by the build.
+        if windows {
+            format!("{}.exe", exe.name())
+        } else {
+            exe.name()
+        }
+    };
+
+    let output_path = context.target_build_path(&target)?;
+    let recorded_path = output_path.join(RECORDED_SBOM_DIR);
+
+    for format in formats {
+        let filename = format.filename(&exe_name);
+        let source_path = recorded_path.join(&filename);
+
+        if !source_path.exists() {
+            return Err(anyhow!(
+                "no SBOM recorded for target {}; run `pyoxidizer build {}` first",
+                target,
+                target
+            ));
+        }
+
+        let dest_path = output_path.join(&filename);
+        std::fs::copy(&source_path, &dest_path)
+            .with_context(|| format!("writing {}", dest_path.display()))?;
+
+        println!("{}", dest_path.display());
+    }
+
+    Ok(())
 }
 
 pub fn cache_clear(env: &Environment) -> Result<()> {
@@ -9,11 +9,13 @@ Defining and manipulating binaries embedding Python.
 use {
     crate::{
         environment::Environment,
+        lock_file::DistributionLock,
         py_packaging::{distribution::AppleSdkInfo, embedding::EmbeddedPythonContext},
+        sbom::SbomFormat,
     },
     anyhow::Result,
     python_packaging::{
-        licensing::{LicensedComponent, LicensedComponents},
+        licensing::{LicensePolicy, LicensedComponent, LicensedComponents},
         policy::PythonPackagingPolicy,
         resource::{
             PythonExtensionModule, PythonModuleSource, PythonPackageDistributionResource,
@@ -23,7 +25,11 @@ use {
             AddResourceAction, PrePackagedResource, PythonResourceAddCollectionContext,
         },
     },
-    std::{collections::HashMap, path::Path, sync::Arc},
+    std::{
+        collections::{BTreeMap, BTreeSet, HashMap},
+        path::{Path, PathBuf},
+        sync::Arc,
+    },
     tugger_file_manifest::File,
     tugger_windows::VcRedistributablePlatform,
 };
@@ -217,6 +223,15 @@ pub trait PythonBinaryBuilder {
     /// Set the directory to install tcl/tk files into.
     fn set_tcl_files_path(&mut self, value: Option<String>);
 
+    /// Path to a PKCS#8 Ed25519 private key used to sign packed resources data.
+    fn packed_resources_signing_key_path(&self) -> &Option<PathBuf>;
+
+    /// Set the path to a PKCS#8 Ed25519 private key used to sign packed resources data.
+    ///
+    /// When set, packed resources data is written with a signed integrity trailer
+    /// and the embedded interpreter config is taught to require it.
+    fn set_packed_resources_signing_key_path(&mut self, value: Option<PathBuf>);
+
     /// The value of the `windows_subsystem` Rust attribute for the generated Rust project.
     fn windows_subsystem(&self) -> &str;
 
@@ -229,12 +244,32 @@ pub trait PythonBinaryBuilder {
     /// Set the path of a filename to write containing a licensing report.
     fn set_licenses_filename(&mut self, value: Option<String>);
 
+    /// Formats of SBOM documents to write next to the built binary.
+    fn sbom_formats(&self) -> &[SbomFormat];
+
+    /// Set formats of SBOM documents to write next to the built binary.
+    fn set_sbom_formats(&mut self, formats: Vec<SbomFormat>);
+
+    /// Policy that licenses of components in the built binary must satisfy.
+    fn license_policy(&self) -> Option<&LicensePolicy>;
+
+    /// Set the policy that licenses of components in the built binary must satisfy.
+    fn set_license_policy(&mut self, policy: Option<LicensePolicy>);
+
     /// How packed Python resources will be loaded by the binary.
     fn packed_resources_load_mode(&self) -> &PackedResourcesLoadMode;
 
     /// Set how packed Python resources will be loaded by the binary.
     fn set_packed_resources_load_mode(&mut self, load_mode: PackedResourcesLoadMode);
 
+    /// Binary relative paths of optional packed resources files overlaying embedded resources.
+    fn packed_resources_overlay_paths(&self) -> &[String];
+
+    /// Set binary relative paths of optional packed resources files overlaying embedded resources.
+    ///
+    /// Files are loaded in order, if present, after other packed resources data.
+    fn set_packed_resources_overlay_paths(&mut self, paths: Vec<String>);
+
     /// Obtain an iterator over all resource entries that will be embedded in the binary.
     ///
     /// This likely does not return extension modules that are statically linked
@@ -243,6 +278,11 @@ pub trait PythonBinaryBuilder {
         &'a self,
     ) -> Box<dyn Iterator<Item = (&'a String, &'a PrePackagedResource)> + 'a>;
 
+    /// Obtain the names of extension modules linking each library.
+    ///
+    /// Keys are library names.
+    fn library_links(&self) -> &BTreeMap<String, BTreeSet<String>>;
+
     /// Resolve license metadata from an iterable of `PythonResource` and store that data.
     ///
     /// The resolved license data can later be used to ensure packages conform
@@ -257,22 +297,39 @@ pub trait PythonBinaryBuilder {
     /// Runs `pip download` using the binary builder's settings.
     ///
     /// Returns resources discovered from the Python packages downloaded.
+    /// Downloaded distributions are recorded in `lock`.
     fn pip_download(
         &mut self,
         env: &Environment,
         verbose: bool,
         args: &[String],
+        lock: &DistributionLock,
     ) -> Result<Vec<PythonResource>>;
 
     /// Runs `pip install` using the binary builder's settings.
     ///
     /// Returns resources discovered as part of performing an install.
+    /// Installed distributions are recorded in `lock`.
     fn pip_install(
         &mut self,
         env: &Environment,
         verbose: bool,
         install_args: &[String],
         extra_envs: &HashMap<String, String>,
+        lock: &DistributionLock,
+    ) -> Result<Vec<PythonResource>>;
+
+    /// Installs wheels for pinned requirements found in local directories.
+    ///
+    /// Unlike [Self::pip_install()], this does not run `pip`.
+    ///
+    /// Returns resources discovered from the installed wheels.
+    /// Installed distributions are recorded in `lock`.
+    fn install_wheels(
+        &mut self,
+        requirements_path: &Path,
+        find_links: &[PathBuf],
+        lock: &DistributionLock,
     ) -> Result<Vec<PythonResource>>;
 
     /// Reads Python resources from the filesystem.
@@ -0,0 +1,491 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Persistent cache of compiled Python bytecode.
+
+Compiling bytecode for every module in a build is slow and the results
+rarely change between builds. [BytecodeCache] stores compiler output in a
+content-addressed directory keyed by the Python interpreter performing
+compilation, the module name, the optimization level, the output mode, and
+the SHA-256 of the source code.
+
+[CachingBytecodeCompiler] is a [PythonBytecodeCompiler] consulting a cache
+before compiling. It only spawns a real compiler on a cache miss.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    python_packaging::{
+        bytecode::{CompileMode, PythonBytecodeCompiler},
+        resource::BytecodeOptimizationLevel,
+    },
+    sha2::{Digest, Sha256},
+    std::{
+        fmt::{Display, Formatter},
+        path::{Path, PathBuf},
+        sync::atomic::{AtomicU64, Ordering},
+    },
+};
+
+/// Version of the cache layout. Bump to invalidate existing entries.
+const CACHE_VERSION: u32 = 1;
+
+/// Counts of cache lookups.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct BytecodeCacheStats {
+    /// Lookups satisfied by the cache.
+    pub hits: u64,
+
+    /// Lookups requiring compilation.
+    pub misses: u64,
+}
+
+impl BytecodeCacheStats {
+    /// Total number of lookups.
+    pub fn lookups(&self) -> u64 {
+        self.hits + self.misses
+    }
+}
+
+impl Display for BytecodeCacheStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "bytecode cache: {} hits, {} misses ({:.1}% hit rate)",
+            self.hits,
+            self.misses,
+            if self.lookups() == 0 {
+                0.0
+            } else {
+                self.hits as f64 * 100.0 / self.lookups() as f64
+            }
+        )
+    }
+}
+
+/// A content-addressed store of compiled bytecode on the filesystem.
+///
+/// The cache can be shared by multiple processes. Entries are written to
+/// temporary files and renamed into place.
+#[derive(Debug)]
+pub struct BytecodeCache {
+    /// Root directory of the cache.
+    path: PathBuf,
+
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl BytecodeCache {
+    /// Construct an instance storing entries in a directory.
+    ///
+    /// The directory is created when the first entry is written.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Root directory of the cache.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Obtain statistics on lookups performed so far.
+    pub fn stats(&self) -> BytecodeCacheStats {
+        BytecodeCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Compute the key of compiled bytecode.
+    ///
+    /// `python` identifies the interpreter performing compilation.
+    pub fn key(
+        python: &str,
+        source: &[u8],
+        filename: &str,
+        optimize: BytecodeOptimizationLevel,
+        output_mode: &CompileMode,
+    ) -> String {
+        let version = CACHE_VERSION.to_string();
+        let optimize = i32::from(optimize).to_string();
+        let output_mode: &[u8] = match output_mode {
+            CompileMode::Bytecode => b"bytecode",
+            CompileMode::PycCheckedHash => b"pyc-checked-hash",
+            CompileMode::PycUncheckedHash => b"pyc-unchecked-hash",
+        };
+        let source_digest = Sha256::digest(source);
+
+        let fields: [&[u8]; 6] = [
+            version.as_bytes(),
+            python.as_bytes(),
+            filename.as_bytes(),
+            optimize.as_bytes(),
+            output_mode,
+            source_digest.as_slice(),
+        ];
+
+        let mut hasher = Sha256::new();
+
+        for field in fields {
+            // Length prefixes keep fields from running into each other.
+            hasher.update((field.len() as u64).to_le_bytes());
+            hasher.update(field);
+        }
+
+        hex::encode(hasher.finalize())
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.path.join(&key[0..2]).join(key)
+    }
+
+    fn magic_number_path(&self, python: &str) -> PathBuf {
+        self.path
+            .join("magic")
+            .join(hex::encode(Sha256::digest(python)))
+    }
+
+    /// Look up compiled bytecode, recording a hit or miss.
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        match std::fs::read(self.entry_path(key)) {
+            Ok(data) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(data)
+            }
+            Err(_) => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Store compiled bytecode.
+    pub fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        self.write(&self.entry_path(key), data)
+    }
+
+    /// Obtain the bytecode magic number of an interpreter, if known.
+    pub fn magic_number(&self, python: &str) -> Option<u32> {
+        let data = std::fs::read(self.magic_number_path(python)).ok()?;
+
+        Some(u32::from_le_bytes(data.as_slice().try_into().ok()?))
+    }
+
+    /// Record the bytecode magic number of an interpreter.
+    pub fn set_magic_number(&self, python: &str, magic_number: u32) -> Result<()> {
+        self.write(&self.magic_number_path(python), &magic_number.to_le_bytes())
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        let parent = path
+            .parent()
+            .ok_or_else(|| anyhow!("{} has no parent directory", path.display()))?;
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("creating {}", parent.display()))?;
+
+        let mut temp = tempfile::NamedTempFile::new_in(parent)
+            .with_context(|| format!("creating temporary file in {}", parent.display()))?;
+        std::io::Write::write_all(&mut temp, data)?;
+        temp.persist(path)
+            .with_context(|| format!("persisting {}", path.display()))?;
+
+        Ok(())
+    }
+}
+
+/// Function spawning a bytecode compiler.
+pub type SpawnBytecodeCompiler<'a> =
+    Box<dyn FnMut() -> Result<Box<dyn PythonBytecodeCompiler>> + 'a>;
+
+/// A [PythonBytecodeCompiler] consulting a [BytecodeCache].
+///
+/// The underlying compiler is spawned on the first cache miss, or on
+/// construction if the magic number of the interpreter isn't cached yet.
+/// Compiler output is stored in the cache. Compilation errors are not cached.
+pub struct CachingBytecodeCompiler<'a> {
+    cache: &'a BytecodeCache,
+    python: String,
+    magic_number: u32,
+    spawn: SpawnBytecodeCompiler<'a>,
+    compiler: Option<Box<dyn PythonBytecodeCompiler>>,
+}
+
+impl<'a> CachingBytecodeCompiler<'a> {
+    /// Construct an instance.
+    ///
+    /// `python` identifies the interpreter `spawn` launches. It should change
+    /// whenever the interpreter may produce different bytecode. e.g.
+    /// `cpython-3.10.4`.
+    pub fn new(
+        cache: &'a BytecodeCache,
+        python: impl ToString,
+        mut spawn: SpawnBytecodeCompiler<'a>,
+    ) -> Result<Self> {
+        let python = python.to_string();
+
+        let (magic_number, compiler) = match cache.magic_number(&python) {
+            Some(magic_number) => (magic_number, None),
+            None => {
+                let compiler = spawn()?;
+                let magic_number = compiler.get_magic_number();
+                cache.set_magic_number(&python, magic_number)?;
+
+                (magic_number, Some(compiler))
+            }
+        };
+
+        Ok(Self {
+            cache,
+            python,
+            magic_number,
+            spawn,
+            compiler,
+        })
+    }
+
+    fn compiler(&mut self) -> Result<&mut dyn PythonBytecodeCompiler> {
+        if self.compiler.is_none() {
+            let compiler = (self.spawn)()?;
+
+            if compiler.get_magic_number() != self.magic_number {
+                return Err(anyhow!(
+                    "bytecode magic number of {} changed from {} to {}; remove {} to reset the bytecode cache",
+                    self.python,
+                    self.magic_number,
+                    compiler.get_magic_number(),
+                    self.cache.path().display()
+                ));
+            }
+
+            self.compiler = Some(compiler);
+        }
+
+        Ok(self.compiler.as_deref_mut().unwrap())
+    }
+}
+
+impl PythonBytecodeCompiler for CachingBytecodeCompiler<'_> {
+    fn get_magic_number(&self) -> u32 {
+        self.magic_number
+    }
+
+    fn compile(
+        &mut self,
+        source: &[u8],
+        filename: &str,
+        optimize: BytecodeOptimizationLevel,
+        output_mode: CompileMode,
+    ) -> Result<Vec<u8>> {
+        let key = BytecodeCache::key(&self.python, source, filename, optimize, &output_mode);
+
+        if let Some(data) = self.cache.get(&key) {
+            return Ok(data);
+        }
+
+        let data = self
+            .compiler()?
+            .compile(source, filename, optimize, output_mode)?;
+
+        self.cache.put(&key, &data)?;
+
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::sync::Arc};
+
+    /// A compiler returning source code with a prefix and counting invocations.
+    struct CountingCompiler {
+        magic_number: u32,
+        compiles: Arc<AtomicU64>,
+    }
+
+    impl PythonBytecodeCompiler for CountingCompiler {
+        fn get_magic_number(&self) -> u32 {
+            self.magic_number
+        }
+
+        fn compile(
+            &mut self,
+            source: &[u8],
+            _filename: &str,
+            optimize: BytecodeOptimizationLevel,
+            _output_mode: CompileMode,
+        ) -> Result<Vec<u8>> {
+            self.compiles.fetch_add(1, Ordering::Relaxed);
+
+            let mut res = format!("bc{}:", i32::from(optimize)).into_bytes();
+            res.extend_from_slice(source);
+
+            Ok(res)
+        }
+    }
+
+    #[test]
+    fn key() {
+        let key = |python, source: &[u8], filename, optimize, mode| {
+            BytecodeCache::key(python, source, filename, optimize, &mode)
+        };
+
+        let base = key(
+            "cpython-3.10.4",
+            b"foo",
+            "foo",
+            BytecodeOptimizationLevel::Zero,
+            CompileMode::Bytecode,
+        );
+
+        assert_eq!(base.len(), 64);
+        assert_eq!(
+            base,
+            key(
+                "cpython-3.10.4",
+                b"foo",
+                "foo",
+                BytecodeOptimizationLevel::Zero,
+                CompileMode::Bytecode
+            )
+        );
+
+        for other in [
+            key(
+                "cpython-3.10.5",
+                b"foo",
+                "foo",
+                BytecodeOptimizationLevel::Zero,
+                CompileMode::Bytecode,
+            ),
+            key(
+                "cpython-3.10.4",
+                b"bar",
+                "foo",
+                BytecodeOptimizationLevel::Zero,
+                CompileMode::Bytecode,
+            ),
+            key(
+                "cpython-3.10.4",
+                b"foo",
+                "bar",
+                BytecodeOptimizationLevel::Zero,
+                CompileMode::Bytecode,
+            ),
+            key(
+                "cpython-3.10.4",
+                b"foo",
+                "foo",
+                BytecodeOptimizationLevel::One,
+                CompileMode::Bytecode,
+            ),
+            key(
+                "cpython-3.10.4",
+                b"foo",
+                "foo",
+                BytecodeOptimizationLevel::Zero,
+                CompileMode::PycUncheckedHash,
+            ),
+        ] {
+            assert_ne!(base, other);
+        }
+    }
+
+    #[test]
+    fn caching_compiler() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let cache = BytecodeCache::new(temp_dir.path());
+
+        let spawns = Arc::new(AtomicU64::new(0));
+        let compiles = Arc::new(AtomicU64::new(0));
+
+        let new_compiler = |magic_number: u32| {
+            let spawns = spawns.clone();
+            let compiles = compiles.clone();
+
+            CachingBytecodeCompiler::new(
+                &cache,
+                "cpython-3.10.4",
+                Box::new(move || {
+                    spawns.fetch_add(1, Ordering::Relaxed);
+
+                    Ok(Box::new(CountingCompiler {
+                        magic_number,
+                        compiles: compiles.clone(),
+                    }) as Box<dyn PythonBytecodeCompiler>)
+                }),
+            )
+        };
+
+        // The magic number isn't known, so the compiler is spawned eagerly.
+        let mut compiler = new_compiler(42)?;
+        assert_eq!(spawns.load(Ordering::Relaxed), 1);
+        assert_eq!(compiler.get_magic_number(), 42);
+
+        assert_eq!(
+            compiler.compile(
+                b"foo",
+                "foo",
+                BytecodeOptimizationLevel::One,
+                CompileMode::Bytecode
+            )?,
+            b"bc1:foo"
+        );
+        assert_eq!(compiles.load(Ordering::Relaxed), 1);
+        assert_eq!(cache.stats(), BytecodeCacheStats { hits: 0, misses: 1 });
+
+        // A new compiler finds everything in the cache and never spawns.
+        let mut compiler = new_compiler(42)?;
+        assert_eq!(compiler.get_magic_number(), 42);
+        assert_eq!(
+            compiler.compile(
+                b"foo",
+                "foo",
+                BytecodeOptimizationLevel::One,
+                CompileMode::Bytecode
+            )?,
+            b"bc1:foo"
+        );
+        assert_eq!(spawns.load(Ordering::Relaxed), 1);
+        assert_eq!(compiles.load(Ordering::Relaxed), 1);
+        assert_eq!(cache.stats(), BytecodeCacheStats { hits: 1, misses: 1 });
+
+        // A miss spawns the compiler.
+        assert_eq!(
+            compiler.compile(
+                b"bar",
+                "bar",
+                BytecodeOptimizationLevel::Zero,
+                CompileMode::Bytecode
+            )?,
+            b"bc0:bar"
+        );
+        assert_eq!(spawns.load(Ordering::Relaxed), 2);
+        assert_eq!(compiles.load(Ordering::Relaxed), 2);
+
+        // A spawned compiler disagreeing with the cached magic number is an error.
+        let mut compiler = new_compiler(43)?;
+        assert_eq!(compiler.get_magic_number(), 42);
+        assert!(compiler
+            .compile(
+                b"baz",
+                "baz",
+                BytecodeOptimizationLevel::Zero,
+                CompileMode::Bytecode
+            )
+            .is_err());
+
+        assert_eq!(
+            cache.stats().to_string(),
+            "bytecode cache: 1 hits, 3 misses (25.0% hit rate)"
+        );
+
+        Ok(())
+    }
+}
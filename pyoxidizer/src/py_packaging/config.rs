@@ -36,6 +36,17 @@ pub fn default_memory_allocator(target_triple: &str) -> MemoryAllocatorBackend {
     }
 }
 
+fn memory_allocator_backend_to_string(value: MemoryAllocatorBackend) -> &'static str {
+    match value {
+        MemoryAllocatorBackend::Jemalloc => "pyembed::MemoryAllocatorBackend::Jemalloc",
+        MemoryAllocatorBackend::Mimalloc => "pyembed::MemoryAllocatorBackend::Mimalloc",
+        MemoryAllocatorBackend::Snmalloc => "pyembed::MemoryAllocatorBackend::Snmalloc",
+        MemoryAllocatorBackend::Rust => "pyembed::MemoryAllocatorBackend::Rust",
+        MemoryAllocatorBackend::Tracing => "pyembed::MemoryAllocatorBackend::Tracing",
+        MemoryAllocatorBackend::Default => "pyembed::MemoryAllocatorBackend::Default",
+    }
+}
+
 fn optional_bool_to_string(value: &Option<bool>) -> String {
     match value {
         Some(value) => format!("Some({})", value),
@@ -50,6 +61,13 @@ fn optional_string_to_string(value: &Option<String>) -> String {
     }
 }
 
+fn optional_bytes_to_string(value: &Option<Vec<u8>>) -> String {
+    match value {
+        Some(value) => format!("Some(vec![{}])", value.iter().join(", ")),
+        None => "None".to_string(),
+    }
+}
+
 fn path_to_string(value: &Path) -> String {
     format!(
         "std::path::PathBuf::from(\"{}\")",
@@ -87,6 +105,10 @@ pub enum PyembedPackedResourcesSource {
     ///
     /// The string `$ORIGIN` is expanded at runtime.
     MemoryMappedPath(PathBuf),
+    /// Load from a file using memory mapped I/O if the file exists.
+    ///
+    /// The string `$ORIGIN` is expanded at runtime.
+    OptionalMemoryMappedPath(PathBuf),
 }
 
 impl ToString for PyembedPackedResourcesSource {
@@ -104,6 +126,12 @@ impl ToString for PyembedPackedResourcesSource {
                     path_to_string(path)
                 )
             }
+            Self::OptionalMemoryMappedPath(path) => {
+                format!(
+                    "pyembed::PackedResourcesSource::OptionalMemoryMappedPath({})",
+                    path_to_string(path)
+                )
+            }
         }
     }
 }
@@ -117,16 +145,23 @@ impl ToString for PyembedPackedResourcesSource {
 #[derive(Clone, Debug, PartialEq)]
 pub struct PyembedPythonInterpreterConfig {
     pub config: PythonInterpreterConfig,
+    pub crash_report_dir: Option<PathBuf>,
     pub allocator_backend: MemoryAllocatorBackend,
     pub allocator_raw: bool,
     pub allocator_mem: bool,
     pub allocator_obj: bool,
     pub allocator_pymalloc_arena: bool,
     pub allocator_debug: bool,
+    pub allocator_stats: bool,
+    pub allocator_stats_report: bool,
+    pub allocator_tracing_backend: MemoryAllocatorBackend,
+    pub allocator_tracing_capacity: usize,
     pub set_missing_path_configuration: bool,
     pub oxidized_importer: bool,
     pub filesystem_importer: bool,
     pub packed_resources: Vec<PyembedPackedResourcesSource>,
+    pub packed_resources_public_key: Option<Vec<u8>>,
+    pub bytecode_cache_dir: Option<PathBuf>,
     pub argvb: bool,
     pub multiprocessing_auto_dispatch: bool,
     pub multiprocessing_start_method: MultiprocessingStartMethod,
@@ -135,6 +170,8 @@ pub struct PyembedPythonInterpreterConfig {
     pub terminfo_resolution: TerminfoResolution,
     pub tcl_library: Option<PathBuf>,
     pub write_modules_directory_env: Option<String>,
+    pub write_import_trace_directory_env: Option<String>,
+    pub entry_point: Option<String>,
 }
 
 impl Default for PyembedPythonInterpreterConfig {
@@ -150,6 +187,7 @@ impl Default for PyembedPythonInterpreterConfig {
                 configure_locale: Some(true),
                 ..PythonInterpreterConfig::default()
             },
+            crash_report_dir: None,
             allocator_backend: MemoryAllocatorBackend::Default,
             // This setting has no effect by itself. But the default of true
             // makes it so a custom backend is used automatically.
@@ -158,10 +196,16 @@ impl Default for PyembedPythonInterpreterConfig {
             allocator_obj: false,
             allocator_pymalloc_arena: false,
             allocator_debug: false,
+            allocator_stats: false,
+            allocator_stats_report: false,
+            allocator_tracing_backend: MemoryAllocatorBackend::Default,
+            allocator_tracing_capacity: 65536,
             set_missing_path_configuration: true,
             oxidized_importer: true,
             filesystem_importer: false,
             packed_resources: vec![],
+            packed_resources_public_key: None,
+            bytecode_cache_dir: None,
             argvb: false,
             multiprocessing_auto_dispatch: true,
             multiprocessing_start_method: MultiprocessingStartMethod::Auto,
@@ -170,11 +214,24 @@ impl Default for PyembedPythonInterpreterConfig {
             terminfo_resolution: TerminfoResolution::None,
             tcl_library: None,
             write_modules_directory_env: None,
+            write_import_trace_directory_env: None,
+            entry_point: None,
         }
     }
 }
 
 impl PyembedPythonInterpreterConfig {
+    /// The memory allocator backend serving allocations.
+    ///
+    /// This is [Self::allocator_tracing_backend] if the tracing backend is used,
+    /// as it wraps that backend.
+    pub fn serving_allocator_backend(&self) -> MemoryAllocatorBackend {
+        match self.allocator_backend {
+            MemoryAllocatorBackend::Tracing => self.allocator_tracing_backend,
+            backend => backend,
+        }
+    }
+
     /// Convert the instance to Rust code that constructs a `pyembed::OxidizedPythonInterpreterConfig`.
     pub fn to_oxidized_python_interpreter_config_rs(&self) -> Result<String> {
         // This code is complicated enough. Let's not worry about format! in format!.
@@ -241,16 +298,23 @@ impl PyembedPythonInterpreterConfig {
             write_bytecode: {},\n        \
             x_options: {},\n        \
             }},\n    \
+            crash_report_dir: {},\n    \
             allocator_backend: {},\n    \
             allocator_raw: {},\n    \
             allocator_mem: {},\n    \
             allocator_obj: {},\n    \
             allocator_pymalloc_arena: {},\n    \
             allocator_debug: {},\n    \
+            allocator_stats: {},\n    \
+            allocator_stats_report: {},\n    \
+            allocator_tracing_backend: {},\n    \
+            allocator_tracing_capacity: {},\n    \
             set_missing_path_configuration: {},\n    \
             oxidized_importer: {},\n    \
             filesystem_importer: {},\n    \
             packed_resources: {},\n    \
+            packed_resources_public_key: {},\n    \
+            bytecode_cache_dir: {},\n    \
             extra_extension_modules: None,\n    \
             argv: None,\n    \
             argvb: {},\n    \
@@ -261,6 +325,8 @@ impl PyembedPythonInterpreterConfig {
             terminfo_resolution: {},\n    \
             tcl_library: {},\n    \
             write_modules_directory_env: {},\n    \
+            write_import_trace_directory_env: {},\n    \
+            entry_point: {},\n    \
             }}\n\
             ",
             match self.config.profile {
@@ -367,18 +433,17 @@ impl PyembedPythonInterpreterConfig {
             optional_vec_string_to_string(&self.config.warn_options),
             optional_bool_to_string(&self.config.write_bytecode),
             optional_vec_string_to_string(&self.config.x_options),
-            match self.allocator_backend {
-                MemoryAllocatorBackend::Jemalloc => "pyembed::MemoryAllocatorBackend::Jemalloc",
-                MemoryAllocatorBackend::Mimalloc => "pyembed::MemoryAllocatorBackend::Mimalloc",
-                MemoryAllocatorBackend::Snmalloc => "pyembed::MemoryAllocatorBackend::Snmalloc",
-                MemoryAllocatorBackend::Rust => "pyembed::MemoryAllocatorBackend::Rust",
-                MemoryAllocatorBackend::Default => "pyembed::MemoryAllocatorBackend::Default",
-            },
+            optional_pathbuf_to_string(&self.crash_report_dir),
+            memory_allocator_backend_to_string(self.allocator_backend),
             self.allocator_raw,
             self.allocator_mem,
             self.allocator_obj,
             self.allocator_pymalloc_arena,
             self.allocator_debug,
+            self.allocator_stats,
+            self.allocator_stats_report,
+            memory_allocator_backend_to_string(self.allocator_tracing_backend),
+            self.allocator_tracing_capacity,
             self.set_missing_path_configuration,
             self.oxidized_importer,
             self.filesystem_importer,
@@ -389,6 +454,8 @@ impl PyembedPythonInterpreterConfig {
                     .map(|e| e.to_string())
                     .join(", ")
             ),
+            optional_bytes_to_string(&self.packed_resources_public_key),
+            optional_pathbuf_to_string(&self.bytecode_cache_dir),
             self.argvb,
             self.multiprocessing_auto_dispatch,
             match self.multiprocessing_start_method {
@@ -414,6 +481,8 @@ impl PyembedPythonInterpreterConfig {
             },
             optional_pathbuf_to_string(&self.tcl_library),
             optional_string_to_string(&self.write_modules_directory_env),
+            optional_string_to_string(&self.write_import_trace_directory_env),
+            optional_string_to_string(&self.entry_point),
         );
 
         Ok(code)
@@ -515,6 +584,20 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_serialize_packed_resources_public_key() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig::default();
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+        assert_contains(&code, "packed_resources_public_key: None,")?;
+
+        let config = PyembedPythonInterpreterConfig {
+            packed_resources_public_key: Some(vec![1, 2, 255]),
+            ..Default::default()
+        };
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+        assert_contains(&code, "packed_resources_public_key: Some(vec![1, 2, 255]),")
+    }
+
     // TODO enable once CI has a linkable Python.
     #[test]
     #[ignore]
@@ -582,12 +665,17 @@ mod tests {
                 write_bytecode: Some(true),
                 x_options: Some(vec!["x0".into(), "x1".into()]),
             },
+            crash_report_dir: Some("$ORIGIN/crashes".into()),
             allocator_backend: MemoryAllocatorBackend::Default,
             allocator_raw: true,
             allocator_mem: true,
             allocator_obj: true,
             allocator_pymalloc_arena: true,
             allocator_debug: true,
+            allocator_stats: true,
+            allocator_stats_report: true,
+            allocator_tracing_backend: MemoryAllocatorBackend::Rust,
+            allocator_tracing_capacity: 1024,
             set_missing_path_configuration: false,
             oxidized_importer: true,
             filesystem_importer: true,
@@ -596,13 +684,20 @@ mod tests {
                 PyembedPackedResourcesSource::MemoryMappedPath(PathBuf::from(
                     "$ORIGIN/packed-resources",
                 )),
+                PyembedPackedResourcesSource::OptionalMemoryMappedPath(PathBuf::from(
+                    "$ORIGIN/packed-resources-overlay",
+                )),
             ],
+            packed_resources_public_key: Some(vec![0; 32]),
+            bytecode_cache_dir: Some("$ORIGIN/pycache".into()),
             argvb: true,
             sys_frozen: false,
             sys_meipass: true,
             terminfo_resolution: TerminfoResolution::Dynamic,
             tcl_library: Some("path".into()),
             write_modules_directory_env: Some("env".into()),
+            write_import_trace_directory_env: Some("env".into()),
+            entry_point: Some("pkg.mod:main".into()),
             multiprocessing_auto_dispatch: false,
             multiprocessing_start_method: MultiprocessingStartMethod::Spawn,
         };
@@ -5,7 +5,7 @@
 /*! Functionality for embedding Python in a binary. */
 
 use {
-    crate::py_packaging::config::PyembedPythonInterpreterConfig,
+    crate::{py_packaging::config::PyembedPythonInterpreterConfig, sbom::SbomFormat},
     anyhow::{anyhow, Context, Result},
     pyo3_build_config::{
         BuildFlags, InterpreterConfig as PyO3InterpreterConfig, PythonImplementation, PythonVersion,
@@ -266,6 +266,9 @@ pub struct EmbeddedPythonContext<'a> {
 
     /// Licensing metadata for components to be built/embedded.
     pub licensing: LicensedComponents,
+
+    /// Formats of SBOM documents to write next to the produced binary.
+    pub sbom_formats: Vec<SbomFormat>,
 }
 
 impl<'a> EmbeddedPythonContext<'a> {
@@ -9,6 +9,7 @@ This module tree holds functionality that is centered around Python.
 */
 
 pub mod binary;
+pub mod bytecode_cache;
 pub mod config;
 pub mod distribution;
 pub mod distutils;
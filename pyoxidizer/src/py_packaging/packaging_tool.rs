@@ -11,13 +11,19 @@ use {
         binary::LibpythonLinkMode, distribution::PythonDistribution,
         distutils::read_built_extensions, standalone_distribution::resolve_python_paths,
     },
-    crate::environment::Environment,
+    crate::{
+        environment::Environment,
+        lock_file::{DistributionLock, LockedDistribution},
+    },
     anyhow::{anyhow, Context, Result},
     duct::{cmd, ReaderHandle},
     log::warn,
     python_packaging::{
-        filesystem_scanning::find_python_resources, policy::PythonPackagingPolicy,
-        resource::PythonResource, wheel::WheelArchive,
+        filesystem_scanning::find_python_resources,
+        policy::PythonPackagingPolicy,
+        resource::PythonResource,
+        wheel::WheelArchive,
+        wheel_resolver::{parse_pinned_requirements, resolve_wheels, CompatibleTags},
     },
     std::{
         collections::{hash_map::RandomState, HashMap},
@@ -97,6 +103,8 @@ pub fn find_resources<'a>(
 /// distribution targeting a different platform allows this command to
 /// resolve resources for a non-native platform, which enables it to be used
 /// when cross-compiling.
+///
+/// Downloaded wheels are recorded in `lock`.
 pub fn pip_download<'a>(
     env: &Environment,
     host_dist: &dyn PythonDistribution,
@@ -104,6 +112,7 @@ pub fn pip_download<'a>(
     policy: &PythonPackagingPolicy,
     verbose: bool,
     args: &[String],
+    lock: &DistributionLock,
 ) -> Result<Vec<PythonResource<'a>>> {
     let temp_dir = env.temporary_directory("pyoxidizer-pip-download")?;
 
@@ -173,6 +182,13 @@ pub fn pip_download<'a>(
         .collect::<Result<Vec<_>>>()?;
     files.sort();
 
+    lock.record(
+        files
+            .iter()
+            .map(|path| LockedDistribution::from_wheel_path(path))
+            .collect::<Result<Vec<_>>>()?,
+    )?;
+
     // TODO there's probably a way to do this using iterators.
     let mut res = Vec::new();
 
@@ -192,7 +208,63 @@ pub fn pip_download<'a>(
     Ok(res)
 }
 
+/// Install wheels for pinned requirements from local directories and return found resources.
+///
+/// This is a pure Rust alternative to `pip install --no-index --find-links`
+/// that doesn't run pip or access the network. `requirements_path` is a
+/// requirements file pinning every distribution to install with `==`.
+/// Wheels compatible with `dist` are found in `find_links` directories.
+/// Resolved wheels are recorded in `lock`.
+pub fn install_wheels<'a>(
+    dist: &dyn PythonDistribution,
+    policy: &PythonPackagingPolicy,
+    requirements_path: &Path,
+    find_links: &[PathBuf],
+    lock: &DistributionLock,
+) -> Result<Vec<PythonResource<'a>>> {
+    let requirements = std::fs::read_to_string(requirements_path)
+        .with_context(|| format!("reading {}", requirements_path.display()))?;
+    let requirements = parse_pinned_requirements(&requirements)
+        .with_context(|| format!("parsing {}", requirements_path.display()))?;
+
+    let tags = CompatibleTags::new(
+        dist.python_implementation_short(),
+        &dist.python_major_minor_version(),
+        dist.python_abi_tag(),
+        dist.python_platform_compatibility_tag(),
+    )?;
+
+    let paths = resolve_wheels(&requirements, find_links, &tags)?;
+
+    lock.record(
+        paths
+            .iter()
+            .map(|path| LockedDistribution::from_wheel_path(path))
+            .collect::<Result<Vec<_>>>()?,
+    )?;
+
+    let mut res = Vec::new();
+
+    for path in paths {
+        warn!("installing {}", path.display());
+
+        let wheel = WheelArchive::from_path(&path)?;
+
+        res.extend(wheel.python_resources(
+            dist.cache_tag(),
+            &dist.python_module_suffixes()?,
+            policy.file_scanner_emit_files(),
+            policy.file_scanner_classify_files(),
+        )?);
+    }
+
+    Ok(res)
+}
+
 /// Run `pip install` and return found resources.
+///
+/// Names and versions of installed distributions are recorded in `lock`.
+#[allow(clippy::too_many_arguments)]
 pub fn pip_install<'a, S: BuildHasher>(
     env: &Environment,
     dist: &dyn PythonDistribution,
@@ -201,6 +273,7 @@ pub fn pip_install<'a, S: BuildHasher>(
     verbose: bool,
     install_args: &[String],
     extra_envs: &HashMap<String, String, S>,
+    lock: &DistributionLock,
 ) -> Result<Vec<PythonResource<'a>>> {
     let temp_dir = env.temporary_directory("pyoxidizer-pip-install")?;
 
@@ -257,6 +330,8 @@ pub fn pip_install<'a, S: BuildHasher>(
     let resources =
         find_resources(dist, policy, &target_dir, state_dir).context("scanning for resources")?;
 
+    lock.record(LockedDistribution::from_resources(resources.iter()))?;
+
     temp_dir.close().context("closing temporary directory")?;
 
     Ok(resources)
@@ -367,7 +442,7 @@ pub fn setup_py_install<'a, S: BuildHasher>(
 mod tests {
     use {
         super::*,
-        crate::testutil::*,
+        crate::{lock_file::LOCK_FILE_NAME, testutil::*},
         std::{collections::BTreeSet, ops::Deref},
     };
 
@@ -375,6 +450,12 @@ mod tests {
     fn test_install_black() -> Result<()> {
         let env = get_env()?;
         let distribution = get_default_distribution(None)?;
+        let temp_dir = env.temporary_directory("pyoxidizer-test")?;
+        let lock = DistributionLock::new(
+            temp_dir.path().join(LOCK_FILE_NAME),
+            distribution.target_triple(),
+            false,
+        );
 
         let resources: Vec<PythonResource> = pip_install(
             &env,
@@ -384,11 +465,14 @@ mod tests {
             false,
             &["black==19.10b0".to_string()],
             &HashMap::new(),
+            &lock,
         )?;
 
         assert!(resources.iter().any(|r| r.full_name() == "appdirs"));
         assert!(resources.iter().any(|r| r.full_name() == "black"));
 
+        temp_dir.close()?;
+
         Ok(())
     }
 
@@ -398,6 +482,12 @@ mod tests {
         let env = get_env()?;
         let distribution = get_default_dynamic_distribution()?;
         let policy = distribution.create_packaging_policy()?;
+        let temp_dir = env.temporary_directory("pyoxidizer-test")?;
+        let lock = DistributionLock::new(
+            temp_dir.path().join(LOCK_FILE_NAME),
+            distribution.target_triple(),
+            false,
+        );
 
         let resources: Vec<PythonResource> = pip_install(
             &env,
@@ -407,6 +497,7 @@ mod tests {
             false,
             &["cffi==1.15.0".to_string()],
             &HashMap::new(),
+            &lock,
         )?;
 
         let ems = resources
@@ -417,12 +508,15 @@ mod tests {
         assert_eq!(ems.len(), 1);
         assert_eq!(ems[0].full_name(), "_cffi_backend");
 
+        temp_dir.close()?;
+
         Ok(())
     }
 
     #[test]
     fn test_pip_download_zstandard() -> Result<()> {
         let env = get_env()?;
+        let temp_dir = env.temporary_directory("pyoxidizer-test")?;
 
         for target_dist in get_all_standalone_distributions()? {
             if target_dist.python_platform_compatibility_tag() == "none" {
@@ -439,6 +533,11 @@ mod tests {
             );
 
             let policy = target_dist.create_packaging_policy()?;
+            let lock = DistributionLock::new(
+                temp_dir.path().join(LOCK_FILE_NAME),
+                &target_dist.target_triple,
+                false,
+            );
 
             let resources = pip_download(
                 &env,
@@ -447,6 +546,7 @@ mod tests {
                 &policy,
                 false,
                 &["zstandard==0.16.0".to_string()],
+                &lock,
             )?;
 
             assert!(!resources.is_empty());
@@ -514,12 +614,15 @@ mod tests {
             assert!(em.shared_library.is_some());
         }
 
+        temp_dir.close()?;
+
         Ok(())
     }
 
     #[test]
     fn test_pip_download_numpy() -> Result<()> {
         let env = get_env()?;
+        let temp_dir = env.temporary_directory("pyoxidizer-test")?;
 
         for target_dist in get_all_standalone_distributions()? {
             if target_dist.python_platform_compatibility_tag() == "none" {
@@ -538,6 +641,11 @@ mod tests {
             let mut policy = target_dist.create_packaging_policy()?;
             policy.set_file_scanner_emit_files(true);
             policy.set_file_scanner_classify_files(true);
+            let lock = DistributionLock::new(
+                temp_dir.path().join(LOCK_FILE_NAME),
+                &target_dist.target_triple,
+                false,
+            );
 
             let res = pip_download(
                 &env,
@@ -546,6 +654,7 @@ mod tests {
                 &policy,
                 false,
                 &["numpy==1.22.1".to_string()],
+                &lock,
             );
 
             // numpy wheel not available for 3.10 win32.
@@ -575,6 +684,8 @@ mod tests {
                 .any(|em| em.name == "numpy.random._common"));
         }
 
+        temp_dir.close()?;
+
         Ok(())
     }
 }
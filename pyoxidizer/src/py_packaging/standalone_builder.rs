@@ -8,6 +8,7 @@ use {
             LibpythonLinkMode, PackedResourcesLoadMode, PythonBinaryBuilder,
             ResourceAddCollectionContextCallback, WindowsRuntimeDllsMode,
         },
+        bytecode_cache::CachingBytecodeCompiler,
         config::{PyembedPackedResourcesSource, PyembedPythonInterpreterConfig},
         distribution::{AppleSdkInfo, BinaryLibpythonLinkMode, PythonDistribution},
         embedding::{
@@ -17,21 +18,23 @@ use {
         filtering::{filter_btreemap, resolve_resource_names_from_files},
         libpython::link_libpython,
         packaging_tool::{
-            find_resources, pip_download, pip_install, read_virtualenv, setup_py_install,
+            find_resources, install_wheels, pip_download, pip_install, read_virtualenv,
+            setup_py_install,
         },
         standalone_distribution::StandaloneDistribution,
     },
-    crate::environment::Environment,
+    crate::{environment::Environment, lock_file::DistributionLock, sbom::SbomFormat},
     anyhow::{anyhow, Context, Result},
     log::warn,
     once_cell::sync::Lazy,
     pyo3_build_config::{BuildFlag, BuildFlags, PythonImplementation, PythonVersion},
     python_packaging::{
-        bytecode::BytecodeCompiler,
+        bytecode::{BytecodeCompiler, PythonBytecodeCompiler},
         interpreter::MemoryAllocatorBackend,
         libpython::LibPythonBuildContext,
         licensing::{
-            derive_package_license_infos, ComponentFlavor, LicensedComponent, LicensedComponents,
+            derive_package_license_infos, ComponentFlavor, LicensePolicy, LicensedComponent,
+            LicensedComponents,
         },
         location::AbstractResourceLocation,
         policy::PythonPackagingPolicy,
@@ -44,6 +47,7 @@ use {
             PythonResourceCollector,
         },
     },
+    python_packed_resources::PackedResourcesSigningKey,
     std::{
         collections::{BTreeMap, BTreeSet, HashMap},
         path::{Path, PathBuf},
@@ -123,12 +127,24 @@ pub struct StandalonePythonExecutableBuilder {
     /// Filename to write out with licensing information.
     licenses_filename: Option<String>,
 
+    /// Formats of SBOM documents to write out.
+    sbom_formats: Vec<SbomFormat>,
+
+    /// Policy licenses of components must satisfy.
+    license_policy: Option<LicensePolicy>,
+
     /// Value for the `windows_subsystem` Rust attribute for generated Rust projects.
     windows_subsystem: String,
 
     /// Path to install tcl/tk files into.
     tcl_files_path: Option<String>,
 
+    /// Path to a PKCS#8 Ed25519 key used to sign packed resources data.
+    packed_resources_signing_key_path: Option<PathBuf>,
+
+    /// Binary relative paths of optional packed resources overlay files.
+    packed_resources_overlay_paths: Vec<String>,
+
     /// Describes how Windows runtime DLLs should be handled during builds.
     windows_runtime_dlls_mode: WindowsRuntimeDllsMode,
 }
@@ -227,8 +243,12 @@ impl StandalonePythonExecutableBuilder {
             config,
             host_python_exe,
             licenses_filename: Some("COPYING.txt".into()),
+            sbom_formats: vec![],
+            license_policy: None,
             windows_subsystem: "console".to_string(),
             tcl_files_path: None,
+            packed_resources_signing_key_path: None,
+            packed_resources_overlay_paths: vec![],
             windows_runtime_dlls_mode: WindowsRuntimeDllsMode::WhenPresent,
         });
 
@@ -495,6 +515,14 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         };
     }
 
+    fn packed_resources_signing_key_path(&self) -> &Option<PathBuf> {
+        &self.packed_resources_signing_key_path
+    }
+
+    fn set_packed_resources_signing_key_path(&mut self, value: Option<PathBuf>) {
+        self.packed_resources_signing_key_path = value;
+    }
+
     fn windows_subsystem(&self) -> &str {
         &self.windows_subsystem
     }
@@ -513,6 +541,22 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         self.licenses_filename = value;
     }
 
+    fn sbom_formats(&self) -> &[SbomFormat] {
+        &self.sbom_formats
+    }
+
+    fn set_sbom_formats(&mut self, formats: Vec<SbomFormat>) {
+        self.sbom_formats = formats;
+    }
+
+    fn license_policy(&self) -> Option<&LicensePolicy> {
+        self.license_policy.as_ref()
+    }
+
+    fn set_license_policy(&mut self, policy: Option<LicensePolicy>) {
+        self.license_policy = policy;
+    }
+
     fn packed_resources_load_mode(&self) -> &PackedResourcesLoadMode {
         &self.resources_load_mode
     }
@@ -521,12 +565,24 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         self.resources_load_mode = load_mode;
     }
 
+    fn packed_resources_overlay_paths(&self) -> &[String] {
+        &self.packed_resources_overlay_paths
+    }
+
+    fn set_packed_resources_overlay_paths(&mut self, paths: Vec<String>) {
+        self.packed_resources_overlay_paths = paths;
+    }
+
     fn iter_resources<'a>(
         &'a self,
     ) -> Box<dyn Iterator<Item = (&'a String, &'a PrePackagedResource)> + 'a> {
         Box::new(self.resources_collector.iter_resources())
     }
 
+    fn library_links(&self) -> &BTreeMap<String, BTreeSet<String>> {
+        self.resources_collector.library_links()
+    }
+
     fn index_package_license_info_from_resources<'a>(
         &mut self,
         resources: &[PythonResource<'a>],
@@ -544,6 +600,7 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         env: &Environment,
         verbose: bool,
         args: &[String],
+        lock: &DistributionLock,
     ) -> Result<Vec<PythonResource>> {
         let resources = pip_download(
             env,
@@ -552,6 +609,7 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             self.python_packaging_policy(),
             verbose,
             args,
+            lock,
         )
         .context("calling pip download")?;
 
@@ -567,6 +625,7 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         verbose: bool,
         install_args: &[String],
         extra_envs: &HashMap<String, String>,
+        lock: &DistributionLock,
     ) -> Result<Vec<PythonResource>> {
         let resources = pip_install(
             env,
@@ -576,6 +635,7 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             verbose,
             install_args,
             extra_envs,
+            lock,
         )
         .context("calling pip install")?;
 
@@ -585,6 +645,27 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         Ok(resources)
     }
 
+    fn install_wheels(
+        &mut self,
+        requirements_path: &Path,
+        find_links: &[PathBuf],
+        lock: &DistributionLock,
+    ) -> Result<Vec<PythonResource>> {
+        let resources = install_wheels(
+            &*self.target_distribution,
+            self.python_packaging_policy(),
+            requirements_path,
+            find_links,
+            lock,
+        )
+        .context("installing wheels")?;
+
+        self.index_package_license_info_from_resources(&resources)
+            .context("indexing package license metadata")?;
+
+        Ok(resources)
+    }
+
     fn read_package_root(
         &mut self,
         path: &Path,
@@ -860,15 +941,15 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
     }
 
     fn requires_jemalloc(&self) -> bool {
-        self.config.allocator_backend == MemoryAllocatorBackend::Jemalloc
+        self.config.serving_allocator_backend() == MemoryAllocatorBackend::Jemalloc
     }
 
     fn requires_mimalloc(&self) -> bool {
-        self.config.allocator_backend == MemoryAllocatorBackend::Mimalloc
+        self.config.serving_allocator_backend() == MemoryAllocatorBackend::Mimalloc
     }
 
     fn requires_snmalloc(&self) -> bool {
-        self.config.allocator_backend == MemoryAllocatorBackend::Snmalloc
+        self.config.serving_allocator_backend() == MemoryAllocatorBackend::Snmalloc
     }
 
     fn licensed_components(&self) -> Result<LicensedComponents> {
@@ -896,10 +977,40 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             warn!("See https://github.com/indygreg/PyOxidizer/issues/69 for more");
         }
 
+        let signing_key = if let Some(path) = &self.packed_resources_signing_key_path {
+            let data = std::fs::read(path)
+                .with_context(|| format!("reading signing key from {}", path.display()))?;
+
+            Some(Arc::new(
+                PackedResourcesSigningKey::from_pkcs8(&data)
+                    .with_context(|| format!("loading signing key from {}", path.display()))?,
+            ))
+        } else {
+            None
+        };
+
         let compiled_resources = {
             let temp_dir = env.temporary_directory("pyoxidizer-bytecode-compile")?;
-            let mut compiler = BytecodeCompiler::new(self.host_python_exe_path(), temp_dir.path())?;
-            let resources = self.resources_collector.compile_resources(&mut compiler)?;
+            let host_python_exe = self.host_python_exe_path();
+            let mut compiler = CachingBytecodeCompiler::new(
+                env.bytecode_cache(),
+                format!(
+                    "{}-{}",
+                    self.host_distribution.python_implementation(),
+                    self.host_distribution.python_version()
+                ),
+                Box::new(|| {
+                    Ok(
+                        Box::new(BytecodeCompiler::new(host_python_exe, temp_dir.path())?)
+                            as Box<dyn PythonBytecodeCompiler>,
+                    )
+                }),
+            )?;
+            let mut resources = self.resources_collector.compile_resources(&mut compiler)?;
+            drop(compiler);
+            resources.compression = self.packaging_policy.resources_compression();
+            resources.name_index = self.packaging_policy.resources_name_index();
+            resources.signing_key = signing_key.clone();
 
             temp_dir.close().context("closing temporary directory")?;
 
@@ -911,6 +1022,8 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         let mut extra_files = compiled_resources.extra_files_manifest()?;
 
         let mut config = self.config.clone();
+        config.packed_resources_public_key =
+            signing_key.as_ref().map(|key| key.public_key().to_vec());
 
         match &self.resources_load_mode {
             PackedResourcesLoadMode::None => {}
@@ -938,6 +1051,15 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             }
         }
 
+        // Overlays are loaded last so their resources take precedence.
+        for path in &self.packed_resources_overlay_paths {
+            config
+                .packed_resources
+                .push(PyembedPackedResourcesSource::OptionalMemoryMappedPath(
+                    PathBuf::from("$ORIGIN").join(path),
+                ));
+        }
+
         let link_settings = self.resolve_python_link_settings(env, opt_level)?;
 
         if self.link_mode == LibpythonLinkMode::Dynamic {
@@ -1043,6 +1165,7 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             python_build_flags,
             licensing_filename: self.licenses_filename.clone(),
             licensing: self.licensed_components()?,
+            sbom_formats: self.sbom_formats.clone(),
         };
 
         context.synchronize_licensing()?;
@@ -1070,7 +1193,7 @@ pub mod tests {
     };
 
     #[cfg(target_os = "linux")]
-    use python_packaging::resource::LibraryDependency;
+    use {crate::lock_file::LOCK_FILE_NAME, python_packaging::resource::LibraryDependency};
 
     pub static WINDOWS_TARGET_TRIPLES: Lazy<Vec<&'static str>> =
         Lazy::new(|| vec!["i686-pc-windows-msvc", "x86_64-pc-windows-msvc"]);
@@ -2934,6 +3057,8 @@ pub mod tests {
         // And 5.3 isn't marked as compatible with 3.10. So we pin to older
         // Python and a package version.
 
+        let temp_dir = env.temporary_directory("pyoxidizer-test")?;
+
         for libpython_link_mode in vec![
             BinaryLibpythonLinkMode::Static,
             BinaryLibpythonLinkMode::Dynamic,
@@ -2949,11 +3074,18 @@ pub mod tests {
 
             let mut builder = options.new_builder()?;
 
+            let lock = DistributionLock::new(
+                temp_dir.path().join(LOCK_FILE_NAME),
+                "x86_64-unknown-linux-gnu",
+                false,
+            );
+
             let resources = builder.pip_install(
                 &env,
                 false,
                 &["pyyaml==5.3.1".to_string()],
                 &HashMap::new(),
+                &lock,
             )?;
 
             let extensions = resources
@@ -3016,6 +3148,8 @@ pub mod tests {
             );
         }
 
+        temp_dir.close()?;
+
         Ok(())
     }
 
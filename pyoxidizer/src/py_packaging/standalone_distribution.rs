@@ -712,6 +712,7 @@ impl StandaloneDistribution {
                 &expression,
             )?;
             component.add_license_text(license_text);
+            component.set_version(&pi.python_version);
 
             Some(component)
         } else {
@@ -1267,7 +1268,9 @@ impl PythonDistribution for StandaloneDistribution {
 
         // In-memory shared library loading is brittle. Disable this configuration
         // even if supported because it leads to pain.
-        if self.supports_in_memory_shared_library_loading() {
+        if self.target_triple.contains("pc-windows")
+            && self.supports_in_memory_shared_library_loading()
+        {
             policy.set_resources_location(ConcreteResourceLocation::InMemory);
             policy.set_resources_location_fallback(Some(ConcreteResourceLocation::RelativePath(
                 "lib".to_string(),
@@ -1408,11 +1411,14 @@ impl PythonDistribution for StandaloneDistribution {
 
     /// Determines whether dynamically linked extension modules can be loaded from memory.
     fn supports_in_memory_shared_library_loading(&self) -> bool {
-        // Loading from memory is only supported on Windows where symbols are
-        // declspec(dllexport) and the distribution is capable of loading
-        // shared library extensions.
-        self.target_triple.contains("pc-windows")
-            && self.python_symbol_visibility == "dllexport"
+        // Loading from memory is supported on Windows where symbols are
+        // declspec(dllexport) and on Linux via memfd_create(). In both cases,
+        // the distribution must be capable of loading shared library extensions.
+        let platform_supported = (self.target_triple.contains("pc-windows")
+            && self.python_symbol_visibility == "dllexport")
+            || self.target_triple.contains("-linux-");
+
+        platform_supported
             && self
                 .extension_module_loading
                 .contains(&"shared-library".to_string())
@@ -0,0 +1,741 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Software bill of materials (SBOM) generation.
+
+SBOMs describe the components a built executable is composed of: the Python
+distribution, Python packages, and Rust crates, along with their versions,
+license expressions, and source locations. They also record SHA-256 hashes of
+the executable, the files installed next to it, and resources embedded in it.
+
+[SPDX 2.3](https://spdx.github.io/spdx-spec/v2.3/) and
+[CycloneDX 1.5](https://cyclonedx.org/docs/1.5/json/) JSON documents can
+be produced.
+*/
+
+use {
+    crate::environment::PYOXIDIZER_VERSION,
+    anyhow::{anyhow, Context, Result},
+    chrono::{DateTime, TimeZone, Utc},
+    python_packaging::licensing::{
+        ComponentFlavor, LicenseFlavor, LicensedComponent, LicensedComponents, SourceLocation,
+    },
+    python_packed_resources::Resource,
+    serde_json::{json, Value},
+    sha2::{Digest, Sha256},
+    std::{
+        borrow::Cow,
+        fmt::{Display, Formatter},
+        path::Path,
+    },
+    tugger_file_manifest::FileManifest,
+    uuid::Uuid,
+};
+
+/// Directory in a target's build output holding the SBOMs recorded by a build.
+pub const RECORDED_SBOM_DIR: &str = ".sbom";
+
+/// Format of an SBOM document.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SbomFormat {
+    /// SPDX 2.3 JSON.
+    Spdx,
+
+    /// CycloneDX 1.5 JSON.
+    CycloneDx,
+}
+
+impl Display for SbomFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Spdx => "spdx",
+            Self::CycloneDx => "cyclonedx",
+        })
+    }
+}
+
+impl TryFrom<&str> for SbomFormat {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "spdx" => Ok(Self::Spdx),
+            "cyclonedx" => Ok(Self::CycloneDx),
+            _ => Err(format!(
+                "{} is not a valid SBOM format; use spdx or cyclonedx",
+                value
+            )),
+        }
+    }
+}
+
+impl SbomFormat {
+    /// All supported formats.
+    pub const ALL: [SbomFormat; 2] = [Self::Spdx, Self::CycloneDx];
+
+    /// Resolve the filename of a document describing a named executable.
+    pub fn filename(&self, exe_name: &str) -> String {
+        match self {
+            Self::Spdx => format!("{}.spdx.json", exe_name),
+            Self::CycloneDx => format!("{}.cdx.json", exe_name),
+        }
+    }
+}
+
+/// A file recorded in an SBOM.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SbomFile {
+    /// Path of the file.
+    ///
+    /// Installed files have paths relative to the executable. Embedded
+    /// resources have paths of the form `<packed resources>!<resource>:<field>`.
+    pub path: String,
+
+    /// Hex encoded SHA-256 digest of the file's content.
+    pub sha256: String,
+}
+
+impl SbomFile {
+    /// Construct an instance from a path and file content.
+    pub fn from_data(path: impl ToString, data: &[u8]) -> Self {
+        Self {
+            path: path.to_string(),
+            sha256: hex::encode(Sha256::digest(data)),
+        }
+    }
+}
+
+/// A stable identifier for a component.
+///
+/// Only contains characters permitted in SPDX identifiers. Names containing
+/// other characters get a short digest of the original name appended, so
+/// names only differing in those characters (e.g. `foo_bar` and `foo-bar`)
+/// map to distinct identifiers.
+fn component_id(flavor: &ComponentFlavor) -> String {
+    let kind = match flavor {
+        ComponentFlavor::PythonDistribution(_) => "PythonDistribution",
+        ComponentFlavor::PythonStandardLibraryModule(_) => "PythonStdlibModule",
+        ComponentFlavor::PythonStandardLibraryExtensionModule(_) => "PythonStdlibExtension",
+        ComponentFlavor::PythonExtensionModule(_) => "PythonExtensionModule",
+        ComponentFlavor::PythonModule(_) => "PythonModule",
+        ComponentFlavor::Library(_) => "Library",
+        ComponentFlavor::RustCrate(_) => "RustCrate",
+    };
+
+    let original = flavor.name();
+
+    let name = original
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>();
+
+    if name == original {
+        format!("{}-{}", kind, name)
+    } else {
+        let digest = hex::encode(Sha256::digest(original.as_bytes()));
+        format!("{}-{}-{}", kind, name, &digest[0..8])
+    }
+}
+
+/// Resolve the package URL of a component, if it has one.
+fn component_purl(component: &LicensedComponent) -> Option<String> {
+    let (kind, name) = match component.flavor() {
+        ComponentFlavor::PythonModule(name) => {
+            ("pypi", name.to_lowercase().replace(['_', '.'], "-"))
+        }
+        ComponentFlavor::RustCrate(name) => ("cargo", name.to_string()),
+        _ => return None,
+    };
+
+    Some(match component.version() {
+        Some(version) => format!("pkg:{}/{}@{}", kind, name, version),
+        None => format!("pkg:{}/{}", kind, name),
+    })
+}
+
+/// Describes licensing of a component that isn't an SPDX expression.
+fn license_comment(component: &LicensedComponent) -> Option<String> {
+    match component.license() {
+        LicenseFlavor::None | LicenseFlavor::Spdx(_) => None,
+        LicenseFlavor::OtherExpression(expression) => Some(format!(
+            "Licensed according to expression with non-SPDX identifiers: {}",
+            expression
+        )),
+        LicenseFlavor::PublicDomain => Some("Licensed to the public domain.".to_string()),
+        LicenseFlavor::Unknown(terms) => {
+            Some(format!("Licensed according to {}", terms.join(", ")))
+        }
+    }
+}
+
+/// Resolve the time documents are created at.
+///
+/// `SOURCE_DATE_EPOCH` is honored so documents can be reproducible.
+fn creation_time() -> Result<DateTime<Utc>> {
+    match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(value) => {
+            let seconds = value
+                .parse::<i64>()
+                .with_context(|| format!("parsing SOURCE_DATE_EPOCH value {}", value))?;
+
+            Utc.timestamp_opt(seconds, 0)
+                .single()
+                .ok_or_else(|| anyhow!("SOURCE_DATE_EPOCH value {} is out of range", value))
+        }
+        Err(_) => Ok(Utc::now()),
+    }
+}
+
+/// A software bill of materials for an executable.
+#[derive(Clone, Debug)]
+pub struct Sbom {
+    /// Name of the executable.
+    name: String,
+
+    /// Components the executable is composed of.
+    components: LicensedComponents,
+
+    /// Files the executable is composed of.
+    files: Vec<SbomFile>,
+
+    /// When the document was created.
+    created: DateTime<Utc>,
+}
+
+impl Sbom {
+    /// Construct an instance describing a named executable.
+    pub fn new(name: impl ToString, components: LicensedComponents) -> Result<Self> {
+        Ok(Self {
+            name: name.to_string(),
+            components,
+            files: vec![],
+            created: creation_time()?,
+        })
+    }
+
+    /// Record a file.
+    pub fn add_file(&mut self, file: SbomFile) {
+        self.files.push(file);
+    }
+
+    /// Record files in a [FileManifest].
+    pub fn add_file_manifest(&mut self, manifest: &FileManifest) -> Result<()> {
+        for (path, entry) in manifest.iter_entries() {
+            let data = entry
+                .resolve_content()
+                .with_context(|| format!("reading {}", path.display()))?;
+
+            self.add_file(SbomFile::from_data(
+                path.display().to_string().replace('\\', "/"),
+                &data,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Record in-memory data of resources embedded in packed resources data.
+    ///
+    /// `packed_path` is the path of the packed resources data.
+    pub fn add_embedded_resources<'a>(
+        &mut self,
+        packed_path: &Path,
+        resources: impl Iterator<Item = &'a Resource<'a, u8>>,
+    ) {
+        let prefix = packed_path.display().to_string().replace('\\', "/");
+
+        for resource in resources {
+            let path = |field: &str| format!("{}!{}:{}", prefix, resource.name, field);
+
+            for (field, data) in [
+                ("in_memory_source", &resource.in_memory_source),
+                ("in_memory_bytecode", &resource.in_memory_bytecode),
+                ("in_memory_bytecode_opt1", &resource.in_memory_bytecode_opt1),
+                ("in_memory_bytecode_opt2", &resource.in_memory_bytecode_opt2),
+                (
+                    "in_memory_extension_module_shared_library",
+                    &resource.in_memory_extension_module_shared_library,
+                ),
+                (
+                    "in_memory_shared_library",
+                    &resource.in_memory_shared_library,
+                ),
+                ("file_data_embedded", &resource.file_data_embedded),
+            ] {
+                if let Some(data) = data {
+                    self.add_file(SbomFile::from_data(path(field), data));
+                }
+            }
+
+            for (field, map) in [
+                (
+                    "in_memory_package_resources",
+                    &resource.in_memory_package_resources,
+                ),
+                (
+                    "in_memory_distribution_resources",
+                    &resource.in_memory_distribution_resources,
+                ),
+            ] {
+                let mut entries = map
+                    .iter()
+                    .flat_map(|map| map.iter())
+                    .collect::<Vec<(&Cow<str>, &Cow<[u8]>)>>();
+                entries.sort();
+
+                for (name, data) in entries {
+                    self.add_file(SbomFile::from_data(
+                        format!("{}/{}", path(field), name),
+                        data,
+                    ));
+                }
+            }
+        }
+    }
+
+    /// A UUID derived from the content of the document.
+    ///
+    /// Documents describing the same content have the same UUID.
+    fn content_uuid(&self) -> Uuid {
+        let mut hasher = Sha256::new();
+
+        hasher.update(self.name.as_bytes());
+        for component in self.components.iter_components() {
+            hasher.update(component_id(component.flavor()).as_bytes());
+            hasher.update(component.version().unwrap_or_default().as_bytes());
+        }
+        for file in &self.files {
+            hasher.update(file.path.as_bytes());
+            hasher.update(file.sha256.as_bytes());
+        }
+
+        Uuid::new_v5(&Uuid::NAMESPACE_URL, &hasher.finalize())
+    }
+
+    /// Produce an SPDX 2.3 JSON document.
+    pub fn to_spdx(&self) -> Value {
+        let root_id = "SPDXRef-Executable".to_string();
+
+        let mut packages = vec![json!({
+            "SPDXID": root_id,
+            "name": self.name,
+            "downloadLocation": "NOASSERTION",
+            "filesAnalyzed": false,
+            "primaryPackagePurpose": "APPLICATION",
+        })];
+        let mut relationships = vec![json!({
+            "spdxElementId": "SPDXRef-DOCUMENT",
+            "relationshipType": "DESCRIBES",
+            "relatedSpdxElement": root_id,
+        })];
+
+        for component in self.components.iter_components() {
+            let id = format!("SPDXRef-{}", component_id(component.flavor()));
+
+            let download_location = match component.source_location() {
+                SourceLocation::Url(url) => url.as_str(),
+                SourceLocation::NotSet => "NOASSERTION",
+            };
+            let license_declared = match component.license() {
+                LicenseFlavor::Spdx(expression) => expression.to_string(),
+                _ => "NOASSERTION".to_string(),
+            };
+
+            let mut package = json!({
+                "SPDXID": id,
+                "name": component.flavor().name(),
+                "downloadLocation": download_location,
+                "filesAnalyzed": false,
+                "licenseConcluded": "NOASSERTION",
+                "licenseDeclared": license_declared,
+                "copyrightText": "NOASSERTION",
+                "primaryPackagePurpose": "LIBRARY",
+            });
+
+            if let Some(version) = component.version() {
+                package["versionInfo"] = json!(version);
+            }
+            if let Some(homepage) = component.homepage() {
+                package["homepage"] = json!(homepage);
+            }
+            if !component.authors().is_empty() {
+                package["originator"] =
+                    json!(format!("Person: {}", component.authors().join(", ")));
+            }
+            if let Some(comment) = license_comment(component) {
+                package["licenseComments"] = json!(comment);
+            }
+            if let Some(purl) = component_purl(component) {
+                package["externalRefs"] = json!([{
+                    "referenceCategory": "PACKAGE-MANAGER",
+                    "referenceType": "purl",
+                    "referenceLocator": purl,
+                }]);
+            }
+
+            packages.push(package);
+            relationships.push(json!({
+                "spdxElementId": root_id,
+                "relationshipType": "CONTAINS",
+                "relatedSpdxElement": id,
+            }));
+        }
+
+        let mut files = vec![];
+
+        for (i, file) in self.files.iter().enumerate() {
+            let id = format!("SPDXRef-File-{}", i);
+
+            files.push(json!({
+                "SPDXID": id,
+                "fileName": format!("./{}", file.path),
+                "checksums": [{
+                    "algorithm": "SHA256",
+                    "checksumValue": file.sha256,
+                }],
+                "licenseConcluded": "NOASSERTION",
+                "copyrightText": "NOASSERTION",
+            }));
+            relationships.push(json!({
+                "spdxElementId": root_id,
+                "relationshipType": "CONTAINS",
+                "relatedSpdxElement": id,
+            }));
+        }
+
+        json!({
+            "spdxVersion": "SPDX-2.3",
+            "dataLicense": "CC0-1.0",
+            "SPDXID": "SPDXRef-DOCUMENT",
+            "name": self.name,
+            "documentNamespace": format!(
+                "https://pyoxidizer.readthedocs.io/spdxdocs/{}-{}",
+                self.name,
+                self.content_uuid()
+            ),
+            "creationInfo": {
+                "created": self.created.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                "creators": [format!("Tool: pyoxidizer-{}", PYOXIDIZER_VERSION)],
+            },
+            "packages": packages,
+            "files": files,
+            "relationships": relationships,
+        })
+    }
+
+    /// Produce a CycloneDX 1.5 JSON document.
+    pub fn to_cyclonedx(&self) -> Value {
+        let root_ref = format!("Executable-{}", self.name);
+
+        let mut components = vec![];
+        let mut depends = vec![];
+
+        for component in self.components.iter_components() {
+            let bom_ref = component_id(component.flavor());
+
+            let mut value = json!({
+                "type": "library",
+                "bom-ref": bom_ref,
+                "name": component.flavor().name(),
+            });
+
+            if let Some(version) = component.version() {
+                value["version"] = json!(version);
+            }
+            if !component.authors().is_empty() {
+                value["author"] = json!(component.authors().join(", "));
+            }
+
+            match component.license() {
+                LicenseFlavor::None => {}
+                LicenseFlavor::Spdx(expression) => {
+                    value["licenses"] = json!([{ "expression": expression.to_string() }]);
+                }
+                LicenseFlavor::OtherExpression(expression) => {
+                    value["licenses"] = json!([{ "license": { "name": expression.to_string() } }]);
+                }
+                LicenseFlavor::PublicDomain => {
+                    value["licenses"] = json!([{ "license": { "name": "Public Domain" } }]);
+                }
+                LicenseFlavor::Unknown(terms) => {
+                    value["licenses"] = json!(terms
+                        .iter()
+                        .map(|term| json!({ "license": { "name": term } }))
+                        .collect::<Vec<_>>());
+                }
+            }
+
+            if let Some(purl) = component_purl(component) {
+                value["purl"] = json!(purl);
+            }
+
+            let mut references = vec![];
+            if let Some(homepage) = component.homepage() {
+                references.push(json!({ "type": "website", "url": homepage }));
+            }
+            if let SourceLocation::Url(url) = component.source_location() {
+                references.push(json!({ "type": "source-distribution", "url": url }));
+            }
+            if !references.is_empty() {
+                value["externalReferences"] = json!(references);
+            }
+
+            components.push(value);
+            depends.push(bom_ref);
+        }
+
+        for (i, file) in self.files.iter().enumerate() {
+            let bom_ref = format!("File-{}", i);
+
+            components.push(json!({
+                "type": "file",
+                "bom-ref": bom_ref,
+                "name": file.path,
+                "hashes": [{ "alg": "SHA-256", "content": file.sha256 }],
+            }));
+            depends.push(bom_ref);
+        }
+
+        json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "serialNumber": format!("urn:uuid:{}", self.content_uuid()),
+            "version": 1,
+            "metadata": {
+                "timestamp": self.created.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                "tools": {
+                    "components": [{
+                        "type": "application",
+                        "name": "pyoxidizer",
+                        "version": PYOXIDIZER_VERSION,
+                    }],
+                },
+                "component": {
+                    "type": "application",
+                    "bom-ref": root_ref,
+                    "name": self.name,
+                },
+            },
+            "components": components,
+            "dependencies": [{
+                "ref": root_ref,
+                "dependsOn": depends,
+            }],
+        })
+    }
+
+    /// Serialize a document of the given format to pretty printed JSON.
+    pub fn to_json(&self, format: SbomFormat) -> Result<Vec<u8>> {
+        let value = match format {
+            SbomFormat::Spdx => self.to_spdx(),
+            SbomFormat::CycloneDx => self.to_cyclonedx(),
+        };
+
+        let mut data = serde_json::to_vec_pretty(&value)?;
+        data.push(b'\n');
+
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::collections::HashMap};
+
+    fn sbom() -> Result<Sbom> {
+        let mut components = LicensedComponents::default();
+
+        let mut component = LicensedComponent::new_spdx(
+            ComponentFlavor::PythonDistribution("cpython".to_string()),
+            "Python-2.0",
+        )?;
+        component.set_version("3.10.5");
+        components.add_component(component);
+
+        let mut component = LicensedComponent::new_spdx(
+            ComponentFlavor::RustCrate("anyhow".to_string()),
+            "MIT OR Apache-2.0",
+        )?;
+        component.set_version("1.0.58");
+        component.set_source_location(SourceLocation::Url(
+            "https://github.com/dtolnay/anyhow".to_string(),
+        ));
+        components.add_component(component);
+
+        let mut component = LicensedComponent::new(
+            ComponentFlavor::PythonModule("Foo_Bar".to_string()),
+            LicenseFlavor::Unknown(vec!["Custom".to_string()]),
+        );
+        component.set_version("0.1");
+        components.add_component(component);
+
+        let mut sbom = Sbom::new("myapp", components)?;
+        sbom.add_file(SbomFile::from_data("myapp", b"exe"));
+
+        let mut resources = HashMap::new();
+        resources.insert(Cow::Borrowed("data.txt"), Cow::Borrowed(b"data".as_ref()));
+
+        let resource = Resource {
+            name: Cow::Borrowed("foo"),
+            is_python_module: true,
+            in_memory_source: Some(Cow::Borrowed(b"import bar".as_ref())),
+            in_memory_package_resources: Some(resources),
+            ..Resource::default()
+        };
+        sbom.add_embedded_resources(Path::new("packed-resources"), [&resource].into_iter());
+
+        Ok(sbom)
+    }
+
+    #[test]
+    fn test_format() -> Result<()> {
+        assert_eq!(SbomFormat::try_from("spdx"), Ok(SbomFormat::Spdx));
+        assert_eq!(SbomFormat::try_from("cyclonedx"), Ok(SbomFormat::CycloneDx));
+        assert!(SbomFormat::try_from("swid").is_err());
+
+        assert_eq!(SbomFormat::Spdx.filename("myapp"), "myapp.spdx.json");
+        assert_eq!(SbomFormat::CycloneDx.filename("myapp"), "myapp.cdx.json");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_component_id() {
+        assert_eq!(
+            component_id(&ComponentFlavor::RustCrate("foo-bar".into())),
+            "RustCrate-foo-bar"
+        );
+
+        let underscore = component_id(&ComponentFlavor::RustCrate("foo_bar".into()));
+        assert!(underscore.starts_with("RustCrate-foo-bar-"));
+        assert_ne!(underscore, "RustCrate-foo-bar");
+    }
+
+    #[test]
+    fn test_embedded_resources() -> Result<()> {
+        let sbom = sbom()?;
+
+        assert_eq!(
+            sbom.files,
+            vec![
+                SbomFile::from_data("myapp", b"exe"),
+                SbomFile::from_data("packed-resources!foo:in_memory_source", b"import bar"),
+                SbomFile::from_data(
+                    "packed-resources!foo:in_memory_package_resources/data.txt",
+                    b"data"
+                ),
+            ]
+        );
+        assert_eq!(
+            sbom.files[0].sha256,
+            "9095bdb859308b62acf04036ffd4adfe366d7f737d276eb6c46ae434f3816c9b"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_spdx() -> Result<()> {
+        let doc = sbom()?.to_spdx();
+
+        assert_eq!(doc["spdxVersion"], "SPDX-2.3");
+        assert_eq!(doc["packages"][0]["SPDXID"], "SPDXRef-Executable");
+
+        let packages = doc["packages"].as_array().unwrap();
+        assert_eq!(packages.len(), 4);
+
+        let cpython = &packages[1];
+        assert_eq!(cpython["SPDXID"], "SPDXRef-PythonDistribution-cpython");
+        assert_eq!(cpython["versionInfo"], "3.10.5");
+        assert_eq!(cpython["licenseDeclared"], "Python-2.0");
+        assert!(cpython.get("externalRefs").is_none());
+
+        let foo = &packages[2];
+        assert_eq!(foo["name"], "Foo_Bar");
+        assert_eq!(foo["licenseDeclared"], "NOASSERTION");
+        assert_eq!(foo["licenseComments"], "Licensed according to Custom");
+        assert_eq!(
+            foo["externalRefs"][0]["referenceLocator"],
+            "pkg:pypi/foo-bar@0.1"
+        );
+
+        let anyhow = &packages[3];
+        assert_eq!(anyhow["licenseDeclared"], "MIT OR Apache-2.0");
+        assert_eq!(
+            anyhow["downloadLocation"],
+            "https://github.com/dtolnay/anyhow"
+        );
+        assert_eq!(
+            anyhow["externalRefs"][0]["referenceLocator"],
+            "pkg:cargo/anyhow@1.0.58"
+        );
+
+        let files = doc["files"].as_array().unwrap();
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0]["fileName"], "./myapp");
+        assert_eq!(
+            files[0]["checksums"][0]["checksumValue"],
+            "9095bdb859308b62acf04036ffd4adfe366d7f737d276eb6c46ae434f3816c9b"
+        );
+
+        // Every package and file is related to the document.
+        assert_eq!(doc["relationships"].as_array().unwrap().len(), 1 + 3 + 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cyclonedx() -> Result<()> {
+        let doc = sbom()?.to_cyclonedx();
+
+        assert_eq!(doc["specVersion"], "1.5");
+        assert_eq!(doc["metadata"]["component"]["name"], "myapp");
+
+        let components = doc["components"].as_array().unwrap();
+        assert_eq!(components.len(), 6);
+
+        assert_eq!(components[0]["version"], "3.10.5");
+        assert_eq!(components[0]["licenses"][0]["expression"], "Python-2.0");
+        assert_eq!(components[1]["licenses"][0]["license"]["name"], "Custom");
+        assert_eq!(components[2]["purl"], "pkg:cargo/anyhow@1.0.58");
+        assert_eq!(
+            components[2]["externalReferences"][0]["type"],
+            "source-distribution"
+        );
+
+        assert_eq!(components[3]["type"], "file");
+        assert_eq!(
+            components[3]["hashes"][0]["content"],
+            "9095bdb859308b62acf04036ffd4adfe366d7f737d276eb6c46ae434f3816c9b"
+        );
+
+        assert_eq!(
+            doc["dependencies"][0]["dependsOn"]
+                .as_array()
+                .unwrap()
+                .len(),
+            6
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_content_uuid() -> Result<()> {
+        let a = sbom()?;
+        let mut b = sbom()?;
+        assert_eq!(a.content_uuid(), b.content_uuid());
+
+        b.add_file(SbomFile::from_data("lib/extra", b"extra"));
+        assert_ne!(a.content_uuid(), b.content_uuid());
+
+        Ok(())
+    }
+}
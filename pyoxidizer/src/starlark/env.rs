@@ -3,7 +3,10 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
-    crate::py_packaging::distribution::DistributionCache,
+    crate::{
+        lock_file::{DistributionLock, LOCK_FILE_NAME},
+        py_packaging::distribution::DistributionCache,
+    },
     anyhow::{Context, Result},
     starlark::{
         environment::{Environment, EnvironmentError, TypeValues},
@@ -59,6 +62,9 @@ pub struct PyOxidizerEnvironmentContext {
 
     /// Extra variables to inject into Starlark environment.
     extra_vars: HashMap<String, Option<String>>,
+
+    /// Records Python distributions resolved by the build and enforces the lock file.
+    pub distribution_lock: DistributionLock,
 }
 
 impl PyOxidizerEnvironmentContext {
@@ -73,6 +79,7 @@ impl PyOxidizerEnvironmentContext {
         build_opt_level: &str,
         distribution_cache: Option<Arc<DistributionCache>>,
         extra_vars: HashMap<String, Option<String>>,
+        locked: bool,
     ) -> Result<PyOxidizerEnvironmentContext> {
         let parent = config_path
             .parent()
@@ -90,6 +97,9 @@ impl PyOxidizerEnvironmentContext {
             )))
         });
 
+        let distribution_lock =
+            DistributionLock::new(parent.join(LOCK_FILE_NAME), build_target_triple, locked);
+
         Ok(PyOxidizerEnvironmentContext {
             env: env.clone(),
             verbose,
@@ -101,6 +111,7 @@ impl PyOxidizerEnvironmentContext {
             build_opt_level: build_opt_level.to_string(),
             distribution_cache,
             extra_vars,
+            distribution_lock,
         })
     }
 
@@ -178,6 +189,7 @@ pub fn register_starlark_dialect(
     starlark_dialect_build_targets::register_starlark_dialect(env, type_values)?;
     tugger::starlark::register_starlark_dialect(env, type_values)?;
     super::file_resource::file_resource_env(env, type_values);
+    super::license_policy::license_policy_module(env, type_values);
     super::python_distribution::python_distribution_module(env, type_values);
     super::python_embedded_resources::python_embedded_resources_module(env, type_values);
     super::python_executable::python_executable_env(env, type_values);
@@ -46,6 +46,7 @@ pub struct EvaluationContextBuilder {
     build_opt_level: String,
     distribution_cache: Option<Arc<DistributionCache>>,
     extra_vars: HashMap<String, Option<String>>,
+    locked: bool,
 }
 
 impl EvaluationContextBuilder {
@@ -65,6 +66,7 @@ impl EvaluationContextBuilder {
             build_opt_level: "0".to_string(),
             distribution_cache: None,
             extra_vars: HashMap::new(),
+            locked: false,
         }
     }
 
@@ -139,6 +141,13 @@ impl EvaluationContextBuilder {
         self.extra_vars = extra_vars;
         self
     }
+
+    /// Whether resolved Python distributions must match the lock file.
+    #[must_use]
+    pub fn locked(mut self, value: bool) -> Self {
+        self.locked = value;
+        self
+    }
 }
 
 /// Interface to evaluate Starlark configuration files.
@@ -174,6 +183,7 @@ impl EvaluationContext {
             &builder.build_opt_level,
             builder.distribution_cache,
             builder.extra_vars,
+            builder.locked,
         )?;
 
         let (mut parent_env, mut type_values) = starlark::stdlib::global_environment();
@@ -319,6 +329,20 @@ impl EvaluationContext {
         pyoxidizer_context.build_path(&self.type_values)
     }
 
+    /// Write Python distributions resolved during evaluation to the lock file.
+    ///
+    /// In locked mode, only verifies that every locked distribution was resolved.
+    pub fn write_lock_file(&self) -> Result<()> {
+        let raw_context = self
+            .pyoxidizer_context_value()
+            .map_err(|_| anyhow!("could not obtain PyOxidizer context"))?;
+        let pyoxidizer_context = raw_context
+            .downcast_ref::<PyOxidizerEnvironmentContext>()
+            .ok_or_else(|| anyhow!("context has incorrect type"))?;
+
+        pyoxidizer_context.distribution_lock.write()
+    }
+
     pub fn target_build_path(&self, target: &str) -> Result<PathBuf> {
         let context_value = self.build_targets_context_value()?;
         let context = context_value.downcast_ref::<EnvironmentContext>().unwrap();
@@ -348,6 +372,18 @@ impl EvaluationContext {
             .collect::<Vec<_>>())
     }
 
+    /// Obtain the value a target resolved to, if it has been resolved.
+    pub fn target_resolved_value(&self, target: &str) -> Result<Option<Value>> {
+        let raw_context = self.build_targets_context_value()?;
+        let context = raw_context
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or_else(|| anyhow!("context has incorrect type"))?;
+
+        Ok(context
+            .get_target(target)
+            .and_then(|t| t.resolved_value.clone()))
+    }
+
     /// Obtain targets that should be resolved.
     pub fn targets_to_resolve(&self) -> Result<Vec<String>> {
         let raw_context = self.build_targets_context_value()?;
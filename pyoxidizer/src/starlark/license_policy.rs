@@ -0,0 +1,177 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    python_packaging::licensing::LicensePolicy,
+    starlark::values::{
+        error::{
+            RuntimeError, UnsupportedOperation, ValueError, INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+        },
+        {Mutable, TypedValue, Value, ValueResult},
+    },
+    starlark::{
+        starlark_fun, starlark_module, starlark_signature, starlark_signature_extraction,
+        starlark_signatures,
+    },
+    starlark_dialect_build_targets::required_list_arg,
+};
+
+/// Starlark value wrapper for `LicensePolicy`.
+#[derive(Clone, Debug, Default)]
+pub struct LicensePolicyValue {
+    pub inner: LicensePolicy,
+}
+
+impl LicensePolicyValue {
+    pub fn new(inner: LicensePolicy) -> Self {
+        Self { inner }
+    }
+}
+
+impl TypedValue for LicensePolicyValue {
+    type Holder = Mutable<LicensePolicyValue>;
+    const TYPE: &'static str = "LicensePolicy";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn get_attr(&self, attribute: &str) -> ValueResult {
+        let v = match attribute {
+            "allow_unknown" => Value::from(self.inner.allow_unknown()),
+            "allowed_licenses" => Value::from(
+                self.inner
+                    .allowed_licenses()
+                    .iter()
+                    .cloned()
+                    .collect::<Vec<_>>(),
+            ),
+            "denied_licenses" => Value::from(
+                self.inner
+                    .denied_licenses()
+                    .iter()
+                    .cloned()
+                    .collect::<Vec<_>>(),
+            ),
+            "exceptions" => {
+                Value::from(self.inner.exceptions().iter().cloned().collect::<Vec<_>>())
+            }
+            _ => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::GetAttr(attribute.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        };
+
+        Ok(v)
+    }
+
+    fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
+        Ok(matches!(
+            attribute,
+            "allow_unknown" | "allowed_licenses" | "denied_licenses" | "exceptions"
+        ))
+    }
+
+    fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
+        let invalid_license = |e: anyhow::Error| {
+            ValueError::from(RuntimeError {
+                code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                message: e.to_string(),
+                label: format!("{}.{}", Self::TYPE, attribute),
+            })
+        };
+
+        match attribute {
+            "allow_unknown" => {
+                self.inner.set_allow_unknown(value.to_bool());
+            }
+            "allowed_licenses" => {
+                required_list_arg(attribute, "string", &value)?;
+                self.inner
+                    .set_allowed_licenses(value.iter()?.iter().map(|x| x.to_string()))
+                    .map_err(invalid_license)?;
+            }
+            "denied_licenses" => {
+                required_list_arg(attribute, "string", &value)?;
+                self.inner
+                    .set_denied_licenses(value.iter()?.iter().map(|x| x.to_string()))
+                    .map_err(invalid_license)?;
+            }
+            "exceptions" => {
+                required_list_arg(attribute, "string", &value)?;
+                self.inner
+                    .set_exceptions(value.iter()?.iter().map(|x| x.to_string()));
+            }
+            _ => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::SetAttr(attribute.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+starlark_module! { license_policy_module =>
+    #[allow(non_snake_case)]
+    LicensePolicy() {
+        Ok(Value::new(LicensePolicyValue::default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::super::testutil::*, super::*, anyhow::Result};
+
+    #[test]
+    fn test_new() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        let value = env.eval("LicensePolicy()")?;
+        assert_eq!(value.get_type(), LicensePolicyValue::TYPE);
+
+        let policy = value.downcast_ref::<LicensePolicyValue>().unwrap();
+        assert_eq!(policy.inner, LicensePolicy::default());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_attributes() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        env.eval("policy = LicensePolicy()")?;
+
+        let value = env.eval("policy.allow_unknown")?;
+        assert_eq!(value.get_type(), "bool");
+        assert!(!value.to_bool());
+
+        let value = env.eval("policy.allow_unknown = True; policy.allow_unknown")?;
+        assert!(value.to_bool());
+
+        let value =
+            env.eval("policy.allowed_licenses = ['MIT', 'Apache-2.0']; policy.allowed_licenses")?;
+        assert_eq!(value.get_type(), "list");
+        assert_eq!(value.to_string(), "[\"Apache-2.0\", \"MIT\"]");
+
+        let value =
+            env.eval("policy.denied_licenses = ['GPL-3.0-only']; policy.denied_licenses")?;
+        assert_eq!(value.to_string(), "[\"GPL-3.0-only\"]");
+
+        let value = env.eval("policy.exceptions = ['readline']; policy.exceptions")?;
+        assert_eq!(value.to_string(), "[\"readline\"]");
+
+        assert!(env
+            .eval("policy.allowed_licenses = ['not-a-license']")
+            .is_err());
+        assert!(env.eval("policy.denied_licenses = [42]").is_err());
+
+        Ok(())
+    }
+}
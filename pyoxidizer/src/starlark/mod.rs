@@ -12,6 +12,7 @@ pub mod env;
 pub mod eval;
 pub mod file;
 pub mod file_resource;
+pub mod license_policy;
 pub mod python_distribution;
 pub mod python_embedded_resources;
 pub mod python_executable;
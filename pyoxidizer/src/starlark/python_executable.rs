@@ -7,6 +7,7 @@ use {
         env::{get_context, PyOxidizerEnvironmentContext},
         file::FileValue,
         file_resource::file_manifest_add_python_executable,
+        license_policy::LicensePolicyValue,
         python_embedded_resources::PythonEmbeddedResourcesValue,
         python_extension_module::PythonExtensionModuleValue,
         python_module_source::PythonModuleSourceValue,
@@ -21,6 +22,7 @@ use {
         project_building::build_python_executable,
         py_packaging::binary::PythonBinaryBuilder,
         py_packaging::binary::{PackedResourcesLoadMode, WindowsRuntimeDllsMode},
+        sbom::{SbomFormat, RECORDED_SBOM_DIR},
     },
     anyhow::{anyhow, Context, Result},
     linked_hash_map::LinkedHashMap,
@@ -100,7 +102,7 @@ pub fn build_internal(
     std::fs::create_dir_all(&output_path)
         .with_context(|| format!("creating output directory {}", output_path.display()))?;
 
-    let dest_path = output_path.join(build.exe_name);
+    let dest_path = output_path.join(&build.exe_name);
     warn!("writing executable to {}", dest_path.display());
     let mut fh =
         std::fs::File::create(&dest_path).context(format!("creating {}", dest_path.display()))?;
@@ -108,6 +110,29 @@ pub fn build_internal(
         .context(format!("writing {}", dest_path.display()))?;
     tugger_file_manifest::set_executable(&mut fh).context("making binary executable")?;
 
+    for format in exe.sbom_formats() {
+        let filename = format.filename(&build.exe_name);
+
+        if let Some(entry) = build.binary_data.extra_files.get(&filename) {
+            let sbom_path = output_path.join(&filename);
+            info!("writing {} SBOM to {}", format, sbom_path.display());
+            entry
+                .write_to_path(&sbom_path)
+                .with_context(|| format!("writing {}", sbom_path.display()))?;
+        }
+    }
+
+    // Record SBOMs in every format so `pyoxidizer sbom` can write them without
+    // building again.
+    let recorded_path = output_path.join(RECORDED_SBOM_DIR);
+    std::fs::create_dir_all(&recorded_path)
+        .with_context(|| format!("creating {}", recorded_path.display()))?;
+    for (format, data) in &build.sbom_documents {
+        let sbom_path = recorded_path.join(format.filename(&build.exe_name));
+        std::fs::write(&sbom_path, data)
+            .with_context(|| format!("writing {}", sbom_path.display()))?;
+    }
+
     Ok((
         ResolvedTarget {
             run_mode: RunMode::Path {
@@ -176,10 +201,27 @@ impl TypedValue for PythonExecutableValue {
         let exe = self.inner(&format!("PythonExecutable.{}", attribute))?;
 
         match attribute {
+            "license_policy" => match exe.license_policy() {
+                Some(policy) => Ok(Value::new(LicensePolicyValue::new(policy.clone()))),
+                None => Ok(Value::from(NoneType::None)),
+            },
             "licenses_filename" => Ok(exe.licenses_filename().to_value()),
             "packed_resources_load_mode" => {
                 Ok(Value::from(exe.packed_resources_load_mode().to_string()))
             }
+            "packed_resources_overlay_paths" => {
+                Ok(Value::from(exe.packed_resources_overlay_paths().to_vec()))
+            }
+            "packed_resources_signing_key_path" => match exe.packed_resources_signing_key_path() {
+                Some(value) => Ok(Value::from(value.display().to_string())),
+                None => Ok(Value::from(NoneType::None)),
+            },
+            "sbom_formats" => Ok(Value::from(
+                exe.sbom_formats()
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>(),
+            )),
             "tcl_files_path" => match exe.tcl_files_path() {
                 Some(value) => Ok(Value::from(value.to_string())),
                 None => Ok(Value::from(NoneType::None)),
@@ -199,8 +241,12 @@ impl TypedValue for PythonExecutableValue {
     fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
         Ok(matches!(
             attribute,
-            "licenses_filename"
+            "license_policy"
+                | "licenses_filename"
                 | "packed_resources_load_mode"
+                | "packed_resources_overlay_paths"
+                | "packed_resources_signing_key_path"
+                | "sbom_formats"
                 | "tcl_files_path"
                 | "windows_runtime_dlls_mode"
                 | "windows_subsystem"
@@ -211,6 +257,17 @@ impl TypedValue for PythonExecutableValue {
         let mut exe = self.inner(&format!("PythonExecutable.{}", attribute))?;
 
         match attribute {
+            "license_policy" => {
+                optional_type_arg(attribute, LicensePolicyValue::TYPE, &value)?;
+
+                exe.set_license_policy(
+                    value
+                        .downcast_ref::<LicensePolicyValue>()
+                        .map(|policy| policy.inner.clone()),
+                );
+
+                Ok(())
+            }
             "licenses_filename" => {
                 let value = optional_str_arg("licenses_filename", &value)?;
                 exe.set_licenses_filename(value);
@@ -230,6 +287,41 @@ impl TypedValue for PythonExecutableValue {
 
                 Ok(())
             }
+            "packed_resources_overlay_paths" => {
+                required_list_arg(attribute, "string", &value)?;
+
+                exe.set_packed_resources_overlay_paths(
+                    value.iter()?.iter().map(|x| x.to_string()).collect(),
+                );
+
+                Ok(())
+            }
+            "packed_resources_signing_key_path" => {
+                let value: Option<String> = value.to_optional();
+                exe.set_packed_resources_signing_key_path(value.map(PathBuf::from));
+
+                Ok(())
+            }
+            "sbom_formats" => {
+                required_list_arg(attribute, "string", &value)?;
+
+                let formats = value
+                    .iter()?
+                    .iter()
+                    .map(|x| SbomFormat::try_from(x.to_string().as_str()))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| {
+                        ValueError::from(RuntimeError {
+                            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                            message: e,
+                            label: format!("{}.{}", Self::TYPE, attribute),
+                        })
+                    })?;
+
+                exe.set_sbom_formats(formats);
+
+                Ok(())
+            }
             "tcl_files_path" => {
                 exe.set_tcl_files_path(value.to_optional());
 
@@ -366,7 +458,12 @@ impl PythonExecutableValue {
         let mut exe = self.inner(LABEL)?;
 
         let resources = error_context("PythonExecutable.pip_download()", || {
-            exe.pip_download(pyoxidizer_context.env(), pyoxidizer_context.verbose, &args)
+            exe.pip_download(
+                pyoxidizer_context.env(),
+                pyoxidizer_context.verbose,
+                &args,
+                &pyoxidizer_context.distribution_lock,
+            )
         })?;
 
         let resources = resources
@@ -430,6 +527,7 @@ impl PythonExecutableValue {
                 pyoxidizer_context.verbose,
                 &args,
                 &extra_envs,
+                &pyoxidizer_context.distribution_lock,
             )
         })?;
 
@@ -450,6 +548,66 @@ impl PythonExecutableValue {
         Ok(Value::from(resources))
     }
 
+    /// PythonExecutable.install_wheels(requirements_path, find_links)
+    pub fn install_wheels(
+        &mut self,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+        requirements_path: String,
+        find_links: &Value,
+    ) -> ValueResult {
+        const LABEL: &str = "PythonExecutable.install_wheels()";
+
+        required_list_arg("find_links", "string", find_links)?;
+
+        let find_links = find_links
+            .iter()?
+            .iter()
+            .map(|x| PathBuf::from(x.to_string()))
+            .collect::<Vec<_>>();
+
+        let pyoxidizer_context_value = get_context(type_values)?;
+        let pyoxidizer_context = pyoxidizer_context_value
+            .downcast_ref::<PyOxidizerEnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let python_packaging_policy = self.python_packaging_policy();
+
+        // Resources borrow from the builder. Convert them to owned Starlark values
+        // before releasing it so they can be added back to this instance.
+        let values = {
+            let mut exe = self.inner(LABEL)?;
+
+            let resources = error_context(LABEL, || {
+                exe.install_wheels(
+                    Path::new(&requirements_path),
+                    &find_links,
+                    &pyoxidizer_context.distribution_lock,
+                )
+            })?;
+
+            resources
+                .iter()
+                .filter(|r| is_resource_starlark_compatible(r))
+                .map(|r| {
+                    python_resource_to_value(
+                        LABEL,
+                        type_values,
+                        call_stack,
+                        r,
+                        &python_packaging_policy,
+                    )
+                })
+                .collect::<Result<Vec<Value>, ValueError>>()?
+        };
+
+        for value in values {
+            self.add_python_resource(&value, LABEL)?;
+        }
+
+        Ok(Value::new(NoneType::None))
+    }
+
     /// PythonExecutable.read_package_root(path, packages)
     pub fn read_package_root(
         &mut self,
@@ -1044,6 +1202,17 @@ starlark_module! { python_executable_env =>
         this.pip_install(env, cs, &args, &extra_envs)
     }
 
+    PythonExecutable.install_wheels(
+        env env,
+        call_stack cs,
+        this,
+        requirements_path: String,
+        find_links
+    ) {
+        let mut this = this.downcast_mut::<PythonExecutableValue>().unwrap().unwrap();
+        this.install_wheels(env, cs, requirements_path, &find_links)
+    }
+
     PythonExecutable.read_package_root(
         env env,
         call_stack cs,
@@ -1170,6 +1339,7 @@ mod tests {
         super::super::testutil::*,
         super::*,
         crate::{python_distributions::PYTHON_DISTRIBUTIONS, testutil::*},
+        python_packaging::wheel_builder::WheelBuilder,
     };
 
     #[test]
@@ -1305,6 +1475,60 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_install_wheels() -> Result<()> {
+        let temp_dir = get_env()?.temporary_directory("pyoxidizer-test")?;
+        let wheels_path = temp_dir.path().join("wheels");
+        std::fs::create_dir(&wheels_path)?;
+
+        let mut builder = WheelBuilder::new("my-package", "0.1");
+        builder.add_file("my_package/__init__.py", b"# my_package".to_vec())?;
+        builder.write_wheel_into_directory(&wheels_path)?;
+        builder.set_tag("cp27-cp27mu-manylinux1_x86_64")?;
+        builder.add_file("my_package/incompatible.py", b"# incompatible".to_vec())?;
+        builder.write_wheel_into_directory(&wheels_path)?;
+
+        let requirements_path = temp_dir.path().join("requirements.txt");
+        std::fs::write(&requirements_path, "my-package==0.1\n")?;
+
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        env.eval("dist = default_python_distribution()")?;
+        env.eval("policy = dist.make_python_packaging_policy()")?;
+        env.eval("policy.include_distribution_sources = False")?;
+        env.eval("exe = dist.to_python_executable('testapp', packaging_policy = policy)")?;
+
+        let value = env.eval(&format!(
+            "exe.install_wheels(\"{}\", [\"{}\"])",
+            requirements_path.display().to_string().replace('\\', "/"),
+            wheels_path.display().to_string().replace('\\', "/")
+        ))?;
+        assert_eq!(value.get_type(), "NoneType");
+
+        let exe_value = env.eval("exe")?;
+        let exe = exe_value.downcast_ref::<PythonExecutableValue>().unwrap();
+        let inner = exe.inner("ignored").unwrap();
+        let names = inner
+            .iter_resources()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        assert!(names.contains(&"my_package".to_string()));
+        assert!(!names.contains(&"my_package.incompatible".to_string()));
+        drop(inner);
+
+        std::fs::write(&requirements_path, "my-package==0.2\n")?;
+        assert!(env
+            .eval(&format!(
+                "exe.install_wheels(\"{}\", [\"{}\"])",
+                requirements_path.display().to_string().replace('\\', "/"),
+                wheels_path.display().to_string().replace('\\', "/")
+            ))
+            .is_err());
+
+        temp_dir.close()?;
+
+        Ok(())
+    }
+
     #[test]
     fn test_read_package_root_simple() -> Result<()> {
         let temp_dir = get_env()?.temporary_directory("pyoxidizer-test")?;
@@ -1364,6 +1588,31 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_license_policy() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+
+        let value = env.eval("exe.license_policy")?;
+        assert_eq!(value.get_type(), "NoneType");
+
+        env.eval("policy = LicensePolicy()")?;
+        env.eval("policy.denied_licenses = ['GPL-3.0-only']")?;
+        let value = env.eval("exe.license_policy = policy; exe.license_policy")?;
+        assert_eq!(value.get_type(), "LicensePolicy");
+        assert_eq!(
+            env.eval("exe.license_policy.denied_licenses")?.to_string(),
+            "[\"GPL-3.0-only\"]"
+        );
+
+        let value = env.eval("exe.license_policy = None; exe.license_policy")?;
+        assert_eq!(value.get_type(), "NoneType");
+
+        assert!(env.eval("exe.license_policy = 'MIT'").is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn licenses_filename() -> Result<()> {
         let mut env = test_evaluation_context_builder()?.into_context()?;
@@ -1429,6 +1678,47 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_packed_resources_overlay_paths() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+
+        let value = env.eval("exe.packed_resources_overlay_paths")?;
+        assert_eq!(value.get_type(), "list");
+        assert_eq!(value.length().unwrap(), 0);
+
+        let value = env.eval(
+            "exe.packed_resources_overlay_paths = ['overlay.pyor']; exe.packed_resources_overlay_paths",
+        )?;
+        assert_eq!(value.get_type(), "list");
+        assert_eq!(value.to_string(), "[\"overlay.pyor\"]");
+
+        assert!(env
+            .eval("exe.packed_resources_overlay_paths = [42]")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sbom_formats() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+
+        let value = env.eval("exe.sbom_formats")?;
+        assert_eq!(value.get_type(), "list");
+        assert_eq!(value.length().unwrap(), 0);
+
+        let value = env.eval("exe.sbom_formats = ['spdx', 'cyclonedx']; exe.sbom_formats")?;
+        assert_eq!(value.get_type(), "list");
+        assert_eq!(value.to_string(), "[\"spdx\", \"cyclonedx\"]");
+
+        assert!(env.eval("exe.sbom_formats = ['bad']").is_err());
+        assert!(env.eval("exe.sbom_formats = [42]").is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_windows_subsystem() -> Result<()> {
         let mut env = test_evaluation_context_builder()?.into_context()?;
@@ -1463,6 +1753,28 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_packed_resources_signing_key_path() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+
+        let value = env.eval("exe.packed_resources_signing_key_path")?;
+        assert_eq!(value.get_type(), "NoneType");
+
+        let value = env.eval(
+            "exe.packed_resources_signing_key_path = 'key.pk8'; exe.packed_resources_signing_key_path",
+        )?;
+        assert_eq!(value.get_type(), "string");
+        assert_eq!(value.to_string(), "key.pk8");
+
+        let value = env.eval(
+            "exe.packed_resources_signing_key_path = None; exe.packed_resources_signing_key_path",
+        )?;
+        assert_eq!(value.get_type(), "NoneType");
+
+        Ok(())
+    }
+
     #[test]
     fn test_to_wix_bundle_builder_callback() -> Result<()> {
         let mut env = test_evaluation_context_builder()?.into_context()?;
@@ -209,14 +209,20 @@ impl TypedValue for PythonInterpreterConfigValue {
             "warn_options" => inner.config.warn_options.to_value(),
             "write_bytecode" => inner.config.write_bytecode.to_value(),
             "x_options" => inner.config.x_options.to_value(),
+            "crash_report_dir" => inner.crash_report_dir.to_value(),
             "allocator_backend" => inner.allocator_backend.to_value(),
             "allocator_raw" => Value::from(inner.allocator_raw),
             "allocator_mem" => Value::from(inner.allocator_mem),
             "allocator_obj" => Value::from(inner.allocator_obj),
             "allocator_pymalloc_arena" => Value::from(inner.allocator_pymalloc_arena),
             "allocator_debug" => Value::from(inner.allocator_debug),
+            "allocator_stats" => Value::from(inner.allocator_stats),
+            "allocator_stats_report" => Value::from(inner.allocator_stats_report),
+            "allocator_tracing_backend" => inner.allocator_tracing_backend.to_value(),
+            "allocator_tracing_capacity" => Value::from(inner.allocator_tracing_capacity as i64),
             "oxidized_importer" => Value::from(inner.oxidized_importer),
             "filesystem_importer" => Value::from(inner.filesystem_importer),
+            "bytecode_cache_dir" => inner.bytecode_cache_dir.to_value(),
             "argvb" => Value::from(inner.argvb),
             "multiprocessing_auto_dispatch" => Value::from(inner.multiprocessing_auto_dispatch),
             "multiprocessing_start_method" => {
@@ -226,6 +232,8 @@ impl TypedValue for PythonInterpreterConfigValue {
             "sys_meipass" => Value::from(inner.sys_meipass),
             "terminfo_resolution" => inner.terminfo_resolution.to_value(),
             "write_modules_directory_env" => inner.write_modules_directory_env.to_value(),
+            "write_import_trace_directory_env" => inner.write_import_trace_directory_env.to_value(),
+            "entry_point" => inner.entry_point.to_value(),
             attr => {
                 return Err(ValueError::OperationNotSupported {
                     op: UnsupportedOperation::GetAttr(attr.to_string()),
@@ -296,14 +304,20 @@ impl TypedValue for PythonInterpreterConfigValue {
                 | "warn_options"
                 | "write_bytecode"
                 | "x_options"
+                | "crash_report_dir"
                 | "allocator_backend"
                 | "allocator_raw"
                 | "allocator_mem"
                 | "allocator_obj"
                 | "allocator_pymalloc_arena"
                 | "allocator_debug"
+                | "allocator_stats"
+                | "allocator_stats_report"
+                | "allocator_tracing_backend"
+                | "allocator_tracing_capacity"
                 | "oxidized_importer"
                 | "filesystem_importer"
+                | "bytecode_cache_dir"
                 | "argvb"
                 | "multiprocessing_auto_dispatch"
                 | "multiprocessing_start_method"
@@ -311,6 +325,8 @@ impl TypedValue for PythonInterpreterConfigValue {
                 | "sys_meipass"
                 | "terminfo_resolution"
                 | "write_modules_directory_env"
+                | "write_import_trace_directory_env"
+                | "entry_point"
         ))
     }
 
@@ -549,6 +565,9 @@ impl TypedValue for PythonInterpreterConfigValue {
             "x_options" => {
                 inner.config.x_options = value.try_to_optional()?;
             }
+            "crash_report_dir" => {
+                inner.crash_report_dir = value.to_optional();
+            }
             "allocator_backend" => {
                 inner.allocator_backend =
                     MemoryAllocatorBackend::try_from(value.to_string().as_str()).map_err(|e| {
@@ -574,12 +593,50 @@ impl TypedValue for PythonInterpreterConfigValue {
             "allocator_debug" => {
                 inner.allocator_debug = value.to_bool();
             }
+            "allocator_stats" => {
+                inner.allocator_stats = value.to_bool();
+            }
+            "allocator_stats_report" => {
+                inner.allocator_stats_report = value.to_bool();
+            }
+            "allocator_tracing_backend" => {
+                let backend = MemoryAllocatorBackend::try_from(value.to_string().as_str())
+                    .and_then(|backend| {
+                        if backend == MemoryAllocatorBackend::Tracing {
+                            Err("the tracing allocator cannot wrap itself".to_string())
+                        } else {
+                            Ok(backend)
+                        }
+                    })
+                    .map_err(|e| {
+                        ValueError::from(RuntimeError {
+                            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                            message: e,
+                            label: format!("{}.{}", Self::TYPE, attribute),
+                        })
+                    })?;
+
+                inner.allocator_tracing_backend = backend;
+            }
+            "allocator_tracing_capacity" => {
+                inner.allocator_tracing_capacity =
+                    usize::try_from(value.to_int()?).map_err(|_| {
+                        ValueError::from(RuntimeError {
+                            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                            message: "capacity must not be negative".to_string(),
+                            label: format!("{}.{}", Self::TYPE, attribute),
+                        })
+                    })?;
+            }
             "oxidized_importer" => {
                 inner.oxidized_importer = value.to_bool();
             }
             "filesystem_importer" => {
                 inner.filesystem_importer = value.to_bool();
             }
+            "bytecode_cache_dir" => {
+                inner.bytecode_cache_dir = value.to_optional();
+            }
             "argvb" => {
                 inner.argvb = value.to_bool();
             }
@@ -617,6 +674,12 @@ impl TypedValue for PythonInterpreterConfigValue {
             "write_modules_directory_env" => {
                 inner.write_modules_directory_env = value.to_optional();
             }
+            "write_import_trace_directory_env" => {
+                inner.write_import_trace_directory_env = value.to_optional();
+            }
+            "entry_point" => {
+                inner.entry_point = value.to_optional();
+            }
             attr => {
                 return Err(ValueError::OperationNotSupported {
                     op: UnsupportedOperation::SetAttr(attr.to_string()),
@@ -1194,6 +1257,21 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_crash_report_dir() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.crash_report_dir == None")?;
+
+        env.eval("config.crash_report_dir = '$ORIGIN/crashes'")?;
+        eval_assert(&mut env, "config.crash_report_dir == '$ORIGIN/crashes'")?;
+
+        env.eval("config.crash_report_dir = None")?;
+        eval_assert(&mut env, "config.crash_report_dir == None")?;
+
+        Ok(())
+    }
+
     #[test]
     fn test_allocator_backend() -> Result<()> {
         let mut env = get_env()?;
@@ -1212,6 +1290,9 @@ mod tests {
         env.eval("config.allocator_backend = 'snmalloc'")?;
         eval_assert(&mut env, "config.allocator_backend == 'snmalloc'")?;
 
+        env.eval("config.allocator_backend = 'tracing'")?;
+        eval_assert(&mut env, "config.allocator_backend == 'tracing'")?;
+
         env.eval("config.allocator_backend = 'default'")?;
         eval_assert(&mut env, "config.allocator_backend == 'default'")?;
 
@@ -1278,6 +1359,44 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_allocator_stats() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.allocator_stats == False")?;
+        eval_assert(&mut env, "config.allocator_stats_report == False")?;
+
+        env.eval("config.allocator_stats = True")?;
+        eval_assert(&mut env, "config.allocator_stats == True")?;
+
+        env.eval("config.allocator_stats_report = True")?;
+        eval_assert(&mut env, "config.allocator_stats_report == True")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_allocator_tracing() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.allocator_tracing_backend == 'default'")?;
+        eval_assert(&mut env, "config.allocator_tracing_capacity == 65536")?;
+
+        env.eval("config.allocator_tracing_backend = 'rust'")?;
+        eval_assert(&mut env, "config.allocator_tracing_backend == 'rust'")?;
+
+        assert!(env
+            .eval("config.allocator_tracing_backend = 'tracing'")
+            .is_err());
+
+        env.eval("config.allocator_tracing_capacity = 1024")?;
+        eval_assert(&mut env, "config.allocator_tracing_capacity == 1024")?;
+
+        assert!(env.eval("config.allocator_tracing_capacity = -1").is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_oxidized_importer() -> Result<()> {
         let mut env = get_env()?;
@@ -1296,6 +1415,21 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_bytecode_cache_dir() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.bytecode_cache_dir == None")?;
+
+        env.eval("config.bytecode_cache_dir = '$ORIGIN/pycache'")?;
+        eval_assert(&mut env, "config.bytecode_cache_dir == '$ORIGIN/pycache'")?;
+
+        env.eval("config.bytecode_cache_dir = None")?;
+        eval_assert(&mut env, "config.bytecode_cache_dir == None")?;
+
+        Ok(())
+    }
+
     #[test]
     fn test_argvb() -> Result<()> {
         let mut env = get_env()?;
@@ -1385,4 +1519,34 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_entry_point() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.entry_point == None")?;
+
+        env.eval("config.entry_point = 'pkg.mod:main'")?;
+        eval_assert(&mut env, "config.entry_point == 'pkg.mod:main'")?;
+
+        env.eval("config.entry_point = None")?;
+        eval_assert(&mut env, "config.entry_point == None")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_import_trace_directory_env() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.write_import_trace_directory_env == None")?;
+
+        env.eval("config.write_import_trace_directory_env = 'TRACE_DIR'")?;
+        eval_assert(
+            &mut env,
+            "config.write_import_trace_directory_env == 'TRACE_DIR'",
+        )?;
+
+        Ok(())
+    }
 }
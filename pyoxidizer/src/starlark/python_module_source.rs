@@ -220,7 +220,9 @@ mod tests {
 
         assert_eq!(
             m.get_attr("add_location_fallback").unwrap().get_type(),
-            if dist.supports_in_memory_shared_library_loading() {
+            if dist.target_triple().contains("pc-windows")
+                && dist.supports_in_memory_shared_library_loading()
+            {
                 "string"
             } else {
                 "NoneType"
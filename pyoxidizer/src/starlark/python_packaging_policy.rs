@@ -9,6 +9,7 @@ use {
         location::ConcreteResourceLocation,
         policy::{ExtensionModuleFilter, PythonPackagingPolicy, ResourceHandlingMode},
     },
+    python_packed_resources::CompressionPolicy,
     starlark::{
         environment::TypeValues,
         eval::call_stack::CallStack,
@@ -140,6 +141,8 @@ impl TypedValue for PythonPackagingPolicyValue {
             "preferred_extension_module_variants" => {
                 Value::try_from(inner.preferred_extension_module_variants().clone())?
             }
+            "resources_compression" => Value::from(inner.resources_compression().as_ref()),
+            "resources_name_index" => Value::from(inner.resources_name_index()),
             "resources_location" => Value::from(inner.resources_location().to_string()),
             "resources_location_fallback" => match inner.resources_location_fallback() {
                 Some(location) => Value::from(location.to_string()),
@@ -175,6 +178,8 @@ impl TypedValue for PythonPackagingPolicyValue {
                 | "include_non_distribution_sources"
                 | "include_test"
                 | "preferred_extension_module_variants"
+                | "resources_compression"
+                | "resources_name_index"
                 | "resources_location"
                 | "resources_location_fallback"
         ))
@@ -235,6 +240,21 @@ impl TypedValue for PythonPackagingPolicyValue {
             "include_test" => {
                 inner.set_include_test(value.to_bool());
             }
+            "resources_compression" => {
+                let policy =
+                    CompressionPolicy::try_from(value.to_string().as_str()).map_err(|e| {
+                        ValueError::from(RuntimeError {
+                            code: "PYOXIDIZER_BUILD",
+                            message: e,
+                            label: format!("{}.{} = {}", Self::TYPE, attribute, value),
+                        })
+                    })?;
+
+                inner.set_resources_compression(policy);
+            }
+            "resources_name_index" => {
+                inner.set_resources_name_index(value.to_bool());
+            }
             "resources_location" => {
                 inner.set_resources_location(
                     ConcreteResourceLocation::try_from(value.to_string().as_str()).map_err(
@@ -477,6 +497,29 @@ mod tests {
         let value = env.eval("policy.include_test = True; policy.include_test")?;
         assert!(value.to_bool());
 
+        let value = env.eval("policy.resources_compression")?;
+        assert_eq!(value.get_type(), "string");
+        assert_eq!(value.to_string(), "none");
+
+        let value =
+            env.eval("policy.resources_compression = 'zstd'; policy.resources_compression")?;
+        assert_eq!(value.to_string(), "zstd");
+
+        let value =
+            env.eval("policy.resources_compression = 'adaptive'; policy.resources_compression")?;
+        assert_eq!(value.to_string(), "adaptive");
+
+        assert!(env
+            .eval("policy.resources_compression = 'invalid'")
+            .is_err());
+
+        let value = env.eval("policy.resources_name_index")?;
+        assert_eq!(value.get_type(), "bool");
+        assert!(!value.to_bool());
+
+        let value = env.eval("policy.resources_name_index = True; policy.resources_name_index")?;
+        assert!(value.to_bool());
+
         let value = env.eval("policy.resources_location")?;
         assert_eq!(value.get_type(), "string");
         assert_eq!(value.to_string(), "in-memory");
@@ -487,7 +530,9 @@ mod tests {
         assert_eq!(value.to_string(), "filesystem-relative:lib");
 
         let value = env.eval("policy.resources_location_fallback")?;
-        if dist_ref.supports_in_memory_shared_library_loading() {
+        if dist_ref.target_triple().contains("pc-windows")
+            && dist_ref.supports_in_memory_shared_library_loading()
+        {
             assert_eq!(value.get_type(), "string");
             assert_eq!(value.to_string(), "filesystem-relative:lib");
         } else {
@@ -7,6 +7,14 @@ use std::path::PathBuf;
 fn main() {
     println!("cargo:rerun-if-env-changed=PYO3_CONFIG_FILE");
 
+    // Dependency resolution needs to know which wheels are compatible with
+    // the Python interpreter we link against.
+    let interpreter_config = pyo3_build_config::get();
+    println!(
+        "cargo:rustc-env=PYOXY_PYTHON_VERSION={}.{}",
+        interpreter_config.version.major, interpreter_config.version.minor
+    );
+
     // If a PyO3 config file is defined, we look for PyOxidizer's packed resources
     // in the same directory. If found, we make those resources available to the crate.
     if let Ok(config_path) = std::env::var("PYO3_CONFIG_FILE") {
@@ -3,28 +3,49 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
-    crate::{interpreter::run_python, yaml::run_yaml_path},
+    crate::{
+        config::{load_config_path, prepare_config, print_config, run_config_path, ConfigFormat},
+        interpreter::{python_config, run_python},
+        yaml::run_yaml_path,
+    },
     anyhow::{anyhow, Context, Result},
-    clap::{Arg, Command},
+    clap::{Arg, ArgMatches, Command},
     std::path::{Path, PathBuf},
 };
 
 const PYOXY_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Whether an executable name selects the `python` personality.
+fn is_python_name(path: &Path) -> bool {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().starts_with("python"))
+        .unwrap_or(false)
+}
+
 pub fn run() -> Result<i32> {
     let exe = std::env::current_exe().context("resolving current executable")?;
 
-    // If the current executable looks like `python`, we effectively dispatch to
-    // `pyoxy run-python -- <args>`.
-    if let Some(stem) = exe.file_stem() {
-        if stem.to_string_lossy().starts_with("python") {
-            return run_python(&exe, &std::env::args_os().skip(1).collect::<Vec<_>>());
-        }
+    // If the executable was invoked as or looks like `python`, we effectively
+    // dispatch to `pyoxy run-python -- <args>`. argv[0] is consulted because
+    // resolving the current executable follows symlinks on some platforms. So
+    // a `python3` symlink to `pyoxy` would otherwise go unnoticed.
+    let argv0 = std::env::args_os().next().map(PathBuf::from);
+
+    if argv0.as_deref().map(is_python_name).unwrap_or(false) || is_python_name(&exe) {
+        return run_python(&exe, &std::env::args_os().skip(1).collect::<Vec<_>>());
     }
 
     run_normal(&exe)
 }
 
+/// Resolve the value of a `--format` argument.
+fn config_format(args: &ArgMatches) -> Result<Option<ConfigFormat>> {
+    args.value_of("format")
+        .map(ConfigFormat::try_from)
+        .transpose()
+        .map_err(|e| anyhow!(e))
+}
+
 fn run_normal(exe: &Path) -> Result<i32> {
     let app = Command::new("pyoxy")
         .version(PYOXY_VERSION)
@@ -64,6 +85,59 @@ fn run_normal(exe: &Path) -> Result<i32> {
             ),
     );
 
+    let app = app.subcommand(
+        Command::new("run-config")
+            .about("Run a Python interpreter defined via a YAML, TOML, or JSON config document")
+            .arg_required_else_help(true)
+            .arg(
+                Arg::new("format")
+                    .long("format")
+                    .takes_value(true)
+                    .possible_values(["yaml", "toml", "json"])
+                    .help("Format of the config document (detected if not specified)"),
+            )
+            .arg(
+                Arg::new("config_path")
+                    .value_name("FILE")
+                    .allow_invalid_utf8(true)
+                    .help("Path to file containing the config document to evaluate"),
+            )
+            .arg(
+                Arg::new("args")
+                    .help("Arguments to Python interpreter")
+                    .multiple_occurrences(true)
+                    .multiple_values(true)
+                    .allow_invalid_utf8(true)
+                    .last(true),
+            ),
+    );
+
+    let app = app.subcommand(
+        Command::new("config")
+            .about("Print the effective Python interpreter config as YAML")
+            .arg(
+                Arg::new("format")
+                    .long("format")
+                    .takes_value(true)
+                    .possible_values(["yaml", "toml", "json"])
+                    .help("Format of the config document (detected if not specified)"),
+            )
+            .arg(
+                Arg::new("config_path")
+                    .value_name("FILE")
+                    .allow_invalid_utf8(true)
+                    .help("Path to file containing a config document (defaults to the config of run-python)"),
+            )
+            .arg(
+                Arg::new("args")
+                    .help("Arguments to Python interpreter")
+                    .multiple_occurrences(true)
+                    .multiple_values(true)
+                    .allow_invalid_utf8(true)
+                    .last(true),
+            ),
+    );
+
     let matches = app.get_matches();
 
     match matches.subcommand() {
@@ -87,6 +161,39 @@ fn run_normal(exe: &Path) -> Result<i32> {
 
             run_yaml_path(&yaml_path, &program_args)
         }
+        Some(("run-config", args)) => {
+            let format = config_format(args)?;
+            let config_path = PathBuf::from(
+                args.value_of_os("config_path")
+                    .expect("config_path should be set"),
+            );
+            let program_args = args
+                .values_of_os("args")
+                .unwrap_or_default()
+                .collect::<Vec<_>>();
+
+            run_config_path(&config_path, format, &program_args)
+        }
+        Some(("config", args)) => {
+            let program_args = args
+                .values_of_os("args")
+                .unwrap_or_default()
+                .collect::<Vec<_>>();
+
+            let config = if let Some(config_path) = args.value_of_os("config_path") {
+                let config_path = PathBuf::from(config_path);
+                let mut config = load_config_path(&config_path, config_format(args)?)?;
+                prepare_config(&mut config, &config_path, &program_args);
+
+                config
+            } else {
+                python_config(exe, &program_args)
+            };
+
+            print_config(config)?;
+
+            Ok(0)
+        }
         _ => Err(anyhow!("invalid sub-command")),
     }
 }
@@ -0,0 +1,346 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Functionality for running from config documents embedded in files.
+
+A file can carry an interpreter config as a YAML, TOML, or JSON document.
+Content before the document, such as a shell shebang, is ignored.
+
+* YAML documents begin with a line beginning with `---` and end at a line
+  beginning with `...` or the end of the file.
+* TOML documents are comment blocks like those of PEP 723. They begin with a
+  `# /// pyoxy` line and end with a `# ///` line. Every line in between must
+  be a comment. The leading `#` and the space following it are stripped.
+* JSON documents begin with a line beginning with `{` and end where the JSON
+  object does.
+*/
+
+use {
+    crate::{dependencies::DependenciesDocument, interpreter::Config},
+    anyhow::{anyhow, Context, Result},
+    pyembed::{MainPythonInterpreter, OxidizedPythonInterpreterConfig, PackedResourcesSource},
+    serde::de::DeserializeOwned,
+    std::{
+        ffi::OsStr,
+        fmt::{Display, Formatter},
+        fs::File,
+        io::{BufRead, BufReader, Read},
+        path::Path,
+    },
+};
+
+/// Line opening a TOML document.
+const TOML_START: &str = "# /// pyoxy";
+
+/// Line closing a TOML document.
+const TOML_END: &str = "# ///";
+
+/// Format of a config document.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConfigFormat {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl Display for ConfigFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Yaml => "YAML",
+            Self::Toml => "TOML",
+            Self::Json => "JSON",
+        })
+    }
+}
+
+impl TryFrom<&str> for ConfigFormat {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "yaml" => Ok(Self::Yaml),
+            "toml" => Ok(Self::Toml),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("{} is not a valid config format", value)),
+        }
+    }
+}
+
+impl ConfigFormat {
+    /// Whether a line begins a document of this format.
+    fn is_document_start(&self, line: &str) -> bool {
+        match self {
+            Self::Yaml => line.starts_with("---"),
+            Self::Toml => line.trim_end() == TOML_START,
+            Self::Json => line.starts_with('{'),
+        }
+    }
+
+    /// Describes the line beginning a document of this format.
+    fn document_start_description(&self) -> &'static str {
+        match self {
+            Self::Yaml => "a line beginning with '---'",
+            Self::Toml => "a '# /// pyoxy' line",
+            Self::Json => "a line beginning with '{'",
+        }
+    }
+}
+
+/// A config document extracted from a file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConfigDocument {
+    /// Format of the document.
+    pub format: ConfigFormat,
+
+    /// Content of the document.
+    ///
+    /// Lines of the file preceding the document are retained as empty lines. So
+    /// line numbers reported when parsing refer to lines in the file.
+    pub content: String,
+}
+
+impl ConfigDocument {
+    /// Extract a config document from a reader.
+    ///
+    /// If `format` is `None`, the format is that of the first line beginning a
+    /// document.
+    pub fn from_reader(reader: impl Read, format: Option<ConfigFormat>) -> Result<Self> {
+        let formats = match format {
+            Some(format) => vec![format],
+            None => vec![ConfigFormat::Yaml, ConfigFormat::Toml, ConfigFormat::Json],
+        };
+
+        let mut lines = vec![];
+        let mut format = None;
+        let mut finished = false;
+        // 1-based line number of the line beginning the document.
+        let mut start_line = 0;
+
+        for (i, line) in BufReader::new(reader).lines().enumerate() {
+            let line = line?;
+
+            match format {
+                None => {
+                    if let Some(f) = formats.iter().find(|f| f.is_document_start(&line)) {
+                        format = Some(*f);
+                        start_line = i + 1;
+
+                        match f {
+                            ConfigFormat::Yaml | ConfigFormat::Json => lines.push(line),
+                            ConfigFormat::Toml => lines.push(String::new()),
+                        }
+                    } else {
+                        lines.push(String::new());
+                    }
+                }
+                Some(ConfigFormat::Yaml) => {
+                    finished = line.starts_with("...");
+                    lines.push(line);
+
+                    if finished {
+                        break;
+                    }
+                }
+                Some(ConfigFormat::Toml) => {
+                    if line.trim_end() == TOML_END {
+                        finished = true;
+                        break;
+                    } else if line == "#" {
+                        lines.push(String::new());
+                    } else if let Some(content) = line.strip_prefix("# ") {
+                        lines.push(content.to_string());
+                    } else {
+                        return Err(anyhow!(
+                            "line {}: lines in a '{}' block must be comments beginning with '# '",
+                            i + 1,
+                            TOML_START
+                        ));
+                    }
+                }
+                Some(ConfigFormat::Json) => {
+                    lines.push(line);
+                }
+            }
+        }
+
+        let format = format.ok_or_else(|| {
+            anyhow!(
+                "failed to locate config document; expected {}",
+                formats
+                    .iter()
+                    .map(|f| format!("{} ({})", f.document_start_description(), f))
+                    .collect::<Vec<_>>()
+                    .join(", or ")
+            )
+        })?;
+
+        if format == ConfigFormat::Toml && !finished {
+            return Err(anyhow!(
+                "line {}: '{}' block is not closed by a '{}' line",
+                start_line,
+                TOML_START,
+                TOML_END
+            ));
+        }
+
+        Ok(Self {
+            format,
+            content: lines.join("\n"),
+        })
+    }
+
+    /// Parse the document into a [Config].
+    ///
+    /// Errors include `path` and the line of the file the error occurred on.
+    pub fn parse(&self, path: &Path) -> Result<Config<'static>> {
+        self.deserialize(path)
+    }
+
+    /// Deserialize the document into an arbitrary type.
+    ///
+    /// Errors are reported like [Self::parse()].
+    pub fn deserialize<T: DeserializeOwned>(&self, path: &Path) -> Result<T> {
+        let res = match self.format {
+            ConfigFormat::Yaml => serde_yaml::from_str(&self.content)
+                .map_err(|e| (e.location().map(|l| l.line()), e.to_string())),
+            ConfigFormat::Toml => toml::from_str(&self.content)
+                .map_err(|e| (e.line_col().map(|(line, _)| line + 1), e.to_string())),
+            // Content following the JSON object is ignored.
+            ConfigFormat::Json => {
+                T::deserialize(&mut serde_json::Deserializer::from_str(&self.content))
+                    .map_err(|e| (Some(e.line()).filter(|line| *line > 0), e.to_string()))
+            }
+        };
+
+        res.map_err(|(line, message)| match line {
+            Some(line) => anyhow!(
+                "{}:{}: error parsing {} config: {}",
+                path.display(),
+                line,
+                self.format,
+                message
+            ),
+            None => anyhow!(
+                "{}: error parsing {} config: {}",
+                path.display(),
+                self.format,
+                message
+            ),
+        })
+    }
+}
+
+/// Fill in a [Config] for running.
+///
+/// The current environment's settings are applied. Unless the fields `exe` or
+/// `argv` are set, the provided values will be used.
+pub fn prepare_config(config: &mut Config, exe: &Path, args: &[&OsStr]) {
+    config.apply_environment();
+
+    if config.exe.is_none() {
+        config.exe = Some(exe.to_path_buf());
+    }
+
+    if config.argv.is_none() {
+        // argv[0] is the program name.
+        config.argv = Some(
+            vec![exe.as_os_str()]
+                .iter()
+                .chain(args.iter())
+                .map(|x| x.to_os_string())
+                .collect::<Vec<_>>(),
+        );
+    }
+}
+
+/// Run a Python interpreter from a [Config].
+///
+/// The config is filled in by [prepare_config()].
+///
+/// A [MainPythonInterpreter] will be spawned from the config. It will then run
+/// whatever it is configured to run and finalize. The function returns an exit
+/// code.
+///
+/// If the interpreter raises a Python exception, this will be handled by
+/// Python and it will not materialize as an `Err`.
+pub fn run_config(mut config: Config, exe: &Path, args: &[&OsStr]) -> Result<i32> {
+    prepare_config(&mut config, exe, args);
+
+    let interp =
+        MainPythonInterpreter::new(config.into()).context("initializing Python interpreter")?;
+    Ok(interp.run())
+}
+
+/// Print the effective configuration of a [Config] as YAML.
+///
+/// The config is resolved like it would be when spawning an interpreter.
+/// Fields that can't be serialized, such as packed resources, are omitted.
+pub fn print_config(config: Config) -> Result<()> {
+    let resolved = OxidizedPythonInterpreterConfig::from(config)
+        .resolve()
+        .context("resolving config")?;
+
+    print!("{}", serde_yaml::to_string(&*resolved)?);
+
+    Ok(())
+}
+
+/// Load a [Config] from a reader that contains a config document.
+///
+/// `path` is the path of the file being read. Dependencies declared by the
+/// document are resolved and made importable.
+pub fn load_config_reader(
+    reader: impl Read,
+    format: Option<ConfigFormat>,
+    path: &Path,
+) -> Result<Config<'static>> {
+    let document = ConfigDocument::from_reader(reader, format)?;
+    let mut config = document.parse(path)?;
+
+    if let Some(dependencies) = document
+        .deserialize::<DependenciesDocument>(path)?
+        .dependencies
+    {
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let resources_path = dependencies
+            .packed_resources_path(base_dir)
+            .context("resolving dependencies")?;
+
+        config
+            .packed_resources
+            .push(PackedResourcesSource::MemoryMappedPath(resources_path));
+        config.oxidized_importer = true;
+    }
+
+    Ok(config)
+}
+
+/// Load a [Config] from a file containing a config document.
+pub fn load_config_path(path: &Path, format: Option<ConfigFormat>) -> Result<Config<'static>> {
+    load_config_reader(File::open(path)?, format, path)
+}
+
+/// Run from a reader that contains a config document.
+///
+/// `exe_path` is the path of the file being read. It and additional arguments
+/// will be defined as `sys.argv` unless the read config overwrites them.
+///
+/// Dependencies declared by the document are resolved and made importable.
+pub fn run_config_reader(
+    reader: impl Read,
+    format: Option<ConfigFormat>,
+    exe_path: &Path,
+    args: &[&OsStr],
+) -> Result<i32> {
+    run_config(
+        load_config_reader(reader, format, exe_path)?,
+        exe_path,
+        args,
+    )
+}
+
+/// Run a file containing a config document.
+pub fn run_config_path(path: &Path, format: Option<ConfigFormat>, args: &[&OsStr]) -> Result<i32> {
+    run_config(load_config_path(path, format)?, path, args)
+}
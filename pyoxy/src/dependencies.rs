@@ -0,0 +1,654 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Resolve Python package dependencies declared by config documents.
+
+Config documents can declare requirements on third-party Python packages.
+These are resolved to pure Python wheels found in local directories or a
+PEP 503 package index. The resources in the wheels are collected into a
+packed resources file that is written to a cache directory. The file is
+keyed by a content hash of the declaration. So later runs with the same
+declaration memory map the existing file instead of resolving again.
+
+Resolution is deliberately simple. Requirements are processed in the order
+they are encountered and the highest compatible version satisfying a
+requirement is selected. There is no backtracking. Environment markers are
+evaluated against the interpreter we run. Requirements with markers
+referencing values we don't know, like `platform_release`, are errors.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    once_cell::sync::Lazy,
+    python_packaging::{
+        bytecode::NoBytecodeCompiler,
+        location::{AbstractResourceLocation, ConcreteResourceLocation},
+        module_util::PythonModuleSuffixes,
+        requirement::{MarkerEnvironment, Requirement, Version, VersionSpecifier},
+        resource::PythonResource,
+        resource_collection::{PythonResourceAddCollectionContext, PythonResourceCollector},
+        wheel::WheelArchive,
+        wheel_resolver::{CompatibleTags, WheelFilename},
+    },
+    serde::{Deserialize, Serialize},
+    sha2::Digest,
+    std::{
+        collections::{BTreeMap, HashMap, VecDeque},
+        ffi::CStr,
+        io::{Cursor, Read, Write},
+        os::raw::c_char,
+        path::{Path, PathBuf},
+    },
+    url::Url,
+};
+
+/// Package index used when no wheel sources are declared.
+const DEFAULT_INDEX_URL: &str = "https://pypi.org/simple/";
+
+/// `X.Y` version of the Python interpreter we are built against.
+const PYTHON_VERSION: &str = env!("PYOXY_PYTHON_VERSION");
+
+const PYOXY_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Environment variable overriding the cache directory.
+const CACHE_DIR_ENV: &str = "PYOXY_CACHE_DIR";
+
+/// Regex for finding links to distributions on a PEP 503 index page.
+static RE_INDEX_LINK: Lazy<regex::Regex> =
+    Lazy::new(|| regex::Regex::new(r#"(?is)<a\s(?P<attrs>[^>]*)>(?P<text>[^<]*)</a>"#).unwrap());
+
+static RE_HREF: Lazy<regex::Regex> =
+    Lazy::new(|| regex::Regex::new(r#"(?i)href\s*=\s*"(?P<href>[^"]*)""#).unwrap());
+
+static RE_REQUIRES_PYTHON: Lazy<regex::Regex> = Lazy::new(|| {
+    regex::Regex::new(r#"(?i)data-requires-python\s*=\s*"(?P<value>[^"]*)""#).unwrap()
+});
+
+extern "C" {
+    // Part of the stable ABI and safe to call before the interpreter is initialized.
+    fn Py_GetVersion() -> *const c_char;
+}
+
+/// Dependencies declared by a config document.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Dependencies {
+    /// Requirements on Python packages.
+    ///
+    /// e.g. `requests` or `six==1.16.0`.
+    pub requirements: Vec<String>,
+
+    /// Directories containing wheels.
+    ///
+    /// Relative paths are relative to the directory of the file containing
+    /// the config document.
+    pub wheel_dirs: Vec<PathBuf>,
+
+    /// URL of a PEP 503 package index to obtain wheels from.
+    ///
+    /// If neither this nor `wheel_dirs` is set, PyPI is used.
+    pub index_url: Option<String>,
+}
+
+/// The parts of a config document related to dependencies.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct DependenciesDocument {
+    /// Dependencies to resolve.
+    pub dependencies: Option<Dependencies>,
+}
+
+/// Where a wheel can be obtained from.
+#[derive(Clone, Debug)]
+enum WheelSource {
+    Path(PathBuf),
+    Url { url: Url, sha256: Option<String> },
+}
+
+/// A wheel that could satisfy a requirement.
+#[derive(Clone, Debug)]
+struct Candidate {
+    filename: String,
+    version: Version,
+    source: WheelSource,
+}
+
+impl Candidate {
+    /// Construct an instance from a wheel filename.
+    ///
+    /// Returns `None` if the filename isn't a wheel for the named package
+    /// that is compatible with our interpreter.
+    fn from_filename(
+        filename: &str,
+        name: &str,
+        tags: &CompatibleTags,
+        source: WheelSource,
+    ) -> Option<Self> {
+        let wheel = WheelFilename::parse(filename).ok()?;
+
+        if wheel.name != name || tags.priority(&wheel).is_none() {
+            return None;
+        }
+
+        Some(Self {
+            filename: filename.to_string(),
+            version: Version::parse(&wheel.version).ok()?,
+            source,
+        })
+    }
+}
+
+/// Resolves requirements to wheels.
+struct Resolver {
+    /// Tags of wheels compatible with our interpreter. Only pure Python wheels are.
+    tags: CompatibleTags,
+    /// Version of our interpreter, for evaluating `Requires-Python` of index links.
+    python_version: Version,
+    wheel_dirs: Vec<PathBuf>,
+    index_url: Option<Url>,
+    client: Option<reqwest::blocking::Client>,
+    /// Candidates found on the index, keyed by normalized package name.
+    index_cache: HashMap<String, Vec<Candidate>>,
+}
+
+impl Resolver {
+    fn new(dependencies: &Dependencies, base_dir: &Path) -> Result<Self> {
+        let wheel_dirs = dependencies
+            .wheel_dirs
+            .iter()
+            .map(|p| base_dir.join(p))
+            .collect::<Vec<_>>();
+
+        let index_url = match &dependencies.index_url {
+            Some(url) => Some(url.as_str()),
+            None if wheel_dirs.is_empty() => Some(DEFAULT_INDEX_URL),
+            None => None,
+        }
+        .map(|url| {
+            // Ensure relative URLs are joined below the index path.
+            let url = if url.ends_with('/') {
+                url.to_string()
+            } else {
+                format!("{}/", url)
+            };
+            Url::parse(&url).with_context(|| format!("parsing index URL {}", url))
+        })
+        .transpose()?;
+
+        let python_version = python_full_version()
+            .and_then(|version| Version::parse(&version).ok())
+            .map_or_else(|| Version::parse(PYTHON_VERSION), Ok)?;
+
+        Ok(Self {
+            tags: CompatibleTags::new("cp", PYTHON_VERSION, None, "none")?,
+            python_version,
+            wheel_dirs,
+            index_url,
+            client: None,
+            index_cache: HashMap::new(),
+        })
+    }
+
+    fn client(&mut self) -> Result<&reqwest::blocking::Client> {
+        if self.client.is_none() {
+            self.client = Some(tugger_common::http::get_http_client()?);
+        }
+
+        Ok(self.client.as_ref().unwrap())
+    }
+
+    fn read_url(&mut self, url: &Url) -> Result<Vec<u8>> {
+        let mut data = vec![];
+
+        if url.scheme() == "file" {
+            let path = url
+                .to_file_path()
+                .map_err(|_| anyhow!("bad file URL: {}", url))?;
+            std::fs::File::open(&path)
+                .with_context(|| format!("opening {}", path.display()))?
+                .read_to_end(&mut data)?;
+        } else {
+            let mut response = self
+                .client()?
+                .get(url.clone())
+                .send()
+                .with_context(|| format!("fetching {}", url))?
+                .error_for_status()?;
+            response.read_to_end(&mut data)?;
+        }
+
+        Ok(data)
+    }
+
+    /// Find candidates for a package on the index.
+    fn index_candidates(&mut self, name: &str) -> Result<Vec<Candidate>> {
+        let index_url = match &self.index_url {
+            Some(url) => url.clone(),
+            None => return Ok(vec![]),
+        };
+
+        if let Some(candidates) = self.index_cache.get(name) {
+            return Ok(candidates.clone());
+        }
+
+        let page_url = index_url.join(&format!("{}/", name))?;
+        let page = String::from_utf8(self.read_url(&page_url)?)
+            .with_context(|| format!("decoding {}", page_url))?;
+
+        let mut candidates = vec![];
+
+        for captures in RE_INDEX_LINK.captures_iter(&page) {
+            let attrs = &captures["attrs"];
+
+            if attrs.contains("data-yanked") {
+                continue;
+            }
+
+            // PEP 503: links can declare the Python versions they support.
+            if let Some(captures) = RE_REQUIRES_PYTHON.captures(attrs) {
+                if !requires_python_matches(
+                    &unescape_attribute(&captures["value"]),
+                    &self.python_version,
+                ) {
+                    continue;
+                }
+            }
+
+            let href = match RE_HREF.captures(attrs) {
+                Some(captures) => unescape_attribute(&captures["href"]),
+                None => continue,
+            };
+
+            let mut url = page_url.join(&href)?;
+            let sha256 = url
+                .fragment()
+                .and_then(|f| f.strip_prefix("sha256="))
+                .map(|x| x.to_string());
+            url.set_fragment(None);
+
+            if let Some(candidate) = Candidate::from_filename(
+                captures["text"].trim(),
+                name,
+                &self.tags,
+                WheelSource::Url { url, sha256 },
+            ) {
+                candidates.push(candidate);
+            }
+        }
+
+        self.index_cache
+            .insert(name.to_string(), candidates.clone());
+
+        Ok(candidates)
+    }
+
+    /// Find candidates for a package in wheel directories.
+    fn dir_candidates(&self, name: &str) -> Result<Vec<Candidate>> {
+        let mut candidates = vec![];
+
+        for dir in &self.wheel_dirs {
+            for entry in std::fs::read_dir(dir)
+                .with_context(|| format!("reading wheel directory {}", dir.display()))?
+            {
+                let entry = entry?;
+                let filename = entry.file_name().to_string_lossy().to_string();
+
+                if let Some(candidate) = Candidate::from_filename(
+                    &filename,
+                    name,
+                    &self.tags,
+                    WheelSource::Path(entry.path()),
+                ) {
+                    candidates.push(candidate);
+                }
+            }
+        }
+
+        Ok(candidates)
+    }
+
+    /// Select the wheel to satisfy a requirement.
+    fn select(&mut self, requirement: &Requirement) -> Result<Candidate> {
+        let mut candidates = self.dir_candidates(&requirement.name)?;
+
+        // Local wheels take precedence. So only consult the index when they
+        // don't satisfy the requirement.
+        if !candidates.iter().any(|c| requirement.matches(&c.version)) {
+            candidates.extend(self.index_candidates(&requirement.name)?);
+        }
+
+        let candidates = candidates
+            .into_iter()
+            .filter(|c| requirement.matches(&c.version))
+            .collect::<Vec<_>>();
+
+        // Pre-releases are only considered if requested or nothing else matches.
+        let has_final = candidates.iter().any(|c| !c.version.is_pre_release());
+        let allow_pre = requirement.allows_pre_releases() || !has_final;
+
+        candidates
+            .into_iter()
+            .filter(|c| allow_pre || !c.version.is_pre_release())
+            .reduce(|a, b| if b.version > a.version { b } else { a })
+            .ok_or_else(|| {
+                anyhow!(
+                    "no compatible wheel found satisfying requirement {} (pure Python wheels for Python {} are supported)",
+                    requirement.raw,
+                    PYTHON_VERSION
+                )
+            })
+    }
+
+    fn fetch(&mut self, candidate: &Candidate) -> Result<WheelArchive> {
+        match &candidate.source {
+            WheelSource::Path(path) => WheelArchive::from_path(path),
+            WheelSource::Url { url, sha256 } => {
+                let data = self.read_url(url)?;
+
+                if let Some(expected) = sha256 {
+                    let actual = hex::encode(sha2::Sha256::digest(&data));
+
+                    if !actual.eq_ignore_ascii_case(expected) {
+                        return Err(anyhow!(
+                            "SHA-256 mismatch for {}: expected {}, got {}",
+                            url,
+                            expected,
+                            actual
+                        ));
+                    }
+                }
+
+                WheelArchive::from_reader(Cursor::new(data), &candidate.filename)
+            }
+        }
+    }
+}
+
+impl Dependencies {
+    /// Compute the key identifying resources built from this declaration.
+    fn cache_key(&self, base_dir: &Path) -> Result<String> {
+        let mut hasher = sha2::Sha256::new();
+
+        hasher.update(format!("pyoxy {}\n", PYOXY_VERSION));
+        hasher.update(format!("python {}\n", PYTHON_VERSION));
+
+        for requirement in &self.requirements {
+            hasher.update(format!("requirement {}\n", requirement));
+        }
+
+        if let Some(url) = &self.index_url {
+            hasher.update(format!("index {}\n", url));
+        }
+
+        // Adding, removing, or replacing wheels invalidates the cache. Wheels
+        // are hashed by content because a rebuilt wheel can keep its filename.
+        for dir in &self.wheel_dirs {
+            let dir = base_dir.join(dir);
+            let dir = dir.canonicalize().unwrap_or(dir);
+            hasher.update(format!("wheel_dir {}\n", dir.display()));
+
+            let mut paths = std::fs::read_dir(&dir)
+                .with_context(|| format!("reading wheel directory {}", dir.display()))?
+                .map(|entry| Ok(entry?.path()))
+                .collect::<Result<Vec<_>>>()?;
+            paths.retain(|path| path.extension().and_then(|x| x.to_str()) == Some("whl"));
+            paths.sort();
+
+            for path in paths {
+                let data =
+                    std::fs::read(&path).with_context(|| format!("reading {}", path.display()))?;
+
+                hasher.update(format!(
+                    "wheel {} {}\n",
+                    path.file_name().unwrap_or_default().to_string_lossy(),
+                    hex::encode(sha2::Sha256::digest(&data))
+                ));
+            }
+        }
+
+        Ok(hex::encode(hasher.finalize()))
+    }
+
+    /// Resolve requirements to wheels, including their dependencies.
+    fn resolve(&self, base_dir: &Path) -> Result<Vec<(String, WheelArchive)>> {
+        let mut resolver = Resolver::new(self, base_dir)?;
+        let env = marker_environment();
+
+        let mut queue = self
+            .requirements
+            .iter()
+            .map(|r| Requirement::parse(r))
+            .collect::<Result<VecDeque<_>>>()?;
+
+        let mut selected: BTreeMap<String, (Version, String)> = BTreeMap::new();
+        let mut wheels = vec![];
+
+        while let Some(requirement) = queue.pop_front() {
+            // Extras of packages aren't installed. So `extra` markers never match.
+            if !requirement.applies(&env, None)? {
+                continue;
+            }
+
+            if !requirement.extras.is_empty() {
+                return Err(anyhow!(
+                    "requirement extras are not supported: {}",
+                    requirement.raw
+                ));
+            }
+
+            if let Some((version, filename)) = selected.get(&requirement.name) {
+                if !requirement.matches(version) {
+                    return Err(anyhow!(
+                        "requirement {} conflicts with already selected {}",
+                        requirement.raw,
+                        filename
+                    ));
+                }
+
+                continue;
+            }
+
+            let candidate = resolver.select(&requirement)?;
+            let wheel = resolver
+                .fetch(&candidate)
+                .with_context(|| format!("obtaining {}", candidate.filename))?;
+
+            for dependency in wheel.metadata()?.find_all_headers("Requires-Dist") {
+                queue
+                    .push_back(Requirement::parse(dependency).with_context(|| {
+                        format!("parsing dependency of {}", candidate.filename)
+                    })?);
+            }
+
+            selected.insert(
+                requirement.name.clone(),
+                (candidate.version.clone(), candidate.filename.clone()),
+            );
+            wheels.push((candidate.filename, wheel));
+        }
+
+        Ok(wheels)
+    }
+
+    /// Build a packed resources file holding the resources of resolved wheels.
+    fn build_packed_resources(&self, base_dir: &Path, dest: &mut impl Write) -> Result<()> {
+        let cache_tag = format!("cpython-{}", PYTHON_VERSION.replace('.', ""));
+        let suffixes = PythonModuleSuffixes {
+            source: vec![".py".to_string()],
+            bytecode: vec![".pyc".to_string()],
+            debug_bytecode: vec![],
+            optimized_bytecode: vec![".opt-1.pyc".to_string(), ".opt-2.pyc".to_string()],
+            extension: vec![".so".to_string(), ".pyd".to_string()],
+        };
+
+        let mut collector = PythonResourceCollector::new(
+            vec![AbstractResourceLocation::InMemory],
+            vec![],
+            false,
+            false,
+        );
+
+        let context = PythonResourceAddCollectionContext {
+            include: true,
+            location: ConcreteResourceLocation::InMemory,
+            location_fallback: None,
+            store_source: true,
+            optimize_level_zero: false,
+            optimize_level_one: false,
+            optimize_level_two: false,
+        };
+
+        for (filename, wheel) in self.resolve(base_dir)? {
+            for resource in wheel.python_resources(&cache_tag, &suffixes, false, true)? {
+                match resource {
+                    PythonResource::ModuleSource(module) => {
+                        collector.add_python_module_source_with_context(&module, &context)?;
+                    }
+                    PythonResource::PackageResource(resource) => {
+                        collector.add_python_package_resource_with_context(&resource, &context)?;
+                    }
+                    PythonResource::PackageDistributionResource(resource) => {
+                        collector.add_python_package_distribution_resource_with_context(
+                            &resource, &context,
+                        )?;
+                    }
+                    PythonResource::ExtensionModule(module) => {
+                        return Err(anyhow!(
+                            "{} contains extension module {}; only pure Python wheels are supported",
+                            filename,
+                            module.name
+                        ));
+                    }
+                    // Bytecode is derived from source when imported.
+                    _ => {}
+                }
+            }
+        }
+
+        collector
+            .compile_resources(&mut NoBytecodeCompiler)?
+            .write_packed_resources(dest)
+    }
+
+    /// Obtain the path to a packed resources file holding resolved dependencies.
+    ///
+    /// The file is built if it isn't already cached.
+    pub fn packed_resources_path(&self, base_dir: &Path) -> Result<PathBuf> {
+        let cache_dir = resources_cache_dir()?;
+        let path = cache_dir.join(self.cache_key(base_dir)?);
+
+        if path.exists() {
+            return Ok(path);
+        }
+
+        std::fs::create_dir_all(&cache_dir)
+            .with_context(|| format!("creating directory {}", cache_dir.display()))?;
+
+        let mut data = vec![];
+        self.build_packed_resources(base_dir, &mut data)
+            .context("building packed resources for dependencies")?;
+
+        // Write to a temporary file and rename so concurrent runs never see
+        // a partially written file.
+        let temp_path = path.with_extension(format!("tmp{}", std::process::id()));
+        std::fs::write(&temp_path, &data)
+            .with_context(|| format!("writing {}", temp_path.display()))?;
+        std::fs::rename(&temp_path, &path)
+            .with_context(|| format!("renaming {} to {}", temp_path.display(), path.display()))?;
+
+        Ok(path)
+    }
+}
+
+/// Decode the HTML character references that can occur in index page attributes.
+fn unescape_attribute(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Whether a `Requires-Python` value admits a Python version.
+///
+/// Like pip, values that can't be parsed don't exclude anything.
+fn requires_python_matches(value: &str, version: &Version) -> bool {
+    value
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(VersionSpecifier::parse)
+        .collect::<Result<Vec<_>>>()
+        .map(|specifiers| specifiers.iter().all(|s| s.matches(version)))
+        .unwrap_or(true)
+}
+
+/// The full version of the Python interpreter we are built against.
+///
+/// e.g. `3.10.5`.
+fn python_full_version() -> Option<String> {
+    // e.g. `3.10.5 (main, ...)`.
+    unsafe { CStr::from_ptr(Py_GetVersion()) }
+        .to_string_lossy()
+        .split_whitespace()
+        .next()
+        .map(|x| x.to_string())
+}
+
+/// Describe the environment we run in, for evaluating environment markers.
+///
+/// `platform_release` and `platform_version` are left unknown.
+fn marker_environment() -> MarkerEnvironment {
+    let os = std::env::consts::OS;
+
+    let full_version = python_full_version();
+
+    let (sys_platform, platform_system) = match os {
+        "linux" => (Some("linux"), Some("Linux")),
+        "macos" => (Some("darwin"), Some("Darwin")),
+        "windows" => (Some("win32"), Some("Windows")),
+        _ => (None, None),
+    };
+
+    let platform_machine = match (os, std::env::consts::ARCH) {
+        ("macos", "aarch64") => "arm64",
+        ("windows", "x86_64") => "AMD64",
+        ("windows", "aarch64") => "ARM64",
+        ("linux", "x86") => "i686",
+        (_, arch) => arch,
+    };
+
+    MarkerEnvironment {
+        implementation_name: Some("cpython".to_string()),
+        implementation_version: full_version.clone(),
+        os_name: Some(if os == "windows" { "nt" } else { "posix" }.to_string()),
+        platform_machine: Some(platform_machine.to_string()),
+        platform_python_implementation: Some("CPython".to_string()),
+        platform_release: None,
+        platform_system: platform_system.map(|x| x.to_string()),
+        platform_version: None,
+        python_full_version: full_version,
+        python_version: Some(PYTHON_VERSION.to_string()),
+        sys_platform: sys_platform.map(|x| x.to_string()),
+    }
+}
+
+/// Resolve the directory holding cached packed resources files.
+fn resources_cache_dir() -> Result<PathBuf> {
+    let cache_dir = if let Ok(p) = std::env::var(CACHE_DIR_ENV) {
+        PathBuf::from(p)
+    } else if let Some(cache_dir) = dirs::cache_dir() {
+        cache_dir.join("pyoxy")
+    } else {
+        dirs::home_dir()
+            .ok_or_else(|| {
+                anyhow!("could not resolve home dir as part of resolving pyoxy cache directory")
+            })?
+            .join(".pyoxy")
+            .join("cache")
+    };
+
+    Ok(cache_dir.join("packed-resources"))
+}
@@ -78,8 +78,8 @@ impl<'a> Config<'a> {
     }
 }
 
-/// Runs an embedded Python interpreter in `python` mode.
-pub fn run_python<T>(exe: &Path, args: &[T]) -> Result<i32>
+/// Obtain the [Config] for running in `python` mode.
+pub fn python_config<T>(exe: &Path, args: &[T]) -> Config<'static>
 where
     T: Into<OsString> + AsRef<OsStr>,
 {
@@ -93,6 +93,16 @@ where
             .collect::<Vec<_>>(),
     );
 
+    config
+}
+
+/// Runs an embedded Python interpreter in `python` mode.
+pub fn run_python<T>(exe: &Path, args: &[T]) -> Result<i32>
+where
+    T: Into<OsString> + AsRef<OsStr>,
+{
+    let config = python_config(exe, args);
+
     let interp =
         MainPythonInterpreter::new(config.into()).context("initializing Python interpreter")?;
     Ok(interp.run())
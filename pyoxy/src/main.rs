@@ -3,6 +3,8 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 mod cli;
+mod config;
+mod dependencies;
 mod interpreter;
 mod yaml;
 
@@ -0,0 +1,98 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    anyhow::{anyhow, Result},
+    assert_cmd::Command,
+    libtest_mimic::{run_tests, Arguments, Outcome, Test},
+    predicates::prelude::*,
+};
+
+fn run() -> Result<()> {
+    // Keep packed resources built for dependencies out of the user's cache.
+    let cache_dir = tempfile::TempDir::new()?;
+
+    for config_path in glob::glob("tests/config/*")? {
+        let config_path = config_path?;
+
+        if matches!(
+            config_path.extension().and_then(|x| x.to_str()),
+            Some("stdout") | Some("stderr")
+        ) {
+            continue;
+        }
+
+        let stdout_path = config_path.with_extension("stdout");
+        let stderr_path = config_path.with_extension("stderr");
+
+        let assert = Command::cargo_bin("pyoxy")?
+            .env("PYOXY_CACHE_DIR", cache_dir.path())
+            .arg("run-config")
+            .arg(&config_path)
+            .assert();
+
+        if stdout_path.exists() {
+            let expected_stdout = std::fs::read_to_string(&stdout_path)?;
+
+            assert
+                .success()
+                .stdout(predicate::str::contains(&expected_stdout).normalize());
+        } else if stderr_path.exists() {
+            let expected_stderr = std::fs::read_to_string(&stderr_path)?;
+
+            assert
+                .failure()
+                .stderr(predicate::str::contains(&expected_stderr));
+        } else {
+            return Err(anyhow!(
+                "{} has no .stdout or .stderr file",
+                config_path.display()
+            ));
+        }
+    }
+
+    // `pyoxy config` prints the effective config of a document.
+    Command::cargo_bin("pyoxy")?
+        .env("PYOXY_CACHE_DIR", cache_dir.path())
+        .arg("config")
+        .arg("tests/config/toml-hello-world.toml")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("run_command:")
+                .and(predicate::str::contains("hello, world"))
+                .and(predicate::str::contains("toml-hello-world.toml")),
+        );
+
+    // Without a document, it prints the config of `pyoxy run-python`.
+    Command::cargo_bin("pyoxy")?
+        .arg("config")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("interpreter_config:")
+                .and(predicate::str::contains("oxidized_importer:")),
+        );
+
+    Ok(())
+}
+
+fn main() {
+    let args = Arguments::from_args();
+
+    // libtest_mimic doesn't properly handle `--list --ignored`.
+    let tests: Vec<Test<()>> = if args.ignored {
+        vec![]
+    } else {
+        vec![Test::test("main")]
+    };
+
+    run_tests(&args, tests, |_| match run() {
+        Ok(_) => Outcome::Passed,
+        Err(e) => Outcome::Failed {
+            msg: Some(format!("{:?}", e)),
+        },
+    })
+    .exit();
+}
@@ -0,0 +1,7 @@
+#!/bin/sh
+"exec" "`dirname $0`/pyoxy" run-config "$0" -- "$@"
+
+# /// pyoxy
+# [interpreter_config]
+# optimization_level = 'invalid'
+# ///
@@ -0,0 +1,11 @@
+#!/bin/sh
+"exec" "`dirname $0`/pyoxy" run-config "$0" -- "$@"
+
+# /// pyoxy
+# [interpreter_config]
+# run_command = 'import pyoxy_test_app; pyoxy_test_app.main()'
+#
+# [dependencies]
+# requirements = ["pyoxy-test-app"]
+# wheel_dirs = ["../wheels"]
+# ///
@@ -0,0 +1,8 @@
+#!/bin/sh
+"exec" "`dirname $0`/pyoxy" run-config "$0" -- "$@"
+
+# /// pyoxy
+# [interpreter_config]
+# run_command = 'print("hello, world")'
+#
+# ///
@@ -19,6 +19,52 @@ fn run() -> Result<()> {
         .success()
         .stdout(predicate::eq("hello, world\n").normalize());
 
+    // `-c` sets `sys.argv` like CPython does.
+    Command::cargo_bin("pyoxy")?
+        .arg("run-python")
+        .arg("--")
+        .arg("-c")
+        .arg("import sys; print(sys.argv[1:])")
+        .arg("foo")
+        .arg("--bar")
+        .assert()
+        .success()
+        .stdout(predicate::eq("['foo', '--bar']\n").normalize());
+
+    // `-m` runs a module as `__main__`.
+    Command::cargo_bin("pyoxy")?
+        .arg("run-python")
+        .arg("--")
+        .arg("-m")
+        .arg("json.tool")
+        .arg("--sort-keys")
+        .write_stdin("{\"b\": 1, \"a\": 2}\n")
+        .assert()
+        .success()
+        .stdout(predicate::eq("{\n    \"a\": 2,\n    \"b\": 1\n}\n").normalize());
+
+    // Uncaught exceptions exit with status 1 like CPython.
+    Command::cargo_bin("pyoxy")?
+        .arg("run-python")
+        .arg("--")
+        .arg("-c")
+        .arg("raise ValueError('oops')")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("ValueError: oops"));
+
+    // `-i` enters the REPL, which reads statements from stdin.
+    Command::cargo_bin("pyoxy")?
+        .arg("run-python")
+        .arg("--")
+        .arg("-i")
+        .arg("-c")
+        .arg("x = 'hello'")
+        .write_stdin("print(x + ', world')\nraise SystemExit(3)\n")
+        .assert()
+        .code(3)
+        .stdout(predicate::str::contains("hello, world"));
+
     // If the executable is named `python` it behaves like `python`.
     for bin_name in ["python", "python3", "python3.9", "pythonfoo"] {
         let td = tempfile::Builder::new().prefix("pyoxy-test-").tempdir()?;
@@ -37,6 +83,31 @@ fn run() -> Result<()> {
             .stdout(predicate::eq("hello, world\n").normalize());
     }
 
+    // argv[0] selects the personality. So symlinks work even though the
+    // current executable resolves to `pyoxy`.
+    #[cfg(unix)]
+    {
+        let td = tempfile::Builder::new().prefix("pyoxy-test-").tempdir()?;
+        let test_exe = td.path().join("python3");
+        std::os::unix::fs::symlink(cargo_bin("pyoxy"), &test_exe)
+            .context("creating python symlink")?;
+
+        Command::new(&test_exe)
+            .arg("-c")
+            .arg("print('hello, world')")
+            .assert()
+            .success()
+            .stdout(predicate::eq("hello, world\n").normalize());
+
+        Command::new(&test_exe)
+            .arg("-m")
+            .arg("json.tool")
+            .write_stdin("[1]\n")
+            .assert()
+            .success()
+            .stdout(predicate::eq("[\n    1\n]\n").normalize());
+    }
+
     Ok(())
 }
 
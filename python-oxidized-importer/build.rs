@@ -0,0 +1,9 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+fn main() {
+    // Expose the Py_3_* cfgs so code can adapt to newer Python versions.
+    println!("cargo:rustc-check-cfg=cfg(Py_3_11)");
+    pyo3_build_config::use_pyo3_cfgs();
+}
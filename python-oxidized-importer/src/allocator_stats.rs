@@ -0,0 +1,385 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Statistics about Python memory allocator usage.
+
+Statistics are recorded by installing a `PyMemAllocatorEx` in front of the
+allocator of each Python memory allocator domain. Allocation requests are
+forwarded to the allocator that was installed before. So statistics can be
+recorded regardless of which allocator backs a domain.
+
+The wrapped allocators don't tell us the size of memory being freed. So each
+allocation is prefixed by a small header holding its size.
+*/
+
+use {
+    core::ffi::c_void,
+    once_cell::sync::OnceCell,
+    pyo3::{ffi as pyffi, prelude::*, types::PyDict},
+    std::{
+        fmt::{Display, Formatter},
+        sync::atomic::{AtomicU64, Ordering},
+    },
+};
+
+/// Size of the header recording the size of an allocation.
+///
+/// This is a multiple of the alignment Python's allocators guarantee, so
+/// memory returned to Python retains the alignment of the wrapped allocator.
+const HEADER_SIZE: usize = 16;
+
+/// Memory allocator domains statistics are recorded for, in [AllocatorStats] order.
+const DOMAINS: [pyffi::PyMemAllocatorDomain; 3] = [
+    pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_RAW,
+    pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_MEM,
+    pyffi::PyMemAllocatorDomain::PYMEM_DOMAIN_OBJ,
+];
+
+/// Statistics for a single Python memory allocator domain.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct AllocatorDomainStats {
+    /// Total number of bytes allocated.
+    ///
+    /// Reallocations count as freeing the old allocation and allocating the new one.
+    pub allocated_bytes: u64,
+
+    /// Total number of bytes freed.
+    pub freed_bytes: u64,
+
+    /// Highest number of bytes allocated at any one time.
+    pub peak_bytes: u64,
+
+    /// Total number of allocations.
+    pub allocations: u64,
+
+    /// Number of allocations that haven't been freed.
+    pub live_allocations: u64,
+}
+
+impl AllocatorDomainStats {
+    /// Number of bytes currently allocated.
+    pub fn current_bytes(&self) -> u64 {
+        self.allocated_bytes.saturating_sub(self.freed_bytes)
+    }
+
+    /// Convert to a Python dict.
+    fn to_dict<'p>(&self, py: Python<'p>) -> PyResult<&'p PyDict> {
+        let dict = PyDict::new(py);
+        dict.set_item("allocated_bytes", self.allocated_bytes)?;
+        dict.set_item("freed_bytes", self.freed_bytes)?;
+        dict.set_item("current_bytes", self.current_bytes())?;
+        dict.set_item("peak_bytes", self.peak_bytes)?;
+        dict.set_item("allocations", self.allocations)?;
+        dict.set_item("live_allocations", self.live_allocations)?;
+
+        Ok(dict)
+    }
+}
+
+/// Statistics for all Python memory allocator domains.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct AllocatorStats {
+    /// The `raw` domain.
+    pub raw: AllocatorDomainStats,
+
+    /// The `mem` domain.
+    pub mem: AllocatorDomainStats,
+
+    /// The `obj` domain.
+    pub obj: AllocatorDomainStats,
+}
+
+impl Display for AllocatorStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Python memory allocator statistics:")?;
+        writeln!(
+            f,
+            "{:<6} {:>16} {:>16} {:>16} {:>16} {:>12} {:>12}",
+            "domain", "allocated", "freed", "current", "peak", "allocations", "live"
+        )?;
+
+        for (name, stats) in [("raw", &self.raw), ("mem", &self.mem), ("obj", &self.obj)] {
+            writeln!(
+                f,
+                "{:<6} {:>16} {:>16} {:>16} {:>16} {:>12} {:>12}",
+                name,
+                stats.allocated_bytes,
+                stats.freed_bytes,
+                stats.current_bytes(),
+                stats.peak_bytes,
+                stats.allocations,
+                stats.live_allocations
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Counters backing [AllocatorDomainStats].
+#[derive(Default)]
+struct DomainCounters {
+    allocated_bytes: AtomicU64,
+    freed_bytes: AtomicU64,
+    current_bytes: AtomicU64,
+    peak_bytes: AtomicU64,
+    allocations: AtomicU64,
+    live_allocations: AtomicU64,
+}
+
+impl DomainCounters {
+    fn record_allocation(&self, size: usize) {
+        let size = size as u64;
+
+        self.allocated_bytes.fetch_add(size, Ordering::Relaxed);
+        let current = self.current_bytes.fetch_add(size, Ordering::Relaxed) + size;
+        self.peak_bytes.fetch_max(current, Ordering::Relaxed);
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+        self.live_allocations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_free(&self, size: usize) {
+        let size = size as u64;
+
+        self.freed_bytes.fetch_add(size, Ordering::Relaxed);
+        self.current_bytes.fetch_sub(size, Ordering::Relaxed);
+        self.live_allocations.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    fn stats(&self) -> AllocatorDomainStats {
+        AllocatorDomainStats {
+            allocated_bytes: self.allocated_bytes.load(Ordering::Relaxed),
+            freed_bytes: self.freed_bytes.load(Ordering::Relaxed),
+            peak_bytes: self.peak_bytes.load(Ordering::Relaxed),
+            allocations: self.allocations.load(Ordering::Relaxed),
+            live_allocations: self.live_allocations.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A memory allocator domain whose allocations are being counted.
+///
+/// A pointer to an instance is the context of the installed `PyMemAllocatorEx`.
+struct TrackedDomain {
+    /// The allocator requests are forwarded to.
+    inner: pyffi::PyMemAllocatorEx,
+
+    counters: DomainCounters,
+}
+
+// The raw domain allocator is called without the GIL held. Counters are atomic
+// and the wrapped allocator is never modified after installation.
+unsafe impl Send for TrackedDomain {}
+unsafe impl Sync for TrackedDomain {}
+
+impl TrackedDomain {
+    /// Record the allocation of memory returned by the wrapped allocator.
+    ///
+    /// Returns the pointer to hand out to Python.
+    unsafe fn allocated(&self, ptr: *mut c_void, size: usize) -> *mut c_void {
+        if ptr.is_null() {
+            return ptr;
+        }
+
+        *(ptr as *mut usize) = size;
+        self.counters.record_allocation(size);
+
+        (ptr as *mut u8).add(HEADER_SIZE) as *mut _
+    }
+}
+
+static TRACKED_DOMAINS: OnceCell<[TrackedDomain; 3]> = OnceCell::new();
+
+unsafe fn tracked_domain<'a>(ctx: *mut c_void) -> &'a TrackedDomain {
+    &*(ctx as *const TrackedDomain)
+}
+
+extern "C" fn stats_malloc(ctx: *mut c_void, size: usize) -> *mut c_void {
+    let domain = unsafe { tracked_domain(ctx) };
+
+    let total = match size.checked_add(HEADER_SIZE) {
+        Some(total) => total,
+        None => return std::ptr::null_mut(),
+    };
+
+    let malloc = domain.inner.malloc.expect("wrapped allocator has malloc");
+
+    unsafe { domain.allocated(malloc(domain.inner.ctx, total), size) }
+}
+
+extern "C" fn stats_calloc(ctx: *mut c_void, nelem: usize, elsize: usize) -> *mut c_void {
+    let domain = unsafe { tracked_domain(ctx) };
+
+    let size = match nelem.checked_mul(elsize) {
+        Some(size) => size,
+        None => return std::ptr::null_mut(),
+    };
+    let total = match size.checked_add(HEADER_SIZE) {
+        Some(total) => total,
+        None => return std::ptr::null_mut(),
+    };
+
+    let calloc = domain.inner.calloc.expect("wrapped allocator has calloc");
+
+    unsafe { domain.allocated(calloc(domain.inner.ctx, 1, total), size) }
+}
+
+extern "C" fn stats_realloc(ctx: *mut c_void, ptr: *mut c_void, new_size: usize) -> *mut c_void {
+    if ptr.is_null() {
+        return stats_malloc(ctx, new_size);
+    }
+
+    let domain = unsafe { tracked_domain(ctx) };
+
+    let total = match new_size.checked_add(HEADER_SIZE) {
+        Some(total) => total,
+        None => return std::ptr::null_mut(),
+    };
+
+    let realloc = domain.inner.realloc.expect("wrapped allocator has realloc");
+
+    unsafe {
+        let base = (ptr as *mut u8).sub(HEADER_SIZE) as *mut c_void;
+        let old_size = *(base as *const usize);
+
+        let res = realloc(domain.inner.ctx, base, total);
+
+        // The original allocation is left intact on failure.
+        if res.is_null() {
+            return res;
+        }
+
+        domain.counters.record_free(old_size);
+        domain.allocated(res, new_size)
+    }
+}
+
+extern "C" fn stats_free(ctx: *mut c_void, ptr: *mut c_void) {
+    if ptr.is_null() {
+        return;
+    }
+
+    let domain = unsafe { tracked_domain(ctx) };
+
+    let free = domain.inner.free.expect("wrapped allocator has free");
+
+    unsafe {
+        let base = (ptr as *mut u8).sub(HEADER_SIZE) as *mut c_void;
+        domain.counters.record_free(*(base as *const usize));
+
+        free(domain.inner.ctx, base);
+    }
+}
+
+/// Record statistics for all Python memory allocator domains.
+///
+/// The allocators currently installed for each domain are wrapped. So this
+/// should be called after custom allocators are installed and before
+/// `PyMem_SetupDebugHooks()` is called. It must be called before
+/// `Py_Initialize*()`, as memory allocated before can't be freed through the
+/// wrapper.
+///
+/// Statistics can only be recorded for one set of allocators per process.
+/// Calling this again is a no-op if statistics are still being recorded for
+/// every domain. If any domain's allocator was replaced since, an error is
+/// returned, as statistics would no longer reflect the allocators in use.
+pub fn install_allocator_stats() -> Result<(), &'static str> {
+    if let Some(tracked) = TRACKED_DOMAINS.get() {
+        return if DOMAINS
+            .into_iter()
+            .zip(tracked.iter())
+            .all(|(domain, tracked)| is_installed(domain, tracked))
+        {
+            Ok(())
+        } else {
+            Err("allocator statistics were already installed for other allocators; they can only be recorded once per process")
+        };
+    }
+
+    let tracked = TRACKED_DOMAINS.get_or_init(|| {
+        DOMAINS.map(|domain| {
+            let mut inner = pyffi::PyMemAllocatorEx {
+                ctx: std::ptr::null_mut(),
+                malloc: None,
+                calloc: None,
+                realloc: None,
+                free: None,
+            };
+
+            unsafe { pyffi::PyMem_GetAllocator(domain, &mut inner) };
+
+            TrackedDomain {
+                inner,
+                counters: DomainCounters::default(),
+            }
+        })
+    });
+
+    for (domain, tracked) in DOMAINS.into_iter().zip(tracked.iter()) {
+        let mut allocator = pyffi::PyMemAllocatorEx {
+            ctx: tracked as *const _ as *mut c_void,
+            malloc: Some(stats_malloc),
+            calloc: Some(stats_calloc),
+            realloc: Some(stats_realloc),
+            free: Some(stats_free),
+        };
+
+        unsafe { pyffi::PyMem_SetAllocator(domain, &mut allocator) };
+    }
+
+    Ok(())
+}
+
+/// Whether the statistics allocator for a tracked domain is installed.
+fn is_installed(domain: pyffi::PyMemAllocatorDomain, tracked: &TrackedDomain) -> bool {
+    let mut current = pyffi::PyMemAllocatorEx {
+        ctx: std::ptr::null_mut(),
+        malloc: None,
+        calloc: None,
+        realloc: None,
+        free: None,
+    };
+
+    unsafe { pyffi::PyMem_GetAllocator(domain, &mut current) };
+
+    current.ctx == tracked as *const _ as *mut c_void
+}
+
+/// Obtain memory allocator statistics.
+///
+/// Returns `None` if statistics aren't being recorded. See
+/// [install_allocator_stats()].
+pub fn allocator_stats() -> Option<AllocatorStats> {
+    TRACKED_DOMAINS.get().map(|[raw, mem, obj]| AllocatorStats {
+        raw: raw.counters.stats(),
+        mem: mem.counters.stats(),
+        obj: obj.counters.stats(),
+    })
+}
+
+/// Obtain memory allocator statistics from Python.
+///
+/// Returns a dict mapping domain names to dicts of statistics or `None` if
+/// statistics aren't being recorded.
+#[pyfunction]
+#[pyo3(name = "allocator_stats")]
+fn allocator_stats_py(py: Python) -> PyResult<PyObject> {
+    let stats = match allocator_stats() {
+        Some(stats) => stats,
+        None => return Ok(py.None()),
+    };
+
+    let dict = PyDict::new(py);
+    dict.set_item("raw", stats.raw.to_dict(py)?)?;
+    dict.set_item("mem", stats.mem.to_dict(py)?)?;
+    dict.set_item("obj", stats.obj.to_dict(py)?)?;
+
+    Ok(dict.into())
+}
+
+pub(crate) fn init_module(m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(allocator_stats_py, m)?)?;
+
+    Ok(())
+}
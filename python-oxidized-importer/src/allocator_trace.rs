@@ -0,0 +1,290 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Record Python memory allocation events and their call sites.
+
+Events are recorded by a tracing memory allocator backend in a bounded ring
+buffer. When the buffer is full, the oldest events are discarded.
+
+Python stack frames are captured like `tracemalloc` does: only when the
+allocating thread holds the GIL. So allocations in the `raw` domain made
+without the GIL are recorded without frames. Frames are read from frame and
+code objects directly, as calling into Python would allocate and re-enter the
+allocator hooks. Python 3.11+ only creates frame objects on demand, which
+allocates. So frames aren't recorded on these versions.
+
+Recording is called from memory allocator hooks and never panics. Events
+are dropped when the buffer is in use by another thread or while the
+current thread is being torn down.
+*/
+
+#[cfg(not(Py_3_11))]
+use pyo3::ffi as pyffi;
+use {
+    once_cell::sync::OnceCell,
+    pyo3::{prelude::*, types::PyTuple},
+    std::{
+        cell::Cell,
+        collections::VecDeque,
+        sync::{Mutex, PoisonError, TryLockError},
+    },
+};
+
+/// Maximum number of Python stack frames recorded per allocation.
+pub const MAX_ALLOCATION_FRAMES: usize = 16;
+
+/// The kind of a recorded allocation event.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AllocationEventKind {
+    /// Memory was allocated.
+    Allocate,
+    /// Memory was reallocated. The address is that of the new memory.
+    Reallocate,
+    /// Memory was freed.
+    Free,
+}
+
+impl AllocationEventKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Allocate => "allocate",
+            Self::Reallocate => "reallocate",
+            Self::Free => "free",
+        }
+    }
+}
+
+/// A recorded memory allocation event.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AllocationEvent {
+    /// What happened.
+    pub kind: AllocationEventKind,
+
+    /// Name of the memory allocator domain. `raw`, `mem`, or `obj`.
+    pub domain: &'static str,
+
+    /// Address of the memory.
+    pub address: usize,
+
+    /// Size of the memory in bytes. `None` for frees.
+    pub size: Option<usize>,
+
+    /// `(filename, line number)` of Python stack frames, most recent first.
+    pub frames: Vec<(String, u32)>,
+}
+
+/// A bounded buffer of allocation events.
+struct AllocationTrace {
+    capacity: usize,
+    events: VecDeque<AllocationEvent>,
+}
+
+static ALLOCATION_TRACE: OnceCell<Mutex<AllocationTrace>> = OnceCell::new();
+
+thread_local! {
+    /// Whether this thread is recording an event.
+    ///
+    /// Capturing frames can allocate. Those allocations aren't recorded.
+    static RECORDING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Obtain the filename of a code object.
+///
+/// The string data is read directly so no Python memory is allocated.
+#[cfg(not(Py_3_11))]
+unsafe fn code_filename(code: *mut pyffi::PyCodeObject) -> String {
+    let filename = (*code).co_filename;
+
+    if filename.is_null()
+        || pyffi::PyUnicode_Check(filename) == 0
+        || pyffi::PyUnicode_IS_READY(filename) == 0
+    {
+        return "<unknown>".to_string();
+    }
+
+    let length = pyffi::PyUnicode_GET_LENGTH(filename) as usize;
+    let data = pyffi::PyUnicode_DATA(filename);
+
+    match pyffi::PyUnicode_KIND(filename) {
+        pyffi::PyUnicode_1BYTE_KIND => std::slice::from_raw_parts(data as *const u8, length)
+            .iter()
+            .map(|c| char::from(*c))
+            .collect(),
+        pyffi::PyUnicode_2BYTE_KIND => std::slice::from_raw_parts(data as *const u16, length)
+            .iter()
+            .map(|c| char::from_u32(*c as u32).unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect(),
+        pyffi::PyUnicode_4BYTE_KIND => std::slice::from_raw_parts(data as *const u32, length)
+            .iter()
+            .map(|c| char::from_u32(*c).unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect(),
+        _ => "<unknown>".to_string(),
+    }
+}
+
+/// Enable recording of allocation events.
+///
+/// `capacity` is the maximum number of events retained. Has no effect if
+/// recording is already enabled.
+pub fn enable_allocation_trace(capacity: usize) {
+    let capacity = capacity.max(1);
+
+    ALLOCATION_TRACE.get_or_init(|| {
+        Mutex::new(AllocationTrace {
+            capacity,
+            events: VecDeque::with_capacity(capacity),
+        })
+    });
+}
+
+/// Capture the Python stack of the current thread.
+///
+/// This doesn't allocate Python memory, call into Python or touch reference
+/// counts, so it is safe to call from allocator hooks.
+///
+/// # Safety
+///
+/// Must be called outside of any allocator state that Python could observe.
+#[cfg(not(Py_3_11))]
+unsafe fn capture_frames() -> Vec<(String, u32)> {
+    if pyffi::Py_IsInitialized() == 0 || pyffi::PyGILState_Check() == 0 {
+        return vec![];
+    }
+
+    let mut frames = vec![];
+
+    // The frames of the current thread and their code objects are kept alive
+    // by the executing code, so borrowed references suffice.
+    let mut frame = pyffi::PyEval_GetFrame();
+
+    while !frame.is_null() && frames.len() < MAX_ALLOCATION_FRAMES {
+        frames.push((
+            code_filename((*frame).f_code),
+            pyffi::PyFrame_GetLineNumber(frame) as u32,
+        ));
+
+        frame = (*frame).f_back;
+    }
+
+    frames
+}
+
+/// Capture the Python stack of the current thread.
+///
+/// Frame objects are created on demand by Python 3.11+. Requesting them from
+/// an allocator hook allocates and re-enters the hook, possibly while the
+/// interpreter is creating the very same frame. So no frames are captured.
+#[cfg(Py_3_11)]
+unsafe fn capture_frames() -> Vec<(String, u32)> {
+    vec![]
+}
+
+/// Record an allocation event, if recording is enabled.
+///
+/// This is meant to be called by memory allocators after the allocation
+/// request was served. Frames are captured for allocations but not frees.
+///
+/// This never panics. The event is dropped if another thread is recording
+/// or if thread local storage of the current thread was destroyed.
+pub fn record_allocation_event(
+    kind: AllocationEventKind,
+    domain: &'static str,
+    address: usize,
+    size: Option<usize>,
+) {
+    let trace = match ALLOCATION_TRACE.get() {
+        Some(trace) => trace,
+        None => return,
+    };
+
+    // A destroyed thread local is treated as recording in progress.
+    if RECORDING
+        .try_with(|recording| recording.replace(true))
+        .unwrap_or(true)
+    {
+        return;
+    }
+
+    let frames = if kind == AllocationEventKind::Free {
+        vec![]
+    } else {
+        unsafe { capture_frames() }
+    };
+
+    let guard = match trace.try_lock() {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(err)) => Some(err.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    };
+
+    if let Some(mut trace) = guard {
+        if trace.events.len() >= trace.capacity {
+            trace.events.pop_front();
+        }
+
+        trace.events.push_back(AllocationEvent {
+            kind,
+            domain,
+            address,
+            size,
+            frames,
+        });
+    }
+
+    let _ = RECORDING.try_with(|recording| recording.set(false));
+}
+
+/// Obtain recorded allocation events, oldest first.
+///
+/// Returns `None` if recording isn't enabled.
+pub fn allocation_trace() -> Option<Vec<AllocationEvent>> {
+    let trace = ALLOCATION_TRACE.get()?;
+
+    let events = trace
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .events
+        .iter()
+        .cloned()
+        .collect();
+
+    Some(events)
+}
+
+/// Obtain recorded allocation events from Python.
+///
+/// Returns a list of `(kind, domain, address, size, frames)` tuples or `None`
+/// if recording isn't enabled.
+#[pyfunction]
+#[pyo3(name = "allocation_trace")]
+fn allocation_trace_py(py: Python) -> PyResult<PyObject> {
+    let events = match allocation_trace() {
+        Some(events) => events,
+        None => return Ok(py.None()),
+    };
+
+    let events = events
+        .into_iter()
+        .map(|event| {
+            let frames = PyTuple::new(py, event.frames);
+
+            (
+                event.kind.as_str(),
+                event.domain,
+                event.address,
+                event.size,
+                frames,
+            )
+                .into_py(py)
+        })
+        .collect::<Vec<PyObject>>();
+
+    Ok(events.into_py(py))
+}
+
+pub(crate) fn init_module(m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(allocation_trace_py, m)?)?;
+
+    Ok(())
+}
@@ -10,11 +10,9 @@ for importing Python modules from memory.
 */
 
 #[cfg(windows)]
-use {
-    crate::memory_dll::{free_library_memory, get_proc_address_memory, load_library_memory},
-    pyo3::exceptions::PySystemError,
-    std::ffi::{c_void, CString},
-};
+use crate::memory_dll::{free_library_memory, get_proc_address_memory, load_library_memory};
+#[cfg(target_os = "linux")]
+use crate::memory_so::{free_library_memory, get_proc_address_memory, load_library_memory};
 use {
     crate::{
         conversion::pyobject_to_pathbuf,
@@ -24,6 +22,7 @@ use {
         python_resources::{
             pyobject_to_resource, ModuleFlavor, OxidizedResource, PythonResourcesState,
         },
+        resource_query::{export_resources, resource_field_sizes, ResourceFilter},
         resource_reader::OxidizedResourceReader,
         OXIDIZED_IMPORTER_NAME_STR,
     },
@@ -34,11 +33,16 @@ use {
         types::{PyBytes, PyDict, PyList, PyString, PyTuple},
         AsPyPointer, FromPyPointer, PyNativeType, PyTraverseError, PyVisit,
     },
-    python_packaging::resource::BytecodeOptimizationLevel,
+    python_packaging::{import_trace::ImportTraceEntry, resource::BytecodeOptimizationLevel},
     std::sync::Arc,
 };
+#[cfg(any(windows, target_os = "linux"))]
+use {
+    pyo3::exceptions::PySystemError,
+    std::ffi::{c_void, CString},
+};
 
-#[cfg(windows)]
+#[cfg(any(windows, target_os = "linux"))]
 #[allow(non_camel_case_types)]
 type py_init_fn = extern "C" fn() -> *mut pyffi::PyObject;
 
@@ -57,7 +61,7 @@ type py_init_fn = extern "C" fn() -> *mut pyffi::PyObject;
 /// `_PyImport_LoadDynamicModuleWithSpec()` is more interesting. It takes a
 /// `FILE*` for the extension location, so we can't call it. So we need to
 /// reimplement it. Documentation of that is inline.
-#[cfg(windows)]
+#[cfg(any(windows, target_os = "linux"))]
 fn extension_module_shared_library_create_module(
     resources_state: &PythonResourcesState<u8>,
     py: Python,
@@ -67,34 +71,55 @@ fn extension_module_shared_library_create_module(
     name: &str,
     library_data: &[u8],
 ) -> PyResult<Py<PyAny>> {
-    let origin = PyString::new(py, "memory");
-
-    let existing_module =
-        unsafe { pyffi::_PyImport_FindExtensionObject(name_py.as_ptr(), origin.as_ptr()) };
-
-    // We found an existing module object. Return it.
-    if !existing_module.is_null() {
-        return Ok(unsafe { PyObject::from_owned_ptr(py, existing_module) });
-    }
+    // `_PyImport_FindExtensionObject()` is a private API that no longer exists
+    // in Python 3.11. The Linux loader doesn't rely on it and always performs
+    // a fresh load. The import machinery has already consulted `sys.modules`
+    // before calling `create_module()`.
+    #[cfg(windows)]
+    {
+        let origin = PyString::new(py, "memory");
+
+        let existing_module =
+            unsafe { pyffi::_PyImport_FindExtensionObject(name_py.as_ptr(), origin.as_ptr()) };
+
+        // We found an existing module object. Return it.
+        if !existing_module.is_null() {
+            return Ok(unsafe { PyObject::from_owned_ptr(py, existing_module) });
+        }
 
-    // An error occurred calling _PyImport_FindExtensionObjectEx(). Raise it.
-    if !unsafe { pyffi::PyErr_Occurred() }.is_null() {
-        return Err(PyErr::fetch(py));
+        // An error occurred calling _PyImport_FindExtensionObjectEx(). Raise it.
+        if !unsafe { pyffi::PyErr_Occurred() }.is_null() {
+            return Err(PyErr::fetch(py));
+        }
     }
 
     // New module load request. Proceed to _PyImport_LoadDynamicModuleWithSpec()
     // functionality.
 
-    let module = unsafe { load_library_memory(resources_state, library_data) };
+    #[cfg(windows)]
+    let module = {
+        let module = unsafe { load_library_memory(resources_state, library_data) };
 
-    if module.is_null() {
-        return Err(PyImportError::new_err((
-            "unable to load extension module library from memory",
-            name.to_owned(),
-        )));
-    }
+        if module.is_null() {
+            return Err(PyImportError::new_err((
+                "unable to load extension module library from memory",
+                name.to_owned(),
+            )));
+        }
+
+        module
+    };
 
-    // Any error past this point should call `MemoryFreeLibrary()` to unload the
+    #[cfg(target_os = "linux")]
+    let module =
+        unsafe { load_library_memory(resources_state, name, library_data) }.map_err(|e| {
+            PyImportError::new_err((
+                format!("unable to load extension module library from memory: {}", e),
+                name.to_owned(),
+            ))
+        })?;
+
+    // Any error past this point should call `free_library_memory()` to unload the
     // library.
 
     load_dynamic_library(py, sys_modules, spec, name_py, name, module).map_err(|e| {
@@ -105,7 +130,7 @@ fn extension_module_shared_library_create_module(
     })
 }
 
-#[cfg(unix)]
+#[cfg(not(any(windows, target_os = "linux")))]
 fn extension_module_shared_library_create_module(
     _resources_state: &PythonResourcesState<u8>,
     _py: Python,
@@ -115,11 +140,11 @@ fn extension_module_shared_library_create_module(
     _name: &str,
     _library_data: &[u8],
 ) -> PyResult<Py<PyAny>> {
-    panic!("should only be called on Windows");
+    panic!("should only be called on Windows and Linux");
 }
 
 /// Reimplementation of `_PyImport_LoadDynamicModuleWithSpec()`.
-#[cfg(windows)]
+#[cfg(any(windows, target_os = "linux"))]
 fn load_dynamic_library(
     py: Python,
     sys_modules: &PyAny,
@@ -523,16 +548,28 @@ impl OxidizedFinder {
     ) -> PyResult<&'p PyAny> {
         let py = slf.py();
         let finder = slf.borrow();
+        let resources_state = finder.state.get_resources_state();
 
-        let module = match finder
-            .state
-            .get_resources_state()
+        let module = match resources_state
             .resolve_importable_module(&fullname, finder.state.optimize_level)
+            .map_err(PyImportError::new_err)?
         {
             Some(module) => module,
-            None => return Ok(py.None().into_ref(py)),
+            None => {
+                resources_state.record_import_trace(|| ImportTraceEntry::Missing {
+                    name: fullname.clone(),
+                });
+
+                return Ok(py.None().into_ref(py));
+            }
         };
 
+        resources_state.record_import_trace(|| ImportTraceEntry::Module {
+            name: fullname.clone(),
+            layer: resources_state.resource_layer(&fullname),
+            flavor: module.flavor.as_str().to_string(),
+        });
+
         match module.flavor {
             ModuleFlavor::Extension | ModuleFlavor::SourceBytecode => module.resolve_module_spec(
                 py,
@@ -591,6 +628,7 @@ impl OxidizedFinder {
         let module = match state
             .get_resources_state()
             .resolve_importable_module(&key, state.optimize_level)
+            .map_err(PyImportError::new_err)?
         {
             Some(module) => module,
             None => return Ok(py.None()),
@@ -606,7 +644,10 @@ impl OxidizedFinder {
             // If we ever implement our own lazy module importer, we could
             // potentially work around this and move all extension module
             // initialization into `exec_module()`.
-            if let Some(library_data) = &module.in_memory_extension_module_shared_library() {
+            if let Some(library_data) = module
+                .in_memory_extension_module_shared_library()
+                .map_err(|e| PyImportError::new_err((e, key.clone())))?
+            {
                 let sys_modules = state.sys_module.getattr(py, "modules")?;
 
                 extension_module_shared_library_create_module(
@@ -616,7 +657,7 @@ impl OxidizedFinder {
                     spec,
                     name,
                     &key,
-                    library_data,
+                    &library_data,
                 )
             } else {
                 // Call `imp.create_dynamic()` for dynamic extension modules.
@@ -642,6 +683,7 @@ impl OxidizedFinder {
         let mut entry = match state
             .get_resources_state()
             .resolve_importable_module(&key, state.optimize_level)
+            .map_err(PyImportError::new_err)?
         {
             Some(entry) => entry,
             None => {
@@ -735,6 +777,7 @@ impl OxidizedFinder {
         let mut module = match state
             .get_resources_state()
             .resolve_importable_module(&key, state.optimize_level)
+            .map_err(PyImportError::new_err)?
         {
             Some(module) => module,
             None => return Ok(py.None()),
@@ -765,6 +808,7 @@ impl OxidizedFinder {
         let module = match state
             .get_resources_state()
             .resolve_importable_module(&key, state.optimize_level)
+            .map_err(PyImportError::new_err)?
         {
             Some(module) => module,
             None => return Ok(py.None()),
@@ -802,6 +846,7 @@ impl OxidizedFinder {
         let module = state
             .get_resources_state()
             .resolve_importable_module(&key, state.optimize_level)
+            .map_err(PyImportError::new_err)?
             .ok_or_else(|| make_error("unknown module"))?;
 
         module
@@ -824,6 +869,7 @@ impl OxidizedFinder {
         let entry = match state
             .get_resources_state()
             .resolve_importable_module(&key, state.optimize_level)
+            .map_err(PyImportError::new_err)?
         {
             Some(entry) => entry,
             None => return Ok(slf.py().None()),
@@ -902,10 +948,14 @@ impl OxidizedFinder {
 
     // Additional methods provided for convenience.
 
-    /// OxidizedFinder.__new__(relative_path_origin=None))
+    /// OxidizedFinder.__new__(relative_path_origin=None, bytecode_cache_dir=None))
     #[new]
-    #[args(relative_path_origin = "None")]
-    fn new(py: Python, relative_path_origin: Option<&PyAny>) -> PyResult<Self> {
+    #[args(relative_path_origin = "None", bytecode_cache_dir = "None")]
+    fn new(
+        py: Python,
+        relative_path_origin: Option<&PyAny>,
+        bytecode_cache_dir: Option<&PyAny>,
+    ) -> PyResult<Self> {
         // We need to obtain an ImporterState instance. This requires handles on a
         // few items...
 
@@ -921,6 +971,10 @@ impl OxidizedFinder {
             resources_state.set_origin(pyobject_to_pathbuf(py, py_origin)?);
         }
 
+        if let Some(py_cache_dir) = bytecode_cache_dir {
+            resources_state.set_bytecode_cache_dir(Some(pyobject_to_pathbuf(py, py_cache_dir)?));
+        }
+
         Ok(OxidizedFinder {
             state: Arc::new(ImporterState::new(
                 py,
@@ -931,6 +985,15 @@ impl OxidizedFinder {
         })
     }
 
+    #[getter]
+    fn bytecode_cache_dir<'p>(&self, py: Python<'p>) -> &'p PyAny {
+        self.state
+            .get_resources_state()
+            .bytecode_cache_dir()
+            .into_py(py)
+            .into_ref(py)
+    }
+
     #[getter]
     fn multiprocessing_set_start_method(&self) -> PyResult<Option<String>> {
         if let Some(v) = &self.state.multiprocessing_set_start_method {
@@ -999,6 +1062,20 @@ impl OxidizedFinder {
         Ok(())
     }
 
+    fn reload_file_memory_mapped(&self, py: Python, path: &PyAny) -> PyResult<()> {
+        let path = pyobject_to_pathbuf(py, path)?;
+
+        self.state
+            .get_resources_state_mut()
+            .reload_path_memory_mapped(path)
+            .map_err(PyValueError::new_err)?;
+
+        // Other finders may have cached lookups against the old resources.
+        py.import("importlib")?.call_method0("invalidate_caches")?;
+
+        Ok(())
+    }
+
     fn index_interpreter_builtins(&self) -> PyResult<()> {
         self.state
             .get_resources_state_mut()
@@ -1057,6 +1134,53 @@ impl OxidizedFinder {
         Ok(())
     }
 
+    fn enable_import_trace(&self) {
+        self.state
+            .get_resources_state_mut()
+            .set_import_trace_enabled(true);
+    }
+
+    fn import_trace<'p>(&self, py: Python<'p>) -> PyResult<Option<&'p PyList>> {
+        let trace = match self.state.get_resources_state().import_trace() {
+            Some(trace) => trace,
+            None => return Ok(None),
+        };
+
+        let entries = trace
+            .entries()
+            .map(|entry| match entry {
+                ImportTraceEntry::Module {
+                    name,
+                    layer,
+                    flavor,
+                } => ("module", name, *layer, flavor).into_py(py),
+                ImportTraceEntry::PackageResource { package, name } => {
+                    ("package-resource", package, name).into_py(py)
+                }
+                ImportTraceEntry::PackageDistributionResource { package, name } => {
+                    ("distribution-resource", package, name).into_py(py)
+                }
+                ImportTraceEntry::Missing { name } => ("missing", name).into_py(py),
+            })
+            .collect::<Vec<PyObject>>();
+
+        Ok(Some(PyList::new(py, entries)))
+    }
+
+    fn write_import_trace(&self, py: Python, path: &PyAny) -> PyResult<()> {
+        let path = pyobject_to_pathbuf(py, path)?;
+
+        let trace = self
+            .state
+            .get_resources_state()
+            .import_trace()
+            .ok_or_else(|| PyValueError::new_err("import tracing is not enabled"))?;
+
+        trace
+            .write_to_path(&path)
+            .map_err(|e| PyValueError::new_err(format!("error writing import trace: {}", e)))
+    }
+
     #[args(ignore_builtin = true, ignore_frozen = true)]
     fn serialize_indexed_resources<'p>(
         &self,
@@ -1072,6 +1196,72 @@ impl OxidizedFinder {
 
         Ok(PyBytes::new(py, &data))
     }
+
+    #[args(name = "None", flags = "None")]
+    fn find_resources<'p>(
+        &self,
+        py: Python<'p>,
+        name: Option<&str>,
+        flags: Option<Vec<String>>,
+    ) -> PyResult<&'p PyList> {
+        let filter = ResourceFilter::new(name, flags)?;
+
+        self.state
+            .get_resources_state()
+            .filtered_resources_as_py_list(py, |r| filter.matches(r))
+    }
+
+    #[args(name = "None", flags = "None")]
+    fn resource_field_sizes<'p>(
+        &self,
+        py: Python<'p>,
+        name: Option<&str>,
+        flags: Option<Vec<String>>,
+    ) -> PyResult<&'p PyDict> {
+        let filter = ResourceFilter::new(name, flags)?;
+        let result = PyDict::new(py);
+
+        for resource in self
+            .state
+            .get_resources_state()
+            .visible_resources()
+            .map_err(PyValueError::new_err)?
+            .into_iter()
+            .filter(|r| filter.matches(r))
+        {
+            let sizes = PyDict::new(py);
+
+            for (field, size) in resource_field_sizes(resource) {
+                sizes.set_item(field, size)?;
+            }
+
+            result.set_item(resource.name.as_ref(), sizes)?;
+        }
+
+        Ok(result)
+    }
+
+    #[args(name = "None", flags = "None")]
+    fn export_resources<'p>(
+        &self,
+        py: Python<'p>,
+        name: Option<&str>,
+        flags: Option<Vec<String>>,
+    ) -> PyResult<&'p PyBytes> {
+        let filter = ResourceFilter::new(name, flags)?;
+
+        let cache_tag = py
+            .import("sys")?
+            .getattr("implementation")?
+            .getattr("cache_tag")?
+            .extract::<Option<String>>()?
+            .ok_or_else(|| PyValueError::new_err("sys.implementation.cache_tag is not set"))?;
+
+        let data = export_resources(self.state.get_resources_state(), &filter, &cache_tag)
+            .map_err(|e| PyValueError::new_err(format!("error exporting resources: {:#}", e)))?;
+
+        Ok(PyBytes::new(py, &data))
+    }
 }
 
 impl OxidizedFinder {
@@ -1198,80 +1388,6 @@ impl OxidizedFinder {
     }
 }
 
-/// Path-like object facilitating Python resource access.
-///
-/// This implements importlib.abc.Traversable.
-#[pyclass(module = "oxidized_importer")]
-pub(crate) struct PyOxidizerTraversable {
-    state: Arc<ImporterState>,
-    path: String,
-}
-
-#[pymethods]
-impl PyOxidizerTraversable {
-    /// Yield Traversable objects in self.
-    fn iterdir(&self) -> PyResult<&PyAny> {
-        unimplemented!()
-    }
-
-    /// Read contents of self as bytes.
-    fn read_bytes(&self) -> PyResult<&PyAny> {
-        unimplemented!()
-    }
-
-    /// Read contents of self as text.
-    fn read_text(&self) -> PyResult<&PyAny> {
-        unimplemented!()
-    }
-
-    /// Return True if self is a dir.
-    fn is_dir(&self) -> PyResult<bool> {
-        // We are a directory if the current path is a known package.
-        // TODO We may need to expand this definition in the future to cover
-        // virtual subdirectories in addressable resources. But this will require
-        // changes to the resources data format to capture said annotations.
-        if let Some(entry) = self
-            .state
-            .get_resources_state()
-            .resolve_importable_module(&self.path, self.state.optimize_level)
-        {
-            if entry.is_package {
-                return Ok(true);
-            }
-        }
-
-        Ok(false)
-    }
-
-    /// Return True if self is a file.
-    fn is_file(&self) -> PyResult<&PyAny> {
-        unimplemented!()
-    }
-
-    /// Return Traversable child in self.
-    #[allow(unused)]
-    fn joinpath(&self, child: &PyAny) -> PyResult<&PyAny> {
-        unimplemented!()
-    }
-
-    /// Return Traversable child in self.
-    #[allow(unused)]
-    fn __truediv__(&self, child: &PyAny) -> PyResult<&PyAny> {
-        unimplemented!()
-    }
-
-    /// mode may be 'r' or 'rb' to open as text or binary. Return a handle
-    /// suitable for reading (same as pathlib.Path.open).
-    ///
-    /// When opening as text, accepts encoding parameters such as those
-    /// accepted by io.TextIOWrapper.
-    #[allow(unused)]
-    #[args(py_args = "*", py_kwargs = "**")]
-    fn open(&self, py_args: &PyTuple, py_kwargs: Option<&PyDict>) -> PyResult<&PyAny> {
-        unimplemented!()
-    }
-}
-
 /// Replace all meta path importers with an OxidizedFinder instance and return it.
 ///
 /// This is called after PyInit_* to finish the initialization of the
@@ -4,11 +4,15 @@
 
 //! oxidized_importer Python extension.
 
+mod allocator_stats;
+mod allocator_trace;
 mod conversion;
 #[allow(clippy::needless_option_as_deref)]
 mod importer;
 #[cfg(windows)]
 mod memory_dll;
+#[cfg(target_os = "linux")]
+mod memory_so;
 mod package_metadata;
 #[allow(clippy::needless_option_as_deref)]
 mod path_entry_finder;
@@ -17,6 +21,7 @@ mod pkg_resources;
 mod python_resource_collector;
 mod python_resource_types;
 mod python_resources;
+mod resource_query;
 mod resource_reader;
 mod resource_scanning;
 #[cfg(feature = "zipimport")]
@@ -24,6 +29,13 @@ mod resource_scanning;
 mod zip_import;
 
 pub use crate::{
+    allocator_stats::{
+        allocator_stats, install_allocator_stats, AllocatorDomainStats, AllocatorStats,
+    },
+    allocator_trace::{
+        allocation_trace, enable_allocation_trace, record_allocation_event, AllocationEvent,
+        AllocationEventKind, MAX_ALLOCATION_FRAMES,
+    },
     importer::{
         install_path_hook, remove_external_importers, replace_meta_path_importers, ImporterState,
         OxidizedFinder,
@@ -40,7 +52,7 @@ use {
         path_entry_finder::OxidizedPathEntryFinder,
         pkg_resources::{register_pkg_resources_with_module, OxidizedPkgResourcesProvider},
         python_resources::OxidizedResource,
-        resource_reader::OxidizedResourceReader,
+        resource_reader::{OxidizedResourceReader, OxidizedTraversable},
     },
     pyo3::{
         exceptions::{PyImportError, PyValueError},
@@ -184,6 +196,8 @@ fn module_init(py: Python, m: &PyModule) -> PyResult<()> {
 
     state.initialized = false;
 
+    crate::allocator_stats::init_module(m)?;
+    crate::allocator_trace::init_module(m)?;
     crate::pkg_resources::init_module(m)?;
     crate::resource_scanning::init_module(m)?;
 
@@ -195,6 +209,7 @@ fn module_init(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<OxidizedResource>()?;
     m.add_class::<crate::python_resource_collector::OxidizedResourceCollector>()?;
     m.add_class::<OxidizedResourceReader>()?;
+    m.add_class::<OxidizedTraversable>()?;
     m.add_class::<OxidizedPathEntryFinder>()?;
     m.add_class::<OxidizedPkgResourcesProvider>()?;
     m.add_class::<crate::python_resource_types::PythonModuleSource>()?;
@@ -210,7 +225,9 @@ fn module_init(py: Python, m: &PyModule) -> PyResult<()> {
 
 #[cfg(feature = "zipimport")]
 fn init_zipimport(m: &PyModule) -> PyResult<()> {
+    m.add_class::<crate::zip_import::OxidizedZipDistribution>()?;
     m.add_class::<crate::zip_import::OxidizedZipFinder>()?;
+    m.add_class::<crate::zip_import::OxidizedZipResourceReader>()?;
 
     Ok(())
 }
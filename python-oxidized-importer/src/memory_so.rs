@@ -0,0 +1,207 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Functionality for loading Linux shared libraries from memory.
+
+Shared library data is written to an anonymous, memory backed file created
+with `memfd_create()`. That file is then loaded with `dlopen()` via its
+`/proc/self/fd/<N>` path. No file is materialized on a filesystem.
+*/
+
+use {
+    crate::python_resources::PythonResourcesState,
+    once_cell::sync::Lazy,
+    std::{
+        collections::HashMap,
+        ffi::{c_void, CStr, CString},
+        fs::File,
+        io::Write,
+        os::unix::io::{FromRawFd, IntoRawFd},
+        sync::Mutex,
+    },
+};
+
+/// Holds state for shared library dependencies loaded from memory.
+struct MemoryLibraries {
+    /// `dlopen()` handles of loaded libraries by name.
+    ///
+    /// Libraries are never unloaded because libraries depending on them may
+    /// still be loaded.
+    libraries: HashMap<String, *mut c_void>,
+}
+
+unsafe impl Send for MemoryLibraries {}
+
+static MEMORY_LIBRARIES: Lazy<Mutex<MemoryLibraries>> = Lazy::new(|| {
+    Mutex::new(MemoryLibraries {
+        libraries: HashMap::new(),
+    })
+});
+
+/// Obtain the error message describing the last `dlopen()` failure.
+fn dlerror_message() -> String {
+    let message = unsafe { libc::dlerror() };
+
+    if message.is_null() {
+        "unknown dlopen() error".to_string()
+    } else {
+        unsafe { CStr::from_ptr(message) }
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+/// Load shared library data by writing it to a memfd and calling `dlopen()` on it.
+unsafe fn dlopen_memory(name: &str, data: &[u8]) -> Result<*mut c_void, String> {
+    let memfd_name = CString::new(name).map_err(|e| e.to_string())?;
+
+    let fd = libc::memfd_create(memfd_name.as_ptr(), libc::MFD_CLOEXEC);
+    if fd < 0 {
+        return Err(format!(
+            "memfd_create() failed: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+
+    let mut fh = File::from_raw_fd(fd);
+    fh.write_all(data)
+        .map_err(|e| format!("error writing {} to memfd: {}", name, e))?;
+
+    let path = CString::new(format!("/proc/self/fd/{}", fd)).unwrap();
+
+    let handle = libc::dlopen(path.as_ptr(), libc::RTLD_NOW);
+
+    if handle.is_null() {
+        Err(format!("error loading {}: {}", name, dlerror_message()))
+    } else {
+        // The descriptor is intentionally kept open. dlopen() identifies loaded
+        // libraries by path. If the descriptor number were reused by a later
+        // memfd, loading that library would return this one instead.
+        let _ = fh.into_raw_fd();
+
+        Ok(handle)
+    }
+}
+
+/// Load shared library dependencies of a named resource that are available in memory.
+///
+/// The dynamic linker satisfies `DT_NEEDED` entries with already loaded libraries
+/// having a matching `DT_SONAME`. So loading dependencies before the library that
+/// needs them makes them resolvable without a filesystem path. Dependencies not
+/// available in memory are left for the dynamic linker to find using its normal
+/// search rules.
+unsafe fn load_dependencies(
+    resources_state: &PythonResourcesState<u8>,
+    name: &str,
+    loading: &mut Vec<String>,
+) -> Result<(), String> {
+    loading.push(name.to_string());
+
+    for dependency in resources_state
+        .shared_library_dependency_names(name)
+        .map_err(|e| e.to_string())?
+    {
+        if loading.contains(&dependency)
+            || MEMORY_LIBRARIES
+                .lock()
+                .unwrap()
+                .libraries
+                .contains_key(&dependency)
+        {
+            continue;
+        }
+
+        let library_data = match resources_state
+            .resolve_in_memory_shared_library_data(&dependency)
+            .map_err(|e| e.to_string())?
+        {
+            Some(data) => data,
+            None => continue,
+        };
+
+        load_dependencies(resources_state, &dependency, loading)?;
+
+        let handle = dlopen_memory(&dependency, &library_data)?;
+
+        MEMORY_LIBRARIES
+            .lock()
+            .unwrap()
+            .libraries
+            .insert(dependency, handle);
+    }
+
+    loading.pop();
+
+    Ok(())
+}
+
+/// Load a library from memory, loading dependencies from resources state first.
+///
+/// `name` is the name of the resource the library belongs to. Its shared library
+/// dependencies available in memory are loaded before the library itself.
+pub(crate) unsafe fn load_library_memory(
+    resources_state: &PythonResourcesState<u8>,
+    name: &str,
+    data: &[u8],
+) -> Result<*const c_void, String> {
+    load_dependencies(resources_state, name, &mut vec![])?;
+
+    dlopen_memory(name, data).map(|handle| handle as *const c_void)
+}
+
+/// Free a library that was loaded from memory.
+pub(crate) unsafe fn free_library_memory(module: *const c_void) {
+    libc::dlclose(module as *mut c_void);
+}
+
+/// Find the address of a symbol in a memory loaded library.
+pub(crate) unsafe fn get_proc_address_memory(module: *const c_void, name: &CStr) -> *mut c_void {
+    libc::dlsym(module as *mut c_void, name.as_ptr())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, pyo3::prelude::*};
+
+    #[test]
+    fn load_extension_module_from_memory() -> PyResult<()> {
+        pyo3::prepare_freethreaded_python();
+
+        let origin = Python::with_gil(|py| -> PyResult<Option<String>> {
+            let spec = py
+                .import("importlib.util")?
+                .getattr("find_spec")?
+                .call1(("_json",))?;
+
+            spec.getattr("origin")?.extract()
+        })?;
+
+        // The extension is compiled into some interpreters. There is nothing
+        // to load from memory in that case.
+        let origin = match origin {
+            Some(origin) if origin.ends_with(".so") => origin,
+            _ => return Ok(()),
+        };
+
+        let data = std::fs::read(origin)?;
+        let resources_state = PythonResourcesState::default();
+
+        let module = unsafe { load_library_memory(&resources_state, "_json", &data) }
+            .map_err(pyo3::exceptions::PyImportError::new_err)?;
+        assert!(!module.is_null());
+
+        let init_name = CString::new("PyInit__json").unwrap();
+        let init = unsafe { get_proc_address_memory(module, &init_name) };
+        assert!(!init.is_null());
+
+        // The symbol must come from the memfd and not a copy of the library
+        // found on the filesystem.
+        let mut info: libc::Dl_info = unsafe { std::mem::zeroed() };
+        assert_ne!(unsafe { libc::dladdr(init, &mut info) }, 0);
+        let path = unsafe { CStr::from_ptr(info.dli_fname) };
+        assert!(path.to_string_lossy().starts_with("/proc/self/fd/"));
+
+        Ok(())
+    }
+}
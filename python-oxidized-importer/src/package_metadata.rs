@@ -34,6 +34,105 @@ fn discover_resolvers(py: Python) -> PyResult<&PyList> {
     Ok(PyList::new(py, resolvers))
 }
 
+/// Decode the content of a distribution metadata file to a `str`.
+pub(crate) fn decode_metadata_text<'p>(py: Python<'p>, data: &[u8]) -> PyResult<&'p PyAny> {
+    let data = PyBytes::new(py, data);
+
+    let io = py.import("io")?;
+
+    let bytes_io = io.getattr("BytesIO")?.call((data,), None)?;
+    let text_wrapper = io
+        .getattr("TextIOWrapper")?
+        .call((bytes_io, "utf-8"), None)?;
+
+    text_wrapper.call_method0("read")
+}
+
+/// Parse the content of a `METADATA` or `PKG-INFO` file.
+pub(crate) fn parse_metadata<'p>(py: Python<'p>, data: &[u8]) -> PyResult<&'p PyAny> {
+    let data = PyBytes::new(py, data);
+    let email = py.import("email")?;
+
+    let message = email.getattr("message_from_bytes")?.call((data,), None)?;
+
+    // Python 3.10+ has an adapter class for the raw email Message.
+    if let Ok(adapters) = py.import("importlib.metadata._adapters") {
+        let adapter_cls = adapters.getattr("Message")?;
+        adapter_cls.call1((message,))
+    } else {
+        Ok(message)
+    }
+}
+
+/// Normalize a distribution name the way `importlib.metadata` does.
+pub(crate) fn normalize_distribution_name<'p>(
+    py: Python<'p>,
+    name: &'p PyAny,
+) -> PyResult<&'p PyAny> {
+    let re = py.import("re")?;
+
+    // PEP 503 normalization plus dashes as underscores.
+    let value = re.call_method("sub", ("[-_.]+", "-", name), None)?;
+    let value = value.call_method0("lower")?;
+    let value = value.call_method("replace", ("-", "_"), None)?;
+
+    Ok(value)
+}
+
+/// Parse the content of an `entry_points.txt` file belonging to `distribution`.
+pub(crate) fn parse_entry_points<'p>(
+    py: Python<'p>,
+    text: &'p PyAny,
+    distribution: PyObject,
+) -> PyResult<&'p PyAny> {
+    let importlib_metadata = py.import("importlib.metadata")?;
+
+    if let Ok(entry_points) = importlib_metadata.getattr("EntryPoints") {
+        entry_points.call_method("_from_text_for", (text, distribution), None)
+    } else {
+        let entry_point = importlib_metadata.getattr("EntryPoint")?;
+
+        entry_point.call_method("_from_text", (text,), None)
+    }
+}
+
+/// Resolve the requirements of a distribution.
+///
+/// Requirements come from the `Requires-Dist` metadata. If not present,
+/// `requires_text` is called to obtain the content of `requires.txt`.
+pub(crate) fn resolve_requires<'p>(
+    py: Python<'p>,
+    metadata: &'p PyAny,
+    requires_text: impl FnOnce() -> PyResult<&'p PyAny>,
+) -> PyResult<&'p PyAny> {
+    let requires = metadata.call_method("get_all", ("Requires-Dist",), None)?;
+
+    let requires = if requires.is_none() {
+        // Fall back to reading from requires.txt.
+        let source = requires_text()?;
+
+        if source.is_none() {
+            py.None().into_ref(py)
+        } else {
+            let importlib_metadata = py.import("importlib.metadata")?;
+            let distribution = importlib_metadata.getattr("Distribution")?;
+
+            distribution.call_method("_deps_from_requires_text", (source,), None)?
+        }
+    } else {
+        requires
+    };
+
+    if requires.is_none() {
+        Ok(py.None().into_ref(py))
+    } else {
+        let res = PyList::empty(py);
+        res.call_method("extend", (requires,), None)?;
+
+        Ok(res.into())
+    }
+}
+
 /// A importlib.metadata.Distribution allowing access to package distribution data.
 #[pyclass(module = "oxidized_importer")]
 pub(crate) struct OxidizedDistribution {
@@ -136,16 +235,7 @@ impl OxidizedDistribution {
             return Ok(py.None().into_ref(py));
         };
 
-        let data = PyBytes::new(py, &data);
-
-        let io = py.import("io")?;
-
-        let bytes_io = io.getattr("BytesIO")?.call((data,), None)?;
-        let text_wrapper = io
-            .getattr("TextIOWrapper")?
-            .call((bytes_io, "utf-8"), None)?;
-
-        text_wrapper.call_method0("read")
+        decode_metadata_text(py, &data)
     }
 
     /// Return the parsed metadata for this Distribution.
@@ -169,18 +259,7 @@ impl OxidizedDistribution {
                 .ok_or_else(|| PyIOError::new_err("package metadata not found"))?
         };
 
-        let data = PyBytes::new(py, &data);
-        let email = py.import("email")?;
-
-        let message = email.getattr("message_from_bytes")?.call((data,), None)?;
-
-        // Python 3.10+ has an adapter class for the raw email Message.
-        if let Ok(adapters) = py.import("importlib.metadata._adapters") {
-            let adapter_cls = adapters.getattr("Message")?;
-            adapter_cls.call1((message,))
-        } else {
-            Ok(message)
-        }
+        parse_metadata(py, &data)
     }
 
     /// Return the `Name` metadata for the distribution package.
@@ -194,15 +273,7 @@ impl OxidizedDistribution {
     /// Return a normalized version of the name.
     #[getter]
     fn _normalized_name<'p>(&self, py: Python<'p>) -> PyResult<&'p PyAny> {
-        let name = self.name(py)?;
-        let re = py.import("re")?;
-
-        // PEP 503 normalization plus dashes as underscores.
-        let value = re.call_method("sub", ("[-_.]+", "-", name), None)?;
-        let value = value.call_method0("lower")?;
-        let value = value.call_method("replace", ("-", "_"), None)?;
-
-        Ok(value)
+        normalize_distribution_name(py, self.name(py)?)
     }
 
     #[getter]
@@ -214,17 +285,9 @@ impl OxidizedDistribution {
 
     #[getter]
     fn entry_points<'p>(self_: PyRef<Self>, py: Python<'p>) -> PyResult<&'p PyAny> {
-        let importlib_metadata = py.import("importlib.metadata")?;
-
         let text = self_.read_text(py, "entry_points.txt".into())?;
 
-        if let Ok(entry_points) = importlib_metadata.getattr("EntryPoints") {
-            entry_points.call_method("_from_text_for", (text, self_), None)
-        } else {
-            let entry_point = importlib_metadata.getattr("EntryPoint")?;
-
-            entry_point.call_method("_from_text", (text,), None)
-        }
+        parse_entry_points(py, text, self_.into_py(py))
     }
 
     #[getter]
@@ -234,34 +297,9 @@ impl OxidizedDistribution {
 
     #[getter]
     fn requires<'p>(&self, py: Python<'p>) -> PyResult<&'p PyAny> {
-        let requires = self
-            .metadata(py)?
-            .call_method("get_all", ("Requires-Dist",), None)?;
-
-        let requires = if requires.is_none() {
-            // Fall back to reading from requires.txt.
-            let source = self.read_text(py, "requires.txt".into())?;
-
-            if source.is_none() {
-                py.None().into_ref(py)
-            } else {
-                let importlib_metadata = py.import("importlib.metadata")?;
-                let distribution = importlib_metadata.getattr("Distribution")?;
-
-                distribution.call_method("_deps_from_requires_text", (source,), None)?
-            }
-        } else {
-            requires
-        };
-
-        if requires.is_none() {
-            Ok(py.None().into_ref(py))
-        } else {
-            let res = PyList::empty(py);
-            res.call_method("extend", (requires,), None)?;
-
-            Ok(res.into())
-        }
+        resolve_requires(py, self.metadata(py)?, || {
+            self.read_text(py, "requires.txt".into())
+        })
     }
 }
 
@@ -287,6 +325,7 @@ pub(crate) fn find_distributions<'p>(
                 true
             }
         })
+        .map_err(PyValueError::new_err)?
         .into_iter()
         .map(|name| {
             PyCell::new(
@@ -326,6 +365,7 @@ pub(crate) fn find_pkg_resources_distributions<'p>(
                 name_within_package_hierarchy(name, package_target)
             }
         })
+        .map_err(PyValueError::new_err)?
         .into_iter()
         .map(|name| {
             let oxidized_distribution = OxidizedDistribution::new(state.clone(), name.to_string());
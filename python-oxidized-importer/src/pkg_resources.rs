@@ -9,7 +9,9 @@ use {
         path_entry_finder::OxidizedPathEntryFinder,
     },
     pyo3::{
-        exceptions::{PyIOError, PyNotImplementedError, PyTypeError, PyUnicodeDecodeError},
+        exceptions::{
+            PyIOError, PyNotImplementedError, PyTypeError, PyUnicodeDecodeError, PyValueError,
+        },
         prelude::*,
         types::{PyList, PyString},
     },
@@ -80,7 +82,9 @@ impl OxidizedPkgResourcesProvider {
     fn metadata_isdir(&self, name: &str) -> PyResult<bool> {
         let resources_state = self.state.get_resources_state();
 
-        Ok(resources_state.package_distribution_resource_name_is_directory(&self.package, name))
+        resources_state
+            .package_distribution_resource_name_is_directory(&self.package, name)
+            .map_err(PyValueError::new_err)
     }
 
     fn metadata_listdir<'p>(&self, py: Python<'p>, name: &str) -> PyResult<&'p PyList> {
@@ -88,6 +92,7 @@ impl OxidizedPkgResourcesProvider {
 
         let entries = resources_state
             .package_distribution_resources_list_directory(&self.package, name)
+            .map_err(PyValueError::new_err)?
             .into_iter()
             .map(|s| PyString::new(py, s))
             .collect::<Vec<_>>();
@@ -149,10 +154,10 @@ impl OxidizedPkgResourcesProvider {
     }
 
     fn resource_isdir(&self, resource_name: &str) -> PyResult<bool> {
-        Ok(self
-            .state
+        self.state
             .get_resources_state()
-            .is_package_resource_directory(&self.package, resource_name))
+            .is_package_resource_directory(&self.package, resource_name)
+            .map_err(PyValueError::new_err)
     }
 
     fn resource_listdir<'p>(&self, py: Python<'p>, resource_name: &str) -> PyResult<&'p PyList> {
@@ -160,6 +165,7 @@ impl OxidizedPkgResourcesProvider {
             .state
             .get_resources_state()
             .package_resources_list_directory(&self.package, resource_name)
+            .map_err(PyValueError::new_err)?
             .into_iter()
             .map(|s| PyString::new(py, &s))
             .collect::<Vec<_>>();
@@ -12,7 +12,8 @@ use {
         pyobject_optional_resources_map_to_pathbuf, pyobject_to_owned_bytes_optional,
         pyobject_to_pathbuf_optional,
     },
-    anyhow::Result,
+    anyhow::{anyhow, Result},
+    once_cell::sync::OnceCell,
     pyo3::{
         buffer::PyBuffer,
         exceptions::{PyImportError, PyOSError, PyValueError},
@@ -21,8 +22,11 @@ use {
         type_object::PyTypeObject,
         types::{PyBytes, PyDict, PyList, PyString, PyTuple},
     },
-    python_packaging::resource::BytecodeOptimizationLevel,
-    python_packed_resources::Resource,
+    python_packaging::{
+        import_trace::{ImportTrace, ImportTraceEntry},
+        resource::BytecodeOptimizationLevel,
+    },
+    python_packed_resources::{CompressionPolicy, Resource, ResourceField, ResourceNameIndex},
     std::{
         borrow::Cow,
         cell::RefCell,
@@ -30,6 +34,7 @@ use {
         ffi::CStr,
         os::raw::c_int,
         path::{Path, PathBuf},
+        sync::Mutex,
     },
 };
 
@@ -103,6 +108,18 @@ pub enum ModuleFlavor {
     SourceBytecode,
 }
 
+impl ModuleFlavor {
+    /// Obtain the name of this flavor as used in import traces.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Builtin => "builtin",
+            Self::Frozen => "frozen",
+            Self::Extension => "extension",
+            Self::SourceBytecode => "source-bytecode",
+        }
+    }
+}
+
 /// Holds state for an importable Python module.
 ///
 /// This essentially is an abstraction over raw `Resource` entries that
@@ -120,6 +137,9 @@ where
     /// Path from which relative paths should be interpreted.
     origin: &'a Path,
 
+    /// Directory holding cached bytecode for modules loaded from filesystem source.
+    bytecode_cache_dir: Option<&'a Path>,
+
     /// The type of importable module.
     pub flavor: ModuleFlavor,
     /// Whether this module is a package.
@@ -139,7 +159,17 @@ impl<'a> ImportablePythonModule<'a, u8> {
         io_module: &PyAny,
     ) -> PyResult<Option<&'p PyAny>> {
         let bytes = if let Some(data) = &self.resource.in_memory_source {
-            Some(PyBytes::new(py, data))
+            let data = self
+                .resource
+                .resolve_field_data(ResourceField::InMemorySource, data)
+                .map_err(|e| {
+                    PyImportError::new_err((
+                        format!("error decompressing module source: {}", e),
+                        self.resource.name.to_string(),
+                    ))
+                })?;
+
+            Some(PyBytes::new(py, &data))
         } else if let Some(relative_path) = &self.resource.relative_path_module_source {
             let path = self.origin.join(relative_path);
 
@@ -172,7 +202,8 @@ impl<'a> ImportablePythonModule<'a, u8> {
     /// module in this scenario, but it isn't known to the resources data structure
     /// (e.g. the case of frozen modules).
     ///
-    /// The returned `PyObject` will be an instance of `memoryview`.
+    /// The returned `PyObject` will be an instance of `memoryview` or `bytes`
+    /// if the bytecode had to be decompressed.
     pub fn resolve_bytecode(
         &mut self,
         py: Python,
@@ -180,11 +211,35 @@ impl<'a> ImportablePythonModule<'a, u8> {
         decode_source: &PyAny,
         io_module: &PyModule,
     ) -> PyResult<Option<Py<PyAny>>> {
-        if let Some(data) = match optimize_level {
-            BytecodeOptimizationLevel::Zero => &self.resource.in_memory_bytecode,
-            BytecodeOptimizationLevel::One => &self.resource.in_memory_bytecode_opt1,
-            BytecodeOptimizationLevel::Two => &self.resource.in_memory_bytecode_opt2,
-        } {
+        let (field, data) = match optimize_level {
+            BytecodeOptimizationLevel::Zero => (
+                ResourceField::InMemoryBytecode,
+                &self.resource.in_memory_bytecode,
+            ),
+            BytecodeOptimizationLevel::One => (
+                ResourceField::InMemoryBytecodeOpt1,
+                &self.resource.in_memory_bytecode_opt1,
+            ),
+            BytecodeOptimizationLevel::Two => (
+                ResourceField::InMemoryBytecodeOpt2,
+                &self.resource.in_memory_bytecode_opt2,
+            ),
+        };
+
+        if let Some(data) = data {
+            // Compressed bytecode is decompressed on demand into a new `bytes`.
+            // Otherwise we can reference the backing memory without a copy.
+            if self.resource.field_compression(field).is_some() {
+                let bytecode = self.resource.resolve_field_data(field, data).map_err(|e| {
+                    PyImportError::new_err((
+                        format!("error decompressing bytecode: {}", e),
+                        self.resource.name.to_string(),
+                    ))
+                })?;
+
+                return Ok(Some(PyBytes::new(py, &bytecode).into_py(py)));
+            }
+
             let ptr = unsafe {
                 pyffi::PyMemoryView_FromMemory(
                     data.as_ptr() as _,
@@ -220,13 +275,19 @@ impl<'a> ImportablePythonModule<'a, u8> {
 
             // First 16 bytes of .pyc files are a header.
             Ok(Some(PyBytes::new(py, &bytecode[16..]).into_py(py)))
+        } else if let Some(cache_path) = self.bytecode_cache_path(py, optimize_level)? {
+            self.resolve_bytecode_cached(py, optimize_level, &cache_path, decode_source, io_module)
+                .map(Some)
         } else if let Some(source) = self.resolve_source(py, decode_source, io_module)? {
             let builtins = py.import("builtins")?;
             let marshal = py.import("marshal")?;
 
+            let kwargs = PyDict::new(py);
+            kwargs.set_item("optimize", i32::from(optimize_level))?;
+
             let code = builtins
                 .getattr("compile")?
-                .call((source, self.resource.name.as_ref(), "exec"), None)?;
+                .call((source, self.resource.name.as_ref(), "exec"), Some(kwargs))?;
             let bytecode = marshal.getattr("dumps")?.call((code,), None)?;
 
             Ok(Some(bytecode.into_py(py)))
@@ -235,6 +296,90 @@ impl<'a> ImportablePythonModule<'a, u8> {
         }
     }
 
+    /// Resolve bytecode for a module with filesystem source using the bytecode cache.
+    ///
+    /// Cached bytecode is used if it was compiled from the current source. Otherwise
+    /// the source is compiled and the cache is updated. Cache files are hash-based
+    /// `.pyc` files, as described by PEP 552, so they are validated against the source
+    /// content and not its modification time. Source is compiled with the given
+    /// optimization level, which must be the level `cache_path` is for.
+    fn resolve_bytecode_cached(
+        &self,
+        py: Python,
+        optimize_level: BytecodeOptimizationLevel,
+        cache_path: &Path,
+        decode_source: &PyAny,
+        io_module: &PyModule,
+    ) -> PyResult<Py<PyAny>> {
+        let source_path = self.origin_path().ok_or_else(|| {
+            PyImportError::new_err((
+                "module source path not known",
+                self.resource.name.to_string(),
+            ))
+        })?;
+
+        let source = std::fs::read(&source_path).map_err(|e| {
+            PyImportError::new_err((
+                format!(
+                    "error reading module source from {}: {}",
+                    source_path.display(),
+                    e
+                ),
+                self.resource.name.to_string(),
+            ))
+        })?;
+
+        let importlib_util = py.import("importlib.util")?;
+        let magic = importlib_util
+            .getattr("MAGIC_NUMBER")?
+            .extract::<Vec<u8>>()?;
+        let source_hash = importlib_util
+            .getattr("source_hash")?
+            .call((PyBytes::new(py, &source),), None)?
+            .extract::<Vec<u8>>()?;
+
+        // Header is the magic number, flags, and the source hash. Flags of 0b11
+        // denote a hash-based .pyc that should be checked against its source.
+        let mut header = Vec::with_capacity(16);
+        header.extend_from_slice(&magic);
+        header.extend_from_slice(&3u32.to_le_bytes());
+        header.extend_from_slice(&source_hash);
+
+        if let Ok(cached) = std::fs::read(cache_path) {
+            if cached.len() >= 16 && cached[0..16] == header[..] {
+                return Ok(PyBytes::new(py, &cached[16..]).into_py(py));
+            }
+        }
+
+        let source = decode_source.call((io_module, PyBytes::new(py, &source)), None)?;
+
+        let builtins = py.import("builtins")?;
+        let marshal = py.import("marshal")?;
+
+        let kwargs = PyDict::new(py);
+        kwargs.set_item("dont_inherit", true)?;
+        kwargs.set_item("optimize", i32::from(optimize_level))?;
+
+        let code = builtins
+            .getattr("compile")?
+            .call((source, source_path.into_py(py), "exec"), Some(kwargs))?;
+        let bytecode = marshal.getattr("dumps")?.call((code,), None)?;
+
+        // Like CPython, failures writing the cache are ignored.
+        if !py
+            .import("sys")?
+            .getattr("dont_write_bytecode")?
+            .is_true()?
+        {
+            let mut data = header;
+            data.extend_from_slice(bytecode.downcast::<PyBytes>()?.as_bytes());
+
+            let _ = write_bytecode_cache_file(cache_path, &data);
+        }
+
+        Ok(bytecode.into_py(py))
+    }
+
     /// Resolve the `importlib.machinery.ModuleSpec` for this module.
     pub fn resolve_module_spec<'p>(
         &self,
@@ -339,7 +484,10 @@ impl<'a> ImportablePythonModule<'a, u8> {
         optimize_level: BytecodeOptimizationLevel,
     ) -> PyResult<Option<&'p PyAny>> {
         let path = match self.flavor {
-            ModuleFlavor::SourceBytecode => self.bytecode_path(optimize_level),
+            ModuleFlavor::SourceBytecode => match self.bytecode_path(optimize_level) {
+                Some(path) => Some(path),
+                None => self.bytecode_cache_path(py, optimize_level)?,
+            },
             _ => None,
         };
 
@@ -380,9 +528,108 @@ impl<'a> ImportablePythonModule<'a, u8> {
             .map(|bytecode_path| self.origin.join(bytecode_path))
     }
 
-    pub fn in_memory_extension_module_shared_library(&self) -> &'a Option<Cow<'a, [u8]>> {
-        &self.resource.in_memory_extension_module_shared_library
+    /// Obtain the path of the bytecode cache file for this module.
+    ///
+    /// Only modules whose source is loaded from the filesystem and that don't
+    /// have bytecode are cached. `None` is returned if the module isn't cached.
+    fn bytecode_cache_path(
+        &self,
+        py: Python,
+        optimize_level: BytecodeOptimizationLevel,
+    ) -> PyResult<Option<PathBuf>> {
+        let cache_dir = match self.bytecode_cache_dir {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        let in_memory_bytecode = match optimize_level {
+            BytecodeOptimizationLevel::Zero => &self.resource.in_memory_bytecode,
+            BytecodeOptimizationLevel::One => &self.resource.in_memory_bytecode_opt1,
+            BytecodeOptimizationLevel::Two => &self.resource.in_memory_bytecode_opt2,
+        };
+
+        if self.flavor != ModuleFlavor::SourceBytecode
+            || in_memory_bytecode.is_some()
+            || self.resource.in_memory_source.is_some()
+            || self.bytecode_path(optimize_level).is_some()
+        {
+            return Ok(None);
+        }
+
+        let source_path = match &self.resource.relative_path_module_source {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        // Cache paths mirror the relative source path. Paths that could escape the
+        // cache directory aren't cached.
+        if !source_path
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_)))
+        {
+            return Ok(None);
+        }
+
+        // Python implementations without a cache tag don't support bytecode caching.
+        let cache_tag = match py
+            .import("sys")?
+            .getattr("implementation")?
+            .getattr("cache_tag")?
+            .extract::<Option<String>>()?
+        {
+            Some(tag) => tag,
+            None => return Ok(None),
+        };
+
+        let stem = match source_path.file_stem() {
+            Some(stem) => stem.to_string_lossy(),
+            None => return Ok(None),
+        };
+
+        let filename = match optimize_level {
+            BytecodeOptimizationLevel::Zero => format!("{}.{}.pyc", stem, cache_tag),
+            BytecodeOptimizationLevel::One => format!("{}.{}.opt-1.pyc", stem, cache_tag),
+            BytecodeOptimizationLevel::Two => format!("{}.{}.opt-2.pyc", stem, cache_tag),
+        };
+
+        Ok(Some(cache_dir.join(source_path).with_file_name(filename)))
     }
+
+    /// Resolve the in-memory shared library data for this extension module.
+    ///
+    /// Data is decompressed if necessary.
+    pub fn in_memory_extension_module_shared_library(
+        &self,
+    ) -> Result<Option<Cow<'a, [u8]>>, &'static str> {
+        match &self.resource.in_memory_extension_module_shared_library {
+            Some(data) => Ok(Some(self.resource.resolve_field_data(
+                ResourceField::InMemoryExtensionModuleSharedLibrary,
+                data,
+            )?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Atomically write a bytecode cache file.
+///
+/// Data is written to a temporary file that is renamed into place so concurrent
+/// readers never see partially written files.
+fn write_bytecode_cache_file(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut temp_name = path.as_os_str().to_owned();
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = PathBuf::from(temp_name);
+
+    std::fs::write(&temp_path, data)?;
+
+    std::fs::rename(&temp_path, path).map_err(|e| {
+        let _ = std::fs::remove_file(&temp_path);
+        e
+    })
 }
 
 /// A source for packed resources data.
@@ -394,6 +641,12 @@ pub enum PackedResourcesSource<'a> {
     /// Load resources data from a filesystem path using memory mapped I/O.
     #[allow(unused)]
     MemoryMappedPath(PathBuf),
+
+    /// Load resources data from a filesystem path using memory mapped I/O if it exists.
+    ///
+    /// Useful for overlays holding resources replacing those from other sources.
+    #[allow(unused)]
+    OptionalMemoryMappedPath(PathBuf),
 }
 
 impl<'a> From<&'a [u8]> for PackedResourcesSource<'a> {
@@ -402,6 +655,87 @@ impl<'a> From<&'a [u8]> for PackedResourcesSource<'a> {
     }
 }
 
+/// Resources from packed resources data having a name index.
+///
+/// Resources are parsed when they are first accessed. Errors parsing a resource
+/// are returned to the caller accessing it.
+#[derive(Debug)]
+struct LazyResources<'a> {
+    index: ResourceNameIndex<'a>,
+    resources: Vec<OnceCell<Box<Resource<'a, u8>>>>,
+}
+
+impl<'a> LazyResources<'a> {
+    fn new(index: ResourceNameIndex<'a>) -> Self {
+        let resources = (0..index.len()).map(|_| OnceCell::new()).collect();
+
+        Self { index, resources }
+    }
+
+    fn resolve(&self, position: usize) -> Result<&Resource<'a, u8>, &'static str> {
+        self.resources[position]
+            .get_or_try_init(|| self.index.resource(position).map(Box::new))
+            .map(|resource| resource.as_ref())
+    }
+
+    fn get(&self, name: &str) -> Result<Option<&Resource<'a, u8>>, &'static str> {
+        match self.index.position(name)? {
+            Some(position) => Ok(Some(self.resolve(position)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn get_mut(&mut self, name: &str) -> Result<Option<&mut Resource<'a, u8>>, &'static str> {
+        let position = match self.index.position(name)? {
+            Some(position) => position,
+            None => return Ok(None),
+        };
+
+        self.resolve(position)?;
+
+        Ok(self.resources[position]
+            .get_mut()
+            .map(|resource| resource.as_mut()))
+    }
+
+    fn contains(&self, name: &str) -> Result<bool, &'static str> {
+        Ok(self.index.position(name)?.is_some())
+    }
+
+    fn iter(&self) -> impl Iterator<Item = Result<&Resource<'a, u8>, &'static str>> {
+        (0..self.resources.len()).map(move |position| self.resolve(position))
+    }
+}
+
+/// Overlay a resource from a higher priority packed resources source.
+///
+/// The new resource is merged into the existing one: set fields on the new
+/// resource overwrite fields on the existing resource. A tombstone replaces
+/// the existing resource outright and a resource overlaying a tombstone
+/// replaces the tombstone, so no data from before the tombstone is resurrected.
+fn overlay_resource<'a>(
+    existing: &mut Resource<'a, u8>,
+    resource: Resource<'a, u8>,
+) -> Result<(), &'static str> {
+    if resource.is_tombstone || existing.is_tombstone {
+        *existing = resource;
+
+        Ok(())
+    } else {
+        existing.merge_from(resource)
+    }
+}
+
+/// A packed resources source that has been indexed.
+#[derive(Debug)]
+struct IndexedLayer<'a> {
+    /// Filesystem path the data was memory mapped from.
+    path: Option<PathBuf>,
+
+    /// The indexed packed resources data.
+    data: &'a [u8],
+}
+
 /// Defines Python resources available for import.
 #[derive(Debug)]
 pub struct PythonResourcesState<'a, X>
@@ -417,8 +751,34 @@ where
     origin: PathBuf,
 
     /// Named resources available for loading.
+    ///
+    /// Does not contain resources in `lazy_resources`.
     resources: HashMap<Cow<'a, str>, Resource<'a, X>>,
 
+    /// Resources that are parsed on first access.
+    ///
+    /// These always come from the first indexed packed resources source.
+    lazy_resources: Option<LazyResources<'a>>,
+
+    /// Number of packed resources sources that have been indexed.
+    layers_count: usize,
+
+    /// Packed resources sources that have been indexed, in the order they were indexed.
+    layers: Vec<IndexedLayer<'a>>,
+
+    /// Whether modules built into the interpreter have been indexed.
+    indexed_builtin_extension_modules: bool,
+
+    /// Whether modules frozen in the interpreter have been indexed.
+    indexed_frozen_modules: bool,
+
+    /// The packed resources source each resource came from.
+    ///
+    /// Values are indices of sources in the order they were indexed. `None` denotes
+    /// a resource that was added directly. Resources in `lazy_resources` not present
+    /// here came from the first source.
+    resource_layers: HashMap<Cow<'a, str>, Option<usize>>,
+
     /// List of `PyObject` that back indexed data.
     ///
     /// Holding a reference to these prevents them from being gc'd and for
@@ -427,6 +787,15 @@ where
 
     /// Holds memory mapped file instances that resources data came from.
     backing_mmaps: Vec<memmap2::Mmap>,
+
+    /// Ed25519 public key that indexed packed resources data must be signed with.
+    packed_resources_public_key: Option<Vec<u8>>,
+
+    /// Directory holding cached bytecode for modules loaded from filesystem source.
+    bytecode_cache_dir: Option<PathBuf>,
+
+    /// Records resources accessed through this instance, if enabled.
+    import_trace: Option<Mutex<ImportTrace>>,
 }
 
 impl<'a> Default for PythonResourcesState<'a, u8> {
@@ -435,8 +804,17 @@ impl<'a> Default for PythonResourcesState<'a, u8> {
             current_exe: PathBuf::new(),
             origin: PathBuf::new(),
             resources: HashMap::new(),
+            lazy_resources: None,
+            layers_count: 0,
+            layers: vec![],
+            indexed_builtin_extension_modules: false,
+            indexed_frozen_modules: false,
+            resource_layers: HashMap::new(),
             backing_py_objects: vec![],
             backing_mmaps: vec![],
+            packed_resources_public_key: None,
+            bytecode_cache_dir: None,
+            import_trace: None,
         }
     }
 }
@@ -477,25 +855,135 @@ impl<'a> PythonResourcesState<'a, u8> {
         self.origin = path;
     }
 
+    /// Obtain the directory holding cached bytecode.
+    pub fn bytecode_cache_dir(&self) -> Option<&Path> {
+        self.bytecode_cache_dir.as_deref()
+    }
+
+    /// Set the directory holding cached bytecode.
+    ///
+    /// If set, bytecode for modules whose source is loaded from the filesystem
+    /// and that don't have bytecode is cached in this directory. The cache is
+    /// populated when modules are first imported.
+    pub fn set_bytecode_cache_dir(&mut self, path: Option<PathBuf>) {
+        self.bytecode_cache_dir = path;
+    }
+
+    /// Whether an import trace is being recorded.
+    pub fn import_trace_enabled(&self) -> bool {
+        self.import_trace.is_some()
+    }
+
+    /// Enable or disable recording of an import trace.
+    ///
+    /// When enabled, modules found and not found by the importer as well as
+    /// package resources and package distribution resources that are read are
+    /// recorded. Disabling discards recorded entries.
+    pub fn set_import_trace_enabled(&mut self, enabled: bool) {
+        if !enabled {
+            self.import_trace = None;
+        } else if self.import_trace.is_none() {
+            self.import_trace = Some(Mutex::new(ImportTrace::default()));
+        }
+    }
+
+    /// Obtain a copy of the recorded import trace, if enabled.
+    pub fn import_trace(&self) -> Option<ImportTrace> {
+        self.import_trace
+            .as_ref()
+            .map(|trace| trace.lock().unwrap().clone())
+    }
+
+    /// Record an entry in the import trace.
+    ///
+    /// `entry` is only called if an import trace is being recorded.
+    pub fn record_import_trace(&self, entry: impl FnOnce() -> ImportTraceEntry) {
+        if let Some(trace) = &self.import_trace {
+            trace.lock().unwrap().record(entry());
+        }
+    }
+
+    /// Obtain the public key that packed resources data must be signed with.
+    pub fn packed_resources_public_key(&self) -> Option<&[u8]> {
+        self.packed_resources_public_key.as_deref()
+    }
+
+    /// Set the public key that packed resources data must be signed with.
+    ///
+    /// If set, all subsequently indexed packed resources data must have an
+    /// integrity trailer signed by the corresponding private key and matching
+    /// the data. Data failing verification is rejected.
+    pub fn set_packed_resources_public_key(&mut self, key: Option<Vec<u8>>) {
+        self.packed_resources_public_key = key;
+    }
+
+    /// Verify the integrity of packed resources data, if a public key is set.
+    fn verify_data(&self, data: &[u8]) -> Result<(), &'static str> {
+        if let Some(key) = &self.packed_resources_public_key {
+            python_packed_resources::verify_integrity(data, Some(key))?;
+        }
+
+        Ok(())
+    }
+
     /// Load resources by parsing a blob.
     ///
-    /// If an existing entry exists, the new entry will be merged into it. Set fields
-    /// on the incoming entry will overwrite fields on the existing entry.
+    /// Each call indexes a new layer of resources. Resources are merged into
+    /// resources having the same name from previously indexed layers: set
+    /// fields on the incoming resource overwrite fields on the existing
+    /// resource. Tombstone resources hide resources having the same name from
+    /// previous layers.
+    ///
+    /// If a packed resources public key is set, the integrity of the data is
+    /// verified before anything is indexed.
     ///
-    /// If an entry doesn't exist, the resource will be inserted as-is.
+    /// If the data has a name index and is the first data indexed, resources
+    /// aren't parsed until they are accessed.
     pub fn index_data(&mut self, data: &'a [u8]) -> Result<(), &'static str> {
+        self.verify_data(data)?;
+        self.index_layer(data)?;
+        self.layers.push(IndexedLayer { path: None, data });
+
+        Ok(())
+    }
+
+    /// Index packed resources data as a new layer.
+    fn index_layer(&mut self, data: &'a [u8]) -> Result<(), &'static str> {
         let resources = python_packed_resources::load_resources(data)?;
 
+        let layer = self.layers_count;
+
+        if layer == 0 && self.resources.is_empty() {
+            if let Some(index) = resources.name_index() {
+                self.lazy_resources = Some(LazyResources::new(index.clone()));
+                self.layers_count += 1;
+
+                return Ok(());
+            }
+        }
+
         // Reserve space for expected number of incoming items so we can avoid extra
         // allocations.
         self.resources.reserve(resources.expected_resources_count());
+        self.resource_layers
+            .reserve(resources.expected_resources_count());
 
         for resource in resources {
             let resource = resource?;
 
+            self.resource_layers
+                .insert(resource.name.clone(), Some(layer));
+
+            if let Some(lazy) = &mut self.lazy_resources {
+                if let Some(existing) = lazy.get_mut(&resource.name)? {
+                    overlay_resource(existing, resource)?;
+                    continue;
+                }
+            }
+
             match self.resources.entry(resource.name.clone()) {
                 Entry::Occupied(existing) => {
-                    existing.into_mut().merge_from(resource)?;
+                    overlay_resource(existing.into_mut(), resource)?;
                 }
                 Entry::Vacant(vacant) => {
                     vacant.insert(resource);
@@ -503,19 +991,126 @@ impl<'a> PythonResourcesState<'a, u8> {
             }
         }
 
+        self.layers_count += 1;
+
         Ok(())
     }
 
-    /// Load resources data from a filesystem path using memory mapped I/O.
-    pub fn index_path_memory_mapped(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
-        let path = path.as_ref();
+    /// Memory map a file, returning the mapping and its data.
+    fn map_path(path: &Path) -> Result<(memmap2::Mmap, &'a [u8]), String> {
         let f = std::fs::File::open(path).map_err(|e| e.to_string())?;
 
         let mapped = unsafe { memmap2::Mmap::map(&f) }.map_err(|e| e.to_string())?;
 
         let data = unsafe { std::slice::from_raw_parts::<u8>(mapped.as_ptr(), mapped.len()) };
 
-        self.index_data(data)?;
+        Ok((mapped, data))
+    }
+
+    /// Load resources data from a filesystem path using memory mapped I/O.
+    pub fn index_path_memory_mapped(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        let (mapped, data) = Self::map_path(path)?;
+
+        self.verify_data(data)?;
+        self.index_layer(data)?;
+        self.layers.push(IndexedLayer {
+            path: Some(path.to_path_buf()),
+            data,
+        });
+        self.backing_mmaps.push(mapped);
+
+        Ok(())
+    }
+
+    /// Replace resources indexed from a memory mapped file with the file's current content.
+    ///
+    /// `path` must be a path previously given to [Self::index_path_memory_mapped()].
+    /// The file is memory mapped again and all layers are indexed again in their
+    /// original order, with the new file content taking the place of the old.
+    /// Resources added via [Self::add_resource()] are retained and take precedence
+    /// over indexed resources. Interpreter builtins are indexed again if they were
+    /// indexed before.
+    ///
+    /// Layers are indexed into a new instance whose state replaces the existing
+    /// state only once everything was indexed. So on error the previously indexed
+    /// resources remain available.
+    ///
+    /// Memory mappings of previous file content are retained for the lifetime of
+    /// this instance, as data handed out from it may still reference them. Files
+    /// should be replaced by renaming a new file into place, not by rewriting
+    /// them, as modifying a memory mapped file is undefined behavior.
+    pub fn reload_path_memory_mapped(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+
+        if !self
+            .layers
+            .iter()
+            .any(|layer| layer.path.as_deref() == Some(path))
+        {
+            return Err(format!(
+                "{} is not an indexed packed resources file",
+                path.display()
+            ));
+        }
+
+        let (mapped, data) = Self::map_path(path)?;
+
+        self.verify_data(data)?;
+        for resource in python_packed_resources::load_resources(data)? {
+            resource?;
+        }
+
+        let added_names = self
+            .resource_layers
+            .iter()
+            .filter(|(_, layer)| layer.is_none())
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+
+        let mut added = Vec::with_capacity(added_names.len());
+        for name in added_names {
+            if let Some(resource) = self.resources.get(&name) {
+                added.push(resource.clone());
+            } else if let Some(lazy) = &self.lazy_resources {
+                if let Some(resource) = lazy.get(&name)? {
+                    added.push(resource.clone());
+                }
+            }
+        }
+
+        let mut reloaded = Self::default();
+
+        for layer in &self.layers {
+            let data = if layer.path.as_deref() == Some(path) {
+                data
+            } else {
+                layer.data
+            };
+
+            reloaded.index_layer(data)?;
+            reloaded.layers.push(IndexedLayer {
+                path: layer.path.clone(),
+                data,
+            });
+        }
+
+        if self.indexed_builtin_extension_modules {
+            reloaded.index_interpreter_builtin_extension_modules()?;
+        }
+        if self.indexed_frozen_modules {
+            reloaded.index_interpreter_frozen_modules()?;
+        }
+
+        for resource in added {
+            reloaded.add_resource(resource)?;
+        }
+
+        self.resources = reloaded.resources;
+        self.lazy_resources = reloaded.lazy_resources;
+        self.layers_count = reloaded.layers_count;
+        self.layers = reloaded.layers;
+        self.resource_layers = reloaded.resource_layers;
         self.backing_mmaps.push(mapped);
 
         Ok(())
@@ -554,6 +1149,13 @@ impl<'a> PythonResourcesState<'a, u8> {
                 }
             };
 
+            if let Some(lazy) = &mut self.lazy_resources {
+                if let Some(existing) = lazy.get_mut(name_str)? {
+                    existing.is_python_builtin_extension_module = true;
+                    continue;
+                }
+            }
+
             self.resources
                 .entry(name_str.into())
                 .and_modify(|r| {
@@ -566,6 +1168,8 @@ impl<'a> PythonResourcesState<'a, u8> {
                 });
         }
 
+        self.indexed_builtin_extension_modules = true;
+
         Ok(())
     }
 
@@ -586,6 +1190,13 @@ impl<'a> PythonResourcesState<'a, u8> {
                 }
             };
 
+            if let Some(lazy) = &mut self.lazy_resources {
+                if let Some(existing) = lazy.get_mut(name_str)? {
+                    existing.is_python_frozen_module = true;
+                    continue;
+                }
+            }
+
             self.resources
                 .entry(name_str.into())
                 .and_modify(|r| {
@@ -598,6 +1209,8 @@ impl<'a> PythonResourcesState<'a, u8> {
                 });
         }
 
+        self.indexed_frozen_modules = true;
+
         Ok(())
     }
 
@@ -614,8 +1227,67 @@ impl<'a> PythonResourcesState<'a, u8> {
     }
 
     /// Says whether a named resource exists.
-    pub fn has_resource(&self, name: &str) -> bool {
-        self.resources.contains_key(name)
+    pub fn has_resource(&self, name: &str) -> Result<bool, &'static str> {
+        Ok(self.get_resource(name)?.is_some())
+    }
+
+    /// Obtain a named resource.
+    ///
+    /// Tombstones aren't returned. Errors parsing a lazily indexed resource are
+    /// returned.
+    fn get_resource(&self, name: &str) -> Result<Option<&Resource<'a, u8>>, &'static str> {
+        let lazy = match &self.lazy_resources {
+            Some(lazy) => lazy.get(name)?,
+            None => None,
+        };
+
+        let resource = match lazy {
+            Some(resource) => resource,
+            None => match self.resources.get(name) {
+                Some(resource) => resource,
+                None => return Ok(None),
+            },
+        };
+
+        if resource.is_tombstone {
+            Ok(None)
+        } else {
+            Ok(Some(resource))
+        }
+    }
+
+    /// Obtain all resources, including tombstones.
+    ///
+    /// Resources not yet parsed are parsed.
+    fn all_resources(&self) -> Result<Vec<&Resource<'a, u8>>, &'static str> {
+        let mut resources = match &self.lazy_resources {
+            Some(lazy) => lazy.iter().collect::<Result<Vec<_>, _>>()?,
+            None => vec![],
+        };
+        resources.extend(self.resources.values());
+
+        Ok(resources)
+    }
+
+    /// Obtain all resources that aren't tombstones.
+    pub(crate) fn visible_resources(&self) -> Result<Vec<&Resource<'a, u8>>, &'static str> {
+        let mut resources = self.all_resources()?;
+        resources.retain(|resource| !resource.is_tombstone);
+
+        Ok(resources)
+    }
+
+    /// Obtain the index of the packed resources source a resource came from.
+    ///
+    /// Returns `None` if the resource wasn't indexed from packed resources data.
+    pub fn resource_layer(&self, name: &str) -> Option<usize> {
+        match self.resource_layers.get(name) {
+            Some(layer) => *layer,
+            None => match &self.lazy_resources {
+                Some(lazy) if matches!(lazy.contains(name), Ok(true)) => Some(0),
+                _ => None,
+            },
+        }
     }
 
     /// Add a resource to the instance.
@@ -626,6 +1298,16 @@ impl<'a> PythonResourcesState<'a, u8> {
         &mut self,
         resource: Resource<'resource, u8>,
     ) -> Result<(), &'static str> {
+        if let Some(lazy) = &mut self.lazy_resources {
+            if let Some(existing) = lazy.get_mut(&resource.name)? {
+                self.resource_layers.insert(resource.name.clone(), None);
+                *existing = resource;
+
+                return Ok(());
+            }
+        }
+
+        self.resource_layers.insert(resource.name.clone(), None);
         self.resources.insert(resource.name.clone(), resource);
 
         Ok(())
@@ -636,7 +1318,7 @@ impl<'a> PythonResourcesState<'a, u8> {
         &self,
         name: &str,
         optimize_level: BytecodeOptimizationLevel,
-    ) -> Option<ImportablePythonModule<u8>> {
+    ) -> Result<Option<ImportablePythonModule<u8>>, &'static str> {
         // Python's filesystem based importer accepts `foo.__init__` as a valid
         // module name. When these names are encountered, it fails to recognize
         // that `__init__` is special and happily searches for and uses/imports a
@@ -662,9 +1344,9 @@ impl<'a> PythonResourcesState<'a, u8> {
         // for recognizing `__init__` because Python code in the wild relies on it.
         let name = name.strip_suffix(".__init__").unwrap_or(name);
 
-        let resource = match self.resources.get(name) {
+        let resource = match self.get_resource(name)? {
             Some(entry) => entry,
-            None => return None,
+            None => return Ok(None),
         };
 
         // Since resources can exist as multiple types and it is possible
@@ -693,11 +1375,12 @@ impl<'a> PythonResourcesState<'a, u8> {
         // 3. extension modules
         // 4. module (covers both source and bytecode)
 
-        if resource.is_python_builtin_extension_module {
+        Ok(if resource.is_python_builtin_extension_module {
             Some(ImportablePythonModule {
                 resource,
                 current_exe: &self.current_exe,
                 origin: &self.origin,
+                bytecode_cache_dir: self.bytecode_cache_dir.as_deref(),
                 flavor: ModuleFlavor::Builtin,
                 is_package: resource.is_python_package,
             })
@@ -706,6 +1389,7 @@ impl<'a> PythonResourcesState<'a, u8> {
                 resource,
                 current_exe: &self.current_exe,
                 origin: &self.origin,
+                bytecode_cache_dir: self.bytecode_cache_dir.as_deref(),
                 flavor: ModuleFlavor::Frozen,
                 is_package: resource.is_python_package,
             })
@@ -714,6 +1398,7 @@ impl<'a> PythonResourcesState<'a, u8> {
                 resource,
                 current_exe: &self.current_exe,
                 origin: &self.origin,
+                bytecode_cache_dir: self.bytecode_cache_dir.as_deref(),
                 flavor: ModuleFlavor::Extension,
                 is_package: resource.is_python_package,
             })
@@ -723,6 +1408,7 @@ impl<'a> PythonResourcesState<'a, u8> {
                     resource,
                     current_exe: &self.current_exe,
                     origin: &self.origin,
+                    bytecode_cache_dir: self.bytecode_cache_dir.as_deref(),
                     flavor: ModuleFlavor::SourceBytecode,
                     is_package: resource.is_python_package,
                 })
@@ -731,7 +1417,14 @@ impl<'a> PythonResourcesState<'a, u8> {
             }
         } else {
             None
-        }
+        })
+    }
+
+    fn record_package_resource_trace(&self, package: &str, name: &str) {
+        self.record_import_trace(|| ImportTraceEntry::PackageResource {
+            package: package.to_string(),
+            name: name.to_string(),
+        });
     }
 
     /// Obtain a single named resource in a package.
@@ -745,7 +1438,7 @@ impl<'a> PythonResourcesState<'a, u8> {
         package: &str,
         resource_name: &str,
     ) -> PyResult<Option<&'p PyAny>> {
-        let entry = match self.resources.get(package) {
+        let entry = match self.get_resource(package).map_err(PyValueError::new_err)? {
             Some(entry) => entry,
             None => return Ok(None),
         };
@@ -755,7 +1448,13 @@ impl<'a> PythonResourcesState<'a, u8> {
                 let io_module = py.import("io")?;
                 let bytes_io = io_module.getattr("BytesIO")?;
 
-                let data = PyBytes::new(py, data);
+                let data = entry
+                    .resolve_field_data(ResourceField::InMemoryResourcesData, data)
+                    .map_err(PyOSError::new_err)?;
+                let data = PyBytes::new(py, &data);
+
+                self.record_package_resource_trace(package, resource_name);
+
                 return Ok(Some(bytes_io.call((data,), None)?));
             }
         }
@@ -765,6 +1464,8 @@ impl<'a> PythonResourcesState<'a, u8> {
                 let path = self.origin.join(path);
                 let io_module = py.import("io")?;
 
+                self.record_package_resource_trace(package, resource_name);
+
                 return Ok(Some(
                     io_module
                         .getattr("FileIO")?
@@ -777,29 +1478,33 @@ impl<'a> PythonResourcesState<'a, u8> {
     }
 
     /// Determines whether a specific package + name pair is a known Python package resource.
-    pub fn is_package_resource(&self, package: &str, resource_name: &str) -> bool {
-        if let Some(entry) = self.resources.get(package) {
+    pub fn is_package_resource(
+        &self,
+        package: &str,
+        resource_name: &str,
+    ) -> Result<bool, &'static str> {
+        if let Some(entry) = self.get_resource(package)? {
             if let Some(resources) = &entry.in_memory_package_resources {
                 if resources.contains_key(resource_name) {
-                    return true;
+                    return Ok(true);
                 }
             }
 
             if let Some(resources) = &entry.relative_path_package_resources {
                 if resources.contains_key(resource_name) {
-                    return true;
+                    return Ok(true);
                 }
             }
         }
 
-        false
+        Ok(false)
     }
 
     /// Obtain the resources available in a Python package, as a Python list.
     ///
     /// The names are returned in sorted order.
     pub fn package_resource_names<'p>(&self, py: Python<'p>, package: &str) -> PyResult<&'p PyAny> {
-        let entry = match self.resources.get(package) {
+        let entry = match self.get_resource(package).map_err(PyValueError::new_err)? {
             Some(entry) => entry,
             None => return Ok(PyList::empty(py).into()),
         };
@@ -823,7 +1528,11 @@ impl<'a> PythonResourcesState<'a, u8> {
     }
 
     /// Whether the given resource name is a directory with resources.
-    pub fn is_package_resource_directory(&self, package: &str, name: &str) -> bool {
+    pub fn is_package_resource_directory(
+        &self,
+        package: &str,
+        name: &str,
+    ) -> Result<bool, &'static str> {
         // Normalize to UNIX style paths.
         let name = name.replace('\\', "/");
 
@@ -833,27 +1542,29 @@ impl<'a> PythonResourcesState<'a, u8> {
             format!("{}/", name)
         };
 
-        if let Some(entry) = self.resources.get(package) {
+        if let Some(entry) = self.get_resource(package)? {
             if let Some(resources) = &entry.in_memory_package_resources {
                 if resources.keys().any(|path| path.starts_with(&prefix)) {
-                    return true;
+                    return Ok(true);
                 }
             }
 
             if let Some(resources) = &entry.relative_path_package_resources {
                 if resources.keys().any(|path| path.starts_with(&prefix)) {
-                    return true;
+                    return Ok(true);
                 }
             }
-
-            false
-        } else {
-            false
         }
+
+        Ok(false)
     }
 
     /// Resolve package resources in a directory.
-    pub fn package_resources_list_directory(&self, package: &str, name: &str) -> Vec<String> {
+    pub fn package_resources_list_directory(
+        &self,
+        package: &str,
+        name: &str,
+    ) -> Result<Vec<String>, &'static str> {
         let name = name.replace('\\', "/");
 
         let prefix = if name.ends_with('/') {
@@ -890,7 +1601,45 @@ impl<'a> PythonResourcesState<'a, u8> {
 
         let mut entries = BTreeSet::new();
 
-        if let Some(entry) = self.resources.get(package) {
+        if let Some(entry) = self.get_resource(package)? {
+            if let Some(resources) = &entry.in_memory_package_resources {
+                entries.extend(resources.keys().filter_map(filter_map_resource));
+            }
+
+            if let Some(resources) = &entry.relative_path_package_resources {
+                entries.extend(resources.keys().filter_map(filter_map_resource));
+            }
+        }
+
+        Ok(entries.into_iter().collect::<Vec<_>>())
+    }
+
+    /// Resolve package resource directories in a directory.
+    ///
+    /// Directories are implied by the paths of resources and only contain
+    /// resources in them or in their descendants.
+    pub fn package_resources_list_subdirectories(
+        &self,
+        package: &str,
+        name: &str,
+    ) -> Result<Vec<String>, &'static str> {
+        let name = name.replace('\\', "/");
+
+        let prefix = if name.ends_with('/') || name.is_empty() {
+            name
+        } else {
+            format!("{}/", name)
+        };
+
+        let filter_map_resource = |path: &'_ Cow<'_, str>| -> Option<String> {
+            path.strip_prefix(&prefix)
+                .and_then(|name| name.split_once('/'))
+                .map(|(directory, _)| directory.to_string())
+        };
+
+        let mut entries = BTreeSet::new();
+
+        if let Some(entry) = self.get_resource(package)? {
             if let Some(resources) = &entry.in_memory_package_resources {
                 entries.extend(resources.keys().filter_map(filter_map_resource));
             }
@@ -900,7 +1649,7 @@ impl<'a> PythonResourcesState<'a, u8> {
             }
         }
 
-        entries.into_iter().collect::<Vec<_>>()
+        Ok(entries.into_iter().collect::<Vec<_>>())
     }
 
     /// Attempt to resolve a PyBytes for resource data given a relative path.
@@ -1008,11 +1757,20 @@ impl<'a> PythonResourcesState<'a, u8> {
             let resource_name = name_parts.join("/");
             let resource_name_ref: &str = &resource_name;
 
-            if let Some(entry) = self.resources.get(package_name_ref) {
+            if let Some(entry) = self
+                .get_resource(package_name_ref)
+                .map_err(PyValueError::new_err)?
+            {
                 if check_in_memory {
                     if let Some(resources) = &entry.in_memory_package_resources {
                         if let Some(data) = resources.get(resource_name_ref) {
-                            return Ok(PyBytes::new(py, data).into());
+                            let data = entry
+                                .resolve_field_data(ResourceField::InMemoryResourcesData, data)
+                                .map_err(PyOSError::new_err)?;
+
+                            self.record_package_resource_trace(package_name_ref, resource_name_ref);
+
+                            return Ok(PyBytes::new(py, &data).into());
                         }
                     }
                 }
@@ -1022,6 +1780,8 @@ impl<'a> PythonResourcesState<'a, u8> {
                         if let Some(resource_relative_path) = resources.get(resource_name_ref) {
                             let resource_path = self.origin.join(resource_relative_path);
 
+                            self.record_package_resource_trace(package_name_ref, resource_name_ref);
+
                             let io_module = py.import("io")?;
 
                             let fh = io_module
@@ -1063,8 +1823,9 @@ impl<'a> PythonResourcesState<'a, u8> {
         optimize_level: BytecodeOptimizationLevel,
     ) -> PyResult<&'p PyList> {
         let infos: PyResult<Vec<_>> = self
-            .resources
-            .values()
+            .visible_resources()
+            .map_err(PyValueError::new_err)?
+            .into_iter()
             .filter(|r| {
                 r.is_python_extension_module
                     || (r.is_python_module && is_module_importable(r, optimize_level))
@@ -1093,9 +1854,13 @@ impl<'a> PythonResourcesState<'a, u8> {
     }
 
     /// Resolve the names of package distributions matching a name filter.
-    pub fn package_distribution_names(&self, filter: impl Fn(&str) -> bool) -> Vec<&'_ str> {
-        self.resources
-            .values()
+    pub fn package_distribution_names(
+        &self,
+        filter: impl Fn(&str) -> bool,
+    ) -> Result<Vec<&'_ str>, &'static str> {
+        Ok(self
+            .visible_resources()?
+            .into_iter()
             .filter(|r| {
                 r.is_python_package
                     && (r.in_memory_distribution_resources.is_some()
@@ -1103,7 +1868,7 @@ impl<'a> PythonResourcesState<'a, u8> {
             })
             .filter(|r| filter(r.name.as_ref()))
             .map(|r| r.name.as_ref())
-            .collect::<Vec<_>>()
+            .collect::<Vec<_>>())
     }
 
     /// Resolve data belonging to a package distribution resource.
@@ -1112,10 +1877,19 @@ impl<'a> PythonResourcesState<'a, u8> {
         package: &str,
         name: &str,
     ) -> Result<Option<Cow<'_, [u8]>>> {
-        if let Some(entry) = self.resources.get(package) {
+        if let Some(entry) = self.get_resource(package).map_err(|e| anyhow!(e))? {
             if let Some(resources) = &entry.in_memory_distribution_resources {
                 if let Some(data) = resources.get(name) {
-                    return Ok(Some(Cow::Borrowed(data.as_ref())));
+                    self.record_import_trace(|| ImportTraceEntry::PackageDistributionResource {
+                        package: package.to_string(),
+                        name: name.to_string(),
+                    });
+
+                    return Ok(Some(
+                        entry
+                            .resolve_field_data(ResourceField::InMemoryDistributionResource, data)
+                            .map_err(|e| anyhow!("resolving distribution resource: {}", e))?,
+                    ));
                 }
             }
 
@@ -1124,6 +1898,11 @@ impl<'a> PythonResourcesState<'a, u8> {
                     let path = &self.origin.join(path);
                     let data = std::fs::read(&path)?;
 
+                    self.record_import_trace(|| ImportTraceEntry::PackageDistributionResource {
+                        package: package.to_string(),
+                        name: name.to_string(),
+                    });
+
                     return Ok(Some(Cow::Owned(data)));
                 }
             }
@@ -1139,7 +1918,7 @@ impl<'a> PythonResourcesState<'a, u8> {
         &self,
         package: &str,
         name: &str,
-    ) -> bool {
+    ) -> Result<bool, &'static str> {
         let name = name.replace('\\', "/");
 
         let prefix = if name.ends_with('/') {
@@ -1148,23 +1927,21 @@ impl<'a> PythonResourcesState<'a, u8> {
             format!("{}/", name)
         };
 
-        if let Some(entry) = &self.resources.get(package) {
+        if let Some(entry) = &self.get_resource(package)? {
             if let Some(resources) = &entry.in_memory_distribution_resources {
                 if resources.keys().any(|path| path.starts_with(&prefix)) {
-                    return true;
+                    return Ok(true);
                 }
             }
 
             if let Some(resources) = &entry.relative_path_distribution_resources {
                 if resources.keys().any(|path| path.starts_with(&prefix)) {
-                    return true;
+                    return Ok(true);
                 }
             }
-
-            false
-        } else {
-            false
         }
+
+        Ok(false)
     }
 
     /// Obtain contents in a package distribution resources "directory."
@@ -1172,7 +1949,7 @@ impl<'a> PythonResourcesState<'a, u8> {
         &'slf self,
         package: &str,
         name: &str,
-    ) -> Vec<&'slf str> {
+    ) -> Result<Vec<&'slf str>, &'static str> {
         let name = name.replace('\\', "/");
 
         let prefix = if name.ends_with('/') {
@@ -1209,7 +1986,7 @@ impl<'a> PythonResourcesState<'a, u8> {
 
         let mut entries = BTreeSet::new();
 
-        if let Some(entry) = self.resources.get(package) {
+        if let Some(entry) = self.get_resource(package)? {
             if let Some(resources) = &entry.in_memory_distribution_resources {
                 entries.extend(resources.keys().filter_map(filter_map_resource));
             }
@@ -1219,31 +1996,63 @@ impl<'a> PythonResourcesState<'a, u8> {
             }
         }
 
-        entries.into_iter().collect::<Vec<_>>()
+        Ok(entries.into_iter().collect::<Vec<_>>())
     }
 
     /// Resolve content of a shared library to load from memory.
-    pub fn resolve_in_memory_shared_library_data(&self, name: &str) -> Option<&[u8]> {
-        if let Some(entry) = &self.resources.get(name) {
+    ///
+    /// Data is decompressed if necessary.
+    pub fn resolve_in_memory_shared_library_data(
+        &self,
+        name: &str,
+    ) -> Result<Option<Cow<'_, [u8]>>, &'static str> {
+        if let Some(entry) = &self.get_resource(name)? {
             if let Some(library_data) = &entry.in_memory_shared_library {
-                Some(library_data.as_ref())
+                Ok(Some(entry.resolve_field_data(
+                    ResourceField::InMemorySharedLibrary,
+                    library_data,
+                )?))
             } else {
-                None
+                Ok(None)
             }
         } else {
-            None
+            Ok(None)
         }
     }
 
+    /// Resolve the names of shared libraries a named resource depends on.
+    pub fn shared_library_dependency_names(&self, name: &str) -> Result<Vec<String>, &'static str> {
+        Ok(self
+            .get_resource(name)?
+            .and_then(|entry| entry.shared_library_dependency_names.as_ref())
+            .map(|names| names.iter().map(|name| name.to_string()).collect())
+            .unwrap_or_default())
+    }
+
     /// Convert indexed resources to a [PyList].
     pub fn resources_as_py_list<'p>(&self, py: Python<'p>) -> PyResult<&'p PyList> {
-        let mut resources = self.resources.values().collect::<Vec<_>>();
+        self.filtered_resources_as_py_list(py, |_| true)
+    }
+
+    /// Convert indexed resources matching a filter to a [PyList].
+    pub fn filtered_resources_as_py_list<'p>(
+        &self,
+        py: Python<'p>,
+        filter: impl Fn(&Resource<'a, u8>) -> bool,
+    ) -> PyResult<&'p PyList> {
+        let mut resources = self.visible_resources().map_err(PyValueError::new_err)?;
+        resources.retain(|r| filter(r));
         resources.sort_by_key(|r| &r.name);
 
         let objects = resources
             .iter()
-            .map(|r| resource_to_pyobject(py, r))
-            .collect::<Result<Vec<_>, _>>()?;
+            .map(|r| {
+                let object = resource_to_pyobject(py, r)?;
+                object.borrow_mut().layer = self.resource_layer(&r.name);
+
+                Ok(object)
+            })
+            .collect::<PyResult<Vec<_>>>()?;
 
         Ok(PyList::new(py, objects))
     }
@@ -1252,14 +2061,17 @@ impl<'a> PythonResourcesState<'a, u8> {
     ///
     /// `ignore_built` and `ignore_frozen` specify whether to ignore built-in
     /// extension modules and frozen modules, respectively.
+    ///
+    /// Tombstones are serialized so the data can be used as an overlay.
     pub fn serialize_resources(
         &self,
         ignore_builtin: bool,
         ignore_frozen: bool,
     ) -> Result<Vec<u8>> {
         let mut resources = self
-            .resources
-            .values()
+            .all_resources()
+            .map_err(|e| anyhow!(e))?
+            .into_iter()
             .filter(|resource| {
                 // This assumes builtins and frozen are mutually exclusive with other types.
                 !((resource.is_python_builtin_extension_module && ignore_builtin)
@@ -1272,7 +2084,22 @@ impl<'a> PythonResourcesState<'a, u8> {
 
         let mut buffer = Vec::new();
 
-        python_packed_resources::write_packed_resources_v3(&resources, &mut buffer, None)?;
+        // Compressed data and tombstones can only be represented by version 4.
+        // Otherwise stick with version 3 for compatibility with older parsers.
+        if resources
+            .iter()
+            .any(|r| r.has_compressed_fields() || r.is_tombstone)
+        {
+            python_packed_resources::write_packed_resources_v4(
+                &resources,
+                &mut buffer,
+                None,
+                CompressionPolicy::None,
+                false,
+            )?;
+        } else {
+            python_packed_resources::write_packed_resources_v3(&resources, &mut buffer, None)?;
+        }
 
         Ok(buffer)
     }
@@ -1281,6 +2108,7 @@ impl<'a> PythonResourcesState<'a, u8> {
 #[pyclass(module = "oxidized_importer")]
 pub(crate) struct OxidizedResource {
     resource: RefCell<Resource<'static, u8>>,
+    layer: Option<usize>,
 }
 
 #[pymethods]
@@ -1296,9 +2124,27 @@ impl OxidizedResource {
     fn new() -> PyResult<Self> {
         Ok(Self {
             resource: RefCell::new(Resource::<u8>::default()),
+            layer: None,
         })
     }
 
+    #[getter]
+    fn get_layer(&self) -> Option<usize> {
+        self.layer
+    }
+
+    #[getter]
+    fn get_is_tombstone(&self) -> bool {
+        self.resource.borrow().is_tombstone
+    }
+
+    #[setter]
+    fn set_is_tombstone(&self, value: bool) -> PyResult<()> {
+        self.resource.borrow_mut().is_tombstone = value;
+
+        Ok(())
+    }
+
     #[getter]
     fn get_is_module(&self) -> bool {
         self.resource.borrow().is_python_module
@@ -1762,10 +2608,14 @@ pub(crate) fn resource_to_pyobject<'p>(
     py: Python<'p>,
     resource: &Resource<u8>,
 ) -> PyResult<&'p PyCell<OxidizedResource>> {
+    // Python only ever sees uncompressed data.
+    let resource = resource.to_decompressed().map_err(PyValueError::new_err)?;
+
     PyCell::new(
         py,
         OxidizedResource {
-            resource: RefCell::new(resource.to_owned()),
+            resource: RefCell::new(resource),
+            layer: None,
         },
     )
 }
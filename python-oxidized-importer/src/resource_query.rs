@@ -0,0 +1,524 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Querying and exporting indexed resources.
+
+Resources are selected by a name glob and type flags without converting
+every indexed resource to a Python object. Selected resources can be
+exported as a new packed resources blob.
+*/
+
+use {
+    crate::python_resources::PythonResourcesState,
+    anyhow::{anyhow, Context, Result},
+    pyo3::{exceptions::PyValueError, prelude::*},
+    python_packaging::{
+        bytecode::NoBytecodeCompiler,
+        location::{AbstractResourceLocation, ConcreteResourceLocation},
+        resource::{
+            BytecodeOptimizationLevel, LibraryDependency, PythonExtensionModule,
+            PythonModuleBytecode, PythonModuleSource, PythonPackageDistributionResource,
+            PythonPackageDistributionResourceFlavor, PythonPackageResource, SharedLibrary,
+        },
+        resource_collection::PythonResourceCollector,
+    },
+    python_packed_resources::{Resource, ResourceField},
+    std::{borrow::Cow, collections::HashMap, path::Path},
+    tugger_file_manifest::{File, FileData, FileEntry},
+};
+
+/// Names of type flags, as exposed by `OxidizedResource` attributes.
+const FLAG_NAMES: &[&str] = &[
+    "is_module",
+    "is_builtin_extension_module",
+    "is_frozen_module",
+    "is_extension_module",
+    "is_shared_library",
+    "is_package",
+    "is_namespace_package",
+    "is_utf8_filename_data",
+];
+
+/// Whether a resource has a named type flag set.
+fn has_flag(resource: &Resource<u8>, flag: &str) -> bool {
+    match flag {
+        "is_module" => resource.is_python_module,
+        "is_builtin_extension_module" => resource.is_python_builtin_extension_module,
+        "is_frozen_module" => resource.is_python_frozen_module,
+        "is_extension_module" => resource.is_python_extension_module,
+        "is_shared_library" => resource.is_shared_library,
+        "is_package" => resource.is_python_package,
+        "is_namespace_package" => resource.is_python_namespace_package,
+        "is_utf8_filename_data" => resource.is_utf8_filename_data,
+        _ => false,
+    }
+}
+
+/// Whether a name matches a glob pattern.
+///
+/// Patterns have the syntax of Python's `fnmatch` module: `*` matches
+/// everything, `?` matches any single character, `[seq]` matches any
+/// character in `seq`, and `[!seq]` matches any character not in `seq`.
+fn glob_matches(pattern: &[char], name: &[char]) -> bool {
+    // Position to resume from when a `*` needs to consume another character.
+    let mut backtrack = None;
+    let (mut p, mut n) = (0, 0);
+
+    while n < name.len() {
+        let advanced = match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(p + 1),
+            Some('[') => match_class(&pattern[p..], name[n]).map(|len| p + len),
+            Some(c) if *c == name[n] => Some(p + 1),
+            _ => None,
+        };
+
+        match (advanced, backtrack) {
+            (Some(next), _) => {
+                p = next;
+                n += 1;
+            }
+            (None, Some((star, consumed))) => {
+                p = star + 1;
+                n = consumed + 1;
+                backtrack = Some((star, consumed + 1));
+            }
+            (None, None) => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Match a character against a `[...]` class at the start of `pattern`.
+///
+/// Returns the length of the class if it matches. A `[` without a closing
+/// `]` matches itself.
+fn match_class(pattern: &[char], c: char) -> Option<usize> {
+    let negate = pattern.get(1) == Some(&'!');
+    let start = if negate { 2 } else { 1 };
+
+    // A `]` immediately following the opening is part of the class.
+    let end = match pattern[(start + 1).min(pattern.len())..]
+        .iter()
+        .position(|x| *x == ']')
+    {
+        Some(pos) => start + 1 + pos,
+        None => return if c == '[' { Some(1) } else { None },
+    };
+
+    let class = &pattern[start..end];
+    let mut matched = false;
+    let mut i = 0;
+
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            matched |= class[i] <= c && c <= class[i + 2];
+            i += 3;
+        } else {
+            matched |= class[i] == c;
+            i += 1;
+        }
+    }
+
+    if matched != negate {
+        Some(end + 1)
+    } else {
+        None
+    }
+}
+
+/// Selects resources by name and type flags.
+pub(crate) struct ResourceFilter {
+    name: Option<Vec<char>>,
+    flags: Vec<String>,
+}
+
+impl ResourceFilter {
+    /// Construct an instance from Python arguments.
+    ///
+    /// `name` is a glob pattern. All `flags` must be set on a resource for
+    /// it to match.
+    pub fn new(name: Option<&str>, flags: Option<Vec<String>>) -> PyResult<Self> {
+        let flags = flags.unwrap_or_default();
+
+        if let Some(flag) = flags.iter().find(|f| !FLAG_NAMES.contains(&f.as_str())) {
+            return Err(PyValueError::new_err(format!(
+                "unknown resource flag: {}; expected one of {}",
+                flag,
+                FLAG_NAMES.join(", ")
+            )));
+        }
+
+        Ok(Self {
+            name: name.map(|x| x.chars().collect()),
+            flags,
+        })
+    }
+
+    pub fn matches(&self, resource: &Resource<u8>) -> bool {
+        self.flags.iter().all(|flag| has_flag(resource, flag))
+            && match &self.name {
+                Some(pattern) => glob_matches(pattern, &resource.name.chars().collect::<Vec<_>>()),
+                None => true,
+            }
+    }
+}
+
+/// Obtain the sizes of data fields of a resource.
+///
+/// Only fields holding data in the packed resources are reported. Sizes are
+/// of the stored data, which is compressed for compressed fields. Fields
+/// holding a mapping report the sum of the sizes of their values.
+pub(crate) fn resource_field_sizes(resource: &Resource<u8>) -> Vec<(&'static str, usize)> {
+    fn map_size(map: &HashMap<Cow<str>, Cow<[u8]>>) -> usize {
+        map.values().map(|v| v.len()).sum()
+    }
+
+    [
+        (
+            "in_memory_source",
+            resource.in_memory_source.as_ref().map(|x| x.len()),
+        ),
+        (
+            "in_memory_bytecode",
+            resource.in_memory_bytecode.as_ref().map(|x| x.len()),
+        ),
+        (
+            "in_memory_bytecode_opt1",
+            resource.in_memory_bytecode_opt1.as_ref().map(|x| x.len()),
+        ),
+        (
+            "in_memory_bytecode_opt2",
+            resource.in_memory_bytecode_opt2.as_ref().map(|x| x.len()),
+        ),
+        (
+            "in_memory_extension_module_shared_library",
+            resource
+                .in_memory_extension_module_shared_library
+                .as_ref()
+                .map(|x| x.len()),
+        ),
+        (
+            "in_memory_package_resources",
+            resource.in_memory_package_resources.as_ref().map(map_size),
+        ),
+        (
+            "in_memory_distribution_resources",
+            resource
+                .in_memory_distribution_resources
+                .as_ref()
+                .map(map_size),
+        ),
+        (
+            "in_memory_shared_library",
+            resource.in_memory_shared_library.as_ref().map(|x| x.len()),
+        ),
+        (
+            "file_data_embedded",
+            resource.file_data_embedded.as_ref().map(|x| x.len()),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(field, size)| size.map(|size| (field, size)))
+    .collect()
+}
+
+/// Resolves the data of resource fields, reading relative paths from `origin`.
+struct FieldResolver<'r, 'a> {
+    resource: &'r Resource<'a, u8>,
+    origin: &'r Path,
+}
+
+impl<'r, 'a> FieldResolver<'r, 'a> {
+    fn resolve(
+        &self,
+        field: ResourceField,
+        data: Option<&Cow<[u8]>>,
+        path: Option<&Cow<Path>>,
+    ) -> Result<Option<Vec<u8>>> {
+        if let Some(data) = data {
+            Ok(Some(
+                self.resource
+                    .resolve_field_data(field, data)
+                    .map_err(|e| anyhow!("resolving {:?} of {}: {}", field, self.resource.name, e))?
+                    .into_owned(),
+            ))
+        } else if let Some(path) = path {
+            let path = self.origin.join(path);
+
+            Ok(Some(
+                std::fs::read(&path).with_context(|| format!("reading {}", path.display()))?,
+            ))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Resolve a field mapping names to data.
+    fn resolve_map(
+        &self,
+        field: ResourceField,
+        data: Option<&HashMap<Cow<str>, Cow<[u8]>>>,
+        paths: Option<&HashMap<Cow<str>, Cow<Path>>>,
+    ) -> Result<Vec<(String, Vec<u8>)>> {
+        let mut entries = vec![];
+
+        for (name, value) in data.into_iter().flatten() {
+            entries.push((
+                name.to_string(),
+                self.resolve(field, Some(value), None)?.unwrap_or_default(),
+            ));
+        }
+
+        for (name, path) in paths.into_iter().flatten() {
+            entries.push((
+                name.to_string(),
+                self.resolve(field, None, Some(path))?.unwrap_or_default(),
+            ));
+        }
+
+        Ok(entries)
+    }
+}
+
+/// Add a resource to a collector, holding all of its data in memory.
+fn add_resource(
+    collector: &mut PythonResourceCollector,
+    state: &PythonResourcesState<u8>,
+    resource: &Resource<u8>,
+    cache_tag: &str,
+) -> Result<()> {
+    let location = ConcreteResourceLocation::InMemory;
+    let name = resource.name.to_string();
+    let resolver = FieldResolver {
+        resource,
+        origin: state.origin(),
+    };
+
+    if resource.is_python_module {
+        if let Some(source) = resolver.resolve(
+            ResourceField::InMemorySource,
+            resource.in_memory_source.as_ref(),
+            resource.relative_path_module_source.as_ref(),
+        )? {
+            collector.add_python_module_source(
+                &PythonModuleSource {
+                    name: name.clone(),
+                    source: FileData::Memory(source),
+                    is_package: resource.is_python_package,
+                    cache_tag: cache_tag.to_string(),
+                    is_stdlib: false,
+                    is_test: false,
+                },
+                &location,
+            )?;
+        }
+
+        for (field, level, data, path) in [
+            (
+                ResourceField::InMemoryBytecode,
+                BytecodeOptimizationLevel::Zero,
+                resource.in_memory_bytecode.as_ref(),
+                resource.relative_path_module_bytecode.as_ref(),
+            ),
+            (
+                ResourceField::InMemoryBytecodeOpt1,
+                BytecodeOptimizationLevel::One,
+                resource.in_memory_bytecode_opt1.as_ref(),
+                resource.relative_path_module_bytecode_opt1.as_ref(),
+            ),
+            (
+                ResourceField::InMemoryBytecodeOpt2,
+                BytecodeOptimizationLevel::Two,
+                resource.in_memory_bytecode_opt2.as_ref(),
+                resource.relative_path_module_bytecode_opt2.as_ref(),
+            ),
+        ] {
+            // Bytecode files on the filesystem have a header. In-memory
+            // bytecode doesn't.
+            let bytecode = match (data, resolver.resolve(field, data, path)?) {
+                (Some(_), Some(bytecode)) => bytecode,
+                (None, Some(bytecode)) if bytecode.len() >= 16 => bytecode[16..].to_vec(),
+                (None, Some(_)) => return Err(anyhow!("bytecode file for {} is too short", name)),
+                (_, None) => continue,
+            };
+
+            collector.add_python_module_bytecode(
+                &PythonModuleBytecode::new(
+                    &name,
+                    level,
+                    resource.is_python_package,
+                    cache_tag,
+                    &bytecode,
+                ),
+                &location,
+            )?;
+        }
+
+        for (relative_name, data) in resolver.resolve_map(
+            ResourceField::InMemoryResourcesData,
+            resource.in_memory_package_resources.as_ref(),
+            resource.relative_path_package_resources.as_ref(),
+        )? {
+            collector.add_python_package_resource(
+                &PythonPackageResource {
+                    leaf_package: name.clone(),
+                    relative_name,
+                    data: FileData::Memory(data),
+                    is_stdlib: false,
+                    is_test: false,
+                },
+                &location,
+            )?;
+        }
+
+        for (resource_name, data) in resolver.resolve_map(
+            ResourceField::InMemoryDistributionResource,
+            resource.in_memory_distribution_resources.as_ref(),
+            resource.relative_path_distribution_resources.as_ref(),
+        )? {
+            collector.add_python_package_distribution_resource(
+                &PythonPackageDistributionResource {
+                    location: PythonPackageDistributionResourceFlavor::DistInfo,
+                    package: name.clone(),
+                    version: String::new(),
+                    name: resource_name,
+                    data: FileData::Memory(data),
+                },
+                &location,
+            )?;
+        }
+    }
+
+    if resource.is_python_extension_module {
+        if let Some(data) = resolver.resolve(
+            ResourceField::InMemoryExtensionModuleSharedLibrary,
+            resource.in_memory_extension_module_shared_library.as_ref(),
+            resource
+                .relative_path_extension_module_shared_library
+                .as_ref(),
+        )? {
+            // Shared libraries the module depends on are resolved from the
+            // indexed resources.
+            let mut link_libraries = vec![];
+
+            for library in resource.shared_library_dependency_names.iter().flatten() {
+                let dynamic_library = state
+                    .resolve_in_memory_shared_library_data(library)
+                    .map_err(|e| anyhow!("resolving shared library {}: {}", library, e))?
+                    .map(|data| FileData::Memory(data.into_owned()));
+
+                link_libraries.push(LibraryDependency {
+                    name: library.to_string(),
+                    static_library: None,
+                    static_filename: None,
+                    dynamic_library,
+                    dynamic_filename: None,
+                    framework: false,
+                    system: false,
+                });
+            }
+
+            collector.add_python_extension_module(
+                &PythonExtensionModule {
+                    name: name.clone(),
+                    init_fn: None,
+                    extension_file_suffix: String::new(),
+                    shared_library: Some(FileData::Memory(data)),
+                    object_file_data: vec![],
+                    is_package: resource.is_python_package,
+                    link_libraries,
+                    is_stdlib: false,
+                    builtin_default: false,
+                    required: false,
+                    variant: None,
+                    license: None,
+                },
+                &location,
+            )?;
+        }
+    }
+
+    if resource.is_shared_library {
+        if let Some(data) = resolver.resolve(
+            ResourceField::InMemorySharedLibrary,
+            resource.in_memory_shared_library.as_ref(),
+            None,
+        )? {
+            collector.add_shared_library(
+                &SharedLibrary {
+                    name: name.clone(),
+                    data: FileData::Memory(data),
+                    filename: None,
+                },
+                &location,
+            )?;
+        }
+    }
+
+    if resource.is_utf8_filename_data {
+        let relative_path = resource
+            .file_data_utf8_relative_path
+            .as_ref()
+            .map(|p| Cow::Owned(Path::new(p.as_ref()).to_path_buf()));
+
+        if let Some(data) = resolver.resolve(
+            ResourceField::FileDataEmbedded,
+            resource.file_data_embedded.as_ref(),
+            relative_path.as_ref(),
+        )? {
+            collector.add_file_data(
+                &File::new(
+                    name.as_str(),
+                    FileEntry::new_from_data(data, resource.file_executable),
+                ),
+                &location,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Export resources as a packed resources blob.
+///
+/// Resources are added to a [PythonResourceCollector] with all of their data
+/// in memory. Data in relative paths is read from the filesystem. Built-in
+/// extension modules and frozen modules are part of the interpreter and
+/// aren't exported.
+pub(crate) fn export_resources(
+    state: &PythonResourcesState<u8>,
+    filter: &ResourceFilter,
+    cache_tag: &str,
+) -> Result<Vec<u8>> {
+    let mut collector = PythonResourceCollector::new(
+        vec![AbstractResourceLocation::InMemory],
+        vec![AbstractResourceLocation::InMemory],
+        false,
+        true,
+    );
+
+    for resource in state
+        .visible_resources()
+        .map_err(|e| anyhow!(e))?
+        .into_iter()
+        .filter(|r| filter.matches(r))
+    {
+        add_resource(&mut collector, state, resource, cache_tag)
+            .with_context(|| format!("exporting {}", resource.name))?;
+    }
+
+    let mut data = vec![];
+
+    collector
+        .compile_resources(&mut NoBytecodeCompiler)?
+        .write_packed_resources(&mut data)?;
+
+    Ok(data)
+}
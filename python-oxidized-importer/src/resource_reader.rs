@@ -4,7 +4,13 @@
 
 use {
     crate::importer::ImporterState,
-    pyo3::{exceptions::PyFileNotFoundError, prelude::*},
+    pyo3::{
+        exceptions::{
+            PyFileNotFoundError, PyIsADirectoryError, PyNotADirectoryError, PyValueError,
+        },
+        prelude::*,
+        types::{PyDict, PyList, PyString, PyTuple},
+    },
     std::sync::Arc,
 };
 
@@ -58,6 +64,7 @@ impl OxidizedResourceReader {
             .state
             .get_resources_state()
             .is_package_resource(&self.package, name)
+            .map_err(PyValueError::new_err)?
         {
             Ok(true)
         } else {
@@ -80,4 +87,240 @@ impl OxidizedResourceReader {
             .get_resources_state()
             .package_resource_names(py, &self.package)
     }
+
+    /// Returns a Traversable object for the package.
+    ///
+    /// This is what `importlib.resources.files()` uses to access resources.
+    fn files(&self, py: Python) -> PyResult<Py<OxidizedTraversable>> {
+        Py::new(
+            py,
+            OxidizedTraversable::new(self.state.clone(), self.package.clone(), String::new()),
+        )
+    }
+}
+
+/// Path-like object facilitating access to package resources.
+///
+/// Implements importlib.abc.Traversable.
+#[pyclass(module = "oxidized_importer")]
+pub(crate) struct OxidizedTraversable {
+    state: Arc<ImporterState>,
+    package: String,
+    /// `/` delimited path relative to the package. Empty for the package itself.
+    path: String,
+}
+
+impl OxidizedTraversable {
+    pub(crate) fn new(state: Arc<ImporterState>, package: String, path: String) -> Self {
+        Self {
+            state,
+            package,
+            path,
+        }
+    }
+
+    /// Obtain a new instance for a path relative to this one.
+    fn join(&self, child: &str) -> Self {
+        let mut components = self
+            .path
+            .split('/')
+            .filter(|c| !c.is_empty())
+            .collect::<Vec<_>>();
+
+        let child = child.replace('\\', "/");
+
+        for component in child.split('/') {
+            match component {
+                "" | "." => {}
+                ".." => {
+                    components.pop();
+                }
+                component => components.push(component),
+            }
+        }
+
+        Self::new(
+            self.state.clone(),
+            self.package.clone(),
+            components.join("/"),
+        )
+    }
+
+    fn is_dir_inner(&self) -> PyResult<bool> {
+        Ok(self.path.is_empty()
+            || self
+                .state
+                .get_resources_state()
+                .is_package_resource_directory(&self.package, &self.path)
+                .map_err(PyValueError::new_err)?)
+    }
+
+    /// Open the resource as a binary file-like object.
+    fn open_binary<'p>(&self, py: Python<'p>) -> PyResult<&'p PyAny> {
+        let file = if self.path.is_empty() {
+            None
+        } else {
+            self.state.get_resources_state().get_package_resource_file(
+                py,
+                &self.package,
+                &self.path,
+            )?
+        };
+
+        match file {
+            Some(file) => Ok(file),
+            None if self.is_dir_inner()? => Err(PyIsADirectoryError::new_err(format!(
+                "resource is a directory: {}",
+                self.path
+            ))),
+            None => Err(PyFileNotFoundError::new_err(format!(
+                "resource not found: {}",
+                self.path
+            ))),
+        }
+    }
+
+    /// Call a function with a file-like object and close it afterwards.
+    fn with_file<'p>(
+        &self,
+        file: &'p PyAny,
+        f: impl FnOnce(&'p PyAny) -> PyResult<&'p PyAny>,
+    ) -> PyResult<&'p PyAny> {
+        let res = f(file);
+        file.call_method0("close")?;
+
+        res
+    }
+}
+
+#[pymethods]
+impl OxidizedTraversable {
+    /// The base name of this object without any parent references.
+    #[getter]
+    fn name(&self) -> &str {
+        if self.path.is_empty() {
+            self.package.rsplit('.').next().unwrap_or(&self.package)
+        } else {
+            self.path.rsplit('/').next().unwrap_or(&self.path)
+        }
+    }
+
+    /// Yield Traversable objects in self.
+    fn iterdir<'p>(&self, py: Python<'p>) -> PyResult<&'p PyAny> {
+        if !self.is_dir_inner()? {
+            return Err(PyNotADirectoryError::new_err(format!(
+                "resource is not a directory: {}",
+                self.path
+            )));
+        }
+
+        let resources_state = self.state.get_resources_state();
+
+        let mut names = resources_state
+            .package_resources_list_subdirectories(&self.package, &self.path)
+            .map_err(PyValueError::new_err)?
+            .into_iter()
+            .chain(
+                resources_state
+                    .package_resources_list_directory(&self.package, &self.path)
+                    .map_err(PyValueError::new_err)?,
+            )
+            .collect::<Vec<_>>();
+        names.sort();
+
+        let entries = names
+            .iter()
+            .map(|name| Py::new(py, self.join(name)))
+            .collect::<PyResult<Vec<_>>>()?;
+
+        PyList::new(py, entries).call_method0("__iter__")
+    }
+
+    /// Read contents of self as bytes.
+    fn read_bytes<'p>(&self, py: Python<'p>) -> PyResult<&'p PyAny> {
+        self.with_file(self.open_binary(py)?, |file| file.call_method0("read"))
+    }
+
+    /// Read contents of self as text.
+    #[args(encoding = "None")]
+    fn read_text<'p>(&self, py: Python<'p>, encoding: Option<&str>) -> PyResult<&'p PyAny> {
+        let kwargs = PyDict::new(py);
+        kwargs.set_item("encoding", encoding)?;
+
+        let file = py
+            .import("io")?
+            .getattr("TextIOWrapper")?
+            .call((self.open_binary(py)?,), Some(kwargs))?;
+
+        self.with_file(file, |file| file.call_method0("read"))
+    }
+
+    /// Return True if self is a dir.
+    fn is_dir(&self) -> PyResult<bool> {
+        self.is_dir_inner()
+    }
+
+    /// Return True if self is a file.
+    fn is_file(&self) -> PyResult<bool> {
+        Ok(!self.path.is_empty()
+            && self
+                .state
+                .get_resources_state()
+                .is_package_resource(&self.package, &self.path)
+                .map_err(PyValueError::new_err)?)
+    }
+
+    /// Return Traversable resolved with any descendants applied.
+    #[args(descendants = "*")]
+    fn joinpath(&self, descendants: &PyTuple) -> PyResult<Self> {
+        let mut res = Self::new(self.state.clone(), self.package.clone(), self.path.clone());
+
+        for descendant in descendants {
+            res = res.join(descendant.downcast::<PyString>()?.to_str()?);
+        }
+
+        Ok(res)
+    }
+
+    /// Return Traversable child in self.
+    fn __truediv__(&self, child: &str) -> Self {
+        self.join(child)
+    }
+
+    /// mode may be 'r' or 'rb' to open as text or binary. Return a handle
+    /// suitable for reading (same as pathlib.Path.open).
+    ///
+    /// When opening as text, accepts encoding parameters such as those
+    /// accepted by io.TextIOWrapper.
+    #[args(mode = "\"r\"", py_args = "*", py_kwargs = "**")]
+    fn open<'p>(
+        &self,
+        py: Python<'p>,
+        mode: &str,
+        py_args: &PyTuple,
+        py_kwargs: Option<&PyDict>,
+    ) -> PyResult<&'p PyAny> {
+        match mode {
+            "rb" => self.open_binary(py),
+            "r" => {
+                let mut args = vec![self.open_binary(py)?];
+                args.extend(py_args.iter());
+
+                py.import("io")?
+                    .getattr("TextIOWrapper")?
+                    .call(PyTuple::new(py, args), py_kwargs)
+            }
+            _ => Err(PyValueError::new_err(format!(
+                "invalid mode: {}; only 'r' and 'rb' are supported",
+                mode
+            ))),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<OxidizedTraversable package={} path={}>",
+            self.package, self.path
+        )
+    }
 }
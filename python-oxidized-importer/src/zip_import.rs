@@ -5,20 +5,30 @@
 /*! Support for importing from zip archives. */
 
 use {
-    crate::{conversion::pyobject_to_pathbuf, decode_source},
+    crate::{
+        conversion::pyobject_to_pathbuf,
+        decode_source,
+        package_metadata::{
+            decode_metadata_text, normalize_distribution_name, parse_entry_points, parse_metadata,
+            resolve_requires,
+        },
+    },
     anyhow::{anyhow, Result},
     pyo3::{
         buffer::PyBuffer,
-        exceptions::{PyImportError, PyValueError},
+        exceptions::{
+            PyFileNotFoundError, PyIOError, PyImportError, PyNotImplementedError, PyValueError,
+        },
         ffi as pyffi,
         prelude::*,
-        types::{PyBytes, PyDict, PyType},
+        types::{PyBytes, PyDict, PyList, PyType},
         PyNativeType, PyTraverseError, PyVisit,
     },
+    python_packaging::{module_util::resolve_path_for_module, resource::BytecodeOptimizationLevel},
     std::{
-        collections::HashMap,
+        collections::{BTreeSet, HashMap},
         io::{BufReader, Cursor, Read, Seek},
-        path::{Path, PathBuf},
+        path::{Component, Path, PathBuf},
     },
     zip::read::ZipArchive,
 };
@@ -90,54 +100,116 @@ impl<R: Read + Seek> ZipIndex<R> {
     /// Attempt to locate a Python module within the zip archive.
     ///
     /// `full_name` is the fully qualified / dotted Python module name.
-    pub fn find_python_module(&mut self, full_name: &str) -> Option<ZipPythonModule> {
-        let mut common_path = self.prefix.clone().unwrap_or_default();
-        common_path.extend(full_name.split('.'));
+    ///
+    /// Bytecode is looked for in `__pycache__` directories using `cache_tag` and
+    /// `optimize_level`, as `importlib` would write it. If not found there, a `.pyc`
+    /// file next to where the `.py` file would be is used, as `zipimport` does.
+    pub fn find_python_module(
+        &self,
+        full_name: &str,
+        cache_tag: Option<&str>,
+        optimize_level: BytecodeOptimizationLevel,
+    ) -> Option<ZipPythonModule> {
+        let bytecode_tag = cache_tag.map(|tag| match optimize_level {
+            BytecodeOptimizationLevel::Zero => tag.to_string(),
+            BytecodeOptimizationLevel::One => format!("{}.opt-1", tag),
+            BytecodeOptimizationLevel::Two => format!("{}.opt-2", tag),
+        });
+
+        for is_package in [true, false] {
+            let py_path = resolve_path_for_module("", full_name, is_package, None);
+            let legacy_pyc_path = py_path.with_extension("pyc");
+            let cached_pyc_path = bytecode_tag
+                .as_ref()
+                .map(|tag| resolve_path_for_module("", full_name, is_package, Some(tag)));
+
+            let source_path = if self.members.contains_key(&py_path) {
+                Some(py_path)
+            } else {
+                None
+            };
 
-        let package_py_path = common_path.join("__init__").with_extension("py");
-        let package_pyc_path = common_path.join("__init__").with_extension("pyc");
+            let bytecode_path = cached_pyc_path
+                .into_iter()
+                .chain(std::iter::once(legacy_pyc_path))
+                .find(|path| self.members.contains_key(path));
+
+            if source_path.is_some() || bytecode_path.is_some() {
+                return Some(ZipPythonModule {
+                    is_package,
+                    source_path,
+                    bytecode_path,
+                });
+            }
+        }
 
-        let non_package_py_path = common_path.with_extension("py");
-        let non_package_pyc_path = common_path.with_extension("pyc");
+        None
+    }
 
-        let mut is_package = false;
-        let mut source_path = None;
-        let mut bytecode_path = None;
+    /// Whether a path is a file in the zip archive.
+    pub fn is_file(&self, path: &Path) -> bool {
+        self.members.contains_key(path)
+    }
 
-        if self.members.contains_key(&package_py_path) {
-            is_package = true;
-            source_path = Some(package_py_path);
-        }
+    /// Whether a path is a directory in the zip archive.
+    ///
+    /// Zip archives don't need to have entries for directories. So a path is a
+    /// directory if any file is present under it.
+    pub fn is_directory(&self, path: &Path) -> bool {
+        self.members
+            .keys()
+            .any(|member| member != path && member.starts_with(path))
+    }
 
-        if self.members.contains_key(&package_pyc_path) {
-            is_package = true;
-            bytecode_path = Some(package_pyc_path);
-        }
+    /// Obtain the sorted names of files and directories directly within a directory.
+    pub fn directory_entries(&self, path: &Path) -> Vec<String> {
+        self.members
+            .keys()
+            .filter_map(|member| member.strip_prefix(path).ok())
+            .filter_map(|relative| relative.components().next())
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>()
+    }
 
-        if is_package {
-            return Some(ZipPythonModule {
-                is_package,
-                source_path,
-                bytecode_path,
-            });
-        }
+    /// Find package distribution metadata directories in the zip archive.
+    ///
+    /// These are top-level `*.dist-info` and `*.egg-info` directories. If `name` is
+    /// defined, only directories for distributions having that name are returned.
+    /// Names are compared case insensitively with hyphens normalized to underscores.
+    pub fn find_distributions(&self, name: Option<&str>) -> Vec<PathBuf> {
+        let name = name.map(|name| name.to_lowercase().replace('-', "_"));
+
+        self.members
+            .keys()
+            .filter_map(|member| match member.components().next() {
+                Some(Component::Normal(dir)) if member.components().count() > 1 => {
+                    dir.to_str().map(|dir| dir.to_string())
+                }
+                _ => None,
+            })
+            .filter(|dir| {
+                let stem = if let Some(stem) = dir.strip_suffix(".dist-info") {
+                    stem
+                } else if let Some(stem) = dir.strip_suffix(".egg-info") {
+                    stem
+                } else {
+                    return false;
+                };
 
-        if self.members.contains_key(&non_package_py_path) {
-            source_path = Some(non_package_py_path);
-        }
-        if self.members.contains_key(&non_package_pyc_path) {
-            bytecode_path = Some(non_package_pyc_path);
-        }
+                if let Some(name) = &name {
+                    let distribution_name = stem.split_once('-').map_or(stem, |(name, _)| name);
 
-        if source_path.is_some() || bytecode_path.is_some() {
-            Some(ZipPythonModule {
-                is_package,
-                source_path,
-                bytecode_path,
+                    distribution_name.to_lowercase().replace('-', "_") == *name
+                } else {
+                    true
+                }
             })
-        } else {
-            None
-        }
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(PathBuf::from)
+            .collect::<Vec<_>>()
     }
 
     /// Resolve the byte content for a given path.
@@ -168,9 +240,7 @@ impl SeekableReader for BufReader<std::fs::File> {}
 ///
 /// Known incompatibilities with `zipimporter`:
 ///
-/// * ResourceReader interface not implemented.
-/// * ResourceLoader interface not implemented.
-/// * Bytecode isn't validated.
+/// * Only the magic number of bytecode is validated.
 #[pyclass(module = "oxidized_importer")]
 pub struct OxidizedZipFinder {
     /// A PyObject backing storage of data.
@@ -207,6 +277,15 @@ pub struct OxidizedZipFinder {
 
     /// `builtins.exec` function.
     builtins_exec: Py<PyAny>,
+
+    /// `sys.implementation.cache_tag` value used to find bytecode in `__pycache__`.
+    cache_tag: Option<String>,
+
+    /// Bytecode optimization level of the running interpreter.
+    optimize_level: BytecodeOptimizationLevel,
+
+    /// Magic number that bytecode must have to be usable.
+    magic_number: Vec<u8>,
 }
 
 impl OxidizedZipFinder {
@@ -277,6 +356,30 @@ impl OxidizedZipFinder {
         let builtins_compile = builtins_module.getattr("compile")?.into_py(py);
         let builtins_exec = builtins_module.getattr("exec")?.into_py(py);
 
+        let sys_module = py.import("sys")?;
+        let cache_tag = sys_module
+            .getattr("implementation")?
+            .getattr("cache_tag")?
+            .extract::<Option<String>>()?;
+
+        let optimize_level = match sys_module
+            .getattr("flags")?
+            .getattr("optimize")?
+            .extract::<i64>()?
+        {
+            0 => Ok(BytecodeOptimizationLevel::Zero),
+            1 => Ok(BytecodeOptimizationLevel::One),
+            2 => Ok(BytecodeOptimizationLevel::Two),
+            _ => Err(PyValueError::new_err(
+                "unexpected value for sys.flags.optimize",
+            )),
+        }?;
+
+        let magic_number = py
+            .import("_frozen_importlib_external")?
+            .getattr("MAGIC_NUMBER")?
+            .extract::<Vec<u8>>()?;
+
         Ok(Self {
             backing_pyobject,
             index,
@@ -286,14 +389,39 @@ impl OxidizedZipFinder {
             marshal_loads,
             builtins_compile,
             builtins_exec,
+            cache_tag,
+            optimize_level,
+            magic_number,
         })
     }
 
+    fn find_python_module(&self, full_name: &str) -> Option<ZipPythonModule> {
+        self.index
+            .find_python_module(full_name, self.cache_tag.as_deref(), self.optimize_level)
+    }
+
+    /// Resolve the path advertised for a path within the zip archive.
+    fn advertised_path(&self, path: &Path) -> PathBuf {
+        let mut res = self.zip_path.clone();
+        if let Some(prefix) = &self.index.prefix {
+            res = res.join(prefix);
+        }
+
+        res.join(path)
+    }
+
+    /// Resolve a path within the zip archive from an advertised or relative path.
+    fn archive_path(&self, path: &Path) -> PathBuf {
+        let root = self.advertised_path(Path::new(""));
+
+        path.strip_prefix(&root).unwrap_or(path).to_path_buf()
+    }
+
     fn resolve_python_module(
         slf: &mut PyRefMut<Self>,
         full_name: &str,
     ) -> PyResult<ZipPythonModule> {
-        if let Some(module) = slf.index.find_python_module(full_name) {
+        if let Some(module) = slf.find_python_module(full_name) {
             Ok(module)
         } else {
             Err(PyImportError::new_err((
@@ -367,7 +495,7 @@ impl OxidizedZipFinder {
         let py = slf.py();
         let mut importer = slf.try_borrow_mut()?;
 
-        let module = if let Some(module) = importer.index.find_python_module(&fullname) {
+        let module = if let Some(module) = importer.find_python_module(&fullname) {
             module
         } else {
             return Ok(py.None().into_ref(py));
@@ -379,16 +507,13 @@ impl OxidizedZipFinder {
         kwargs.set_item("is_package", module.is_package)?;
 
         // origin is the path to the zip archive + the path within the archive.
-        let mut origin = importer.zip_path.clone();
-        if let Some(prefix) = &importer.index.prefix {
-            origin = origin.join(prefix);
-        }
-
-        if let Some(path) = module.source_path {
-            origin = origin.join(path);
-        } else if let Some(path) = module.bytecode_path {
-            origin = origin.join(path);
-        }
+        let origin = if let Some(path) = &module.source_path {
+            importer.advertised_path(path)
+        } else if let Some(path) = &module.bytecode_path {
+            importer.advertised_path(path)
+        } else {
+            importer.advertised_path(Path::new(""))
+        };
 
         kwargs.set_item("origin", (&origin).into_py(py))?;
 
@@ -402,13 +527,11 @@ impl OxidizedZipFinder {
         // __path__ MUST be set on packages.
         // __path__ is an iterable of strings, which can be empty.
         if module.is_package {
-            let parent = origin.parent().ok_or_else(|| {
-                PyValueError::new_err(
-                    "unable to determine dirname(origin); this should never happen",
-                )
-            })?;
+            // origin may be in a __pycache__ directory. So derive the package
+            // directory from the module name.
+            let package_path = fullname.split('.').collect::<PathBuf>();
 
-            let locations = vec![parent.into_py(py)];
+            let locations = vec![importer.advertised_path(&package_path).into_py(py)];
             spec.setattr("submodule_search_locations", locations)?;
         }
 
@@ -440,6 +563,51 @@ impl OxidizedZipFinder {
 
     // End of importlib.abc.MetaPathFinder interface.
 
+    // Start of importlib.metadata.DistributionFinder interface.
+
+    /// def find_distributions(context=DistributionFinder.Context()):
+    ///
+    /// Return an iterable of all Distribution instances capable of
+    /// loading the metadata for packages for the indicated `context`.
+    #[args(context = "None")]
+    fn find_distributions<'p>(
+        slf: &'p PyCell<Self>,
+        context: Option<&PyAny>,
+    ) -> PyResult<&'p PyAny> {
+        let py = slf.py();
+
+        let name = if let Some(context) = context {
+            let name = context.getattr("name")?;
+
+            if name.is_none() {
+                None
+            } else {
+                Some(name.to_string())
+            }
+        } else {
+            None
+        };
+
+        let paths = slf.try_borrow()?.index.find_distributions(name.as_deref());
+
+        let distributions = paths
+            .into_iter()
+            .map(|path| {
+                PyCell::new(
+                    py,
+                    OxidizedZipDistribution {
+                        finder: slf.into(),
+                        path,
+                    },
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        PyList::new(py, &distributions).call_method0("__iter__")
+    }
+
+    // End of importlib.metadata.DistributionFinder interface.
+
     // Start of importlib.abc.Loader interface.
 
     #[allow(unused)]
@@ -468,6 +636,35 @@ impl OxidizedZipFinder {
 
     // End of importlib.abc.Loader interface.
 
+    // Start of importlib.abc.ResourceLoader interface.
+
+    /// Obtain the content of a file in the zip archive.
+    ///
+    /// `path` can be relative to the zip archive or include the advertised path of
+    /// the zip archive, such as the value of a module's `__file__`.
+    fn get_data<'p>(slf: &'p PyCell<Self>, path: &str) -> PyResult<&'p PyAny> {
+        let py = slf.py();
+        let mut importer = slf.try_borrow_mut()?;
+
+        let path = importer.archive_path(Path::new(path));
+
+        if !importer.index.is_file(&path) {
+            return Err(PyFileNotFoundError::new_err(format!(
+                "{} not found in zip archive",
+                path.display()
+            )));
+        }
+
+        let data = importer
+            .index
+            .resolve_path_content(&path)
+            .map_err(|e| PyIOError::new_err(format!("error reading from zip: {}", e)))?;
+
+        Ok(PyBytes::new(py, &data))
+    }
+
+    // End of importlib.abc.ResourceLoader interface.
+
     // Start of importlib.abc.InspectLoader interface.
 
     fn get_code(slf: &PyCell<Self>, fullname: &str) -> PyResult<Py<PyAny>> {
@@ -476,20 +673,35 @@ impl OxidizedZipFinder {
 
         let module: ZipPythonModule = Self::resolve_python_module(&mut importer, fullname)?;
 
-        if let Some(path) = module.bytecode_path {
-            let bytecode_data = importer.index.resolve_path_content(&path).map_err(|e| {
+        let bytecode_data = if let Some(path) = &module.bytecode_path {
+            let data = importer.index.resolve_path_content(path).map_err(|e| {
                 PyImportError::new_err((
                     format!("error reading module bytecode from zip: {}", e),
                     fullname.to_string(),
                 ))
             })?;
 
+            // Bytecode from a different Python version can't be used. Fall back to
+            // source if it is available.
+            if data.len() >= 16 && data[0..4] == importer.magic_number[..] {
+                Some(data)
+            } else if module.source_path.is_some() {
+                None
+            } else {
+                return Err(PyImportError::new_err((
+                    "bad magic number in module bytecode",
+                    fullname.to_string(),
+                )));
+            }
+        } else {
+            None
+        };
+
+        if let Some(bytecode_data) = bytecode_data {
             // Minimize potential for nested borrow by dropping borrow as soon as possible.
             let marshal_loads = importer.marshal_loads.clone_ref(py);
             std::mem::drop(importer);
 
-            // TODO validate the .pyc header.
-
             let bytecode = &bytecode_data[16..];
             let ptr = unsafe {
                 pyffi::PyMemoryView_FromMemory(
@@ -578,4 +790,199 @@ impl OxidizedZipFinder {
     }
 
     // End of importlib.abc.InspectLoader interface.
+
+    // Support obtaining ResourceReader instances.
+    fn get_resource_reader<'p>(slf: &'p PyCell<Self>, fullname: &str) -> PyResult<&'p PyAny> {
+        let py = slf.py();
+
+        let is_package = slf
+            .try_borrow()?
+            .find_python_module(fullname)
+            .map(|module| module.is_package)
+            .unwrap_or(false);
+
+        // Only packages have resources.
+        if !is_package {
+            return Ok(py.None().into_ref(py));
+        }
+
+        let reader = PyCell::new(
+            py,
+            OxidizedZipResourceReader {
+                finder: slf.into(),
+                package_path: fullname.split('.').collect::<PathBuf>(),
+            },
+        )?;
+
+        Ok(reader)
+    }
+}
+
+/// An `importlib.abc.ResourceReader` for packages in a zip archive.
+///
+/// Resources are files in the package's directory within the zip archive.
+#[pyclass(module = "oxidized_importer")]
+pub struct OxidizedZipResourceReader {
+    finder: Py<OxidizedZipFinder>,
+    package_path: PathBuf,
+}
+
+#[pymethods]
+impl OxidizedZipResourceReader {
+    fn __traverse__(&self, visit: PyVisit) -> Result<(), PyTraverseError> {
+        visit.call(&self.finder)?;
+
+        Ok(())
+    }
+
+    /// Returns an opened, file-like object for binary reading of the resource.
+    ///
+    /// If the resource cannot be found, FileNotFoundError is raised.
+    fn open_resource<'p>(&self, py: Python<'p>, resource: &str) -> PyResult<&'p PyAny> {
+        let path = self.package_path.join(resource);
+        let mut finder = self.finder.try_borrow_mut(py)?;
+
+        if !finder.index.is_file(&path) {
+            return Err(PyFileNotFoundError::new_err(resource.to_string()));
+        }
+
+        let data = finder
+            .index
+            .resolve_path_content(&path)
+            .map_err(|e| PyIOError::new_err(format!("error reading from zip: {}", e)))?;
+
+        py.import("io")?
+            .getattr("BytesIO")?
+            .call((PyBytes::new(py, &data),), None)
+    }
+
+    /// Returns the file system path to the resource.
+    ///
+    /// Resources in zip archives don't exist on the filesystem. So this always
+    /// raises FileNotFoundError.
+    fn resource_path(&self, resource: &str) -> PyResult<()> {
+        Err(PyFileNotFoundError::new_err(resource.to_string()))
+    }
+
+    /// Returns True if the named name is considered a resource. FileNotFoundError
+    /// is raised if name does not exist.
+    fn is_resource(&self, py: Python, name: &str) -> PyResult<bool> {
+        let path = self.package_path.join(name);
+        let finder = self.finder.try_borrow(py)?;
+
+        if finder.index.is_file(&path) {
+            Ok(true)
+        } else if finder.index.is_directory(&path) {
+            Ok(false)
+        } else {
+            Err(PyFileNotFoundError::new_err(name.to_string()))
+        }
+    }
+
+    /// Returns an iterable of strings over the contents of the package.
+    fn contents<'p>(&self, py: Python<'p>) -> PyResult<&'p PyAny> {
+        let finder = self.finder.try_borrow(py)?;
+
+        PyList::new(py, finder.index.directory_entries(&self.package_path)).call_method0("__iter__")
+    }
+}
+
+/// An `importlib.metadata.Distribution` for metadata in a zip archive.
+#[pyclass(module = "oxidized_importer")]
+pub struct OxidizedZipDistribution {
+    finder: Py<OxidizedZipFinder>,
+
+    /// Path of the `.dist-info` or `.egg-info` directory within the zip archive.
+    path: PathBuf,
+}
+
+impl OxidizedZipDistribution {
+    fn read_data(&self, py: Python, filename: &str) -> PyResult<Option<Vec<u8>>> {
+        let path = self.path.join(filename);
+        let mut finder = self.finder.try_borrow_mut(py)?;
+
+        if !finder.index.is_file(&path) {
+            return Ok(None);
+        }
+
+        Ok(Some(finder.index.resolve_path_content(&path).map_err(
+            |e| PyIOError::new_err(format!("error when resolving resource: {}", e)),
+        )?))
+    }
+}
+
+#[pymethods]
+impl OxidizedZipDistribution {
+    fn __traverse__(&self, visit: PyVisit) -> Result<(), PyTraverseError> {
+        visit.call(&self.finder)?;
+
+        Ok(())
+    }
+
+    /// Attempt to load metadata file given by the filename.
+    fn read_text<'p>(&self, py: Python<'p>, filename: String) -> PyResult<&'p PyAny> {
+        // Missing resource returns None.
+        if let Some(data) = self.read_data(py, &filename)? {
+            decode_metadata_text(py, &data)
+        } else {
+            Ok(py.None().into_ref(py))
+        }
+    }
+
+    /// Return a path to a file relative to the distribution.
+    fn locate_file(&self, py: Python, path: &PyAny) -> PyResult<PathBuf> {
+        let path = pyobject_to_pathbuf(py, path)?;
+        let finder = self.finder.try_borrow(py)?;
+
+        Ok(finder.advertised_path(&path))
+    }
+
+    /// Return the parsed metadata for this Distribution.
+    #[getter]
+    fn metadata<'p>(&self, py: Python<'p>) -> PyResult<&'p PyAny> {
+        let data = if let Some(data) = self.read_data(py, "METADATA")? {
+            data
+        } else {
+            self.read_data(py, "PKG-INFO")?
+                .ok_or_else(|| PyIOError::new_err("package metadata not found"))?
+        };
+
+        parse_metadata(py, &data)
+    }
+
+    /// Return the `Name` metadata for the distribution package.
+    #[getter]
+    fn name<'p>(&self, py: Python<'p>) -> PyResult<&'p PyAny> {
+        self.metadata(py)?.get_item("Name")
+    }
+
+    /// Return a normalized version of the name.
+    #[getter]
+    fn _normalized_name<'p>(&self, py: Python<'p>) -> PyResult<&'p PyAny> {
+        normalize_distribution_name(py, self.name(py)?)
+    }
+
+    #[getter]
+    fn version<'p>(&self, py: Python<'p>) -> PyResult<&'p PyAny> {
+        self.metadata(py)?.get_item("Version")
+    }
+
+    #[getter]
+    fn entry_points<'p>(self_: PyRef<Self>, py: Python<'p>) -> PyResult<&'p PyAny> {
+        let text = self_.read_text(py, "entry_points.txt".into())?;
+
+        parse_entry_points(py, text, self_.into_py(py))
+    }
+
+    #[getter]
+    fn files(&self) -> PyResult<()> {
+        Err(PyNotImplementedError::new_err(()))
+    }
+
+    #[getter]
+    fn requires<'p>(&self, py: Python<'p>) -> PyResult<&'p PyAny> {
+        resolve_requires(py, self.metadata(py)?, || {
+            self.read_text(py, "requires.txt".into())
+        })
+    }
 }
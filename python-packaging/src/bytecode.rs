@@ -225,6 +225,32 @@ impl Drop for BytecodeCompiler {
     }
 }
 
+/// A bytecode compiler that refuses to compile.
+///
+/// For callers that store modules as source or only copy existing bytecode,
+/// so nothing should need compiling.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoBytecodeCompiler;
+
+impl PythonBytecodeCompiler for NoBytecodeCompiler {
+    fn get_magic_number(&self) -> u32 {
+        0
+    }
+
+    fn compile(
+        &mut self,
+        _source: &[u8],
+        filename: &str,
+        _optimize: BytecodeOptimizationLevel,
+        _output_mode: CompileMode,
+    ) -> Result<Vec<u8>> {
+        Err(anyhow!(
+            "bytecode compilation is not supported: {}",
+            filename
+        ))
+    }
+}
+
 /// How to write out a .pyc bytecode header.
 #[derive(Debug, Clone, Copy)]
 pub enum BytecodeHeaderMode {
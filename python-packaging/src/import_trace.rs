@@ -0,0 +1,347 @@
+// Copyright 2022 Gregory Szorc.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*! Records of Python resources accessed by a running interpreter.
+
+An import trace records which modules an importer served, which package
+resources and package distribution resources were read, and which module
+lookups failed. Traces from multiple runs can be merged and compared against
+the resources that were packaged to find resources that are never used.
+
+Traces are serialized as text. The first line is [IMPORT_TRACE_HEADER]. Each
+following line is a tab delimited entry whose first field denotes the entry
+type.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    std::{
+        collections::BTreeSet,
+        io::{BufRead, BufReader, Write},
+        path::Path,
+    },
+};
+
+/// First line of serialized import traces.
+pub const IMPORT_TRACE_HEADER: &str = "# oxidized_importer import trace v1";
+
+/// An entry in an import trace.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum ImportTraceEntry {
+    /// A module was found by the importer.
+    Module {
+        /// Fully qualified name of the module.
+        name: String,
+        /// Index of the packed resources source the module came from.
+        ///
+        /// `None` if the module didn't come from packed resources data.
+        layer: Option<usize>,
+        /// Describes how the module is loaded. e.g. `source-bytecode` or `extension`.
+        flavor: String,
+    },
+
+    /// A package resource was read.
+    PackageResource {
+        /// Name of the package the resource belongs to.
+        package: String,
+        /// Name of the resource relative to the package.
+        name: String,
+    },
+
+    /// A package distribution resource was read.
+    PackageDistributionResource {
+        /// Name of the package the distribution resource belongs to.
+        package: String,
+        /// Name of the resource within the distribution metadata directory.
+        name: String,
+    },
+
+    /// A module was requested but not found by the importer.
+    Missing {
+        /// Fully qualified name of the module.
+        name: String,
+    },
+}
+
+impl ImportTraceEntry {
+    /// Serialize this entry to a line of text, without a trailing newline.
+    pub fn to_line(&self) -> String {
+        match self {
+            Self::Module {
+                name,
+                layer,
+                flavor,
+            } => format!(
+                "module\t{}\t{}\t{}",
+                name,
+                layer.map_or_else(|| "-".to_string(), |layer| layer.to_string()),
+                flavor
+            ),
+            Self::PackageResource { package, name } => {
+                format!("package-resource\t{}\t{}", package, name)
+            }
+            Self::PackageDistributionResource { package, name } => {
+                format!("distribution-resource\t{}\t{}", package, name)
+            }
+            Self::Missing { name } => format!("missing\t{}", name),
+        }
+    }
+
+    /// Parse an entry from a line of text.
+    pub fn from_line(line: &str) -> Result<Self> {
+        let fields = line.split('\t').collect::<Vec<_>>();
+
+        match fields.as_slice() {
+            ["module", name, layer, flavor] => Ok(Self::Module {
+                name: name.to_string(),
+                layer: if *layer == "-" {
+                    None
+                } else {
+                    Some(
+                        layer
+                            .parse::<usize>()
+                            .with_context(|| format!("parsing layer {}", layer))?,
+                    )
+                },
+                flavor: flavor.to_string(),
+            }),
+            ["package-resource", package, name] => Ok(Self::PackageResource {
+                package: package.to_string(),
+                name: name.to_string(),
+            }),
+            ["distribution-resource", package, name] => Ok(Self::PackageDistributionResource {
+                package: package.to_string(),
+                name: name.to_string(),
+            }),
+            ["missing", name] => Ok(Self::Missing {
+                name: name.to_string(),
+            }),
+            _ => Err(anyhow!("malformed import trace entry: {}", line)),
+        }
+    }
+}
+
+/// A collection of [ImportTraceEntry].
+///
+/// Entries are deduplicated and ordered.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ImportTrace {
+    entries: BTreeSet<ImportTraceEntry>,
+}
+
+impl ImportTrace {
+    /// Record an entry in this trace.
+    pub fn record(&mut self, entry: ImportTraceEntry) {
+        self.entries.insert(entry);
+    }
+
+    /// Iterate over entries in this trace.
+    pub fn entries(&self) -> impl Iterator<Item = &ImportTraceEntry> {
+        self.entries.iter()
+    }
+
+    /// Add all entries from another trace to this one.
+    pub fn merge(&mut self, other: &ImportTrace) {
+        self.entries.extend(other.entries.iter().cloned());
+    }
+
+    /// Names of modules found by the importer.
+    pub fn module_names(&self) -> BTreeSet<&str> {
+        self.entries
+            .iter()
+            .filter_map(|entry| match entry {
+                ImportTraceEntry::Module { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Package resources that were read, as `(package, name)` tuples.
+    pub fn package_resources(&self) -> BTreeSet<(&str, &str)> {
+        self.entries
+            .iter()
+            .filter_map(|entry| match entry {
+                ImportTraceEntry::PackageResource { package, name } => {
+                    Some((package.as_str(), name.as_str()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Package distribution resources that were read, as `(package, name)` tuples.
+    pub fn package_distribution_resources(&self) -> BTreeSet<(&str, &str)> {
+        self.entries
+            .iter()
+            .filter_map(|entry| match entry {
+                ImportTraceEntry::PackageDistributionResource { package, name } => {
+                    Some((package.as_str(), name.as_str()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Names of modules that were requested but not found.
+    pub fn missing_names(&self) -> BTreeSet<&str> {
+        self.entries
+            .iter()
+            .filter_map(|entry| match entry {
+                ImportTraceEntry::Missing { name } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Serialize this trace to a writer.
+    pub fn write_to(&self, writer: &mut impl Write) -> Result<()> {
+        writer.write_all(IMPORT_TRACE_HEADER.as_bytes())?;
+        writer.write_all(b"\n")?;
+
+        for entry in &self.entries {
+            writer.write_all(entry.to_line().as_bytes())?;
+            writer.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+
+    /// Serialize this trace to a file.
+    pub fn write_to_path(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+
+        let mut fh =
+            std::fs::File::create(path).with_context(|| format!("creating {}", path.display()))?;
+
+        self.write_to(&mut fh)
+    }
+
+    /// Parse a trace from a reader.
+    pub fn from_reader(reader: impl BufRead) -> Result<Self> {
+        let mut lines = reader.lines();
+
+        if lines.next().transpose()?.as_deref() != Some(IMPORT_TRACE_HEADER) {
+            return Err(anyhow!("import trace header not found"));
+        }
+
+        let mut res = Self::default();
+
+        for line in lines {
+            let line = line?;
+
+            if line.is_empty() {
+                continue;
+            }
+
+            res.record(ImportTraceEntry::from_line(&line)?);
+        }
+
+        Ok(res)
+    }
+
+    /// Parse a trace from a file.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+
+        let fh =
+            std::fs::File::open(path).with_context(|| format!("opening {}", path.display()))?;
+
+        Self::from_reader(BufReader::new(fh))
+            .with_context(|| format!("parsing import trace {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> ImportTrace {
+        let mut trace = ImportTrace::default();
+        trace.record(ImportTraceEntry::Module {
+            name: "foo".to_string(),
+            layer: Some(0),
+            flavor: "source-bytecode".to_string(),
+        });
+        trace.record(ImportTraceEntry::Module {
+            name: "sys".to_string(),
+            layer: None,
+            flavor: "builtin".to_string(),
+        });
+        trace.record(ImportTraceEntry::PackageResource {
+            package: "foo".to_string(),
+            name: "data dir/file.txt".to_string(),
+        });
+        trace.record(ImportTraceEntry::PackageDistributionResource {
+            package: "foo".to_string(),
+            name: "METADATA".to_string(),
+        });
+        trace.record(ImportTraceEntry::Missing {
+            name: "bar".to_string(),
+        });
+
+        trace
+    }
+
+    #[test]
+    fn roundtrip() -> Result<()> {
+        let trace = sample();
+
+        let mut data = vec![];
+        trace.write_to(&mut data)?;
+
+        assert_eq!(
+            String::from_utf8(data.clone())?,
+            "# oxidized_importer import trace v1\n\
+            module\tfoo\t0\tsource-bytecode\n\
+            module\tsys\t-\tbuiltin\n\
+            package-resource\tfoo\tdata dir/file.txt\n\
+            distribution-resource\tfoo\tMETADATA\n\
+            missing\tbar\n"
+        );
+
+        assert_eq!(ImportTrace::from_reader(data.as_slice())?, trace);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(ImportTrace::from_reader(b"module\tfoo\t0\tbuiltin\n".as_ref()).is_err());
+        assert!(ImportTrace::from_reader(
+            b"# oxidized_importer import trace v1\nmodule\tfoo\n".as_ref()
+        )
+        .is_err());
+        assert!(ImportTrace::from_reader(
+            b"# oxidized_importer import trace v1\nmodule\tfoo\tx\tbuiltin\n".as_ref()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn merge() {
+        let mut a = ImportTrace::default();
+        a.record(ImportTraceEntry::Missing {
+            name: "bar".to_string(),
+        });
+
+        a.merge(&sample());
+        a.merge(&sample());
+
+        assert_eq!(a, sample());
+        assert_eq!(a.module_names(), ["foo", "sys"].into_iter().collect());
+        assert_eq!(
+            a.package_resources(),
+            [("foo", "data dir/file.txt")].into_iter().collect()
+        );
+        assert_eq!(
+            a.package_distribution_resources(),
+            [("foo", "METADATA")].into_iter().collect()
+        );
+        assert_eq!(a.missing_names(), ["bar"].into_iter().collect());
+    }
+}
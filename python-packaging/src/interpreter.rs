@@ -231,6 +231,14 @@ pub enum MemoryAllocatorBackend {
     ///
     /// Serialized value: `rust`
     Rust,
+
+    /// Wrap another backend and record allocations and their Python call sites.
+    ///
+    /// This is a debugging aid for tracking down memory growth. Records are
+    /// kept in a bounded ring buffer. Recording call sites is slow.
+    ///
+    /// Serialized value: `tracing`
+    Tracing,
 }
 
 impl Default for MemoryAllocatorBackend {
@@ -251,6 +259,7 @@ impl ToString for MemoryAllocatorBackend {
             Self::Mimalloc => "mimalloc",
             Self::Snmalloc => "snmalloc",
             Self::Rust => "rust",
+            Self::Tracing => "tracing",
         }
         .to_string()
     }
@@ -272,6 +281,7 @@ impl TryFrom<&str> for MemoryAllocatorBackend {
             "mimalloc" => Ok(Self::Mimalloc),
             "snmalloc" => Ok(Self::Snmalloc),
             "rust" => Ok(Self::Rust),
+            "tracing" => Ok(Self::Tracing),
             _ => Err(format!("{} is not a valid memory allocator backend", value)),
         }
     }
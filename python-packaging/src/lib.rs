@@ -14,6 +14,7 @@ and packaging facilities.
 
 pub mod bytecode;
 pub mod filesystem_scanning;
+pub mod import_trace;
 pub mod interpreter;
 pub mod libpython;
 pub mod licensing;
@@ -22,6 +23,7 @@ pub mod module_util;
 pub mod package_metadata;
 pub mod policy;
 pub mod python_source;
+pub mod requirement;
 pub mod resource;
 pub mod resource_collection;
 #[cfg(test)]
@@ -30,5 +32,7 @@ mod testutil;
 pub mod wheel;
 #[cfg(feature = "wheel")]
 pub mod wheel_builder;
+#[cfg(feature = "wheel")]
+pub mod wheel_resolver;
 #[cfg(feature = "zip")]
 pub mod zip_app_builder;
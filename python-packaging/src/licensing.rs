@@ -145,6 +145,19 @@ impl ComponentFlavor {
         }
     }
 
+    /// The name of the component, without its type.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::PythonDistribution(name)
+            | Self::PythonStandardLibraryModule(name)
+            | Self::PythonStandardLibraryExtensionModule(name)
+            | Self::PythonExtensionModule(name)
+            | Self::PythonModule(name)
+            | Self::Library(name)
+            | Self::RustCrate(name) => name,
+        }
+    }
+
     /// Whether this component is part of the Python standard library.
     pub fn is_python_standard_library(&self) -> bool {
         match self {
@@ -199,6 +212,9 @@ pub struct LicensedComponent {
     /// The type of license.
     license: LicenseFlavor,
 
+    /// Version of the component.
+    version: Option<String>,
+
     /// Location where source code for this component can be obtained.
     source_location: SourceLocation,
 
@@ -240,6 +256,7 @@ impl LicensedComponent {
         Self {
             flavor,
             license,
+            version: None,
             source_location: SourceLocation::NotSet,
             homepage: None,
             authors: vec![],
@@ -290,6 +307,16 @@ impl LicensedComponent {
         }
     }
 
+    /// Obtain the version of this component.
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// Set the version of this component.
+    pub fn set_version(&mut self, value: impl ToString) {
+        self.version = Some(value.to_string());
+    }
+
     /// Obtain the location where the source of this component can be obtained.
     pub fn source_location(&self) -> &SourceLocation {
         &self.source_location
@@ -879,6 +906,177 @@ impl LicensedComponents {
     }
 }
 
+/// Rules for which licenses components may have.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LicensePolicy {
+    /// SPDX license identifiers components may be licensed under.
+    ///
+    /// If empty, all licenses not in [Self::denied_licenses] are allowed.
+    allowed_licenses: BTreeSet<String>,
+
+    /// SPDX license identifiers components may not be licensed under.
+    denied_licenses: BTreeSet<String>,
+
+    /// Whether components with missing or unknown licenses are allowed.
+    allow_unknown: bool,
+
+    /// Names of components exempt from the policy.
+    exceptions: BTreeSet<String>,
+}
+
+/// Resolve an SPDX license identifier to its canonical form.
+fn canonical_license_id(value: &str) -> Result<String> {
+    spdx::license_id(value)
+        .map(|id| id.name.to_string())
+        .ok_or_else(|| anyhow!("{} is not a known SPDX license identifier", value))
+}
+
+/// Obtain the key a license is compared by in a [LicensePolicy].
+///
+/// Parsed SPDX expressions store GNU `-only` licenses under their root
+/// identifier (e.g. `GPL-3.0`), so identifiers are normalized the same way.
+fn license_key(item: &spdx::LicenseItem) -> String {
+    item.to_string()
+}
+
+/// Obtain the [license_key()] of a canonical SPDX license identifier.
+fn license_id_key(id: &str) -> Option<String> {
+    spdx::license_id(id).map(|id| license_key(&spdx::LicenseReq::from(id).license))
+}
+
+impl LicensePolicy {
+    /// SPDX license identifiers components may be licensed under.
+    pub fn allowed_licenses(&self) -> &BTreeSet<String> {
+        &self.allowed_licenses
+    }
+
+    /// Set the SPDX license identifiers components may be licensed under.
+    ///
+    /// Errors if an identifier isn't a known SPDX license identifier.
+    pub fn set_allowed_licenses(
+        &mut self,
+        values: impl Iterator<Item = impl AsRef<str>>,
+    ) -> Result<()> {
+        self.allowed_licenses = values
+            .map(|x| canonical_license_id(x.as_ref()))
+            .collect::<Result<_>>()?;
+
+        Ok(())
+    }
+
+    /// SPDX license identifiers components may not be licensed under.
+    pub fn denied_licenses(&self) -> &BTreeSet<String> {
+        &self.denied_licenses
+    }
+
+    /// Set the SPDX license identifiers components may not be licensed under.
+    ///
+    /// Errors if an identifier isn't a known SPDX license identifier.
+    pub fn set_denied_licenses(
+        &mut self,
+        values: impl Iterator<Item = impl AsRef<str>>,
+    ) -> Result<()> {
+        self.denied_licenses = values
+            .map(|x| canonical_license_id(x.as_ref()))
+            .collect::<Result<_>>()?;
+
+        Ok(())
+    }
+
+    /// Whether components with missing or unknown licenses are allowed.
+    pub fn allow_unknown(&self) -> bool {
+        self.allow_unknown
+    }
+
+    /// Set whether components with missing or unknown licenses are allowed.
+    pub fn set_allow_unknown(&mut self, value: bool) {
+        self.allow_unknown = value;
+    }
+
+    /// Names of components exempt from the policy.
+    pub fn exceptions(&self) -> &BTreeSet<String> {
+        &self.exceptions
+    }
+
+    /// Set the names of components exempt from the policy.
+    ///
+    /// Names are matched against [ComponentFlavor::name()].
+    pub fn set_exceptions(&mut self, values: impl Iterator<Item = impl ToString>) {
+        self.exceptions = values.map(|x| x.to_string()).collect();
+    }
+
+    /// Whether a license is acceptable under this policy.
+    fn license_allowed(&self, item: &spdx::LicenseItem) -> bool {
+        let key = license_key(item);
+        let matches = |id: &String| license_id_key(id).as_ref() == Some(&key);
+
+        !self.denied_licenses.iter().any(matches)
+            && (self.allowed_licenses.is_empty() || self.allowed_licenses.iter().any(matches))
+    }
+
+    /// Evaluate a component against this policy.
+    ///
+    /// Returns `Some` with a description of the problem if the component
+    /// violates the policy.
+    ///
+    /// SPDX expressions with alternatives are satisfied if any alternative
+    /// only has acceptable licenses.
+    pub fn evaluate_component(&self, component: &LicensedComponent) -> Option<String> {
+        if self.exceptions.contains(component.flavor().name()) {
+            return None;
+        }
+
+        match component.license() {
+            LicenseFlavor::PublicDomain => None,
+            LicenseFlavor::Spdx(expression) => {
+                if expression.evaluate(|req| {
+                    req.license.id().is_some() && self.license_allowed(&req.license)
+                }) {
+                    None
+                } else if self.allowed_licenses.is_empty() {
+                    Some(format!("license {} is denied", expression))
+                } else {
+                    Some(format!("license {} is not allowed", expression))
+                }
+            }
+            LicenseFlavor::None => {
+                if self.allow_unknown {
+                    None
+                } else {
+                    Some("license is missing".to_string())
+                }
+            }
+            LicenseFlavor::OtherExpression(expression) => {
+                if self.allow_unknown {
+                    None
+                } else {
+                    Some(format!("license {} is unknown", expression))
+                }
+            }
+            LicenseFlavor::Unknown(terms) => {
+                if self.allow_unknown {
+                    None
+                } else {
+                    Some(format!("license {} is unknown", terms.join(", ")))
+                }
+            }
+        }
+    }
+
+    /// Obtain all components violating this policy.
+    ///
+    /// Each entry holds the component and a description of the problem.
+    pub fn violations<'a>(
+        &self,
+        components: &'a LicensedComponents,
+    ) -> Vec<(&'a LicensedComponent, String)> {
+        components
+            .iter_components()
+            .filter_map(|c| self.evaluate_component(c).map(|reason| (c, reason)))
+            .collect()
+    }
+}
+
 /// Defines license information for a Python package.
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct PackageLicenseInfo {
@@ -965,6 +1163,9 @@ impl TryInto<LicensedComponent> for PackageLicenseInfo {
             component.add_license_text(text);
         }
 
+        if !self.version.is_empty() {
+            component.set_version(self.version);
+        }
         if let Some(value) = self.homepage {
             component.set_homepage(value);
         }
@@ -1269,6 +1470,7 @@ mod tests {
             LicenseFlavor::None,
         );
         assert_eq!(c, wanted);
+        assert_eq!(c.version(), Some("0.1"));
 
         Ok(())
     }
@@ -1403,4 +1605,108 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn license_policy_set_licenses() -> Result<()> {
+        let mut policy = LicensePolicy::default();
+
+        policy.set_allowed_licenses(["MIT", "Apache-2.0"].iter())?;
+        assert_eq!(
+            policy.allowed_licenses().iter().collect::<Vec<_>>(),
+            vec!["Apache-2.0", "MIT"]
+        );
+
+        assert!(policy.set_denied_licenses(["bad"].iter()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn license_policy_allowed() -> Result<()> {
+        let mut policy = LicensePolicy::default();
+        policy.set_allowed_licenses(["MIT"].iter())?;
+
+        let mit = LicensedComponent::new_spdx(ComponentFlavor::PythonModule("a".into()), "MIT")?;
+        let either = LicensedComponent::new_spdx(
+            ComponentFlavor::PythonModule("b".into()),
+            "MIT OR GPL-3.0-only",
+        )?;
+        let gpl =
+            LicensedComponent::new_spdx(ComponentFlavor::RustCrate("c".into()), "GPL-3.0-only")?;
+        let both = LicensedComponent::new_spdx(
+            ComponentFlavor::PythonModule("d".into()),
+            "MIT AND GPL-3.0-only",
+        )?;
+
+        assert_eq!(policy.evaluate_component(&mit), None);
+        assert_eq!(policy.evaluate_component(&either), None);
+        assert_eq!(
+            policy.evaluate_component(&gpl),
+            Some("license GPL-3.0-only is not allowed".to_string())
+        );
+        assert!(policy.evaluate_component(&both).is_some());
+
+        policy.set_exceptions(["c"].iter());
+        assert_eq!(policy.evaluate_component(&gpl), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn license_policy_denied() -> Result<()> {
+        let mut policy = LicensePolicy::default();
+        policy.set_denied_licenses(["GPL-3.0-only"].iter())?;
+
+        let either = LicensedComponent::new_spdx(
+            ComponentFlavor::PythonModule("a".into()),
+            "MIT OR GPL-3.0-only",
+        )?;
+        let gpl =
+            LicensedComponent::new_spdx(ComponentFlavor::Library("b".into()), "GPL-3.0-only")?;
+
+        assert_eq!(policy.evaluate_component(&either), None);
+        assert_eq!(
+            policy.evaluate_component(&gpl),
+            Some("license GPL-3.0-only is denied".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn license_policy_unknown() -> Result<()> {
+        let mut policy = LicensePolicy::default();
+
+        let mut components = LicensedComponents::default();
+        components.add_component(LicensedComponent::new(
+            ComponentFlavor::PythonModule("a".into()),
+            LicenseFlavor::None,
+        ));
+        components.add_component(LicensedComponent::new(
+            ComponentFlavor::PythonModule("b".into()),
+            LicenseFlavor::Unknown(vec!["Custom".into()]),
+        ));
+        components.add_component(LicensedComponent::new(
+            ComponentFlavor::PythonModule("c".into()),
+            LicenseFlavor::PublicDomain,
+        ));
+
+        let violations = policy
+            .violations(&components)
+            .into_iter()
+            .map(|(c, reason)| (c.flavor().name().to_string(), reason))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            violations,
+            vec![
+                ("a".to_string(), "license is missing".to_string()),
+                ("b".to_string(), "license Custom is unknown".to_string()),
+            ]
+        );
+
+        policy.set_allow_unknown(true);
+        assert!(policy.violations(&components).is_empty());
+
+        Ok(())
+    }
 }
@@ -18,6 +18,7 @@ use {
         resource_collection::PythonResourceAddCollectionContext,
     },
     anyhow::Result,
+    python_packed_resources::CompressionPolicy,
     std::collections::{HashMap, HashSet},
 };
 
@@ -107,6 +108,18 @@ pub struct PythonPackagingPolicy {
     /// Optional fallback location for resources should `resources_location` fail.
     resources_location_fallback: Option<ConcreteResourceLocation>,
 
+    /// How to compress data in packed resources.
+    ///
+    /// Any value other than `None` results in version 4 of the packed
+    /// resources format being written.
+    resources_compression: CompressionPolicy,
+
+    /// Whether to write a sorted name index in packed resources.
+    ///
+    /// Enabling results in version 4 of the packed resources format being
+    /// written.
+    resources_name_index: bool,
+
     /// Whether to allow in-memory shared library loading.
     ///
     /// If true, we will attempt to load Python extension modules
@@ -187,6 +200,8 @@ impl Default for PythonPackagingPolicy {
             preferred_extension_module_variants: HashMap::new(),
             resources_location: ConcreteResourceLocation::InMemory,
             resources_location_fallback: None,
+            resources_compression: CompressionPolicy::None,
+            resources_name_index: false,
             allow_in_memory_shared_library_loading: false,
             allow_files: false,
             file_scanner_emit_files: false,
@@ -283,6 +298,26 @@ impl PythonPackagingPolicy {
         self.file_scanner_classify_files = value;
     }
 
+    /// Obtain how data in packed resources should be compressed.
+    pub fn resources_compression(&self) -> CompressionPolicy {
+        self.resources_compression
+    }
+
+    /// Set how data in packed resources should be compressed.
+    pub fn set_resources_compression(&mut self, value: CompressionPolicy) {
+        self.resources_compression = value;
+    }
+
+    /// Whether to write a sorted name index in packed resources.
+    pub fn resources_name_index(&self) -> bool {
+        self.resources_name_index
+    }
+
+    /// Set whether to write a sorted name index in packed resources.
+    pub fn set_resources_name_index(&mut self, value: bool) {
+        self.resources_name_index = value;
+    }
+
     /// Whether to allow in-memory shared library loading.
     pub fn allow_in_memory_shared_library_loading(&self) -> bool {
         self.allow_in_memory_shared_library_loading
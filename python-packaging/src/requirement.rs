@@ -0,0 +1,805 @@
+// Copyright 2022 Gregory Szorc.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*! Requirements on Python distributions.
+
+This implements the subset of PEP 440 versions and PEP 508 requirements
+needed to select distributions:
+
+* Versions are ordered by their epoch, release segments, and whether they
+  are a pre-release. Other segments, like post-release numbers, are ignored.
+* Requirements have a name, extras, version specifiers, and an environment
+  marker. Both `name>=1.0` and the `name (>=1.0)` form found in
+  `Requires-Dist` metadata are accepted. Direct URL requirements are not
+  supported.
+* Environment markers are evaluated against a [MarkerEnvironment]. Markers
+  referencing values the environment doesn't define are errors.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    std::cmp::Ordering,
+};
+
+/// Normalize a distribution name per PEP 503.
+///
+/// Runs of `-`, `_`, and `.` become a single `-` and the name is lowercased.
+pub fn normalize_project_name(name: &str) -> String {
+    let mut res = String::with_capacity(name.len());
+    let mut in_separator = false;
+
+    for c in name.chars() {
+        if matches!(c, '-' | '_' | '.') {
+            if !in_separator {
+                res.push('-');
+            }
+            in_separator = true;
+        } else {
+            res.push(c.to_ascii_lowercase());
+            in_separator = false;
+        }
+    }
+
+    res
+}
+
+/// A distribution version.
+///
+/// Only the epoch and release segments are interpreted. Versions with other
+/// segments, except post-releases and local versions, are pre-releases.
+#[derive(Clone, Debug)]
+pub struct Version {
+    epoch: u64,
+    release: Vec<u64>,
+    pre_release: bool,
+    /// Segments after the release, lowercased and without separators.
+    suffix: String,
+}
+
+impl Version {
+    /// Parse a version string.
+    pub fn parse(s: &str) -> Result<Self> {
+        let raw = s.trim();
+        let s = raw.to_ascii_lowercase();
+        let s = s.strip_prefix('v').unwrap_or(&s);
+
+        let (epoch, s) = match s.split_once('!') {
+            Some((epoch, rest)) => (epoch.parse::<u64>()?, rest),
+            None => (0, s),
+        };
+
+        let end = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(s.len());
+        let (release, suffix) = s.split_at(end);
+
+        let release = release
+            .trim_end_matches('.')
+            .split('.')
+            .map(|x| x.parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("parsing version {}", raw))?;
+
+        let suffix = suffix.trim_start_matches(['.', '-', '_']);
+        let pre_release =
+            !(suffix.is_empty() || suffix.starts_with("post") || suffix.starts_with('+'));
+
+        Ok(Self {
+            epoch,
+            release,
+            pre_release,
+            suffix: suffix.replace(['.', '-', '_'], ""),
+        })
+    }
+
+    /// Whether this is exactly the same version as `other`.
+    ///
+    /// Unlike `==`, which only compares the epoch and release segments,
+    /// pre-release, post-release, development, and local segments must match
+    /// as well.
+    pub fn is_identical(&self, other: &Self) -> bool {
+        self == other && self.suffix == other.suffix
+    }
+
+    /// Whether this is a pre-release or development release.
+    pub fn is_pre_release(&self) -> bool {
+        self.pre_release
+    }
+
+    /// Compare release segments, padding the shorter with zeros.
+    fn cmp_release(&self, other: &Self) -> Ordering {
+        let len = self.release.len().max(other.release.len());
+
+        (0..len)
+            .map(|i| {
+                let a = self.release.get(i).copied().unwrap_or(0);
+                let b = other.release.get(i).copied().unwrap_or(0);
+                a.cmp(&b)
+            })
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+
+    /// Whether the release segments begin with those of `prefix`.
+    fn has_release_prefix(&self, prefix: &Self) -> bool {
+        self.epoch == prefix.epoch
+            && prefix
+                .release
+                .iter()
+                .enumerate()
+                .all(|(i, x)| self.release.get(i).copied().unwrap_or(0) == *x)
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.epoch
+            .cmp(&other.epoch)
+            .then_with(|| self.cmp_release(other))
+            // Pre-releases sort before the release.
+            .then_with(|| other.pre_release.cmp(&self.pre_release))
+    }
+}
+
+/// A comparison operator in a version specifier.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Operator {
+    Compatible,
+    Equal,
+    NotEqual,
+    LessEqual,
+    GreaterEqual,
+    Less,
+    Greater,
+}
+
+impl Operator {
+    /// Operators and their spelling, longest first so prefixes match correctly.
+    const ALL: [(&'static str, Operator); 8] = [
+        ("~=", Operator::Compatible),
+        ("===", Operator::Equal),
+        ("==", Operator::Equal),
+        ("!=", Operator::NotEqual),
+        ("<=", Operator::LessEqual),
+        (">=", Operator::GreaterEqual),
+        ("<", Operator::Less),
+        (">", Operator::Greater),
+    ];
+
+    fn from_spelling(s: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|(spelling, _)| *spelling == s)
+            .map(|(_, operator)| *operator)
+    }
+}
+
+/// A version specifier, like `>=1.0`.
+#[derive(Clone, Debug)]
+pub struct VersionSpecifier {
+    operator: Operator,
+    version: Version,
+    /// Whether the version ends with `.*`.
+    wildcard: bool,
+}
+
+impl VersionSpecifier {
+    /// Parse a version specifier.
+    ///
+    /// `===` is treated like `==`.
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+
+        let (operator, version) = Operator::ALL
+            .iter()
+            .find_map(|(prefix, operator)| s.strip_prefix(prefix).map(|v| (*operator, v)))
+            .ok_or_else(|| anyhow!("invalid version specifier: {}", s))?;
+
+        let version = version.trim();
+        let (version, wildcard) = match version.strip_suffix(".*") {
+            Some(v) if matches!(operator, Operator::Equal | Operator::NotEqual) => (v, true),
+            _ => (version, false),
+        };
+
+        Ok(Self {
+            operator,
+            version: Version::parse(version)?,
+            wildcard,
+        })
+    }
+
+    /// Whether a version satisfies this specifier.
+    pub fn matches(&self, version: &Version) -> bool {
+        match self.operator {
+            Operator::Compatible => {
+                let mut prefix = self.version.clone();
+                if prefix.release.len() > 1 {
+                    prefix.release.pop();
+                }
+
+                version >= &self.version && version.has_release_prefix(&prefix)
+            }
+            Operator::Equal if self.wildcard => version.has_release_prefix(&self.version),
+            Operator::Equal => version == &self.version,
+            Operator::NotEqual if self.wildcard => !version.has_release_prefix(&self.version),
+            Operator::NotEqual => version != &self.version,
+            Operator::LessEqual => version <= &self.version,
+            Operator::GreaterEqual => version >= &self.version,
+            Operator::Less => version < &self.version,
+            Operator::Greater => version > &self.version,
+        }
+    }
+}
+
+/// Values of environment marker variables.
+///
+/// Fields are named after the marker variables of PEP 508. `None` means the
+/// value isn't known. Evaluating a marker referencing it is an error.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MarkerEnvironment {
+    pub implementation_name: Option<String>,
+    pub implementation_version: Option<String>,
+    pub os_name: Option<String>,
+    pub platform_machine: Option<String>,
+    pub platform_python_implementation: Option<String>,
+    pub platform_release: Option<String>,
+    pub platform_system: Option<String>,
+    pub platform_version: Option<String>,
+    pub python_full_version: Option<String>,
+    pub python_version: Option<String>,
+    pub sys_platform: Option<String>,
+}
+
+impl MarkerEnvironment {
+    /// Obtain the value of a variable.
+    ///
+    /// `Err` is returned for unknown variable names and `Ok(None)` if the
+    /// value isn't known.
+    fn get(&self, name: &str) -> Result<Option<&str>> {
+        let value = match name {
+            "implementation_name" => &self.implementation_name,
+            "implementation_version" => &self.implementation_version,
+            "os_name" | "os.name" => &self.os_name,
+            "platform_machine" | "platform.machine" => &self.platform_machine,
+            "platform_python_implementation"
+            | "platform.python_implementation"
+            | "python_implementation" => &self.platform_python_implementation,
+            "platform_release" => &self.platform_release,
+            "platform_system" => &self.platform_system,
+            "platform_version" | "platform.version" => &self.platform_version,
+            "python_full_version" => &self.python_full_version,
+            "python_version" => &self.python_version,
+            "sys_platform" | "sys.platform" => &self.sys_platform,
+            _ => return Err(anyhow!("unknown environment marker variable: {}", name)),
+        };
+
+        Ok(value.as_deref())
+    }
+}
+
+/// An operand in an environment marker comparison.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum MarkerValue {
+    Variable(String),
+    Literal(String),
+}
+
+/// An operator in an environment marker comparison.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum MarkerOperator {
+    Version(String),
+    In,
+    NotIn,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum MarkerExpression {
+    And(Box<MarkerExpression>, Box<MarkerExpression>),
+    Or(Box<MarkerExpression>, Box<MarkerExpression>),
+    Compare(MarkerValue, MarkerOperator, MarkerValue),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum MarkerToken<'a> {
+    OpenParen,
+    CloseParen,
+    String(&'a str),
+    Word(&'a str),
+    Operator(&'a str),
+}
+
+fn tokenize_marker(s: &str) -> Result<Vec<MarkerToken<'_>>> {
+    let mut tokens = vec![];
+    let mut rest = s;
+
+    loop {
+        rest = rest.trim_start();
+
+        let c = match rest.chars().next() {
+            Some(c) => c,
+            None => break,
+        };
+
+        let len = match c {
+            '(' => {
+                tokens.push(MarkerToken::OpenParen);
+                1
+            }
+            ')' => {
+                tokens.push(MarkerToken::CloseParen);
+                1
+            }
+            '\'' | '"' => {
+                let end = rest[1..]
+                    .find(c)
+                    .ok_or_else(|| anyhow!("unterminated string in marker: {}", s))?;
+                tokens.push(MarkerToken::String(&rest[1..1 + end]));
+                end + 2
+            }
+            c if c.is_ascii_alphanumeric() || c == '_' => {
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '.')))
+                    .unwrap_or(rest.len());
+                tokens.push(MarkerToken::Word(&rest[0..len]));
+                len
+            }
+            '<' | '>' | '=' | '!' | '~' => {
+                let len = rest
+                    .find(|c: char| !matches!(c, '<' | '>' | '=' | '!' | '~'))
+                    .unwrap_or(rest.len());
+                tokens.push(MarkerToken::Operator(&rest[0..len]));
+                len
+            }
+            _ => return Err(anyhow!("unexpected character {:?} in marker: {}", c, s)),
+        };
+
+        rest = &rest[len..];
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent parser for environment markers.
+struct MarkerParser<'a> {
+    tokens: Vec<MarkerToken<'a>>,
+    position: usize,
+}
+
+impl<'a> MarkerParser<'a> {
+    fn peek(&self) -> Option<&MarkerToken<'a>> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<MarkerToken<'a>> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<MarkerExpression> {
+        let mut expression = self.parse_and()?;
+
+        while self.peek() == Some(&MarkerToken::Word("or")) {
+            self.next();
+            expression = MarkerExpression::Or(Box::new(expression), Box::new(self.parse_and()?));
+        }
+
+        Ok(expression)
+    }
+
+    fn parse_and(&mut self) -> Result<MarkerExpression> {
+        let mut expression = self.parse_atom()?;
+
+        while self.peek() == Some(&MarkerToken::Word("and")) {
+            self.next();
+            expression = MarkerExpression::And(Box::new(expression), Box::new(self.parse_atom()?));
+        }
+
+        Ok(expression)
+    }
+
+    fn parse_atom(&mut self) -> Result<MarkerExpression> {
+        if self.peek() == Some(&MarkerToken::OpenParen) {
+            self.next();
+            let expression = self.parse_or()?;
+
+            return match self.next() {
+                Some(MarkerToken::CloseParen) => Ok(expression),
+                _ => Err(anyhow!("expected )")),
+            };
+        }
+
+        let left = self.parse_value()?;
+        let operator = self.parse_operator()?;
+        let right = self.parse_value()?;
+
+        Ok(MarkerExpression::Compare(left, operator, right))
+    }
+
+    fn parse_value(&mut self) -> Result<MarkerValue> {
+        match self.next() {
+            Some(MarkerToken::String(s)) => Ok(MarkerValue::Literal(s.to_string())),
+            Some(MarkerToken::Word(name)) if !matches!(name, "and" | "or" | "in" | "not") => {
+                Ok(MarkerValue::Variable(name.to_string()))
+            }
+            token => Err(anyhow!("expected variable or string, got {:?}", token)),
+        }
+    }
+
+    fn parse_operator(&mut self) -> Result<MarkerOperator> {
+        match self.next() {
+            Some(MarkerToken::Operator(op)) if Operator::from_spelling(op).is_some() => {
+                Ok(MarkerOperator::Version(op.to_string()))
+            }
+            Some(MarkerToken::Word("in")) => Ok(MarkerOperator::In),
+            Some(MarkerToken::Word("not")) => match self.next() {
+                Some(MarkerToken::Word("in")) => Ok(MarkerOperator::NotIn),
+                token => Err(anyhow!("expected in, got {:?}", token)),
+            },
+            token => Err(anyhow!("expected comparison operator, got {:?}", token)),
+        }
+    }
+}
+
+/// Compare the values of a marker comparison.
+///
+/// Values are compared as versions if both are valid versions. Otherwise they
+/// are compared as strings.
+fn compare_marker_values(left: &str, operator: &MarkerOperator, right: &str) -> Result<bool> {
+    let op = match operator {
+        MarkerOperator::In => return Ok(right.contains(left)),
+        MarkerOperator::NotIn => return Ok(!right.contains(left)),
+        MarkerOperator::Version(op) => op.as_str(),
+    };
+
+    if op == "===" {
+        return Ok(left == right);
+    }
+
+    if let (Ok(specifier), Ok(version)) = (
+        VersionSpecifier::parse(&format!("{}{}", op, right)),
+        Version::parse(left),
+    ) {
+        return Ok(specifier.matches(&version));
+    }
+
+    match op {
+        "==" => Ok(left == right),
+        "!=" => Ok(left != right),
+        "<" => Ok(left < right),
+        "<=" => Ok(left <= right),
+        ">" => Ok(left > right),
+        ">=" => Ok(left >= right),
+        _ => Err(anyhow!(
+            "cannot compare {:?} {} {:?}: they are not versions",
+            left,
+            op,
+            right
+        )),
+    }
+}
+
+impl MarkerExpression {
+    fn evaluate(&self, env: &MarkerEnvironment, extra: Option<&str>) -> Result<bool> {
+        match self {
+            Self::And(a, b) => Ok(a.evaluate(env, extra)? && b.evaluate(env, extra)?),
+            Self::Or(a, b) => Ok(a.evaluate(env, extra)? || b.evaluate(env, extra)?),
+            Self::Compare(left, operator, right) => {
+                // Extra names are normalized, like distribution names.
+                let is_extra = [left, right]
+                    .iter()
+                    .any(|v| matches!(v, MarkerValue::Variable(name) if name == "extra"));
+
+                let resolve = |value: &MarkerValue| -> Result<String> {
+                    let value = match value {
+                        MarkerValue::Literal(s) => s.as_str(),
+                        MarkerValue::Variable(name) if name == "extra" => extra.unwrap_or(""),
+                        MarkerValue::Variable(name) => env.get(name)?.ok_or_else(|| {
+                            anyhow!("value of environment marker variable {} is not known", name)
+                        })?,
+                    };
+
+                    Ok(if is_extra {
+                        normalize_project_name(value)
+                    } else {
+                        value.to_string()
+                    })
+                };
+
+                compare_marker_values(&resolve(left)?, operator, &resolve(right)?)
+            }
+        }
+    }
+}
+
+/// An environment marker, like `python_version < "3.8"`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Marker {
+    expression: MarkerExpression,
+}
+
+impl Marker {
+    /// Parse an environment marker.
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut parser = MarkerParser {
+            tokens: tokenize_marker(s)?,
+            position: 0,
+        };
+
+        let expression = parser
+            .parse_or()
+            .and_then(|expression| match parser.peek() {
+                None => Ok(expression),
+                Some(token) => Err(anyhow!("unexpected {:?}", token)),
+            })
+            .with_context(|| format!("parsing marker {}", s.trim()))?;
+
+        Ok(Self { expression })
+    }
+
+    /// Evaluate the marker.
+    ///
+    /// `extra` is the value of the `extra` variable. It is the extra being
+    /// installed when evaluating dependencies of a distribution.
+    pub fn evaluate(&self, env: &MarkerEnvironment, extra: Option<&str>) -> Result<bool> {
+        self.expression.evaluate(env, extra)
+    }
+}
+
+/// A requirement on a distribution.
+#[derive(Clone, Debug)]
+pub struct Requirement {
+    /// Normalized name of the distribution.
+    pub name: String,
+
+    /// Normalized names of requested extras.
+    pub extras: Vec<String>,
+
+    /// Version specifiers. All must match.
+    pub specifiers: Vec<VersionSpecifier>,
+
+    /// Environment marker the requirement is conditional on.
+    pub marker: Option<Marker>,
+
+    /// The requirement as written.
+    pub raw: String,
+}
+
+impl Requirement {
+    /// Parse a requirement.
+    pub fn parse(s: &str) -> Result<Self> {
+        let raw = s.trim().to_string();
+
+        let (requirement, marker) = match raw.split_once(';') {
+            Some((requirement, marker)) => (requirement, Some(marker)),
+            None => (raw.as_str(), None),
+        };
+
+        let end = requirement
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+            .unwrap_or(requirement.len());
+        let (name, rest) = requirement.split_at(end);
+
+        if name.is_empty() {
+            return Err(anyhow!("invalid requirement: {}", raw));
+        }
+
+        let mut rest = rest.trim();
+        let mut extras = vec![];
+
+        if let Some(s) = rest.strip_prefix('[') {
+            let (names, remaining) = s
+                .split_once(']')
+                .ok_or_else(|| anyhow!("unterminated extras in requirement: {}", raw))?;
+
+            extras = names
+                .split(',')
+                .map(|x| x.trim())
+                .filter(|x| !x.is_empty())
+                .map(normalize_project_name)
+                .collect();
+            rest = remaining.trim();
+        }
+
+        if rest.starts_with('@') {
+            return Err(anyhow!(
+                "direct URL requirements are not supported: {}",
+                raw
+            ));
+        }
+
+        let rest = rest
+            .strip_prefix('(')
+            .and_then(|x| x.strip_suffix(')'))
+            .unwrap_or(rest)
+            .trim();
+
+        let specifiers = if rest.is_empty() {
+            vec![]
+        } else {
+            rest.split(',')
+                .map(VersionSpecifier::parse)
+                .collect::<Result<Vec<_>>>()
+                .with_context(|| format!("parsing requirement {}", raw))?
+        };
+
+        let marker = marker
+            .map(Marker::parse)
+            .transpose()
+            .with_context(|| format!("parsing requirement {}", raw))?;
+
+        Ok(Self {
+            name: normalize_project_name(name),
+            extras,
+            specifiers,
+            marker,
+            raw,
+        })
+    }
+
+    /// Whether a version satisfies all version specifiers.
+    pub fn matches(&self, version: &Version) -> bool {
+        self.specifiers.iter().all(|s| s.matches(version))
+    }
+
+    /// Whether a specifier explicitly references a pre-release.
+    pub fn allows_pre_releases(&self) -> bool {
+        self.specifiers.iter().any(|s| s.version.pre_release)
+    }
+
+    /// Whether the requirement applies to an environment.
+    ///
+    /// Requirements without a marker always apply. See [Marker::evaluate()]
+    /// for the meaning of `extra`.
+    pub fn applies(&self, env: &MarkerEnvironment, extra: Option<&str>) -> Result<bool> {
+        match &self.marker {
+            Some(marker) => marker
+                .evaluate(env, extra)
+                .with_context(|| format!("evaluating marker of requirement {}", self.raw)),
+            None => Ok(true),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linux_env() -> MarkerEnvironment {
+        MarkerEnvironment {
+            implementation_name: Some("cpython".to_string()),
+            os_name: Some("posix".to_string()),
+            platform_machine: Some("x86_64".to_string()),
+            python_full_version: Some("3.10.5".to_string()),
+            python_version: Some("3.10".to_string()),
+            sys_platform: Some("linux".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_normalize_project_name() {
+        assert_eq!(normalize_project_name("Foo"), "foo");
+        assert_eq!(normalize_project_name("foo_bar"), "foo-bar");
+        assert_eq!(normalize_project_name("Foo.-_Bar"), "foo-bar");
+    }
+
+    #[test]
+    fn test_version_ordering() {
+        let v = |s| Version::parse(s).unwrap();
+
+        assert!(v("1.0") == v("1.0.0"));
+        assert!(v("1.0a1") < v("1.0"));
+        assert!(v("1.0") < v("1.0.1"));
+        assert!(v("1.0.post1") == v("1.0"));
+        assert!(v("1!0.1") > v("2.0"));
+        assert!(v("1.0").is_identical(&v("1.0.0")));
+        assert!(v("1.0-RC.1").is_identical(&v("1.0rc1")));
+        assert!(!v("1.0.post1").is_identical(&v("1.0")));
+        assert!(!v("1.0rc1").is_identical(&v("1.0rc2")));
+        assert!(v("1.0rc1").is_pre_release());
+        assert!(!v("1.0+local").is_pre_release());
+        assert!(Version::parse("linux").is_err());
+    }
+
+    #[test]
+    fn test_version_specifier() {
+        let matches = |spec: &str, version: &str| {
+            VersionSpecifier::parse(spec)
+                .unwrap()
+                .matches(&Version::parse(version).unwrap())
+        };
+
+        assert!(matches("~=1.4.2", "1.4.5"));
+        assert!(!matches("~=1.4.2", "1.5"));
+        assert!(matches("==1.4.*", "1.4.9"));
+        assert!(!matches("!=1.4.*", "1.4.9"));
+        assert!(matches("===1.0", "1.0"));
+        assert!(matches("<2", "1.9"));
+        assert!(VersionSpecifier::parse("1.0").is_err());
+    }
+
+    #[test]
+    fn test_requirement_parse() -> Result<()> {
+        let req = Requirement::parse("Foo_Bar[Socks, security] (>=1.0,<2); os_name == 'nt'")?;
+
+        assert_eq!(req.name, "foo-bar");
+        assert_eq!(req.extras, vec!["socks", "security"]);
+        assert!(req.matches(&Version::parse("1.5")?));
+        assert!(!req.matches(&Version::parse("2.0")?));
+        assert!(!req.applies(&linux_env(), None)?);
+
+        let req = Requirement::parse("six")?;
+        assert!(req.specifiers.is_empty());
+        assert!(req.marker.is_none());
+        assert!(req.applies(&MarkerEnvironment::default(), None)?);
+
+        assert!(Requirement::parse("foo @ https://example.com/foo.whl").is_err());
+        assert!(Requirement::parse("foo; python_version <").is_err());
+        assert!(Requirement::parse(">=1.0").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_marker_evaluate() -> Result<()> {
+        let env = linux_env();
+        let evaluate = |marker: &str, extra: Option<&str>| {
+            Marker::parse(marker).unwrap().evaluate(&env, extra)
+        };
+
+        assert!(evaluate("python_version >= '3.8'", None)?);
+        assert!(!evaluate("python_version < \"3.8\"", None)?);
+        // Versions are compared as versions, not strings.
+        assert!(evaluate("python_full_version > '3.9.10'", None)?);
+        assert!(evaluate("'3.6' < python_version", None)?);
+        assert!(evaluate(
+            "sys_platform == 'linux' and os_name == 'posix'",
+            None
+        )?);
+        assert!(evaluate(
+            "sys_platform == 'win32' or (platform_machine in 'x86_64 amd64' and python_version != '2.7')",
+            None
+        )?);
+        assert!(!evaluate(
+            "sys_platform == 'win32' or sys_platform == 'darwin' and os_name == 'posix'",
+            None
+        )?);
+        assert!(evaluate("sys.platform not in 'win32 cygwin'", None)?);
+
+        assert!(!evaluate("extra == 'socks'", None)?);
+        assert!(evaluate("extra == 'SOCKS'", Some("socks"))?);
+
+        // Values the environment doesn't know and unsupported comparisons are errors.
+        assert!(evaluate("platform_release >= '5.0'", None).is_err());
+        assert!(evaluate("sys_platform ~= 'linux'", None).is_err());
+
+        assert!(Marker::parse("unknown == '1'")
+            .unwrap()
+            .evaluate(&env, None)
+            .is_err());
+        assert!(Marker::parse("(python_version > '3'").is_err());
+        assert!(Marker::parse("python_version > '3' python_version").is_err());
+
+        Ok(())
+    }
+}
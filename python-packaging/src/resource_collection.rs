@@ -25,11 +25,12 @@ use {
         },
     },
     anyhow::{anyhow, Context, Result},
-    python_packed_resources::Resource,
+    python_packed_resources::{CompressionPolicy, PackedResourcesSigningKey, Resource},
     std::{
         borrow::Cow,
         collections::{BTreeMap, BTreeSet, HashMap},
         path::PathBuf,
+        sync::Arc,
     },
     tugger_file_manifest::{File, FileData, FileEntry, FileManifest},
 };
@@ -402,6 +403,8 @@ impl PrePackagedResource {
             } else {
                 None
             },
+            field_compression: None,
+            is_tombstone: false,
         };
 
         if let Some((prefix, filename, location)) = &self.relative_path_shared_library {
@@ -642,20 +645,57 @@ pub struct CompiledResourcesCollection<'a> {
 
     /// Extra file installs that must be performed so referenced files are available.
     pub extra_files: Vec<FileInstall>,
+
+    /// How to compress field data when writing packed resources.
+    pub compression: CompressionPolicy,
+
+    /// Whether to write a sorted name index in packed resources.
+    ///
+    /// The index allows resources to be looked up without parsing all of
+    /// them.
+    pub name_index: bool,
+
+    /// Key to sign packed resources data with.
+    ///
+    /// If set, an integrity trailer signed with this key is appended to
+    /// written packed resources data.
+    pub signing_key: Option<Arc<PackedResourcesSigningKey>>,
 }
 
 impl<'a> CompiledResourcesCollection<'a> {
-    /// Write resources to packed resources data, version 1.
+    /// Write resources to packed resources data.
+    ///
+    /// Version 3 of the format is written unless compression or the name
+    /// index is enabled, in which case version 4 is written.
     pub fn write_packed_resources<W: std::io::Write>(&self, writer: &mut W) -> Result<()> {
-        python_packed_resources::write_packed_resources_v3(
-            &self
-                .resources
-                .values()
-                .cloned()
-                .collect::<Vec<Resource<'a, u8>>>(),
-            writer,
-            None,
-        )
+        let resources = self
+            .resources
+            .values()
+            .cloned()
+            .collect::<Vec<Resource<'a, u8>>>();
+
+        let mut data = vec![];
+
+        if self.compression == CompressionPolicy::None && !self.name_index {
+            python_packed_resources::write_packed_resources_v3(&resources, &mut data, None)?;
+        } else {
+            python_packed_resources::write_packed_resources_v4(
+                &resources,
+                &mut data,
+                None,
+                self.compression,
+                self.name_index,
+            )?;
+        }
+
+        if let Some(key) = &self.signing_key {
+            python_packed_resources::write_integrity_trailer(&mut data, Some(key))
+                .context("writing packed resources integrity trailer")?;
+        }
+
+        writer.write_all(&data)?;
+
+        Ok(())
     }
 
     /// Convert the file installs to a [FileManifest].
@@ -712,6 +752,9 @@ pub struct PythonResourceCollector {
 
     /// Collection of software components which are licensed.
     licensed_components: LicensedComponents,
+
+    /// Names of extension modules linking each library, keyed by library name.
+    library_links: BTreeMap<String, BTreeSet<String>>,
 }
 
 impl PythonResourceCollector {
@@ -735,6 +778,7 @@ impl PythonResourceCollector {
             allow_files,
             resources: BTreeMap::new(),
             licensed_components: LicensedComponents::default(),
+            library_links: BTreeMap::new(),
         }
     }
 
@@ -812,6 +856,24 @@ impl PythonResourceCollector {
         Ok(())
     }
 
+    /// Obtain the names of extension modules linking each library.
+    ///
+    /// Keys are library names. Values are the names of the added extension
+    /// modules linking that library.
+    pub fn library_links(&self) -> &BTreeMap<String, BTreeSet<String>> {
+        &self.library_links
+    }
+
+    /// Record the libraries an added extension module links.
+    fn record_library_links(&mut self, module: &PythonExtensionModule) {
+        for link in &module.link_libraries {
+            self.library_links
+                .entry(link.name.clone())
+                .or_default()
+                .insert(module.name.clone());
+        }
+    }
+
     /// Obtain a finalized collection of licensed components.
     ///
     /// The collection has entries for components that lack licenses and has additional
@@ -1486,6 +1548,8 @@ impl PythonResourceCollector {
         entry.is_builtin_extension_module = true;
         entry.is_package = module.is_package;
 
+        self.record_library_links(module);
+
         Ok(vec![AddResourceAction::AddedBuiltinExtensionModule(
             module.name.clone(),
         )])
@@ -1581,6 +1645,8 @@ impl PythonResourceCollector {
             location.clone(),
         ));
 
+        self.record_library_links(module);
+
         Ok(actions)
     }
 
@@ -1865,6 +1931,9 @@ impl PythonResourceCollector {
         Ok(CompiledResourcesCollection {
             resources,
             extra_files,
+            compression: CompressionPolicy::None,
+            name_index: false,
+            signing_key: None,
         })
     }
 }
@@ -4234,6 +4303,10 @@ mod tests {
                 ..PrePackagedResource::default()
             })
         );
+        assert_eq!(
+            c.library_links().get("foo"),
+            Some(&BTreeSet::from(["myext".to_string()]))
+        );
 
         let mut compiler = FakeBytecodeCompiler { magic_number: 42 };
 
@@ -4684,4 +4757,99 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_write_packed_resources_compression() -> Result<()> {
+        let mut r = PythonResourceCollector::new(
+            vec![AbstractResourceLocation::InMemory],
+            vec![],
+            false,
+            false,
+        );
+        r.add_python_module_source(
+            &PythonModuleSource {
+                name: "foo".to_string(),
+                source: FileData::Memory(b"import os\n".repeat(64)),
+                is_package: false,
+                cache_tag: DEFAULT_CACHE_TAG.to_string(),
+                is_stdlib: false,
+                is_test: false,
+            },
+            &ConcreteResourceLocation::InMemory,
+        )?;
+
+        let mut compiler = FakeBytecodeCompiler { magic_number: 42 };
+        let mut compiled = r.compile_resources(&mut compiler)?;
+
+        let mut buffer = vec![];
+        compiled.write_packed_resources(&mut buffer)?;
+        assert!(buffer.starts_with(python_packed_resources::HEADER_V3));
+
+        compiled.compression = CompressionPolicy::Zstd;
+        let mut buffer = vec![];
+        compiled.write_packed_resources(&mut buffer)?;
+        assert!(buffer.starts_with(python_packed_resources::HEADER_V4));
+
+        let resources = python_packed_resources::load_resources(&buffer)
+            .map_err(|e| anyhow!(e))?
+            .collect::<Result<Vec<_>, &'static str>>()
+            .map_err(|e| anyhow!(e))?;
+        assert_eq!(resources.len(), 1);
+
+        let resource = resources[0].to_decompressed().map_err(|e| anyhow!(e))?;
+        assert_eq!(
+            resource.in_memory_source,
+            Some(Cow::Owned(b"import os\n".repeat(64)))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_packed_resources_name_index() -> Result<()> {
+        let mut r = PythonResourceCollector::new(
+            vec![AbstractResourceLocation::InMemory],
+            vec![],
+            false,
+            false,
+        );
+        for name in ["foo", "bar"] {
+            r.add_python_module_source(
+                &PythonModuleSource {
+                    name: name.to_string(),
+                    source: FileData::Memory(name.as_bytes().to_vec()),
+                    is_package: false,
+                    cache_tag: DEFAULT_CACHE_TAG.to_string(),
+                    is_stdlib: false,
+                    is_test: false,
+                },
+                &ConcreteResourceLocation::InMemory,
+            )?;
+        }
+
+        let mut compiler = FakeBytecodeCompiler { magic_number: 42 };
+        let mut compiled = r.compile_resources(&mut compiler)?;
+        compiled.name_index = true;
+
+        let mut buffer = vec![];
+        compiled.write_packed_resources(&mut buffer)?;
+        assert!(buffer.starts_with(python_packed_resources::HEADER_V4));
+
+        let resources = python_packed_resources::load_resources(&buffer).map_err(|e| anyhow!(e))?;
+        let index = resources
+            .name_index()
+            .ok_or_else(|| anyhow!("name index not written"))?;
+        assert_eq!(index.len(), 2);
+
+        let resource = index
+            .find("foo")
+            .map_err(|e| anyhow!(e))?
+            .ok_or_else(|| anyhow!("resource not found"))?;
+        assert_eq!(
+            resource.in_memory_source,
+            Some(Cow::Borrowed(b"foo".as_ref()))
+        );
+
+        Ok(())
+    }
 }
@@ -24,7 +24,7 @@ use {
 ///
 /// This is copied from the wheel.wheelfile Python module.
 
-static RE_WHEEL_INFO: Lazy<regex::Regex> = Lazy::new(|| {
+pub(crate) static RE_WHEEL_INFO: Lazy<regex::Regex> = Lazy::new(|| {
     regex::Regex::new(r"^(?P<namever>(?P<name>.+?)-(?P<ver>.+?))(-(?P<build>\d[^-]*))?-(?P<pyver>.+?)-(?P<abi>.+?)-(?P<plat>.+?)\.whl$").unwrap()
 });
 
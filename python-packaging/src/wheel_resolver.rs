@@ -0,0 +1,723 @@
+// Copyright 2022 Gregory Szorc.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*! Resolve pinned requirements to wheel files without invoking pip.
+
+Requirements files must pin every distribution to an exact version with
+`==`, as a lock file produced by `pip-compile` or `pip freeze` does.
+Dependencies of wheels are not resolved: the requirements are expected to
+already list the full set of distributions to install.
+
+Wheels are found in local directories, like pip's `--find-links` with
+`--no-index`. The most specific wheel compatible with the target
+interpreter and platform is chosen for each requirement.
+*/
+
+use {
+    crate::{
+        requirement::{normalize_project_name, Version},
+        wheel::RE_WHEEL_INFO,
+    },
+    anyhow::{anyhow, Context, Result},
+    sha2::Digest,
+    std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+    },
+};
+
+/// A requirement pinned to an exact version.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PinnedRequirement {
+    /// Normalized name of the distribution.
+    pub name: String,
+
+    /// Exact version of the distribution.
+    pub version: String,
+
+    /// Acceptable SHA-256 digests of the wheel file, as hex.
+    ///
+    /// If empty, any wheel is accepted.
+    pub sha256_hashes: Vec<String>,
+}
+
+impl PinnedRequirement {
+    /// Parse a single requirement line.
+    ///
+    /// Line continuations and comments must have been removed.
+    fn parse(line: &str) -> Result<Self> {
+        let mut parts = line.split_whitespace();
+
+        let requirement = parts.next().ok_or_else(|| anyhow!("empty requirement"))?;
+
+        if requirement.starts_with('-') {
+            return Err(anyhow!("unsupported requirements option: {}", requirement));
+        }
+
+        if line.contains(';') {
+            return Err(anyhow!(
+                "environment markers are not supported: {}",
+                line.trim()
+            ));
+        }
+
+        let (name, version) = requirement
+            .split_once("==")
+            .filter(|(_, version)| !version.is_empty() && !version.starts_with('='))
+            .ok_or_else(|| {
+                anyhow!(
+                    "requirement {} is not pinned to an exact version with ==",
+                    requirement
+                )
+            })?;
+
+        Version::parse(version)?;
+
+        // Extras only influence dependencies, which must be pinned separately.
+        let name = name.split_once('[').map(|(name, _)| name).unwrap_or(name);
+
+        let mut sha256_hashes = vec![];
+
+        for option in parts {
+            let value = option
+                .strip_prefix("--hash=")
+                .ok_or_else(|| anyhow!("unsupported requirement option: {}", option))?;
+
+            let digest = value
+                .strip_prefix("sha256:")
+                .ok_or_else(|| anyhow!("unsupported hash algorithm: {}", value))?;
+
+            sha256_hashes.push(digest.to_ascii_lowercase());
+        }
+
+        Ok(Self {
+            name: normalize_project_name(name),
+            version: version.to_string(),
+            sha256_hashes,
+        })
+    }
+}
+
+/// Parse the content of a requirements file where every requirement is pinned.
+///
+/// Comments, blank lines, line continuations, and `--hash=sha256:` options are
+/// supported. Other options, environment markers, and requirements not pinned
+/// with `==` are errors.
+pub fn parse_pinned_requirements(data: &str) -> Result<Vec<PinnedRequirement>> {
+    let mut res = vec![];
+    let mut logical = String::new();
+    let mut start_line = 0;
+
+    for (i, line) in data.lines().enumerate() {
+        if logical.is_empty() {
+            start_line = i + 1;
+        }
+
+        let line = match line.find('#') {
+            Some(pos) if pos == 0 || line[..pos].ends_with(char::is_whitespace) => &line[..pos],
+            _ => line,
+        };
+
+        if let Some(line) = line.trim_end().strip_suffix('\\') {
+            logical.push_str(line);
+            logical.push(' ');
+            continue;
+        }
+
+        logical.push_str(line);
+
+        if !logical.trim().is_empty() {
+            res.push(
+                PinnedRequirement::parse(&logical)
+                    .with_context(|| format!("line {}", start_line))?,
+            );
+        }
+
+        logical.clear();
+    }
+
+    if !logical.trim().is_empty() {
+        res.push(
+            PinnedRequirement::parse(&logical).with_context(|| format!("line {}", start_line))?,
+        );
+    }
+
+    Ok(res)
+}
+
+/// The components of a wheel filename.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WheelFilename {
+    /// Normalized name of the distribution.
+    pub name: String,
+
+    /// Version of the distribution.
+    pub version: String,
+
+    /// Build tag.
+    pub build: Option<String>,
+
+    /// Python tags the wheel is compatible with.
+    pub python_tags: Vec<String>,
+
+    /// ABI tags the wheel is compatible with.
+    pub abi_tags: Vec<String>,
+
+    /// Platform tags the wheel is compatible with.
+    pub platform_tags: Vec<String>,
+}
+
+impl WheelFilename {
+    /// Parse a wheel filename.
+    pub fn parse(basename: &str) -> Result<Self> {
+        let captures = RE_WHEEL_INFO
+            .captures(basename)
+            .ok_or_else(|| anyhow!("failed to parse wheel basename: {}", basename))?;
+
+        let tags = |name: &str| {
+            captures[name]
+                .split('.')
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        Ok(Self {
+            name: normalize_project_name(&captures["name"]),
+            version: captures["ver"].to_string(),
+            build: captures.name("build").map(|x| x.as_str().to_string()),
+            python_tags: tags("pyver"),
+            abi_tags: tags("abi"),
+            platform_tags: tags("plat"),
+        })
+    }
+
+    /// Obtain all `(python, abi, platform)` tags this wheel is compatible with.
+    ///
+    /// Compressed tag sets like `py2.py3` are expanded.
+    pub fn tags(&self) -> impl Iterator<Item = (&str, &str, &str)> + '_ {
+        self.python_tags.iter().flat_map(move |python| {
+            self.abi_tags.iter().flat_map(move |abi| {
+                self.platform_tags
+                    .iter()
+                    .map(move |platform| (python.as_str(), abi.as_str(), platform.as_str()))
+            })
+        })
+    }
+
+    /// The numeric value of the build tag, used to order otherwise identical wheels.
+    fn build_number(&self) -> u64 {
+        self.build
+            .as_deref()
+            .map(|build| {
+                build
+                    .chars()
+                    .take_while(|c| c.is_ascii_digit())
+                    .collect::<String>()
+            })
+            .and_then(|digits| digits.parse().ok())
+            .unwrap_or(0)
+    }
+}
+
+/// Expand a platform tag into the platform tags a wheel may have to be installable.
+///
+/// Older manylinux and macOS versions are compatible with newer ones. The
+/// special `none` platform is only compatible with `any`, which is always
+/// accepted.
+fn compatible_platforms(platform: &str) -> Vec<String> {
+    if platform == "none" {
+        return vec![];
+    }
+
+    let legacy_manylinux = [("2014", 17), ("2010", 12), ("1", 5)];
+
+    for (alias, glibc_minor) in legacy_manylinux {
+        if let Some(arch) = platform.strip_prefix(&format!("manylinux{}_", alias)) {
+            return manylinux_platforms(glibc_minor, arch);
+        }
+    }
+
+    if let Some(rest) = platform.strip_prefix("manylinux_2_") {
+        if let Some((minor, arch)) = rest.split_once('_') {
+            if let Ok(minor) = minor.parse() {
+                return manylinux_platforms(minor, arch);
+            }
+        }
+    }
+
+    if let Some(rest) = platform.strip_prefix("macosx_") {
+        let parts = rest.splitn(3, '_').collect::<Vec<_>>();
+
+        if let [major, minor, arch] = parts[..] {
+            if let (Ok(major), Ok(minor)) = (major.parse(), minor.parse()) {
+                return macos_platforms(major, minor, arch);
+            }
+        }
+    }
+
+    vec![platform.to_string()]
+}
+
+/// Platform tags for a glibc 2.x based Linux, newest first.
+fn manylinux_platforms(glibc_minor: u32, arch: &str) -> Vec<String> {
+    let mut res = vec![];
+
+    for minor in (5..=glibc_minor).rev() {
+        res.push(format!("manylinux_2_{}_{}", minor, arch));
+
+        match minor {
+            17 => res.push(format!("manylinux2014_{}", arch)),
+            12 => res.push(format!("manylinux2010_{}", arch)),
+            5 => res.push(format!("manylinux1_{}", arch)),
+            _ => {}
+        }
+    }
+
+    res
+}
+
+/// Platform tags for a macOS version, newest first.
+fn macos_platforms(major: u32, minor: u32, arch: &str) -> Vec<String> {
+    let mut versions = vec![];
+
+    if major >= 11 {
+        for major in (11..=major).rev() {
+            versions.push((major, 0));
+        }
+
+        // Intel machines running macOS 11+ can also run binaries for 10.x.
+        if arch == "x86_64" {
+            for minor in (4..=16).rev() {
+                versions.push((10, minor));
+            }
+        }
+    } else if major == 10 {
+        for minor in (4..=minor).rev() {
+            versions.push((10, minor));
+        }
+    }
+
+    let arches = match arch {
+        "x86_64" | "arm64" => vec![arch, "universal2"],
+        _ => vec![arch],
+    };
+
+    versions
+        .into_iter()
+        .flat_map(|(major, minor)| {
+            arches
+                .iter()
+                .map(move |arch| format!("macosx_{}_{}_{}", major, minor, arch))
+        })
+        .collect()
+}
+
+/// Wheel tags supported by a Python interpreter, in order of preference.
+#[derive(Clone, Debug)]
+pub struct CompatibleTags {
+    tags: Vec<(String, String, String)>,
+}
+
+impl CompatibleTags {
+    /// Derive supported tags for an interpreter.
+    ///
+    /// `implementation` is the short implementation name (e.g. `cp`).
+    /// `version` is the `X.Y` version of Python. `abi` is the interpreter's ABI
+    /// tag, if any. `platform` is the most specific platform tag the
+    /// interpreter supports or `none` if it can't load extension modules.
+    pub fn new(
+        implementation: &str,
+        version: &str,
+        abi: Option<&str>,
+        platform: &str,
+    ) -> Result<Self> {
+        let (major, minor) = version
+            .split_once('.')
+            .and_then(|(major, minor)| {
+                Some((major.parse::<u32>().ok()?, minor.parse::<u32>().ok()?))
+            })
+            .ok_or_else(|| anyhow!("invalid Python version: {}", version))?;
+
+        let platforms = compatible_platforms(platform);
+        let interpreter = format!("{}{}{}", implementation, major, minor);
+
+        let mut tags = vec![];
+        let mut add = |python: &str, abi: &str, platforms: &[String]| {
+            for platform in platforms {
+                tags.push((python.to_string(), abi.to_string(), platform.clone()));
+            }
+        };
+
+        // Interpreter specific tags.
+        if let Some(abi) = abi {
+            add(&interpreter, abi, &platforms);
+        }
+        if implementation == "cp" {
+            add(&interpreter, "abi3", &platforms);
+        }
+        add(&interpreter, "none", &platforms);
+
+        // The stable ABI of older CPython versions is forward compatible.
+        if implementation == "cp" && major == 3 {
+            for older in (2..minor).rev() {
+                add(&format!("cp3{}", older), "abi3", &platforms);
+            }
+        }
+
+        // Generic Python tags.
+        let mut generic = vec![format!("py{}{}", major, minor), format!("py{}", major)];
+        generic.extend(
+            (0..minor)
+                .rev()
+                .map(|older| format!("py{}{}", major, older)),
+        );
+
+        for python in &generic {
+            add(python, "none", &platforms);
+        }
+
+        // Platform independent tags.
+        let any = vec!["any".to_string()];
+        add(&interpreter, "none", &any);
+        for python in &generic {
+            add(python, "none", &any);
+        }
+
+        Ok(Self { tags })
+    }
+
+    /// Obtain the preference of a wheel, lower being better.
+    ///
+    /// Returns `None` if the wheel isn't compatible.
+    pub fn priority(&self, wheel: &WheelFilename) -> Option<usize> {
+        wheel
+            .tags()
+            .filter_map(|(python, abi, platform)| {
+                self.tags
+                    .iter()
+                    .position(|(p, a, pl)| p == python && a == abi && pl == platform)
+            })
+            .min()
+    }
+}
+
+/// Resolve pinned requirements to wheel files in local directories.
+///
+/// Every `.whl` file in `find_links` directories is considered, except those
+/// whose filenames can't be parsed. Wheel versions are compared according to
+/// PEP 440, so `foo==1.0` matches `foo-1.0.0-py3-none-any.whl`. For each
+/// requirement, the compatible wheel with the most specific tags is chosen. If
+/// the requirement has hashes, the most specific compatible wheel whose content
+/// matches one is chosen.
+///
+/// Errors list every requirement that couldn't be satisfied.
+pub fn resolve_wheels(
+    requirements: &[PinnedRequirement],
+    find_links: &[impl AsRef<Path>],
+    tags: &CompatibleTags,
+) -> Result<Vec<PathBuf>> {
+    let mut candidates: BTreeMap<String, Vec<(Version, WheelFilename, PathBuf)>> = BTreeMap::new();
+
+    for dir in find_links {
+        let dir = dir.as_ref();
+
+        for entry in std::fs::read_dir(dir)
+            .with_context(|| format!("reading wheel directory {}", dir.display()))?
+        {
+            let path = entry?.path();
+            let basename = path
+                .file_name()
+                .map(|x| x.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            if !basename.ends_with(".whl") {
+                continue;
+            }
+
+            // Other tools leave files that aren't valid wheels around. They
+            // can never satisfy a requirement, so ignore them.
+            let wheel = match WheelFilename::parse(&basename) {
+                Ok(wheel) => wheel,
+                Err(_) => continue,
+            };
+            let version = match Version::parse(&wheel.version) {
+                Ok(version) => version,
+                Err(_) => continue,
+            };
+
+            candidates
+                .entry(wheel.name.clone())
+                .or_default()
+                .push((version, wheel, path));
+        }
+    }
+
+    let mut res = vec![];
+    let mut errors = vec![];
+
+    for requirement in requirements {
+        let version = Version::parse(&requirement.version)?;
+
+        let wheels = candidates
+            .get(&requirement.name)
+            .map(|x| x.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .filter(|(wheel_version, _, _)| wheel_version.is_identical(&version))
+            .map(|(_, wheel, path)| (wheel, path))
+            .collect::<Vec<_>>();
+
+        let mut compatible = wheels
+            .iter()
+            .filter_map(|(wheel, path)| tags.priority(wheel).map(|p| (p, *wheel, *path)))
+            .collect::<Vec<_>>();
+
+        compatible.sort_by(
+            |(a_priority, a_wheel, a_path), (b_priority, b_wheel, b_path)| {
+                a_priority
+                    .cmp(b_priority)
+                    .then_with(|| b_wheel.build_number().cmp(&a_wheel.build_number()))
+                    .then_with(|| a_path.cmp(b_path))
+            },
+        );
+
+        let best = if requirement.sha256_hashes.is_empty() {
+            compatible.first().map(|(_, _, path)| *path)
+        } else {
+            let mut matching = None;
+
+            for (_, _, path) in &compatible {
+                let data =
+                    std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+                let digest = format!("{:x}", sha2::Sha256::digest(&data));
+
+                if requirement.sha256_hashes.contains(&digest) {
+                    matching = Some(*path);
+                    break;
+                }
+            }
+
+            if matching.is_none() && !compatible.is_empty() {
+                errors.push(format!(
+                    "no compatible wheel for {}=={} matches its hashes (found {})",
+                    requirement.name,
+                    requirement.version,
+                    compatible
+                        .iter()
+                        .map(|(_, _, path)| path.file_name().unwrap().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+                continue;
+            }
+
+            matching
+        };
+
+        let path = match best {
+            Some(path) => path,
+            None if wheels.is_empty() => {
+                errors.push(format!(
+                    "no wheel found for {}=={}",
+                    requirement.name, requirement.version
+                ));
+                continue;
+            }
+            None => {
+                errors.push(format!(
+                    "no compatible wheel for {}=={} (found incompatible {})",
+                    requirement.name,
+                    requirement.version,
+                    wheels
+                        .iter()
+                        .map(|(_, path)| path.file_name().unwrap().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+                continue;
+            }
+        };
+
+        res.push(path.clone());
+    }
+
+    if errors.is_empty() {
+        Ok(res)
+    } else {
+        Err(anyhow!(
+            "unable to resolve requirements to wheels:\n  {}",
+            errors.join("\n  ")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::wheel_builder::WheelBuilder};
+
+    #[test]
+    fn test_parse_pinned_requirements() -> Result<()> {
+        let reqs = parse_pinned_requirements(
+            "# comment\n\
+             \n\
+             Foo_Bar==1.0  # trailing comment\n\
+             baz[extra]==2.0 \\\n    --hash=sha256:ABCD \\\n    --hash=sha256:ef01\n",
+        )?;
+
+        assert_eq!(
+            reqs,
+            vec![
+                PinnedRequirement {
+                    name: "foo-bar".to_string(),
+                    version: "1.0".to_string(),
+                    sha256_hashes: vec![],
+                },
+                PinnedRequirement {
+                    name: "baz".to_string(),
+                    version: "2.0".to_string(),
+                    sha256_hashes: vec!["abcd".to_string(), "ef01".to_string()],
+                },
+            ]
+        );
+
+        assert!(parse_pinned_requirements("foo>=1.0").is_err());
+        assert!(parse_pinned_requirements("foo===1.0").is_err());
+        assert!(parse_pinned_requirements("foo==1.0; python_version < '3'").is_err());
+        assert!(parse_pinned_requirements("--index-url https://example.com").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_wheel_filename() -> Result<()> {
+        let wheel = WheelFilename::parse("My_Package-1.0-2-py2.py3-none-any.whl")?;
+
+        assert_eq!(wheel.name, "my-package");
+        assert_eq!(wheel.version, "1.0");
+        assert_eq!(wheel.build.as_deref(), Some("2"));
+        assert_eq!(
+            wheel.tags().collect::<Vec<_>>(),
+            vec![("py2", "none", "any"), ("py3", "none", "any")]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compatible_tags() -> Result<()> {
+        let tags = CompatibleTags::new("cp", "3.10", Some("cp310"), "manylinux2014_x86_64")?;
+
+        let priority = |name: &str| tags.priority(&WheelFilename::parse(name).unwrap());
+
+        let native = priority("foo-1.0-cp310-cp310-manylinux_2_17_x86_64.manylinux2014_x86_64.whl");
+        let older = priority("foo-1.0-cp310-cp310-manylinux1_x86_64.whl");
+        let abi3 = priority("foo-1.0-cp36-abi3-manylinux2010_x86_64.whl");
+        let pure = priority("foo-1.0-py3-none-any.whl");
+
+        assert!(native.is_some());
+        assert!(native < older);
+        assert!(older < abi3);
+        assert!(abi3 < pure);
+
+        assert_eq!(priority("foo-1.0-cp39-cp39-manylinux2014_x86_64.whl"), None);
+        assert_eq!(
+            priority("foo-1.0-cp310-cp310-manylinux_2_28_x86_64.whl"),
+            None
+        );
+        assert_eq!(priority("foo-1.0-cp310-cp310-win_amd64.whl"), None);
+
+        let tags = CompatibleTags::new("cp", "3.10", Some("cp310"), "none")?;
+        assert!(tags
+            .priority(&WheelFilename::parse("foo-1.0-py3-none-any.whl")?)
+            .is_some());
+        assert!(tags
+            .priority(&WheelFilename::parse(
+                "foo-1.0-cp310-cp310-manylinux2014_x86_64.whl"
+            )?)
+            .is_none());
+
+        let tags = CompatibleTags::new("cp", "3.10", Some("cp310"), "macosx_11_0_arm64")?;
+        assert!(tags
+            .priority(&WheelFilename::parse(
+                "foo-1.0-cp310-cp310-macosx_11_0_universal2.whl"
+            )?)
+            .is_some());
+        assert!(tags
+            .priority(&WheelFilename::parse(
+                "foo-1.0-cp310-cp310-macosx_10_9_x86_64.whl"
+            )?)
+            .is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_wheels() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("python-packaging-test")
+            .tempdir()?;
+
+        let mut builder = WheelBuilder::new("foo", "1.0");
+        let pure = builder.write_wheel_into_directory(temp_dir.path())?;
+        builder.set_tag("cp310-cp310-manylinux2014_x86_64")?;
+        let native = builder.write_wheel_into_directory(temp_dir.path())?;
+        builder.set_tag("cp310-cp310-win_amd64")?;
+        builder.write_wheel_into_directory(temp_dir.path())?;
+        std::fs::write(temp_dir.path().join("not-a-wheel.whl"), b"")?;
+
+        WheelBuilder::new("foo", "1.0.post1").write_wheel_into_directory(temp_dir.path())?;
+
+        let linux = CompatibleTags::new("cp", "3.10", Some("cp310"), "manylinux2014_x86_64")?;
+        let static_linux = CompatibleTags::new("cp", "3.10", Some("cp310"), "none")?;
+        let macos = CompatibleTags::new("cp", "3.10", Some("cp310"), "macosx_10_9_x86_64")?;
+
+        let reqs = parse_pinned_requirements("foo==1.0")?;
+        assert_eq!(
+            resolve_wheels(&reqs, &[temp_dir.path()], &linux)?,
+            vec![native.clone()]
+        );
+        assert_eq!(
+            resolve_wheels(&reqs, &[temp_dir.path()], &static_linux)?,
+            vec![pure.clone()]
+        );
+        assert_eq!(
+            resolve_wheels(&reqs, &[temp_dir.path()], &macos)?,
+            vec![pure.clone()]
+        );
+
+        let reqs = parse_pinned_requirements("foo==1.0.0")?;
+        assert_eq!(
+            resolve_wheels(&reqs, &[temp_dir.path()], &linux)?,
+            vec![native]
+        );
+
+        let digest = format!("{:x}", sha2::Sha256::digest(&std::fs::read(&pure)?));
+        let reqs = parse_pinned_requirements(&format!("foo==1.0 --hash=sha256:{}", digest))?;
+        assert_eq!(
+            resolve_wheels(&reqs, &[temp_dir.path()], &macos)?,
+            vec![pure.clone()]
+        );
+        // The native wheel is preferred but doesn't match the hash.
+        assert_eq!(
+            resolve_wheels(&reqs, &[temp_dir.path()], &linux)?,
+            vec![pure]
+        );
+
+        let reqs = parse_pinned_requirements("foo==1.0 --hash=sha256:00")?;
+        assert!(resolve_wheels(&reqs, &[temp_dir.path()], &macos).is_err());
+
+        let reqs = parse_pinned_requirements("foo==2.0\nbar==1.0")?;
+        let err = resolve_wheels(&reqs, &[temp_dir.path()], &linux).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unable to resolve requirements to wheels:\n  no wheel found for foo==2.0\n  no wheel found for bar==1.0"
+        );
+
+        Ok(())
+    }
+}
@@ -12,7 +12,10 @@ use {
     crate::{
         bytecode::{CompileMode, PythonBytecodeCompiler},
         module_util::resolve_path_for_module,
-        resource::{BytecodeOptimizationLevel, PythonModuleBytecode, PythonModuleSource},
+        resource::{
+            BytecodeOptimizationLevel, PythonModuleBytecode, PythonModuleSource,
+            PythonPackageDistributionResource, PythonPackageResource,
+        },
     },
     anyhow::{anyhow, Context, Result},
     std::{
@@ -184,6 +187,75 @@ impl ZipAppBuilder {
         Ok(())
     }
 
+    /// Add bytecode for Python module source to the `__pycache__` directory layout.
+    ///
+    /// Bytecode is compiled at the specified optimization level and stored where
+    /// `importlib` would write it. Unlike the `.pyc` files written by
+    /// [Self::add_python_module_source_and_bytecode], this allows bytecode for
+    /// multiple optimization levels to coexist. The stdlib `zipimport` module
+    /// ignores these files. But `OxidizedZipFinder` uses them.
+    ///
+    /// The source module must have a cache tag defined.
+    pub fn add_python_module_cached_bytecode(
+        &mut self,
+        source: &PythonModuleSource,
+        prefix: &str,
+        optimize_level: BytecodeOptimizationLevel,
+    ) -> Result<()> {
+        if source.cache_tag.is_empty() {
+            return Err(anyhow!(
+                "cache tag not defined for module {}; cannot resolve bytecode path",
+                source.name
+            ));
+        }
+
+        let compiler = self
+            .compiler
+            .as_mut()
+            .ok_or_else(|| anyhow!("bytecode compiler not available"))?;
+
+        let module = source.as_bytecode_module(optimize_level);
+        let bytecode = module.compile(compiler.as_mut(), CompileMode::PycUncheckedHash)?;
+
+        self.manifest.add_file_entry(
+            module.resolve_path(prefix),
+            FileEntry::new_from_data(bytecode, false),
+        )?;
+
+        Ok(())
+    }
+
+    /// Add a Python package resource to the archive.
+    pub fn add_python_package_resource(
+        &mut self,
+        resource: &PythonPackageResource,
+        prefix: &str,
+    ) -> Result<()> {
+        self.manifest.add_file_entry(
+            resource.resolve_path(prefix),
+            FileEntry::new_from_data(resource.data.resolve_content()?, false),
+        )?;
+
+        Ok(())
+    }
+
+    /// Add a Python package distribution resource to the archive.
+    ///
+    /// Resources are stored in `.dist-info` or `.egg-info` directories so
+    /// `importlib.metadata` compatible finders can discover them.
+    pub fn add_python_package_distribution_resource(
+        &mut self,
+        resource: &PythonPackageDistributionResource,
+        prefix: &str,
+    ) -> Result<()> {
+        self.manifest.add_file_entry(
+            resource.resolve_path(prefix),
+            FileEntry::new_from_data(resource.data.resolve_content()?, false),
+        )?;
+
+        Ok(())
+    }
+
     /// Define the function called when the zip-based application is executed.
     ///
     /// This defines a `__main__.py[c]` that invokes the `func` function in the `module` module.
@@ -282,7 +354,13 @@ impl ZipAppBuilder {
 
 #[cfg(test)]
 mod test {
-    use {super::*, crate::testutil::FakeBytecodeCompiler, std::io::Read};
+    use {
+        super::*,
+        crate::{
+            resource::PythonPackageDistributionResourceFlavor, testutil::FakeBytecodeCompiler,
+        },
+        std::io::Read,
+    };
 
     #[test]
     fn empty() -> Result<()> {
@@ -409,6 +487,96 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn add_cached_bytecode() -> Result<()> {
+        let mut builder = ZipAppBuilder::default();
+        builder.set_bytecode_compiler(Box::new(FakeBytecodeCompiler { magic_number: 42 }));
+
+        let mut m = PythonModuleSource {
+            name: "foo".to_string(),
+            source: b"foo".to_vec().into(),
+            is_package: true,
+            cache_tag: "".to_string(),
+            is_stdlib: false,
+            is_test: false,
+        };
+
+        assert!(builder
+            .add_python_module_cached_bytecode(&m, "lib", BytecodeOptimizationLevel::Zero)
+            .is_err());
+
+        m.cache_tag = "cpython-39".to_string();
+
+        builder.add_python_module_cached_bytecode(&m, "lib", BytecodeOptimizationLevel::Zero)?;
+        builder.add_python_module_cached_bytecode(&m, "lib", BytecodeOptimizationLevel::Two)?;
+
+        let mut dest = std::io::Cursor::new(Vec::<u8>::new());
+        builder.write_zip_app(&mut dest)?;
+
+        let mut z = zip::ZipArchive::new(dest)?;
+        assert_eq!(z.len(), 2);
+
+        {
+            let mut zf = z.by_index(0)?;
+            let mut b = Vec::<u8>::new();
+            zf.read_to_end(&mut b)?;
+            assert_eq!(
+                zf.name(),
+                "lib/foo/__pycache__/__init__.cpython-39.opt-2.pyc"
+            );
+            assert_eq!(b, b"bc2foo");
+        }
+
+        {
+            let mut zf = z.by_index(1)?;
+            let mut b = Vec::<u8>::new();
+            zf.read_to_end(&mut b)?;
+            assert_eq!(zf.name(), "lib/foo/__pycache__/__init__.cpython-39.pyc");
+            assert_eq!(b, b"bc0foo");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn add_package_resources() -> Result<()> {
+        let mut builder = ZipAppBuilder::default();
+
+        builder.add_python_package_resource(
+            &PythonPackageResource {
+                leaf_package: "foo.bar".to_string(),
+                relative_name: "data.txt".to_string(),
+                data: b"data".to_vec().into(),
+                is_stdlib: false,
+                is_test: false,
+            },
+            "",
+        )?;
+        builder.add_python_package_distribution_resource(
+            &PythonPackageDistributionResource {
+                location: PythonPackageDistributionResourceFlavor::DistInfo,
+                package: "My-Package".to_string(),
+                version: "1.0".to_string(),
+                name: "METADATA".to_string(),
+                data: b"Name: My-Package\n".to_vec().into(),
+            },
+            "",
+        )?;
+
+        let mut dest = std::io::Cursor::new(Vec::<u8>::new());
+        builder.write_zip_app(&mut dest)?;
+
+        let z = zip::ZipArchive::new(dest)?;
+        assert_eq!(
+            z.file_names().collect::<std::collections::BTreeSet<_>>(),
+            ["foo/bar/data.txt", "my_package-1.0.dist-info/METADATA"]
+                .into_iter()
+                .collect()
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn add_main() -> Result<()> {
         let mut builder = ZipAppBuilder::default();
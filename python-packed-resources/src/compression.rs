@@ -0,0 +1,286 @@
+// Copyright 2022 Gregory Szorc.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*! Compression of resource field data. */
+
+use crate::serialization::{CompressionCodec, ResourceField};
+
+/// Payloads smaller than this are never compressed.
+///
+/// Codec framing overhead makes compressing tiny payloads a net loss.
+const MIN_COMPRESSIBLE_LENGTH: usize = 64;
+
+/// Payloads at least this large are compressed with LZ4 by the adaptive policy.
+///
+/// Large native code payloads are read in full on load. So we favor
+/// decompression speed over ratio for them.
+const ADAPTIVE_LZ4_THRESHOLD: usize = 4 * 1024 * 1024;
+
+/// Maximum ratio between decompressed and compressed size of an LZ4 block.
+#[cfg(feature = "lz4")]
+const LZ4_MAX_RATIO: usize = 255;
+
+/// Maximum size of decompressed zstd data.
+///
+/// zstd frames declare their content size in a header we can't trust. Unlike
+/// LZ4, zstd has no practical bound on its compression ratio. So we cap the
+/// output at the largest length a field can describe.
+#[cfg(feature = "zstd")]
+const ZSTD_MAX_DECOMPRESSED_LENGTH: u64 = u32::MAX as u64;
+
+/// zstd compression level used by the writer.
+#[cfg(feature = "zstd")]
+const ZSTD_LEVEL: i32 = 10;
+
+/// Defines how a packed resources writer should compress field data.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CompressionPolicy {
+    /// Never compress field data.
+    #[default]
+    None,
+
+    /// Compress field data with zstd.
+    Zstd,
+
+    /// Compress field data with LZ4.
+    Lz4,
+
+    /// Choose a codec per field based on the type and size of its data.
+    ///
+    /// Python source, bytecode and resource data use zstd. Large shared
+    /// libraries use LZ4.
+    Adaptive,
+}
+
+impl TryFrom<&str> for CompressionPolicy {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "none" => Ok(Self::None),
+            "zstd" => Ok(Self::Zstd),
+            "lz4" => Ok(Self::Lz4),
+            "adaptive" => Ok(Self::Adaptive),
+            _ => Err(format!(
+                "{} is not a valid compression policy; use \"none\", \"zstd\", \"lz4\", or \"adaptive\"",
+                value
+            )),
+        }
+    }
+}
+
+impl AsRef<str> for CompressionPolicy {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::None => "none",
+            Self::Zstd => "zstd",
+            Self::Lz4 => "lz4",
+            Self::Adaptive => "adaptive",
+        }
+    }
+}
+
+impl CompressionPolicy {
+    /// Resolve the codec to use for data in a given field.
+    ///
+    /// `length` is the total length of uncompressed data in the field.
+    ///
+    /// Returns `None` if the data should not be compressed.
+    pub fn resolve_codec(&self, field: ResourceField, length: usize) -> Option<CompressionCodec> {
+        if !field.is_compressible() || length < MIN_COMPRESSIBLE_LENGTH {
+            return None;
+        }
+
+        match self {
+            Self::None => None,
+            Self::Zstd => Some(CompressionCodec::Zstd),
+            Self::Lz4 => Some(CompressionCodec::Lz4),
+            Self::Adaptive => match field {
+                ResourceField::InMemoryExtensionModuleSharedLibrary
+                | ResourceField::InMemorySharedLibrary
+                    if length >= ADAPTIVE_LZ4_THRESHOLD =>
+                {
+                    Some(CompressionCodec::Lz4)
+                }
+                _ => Some(CompressionCodec::Zstd),
+            },
+        }
+    }
+}
+
+/// Compress data using the specified codec.
+#[cfg_attr(not(all(feature = "zstd", feature = "lz4")), allow(unused_variables))]
+pub fn compress_data(codec: CompressionCodec, data: &[u8]) -> Result<Vec<u8>, &'static str> {
+    match codec {
+        #[cfg(feature = "zstd")]
+        CompressionCodec::Zstd => {
+            zstd::block::compress(data, ZSTD_LEVEL).map_err(|_| "error compressing zstd data")
+        }
+        #[cfg(not(feature = "zstd"))]
+        CompressionCodec::Zstd => Err("zstd support is not enabled"),
+        #[cfg(feature = "lz4")]
+        CompressionCodec::Lz4 => Ok(lz4_flex::compress_prepend_size(data)),
+        #[cfg(not(feature = "lz4"))]
+        CompressionCodec::Lz4 => Err("lz4 support is not enabled"),
+    }
+}
+
+/// Decompress zstd data without trusting the size declared by its frame header.
+#[cfg(feature = "zstd")]
+fn decompress_zstd(data: &[u8]) -> Result<Vec<u8>, &'static str> {
+    use std::io::Read;
+
+    // ZSTD_CONTENTSIZE_UNKNOWN and ZSTD_CONTENTSIZE_ERROR are -1 and -2.
+    let size = zstd::zstd_safe::get_frame_content_size(data);
+
+    // Content size is always written by our writer. But be defensive
+    // against frames from other producers.
+    let declared = if size >= u64::MAX - 1 {
+        None
+    } else {
+        Some(size)
+    };
+    let limit = declared.unwrap_or(ZSTD_MAX_DECOMPRESSED_LENGTH);
+    if limit > ZSTD_MAX_DECOMPRESSED_LENGTH {
+        return Err("zstd data exceeds maximum decompressed size");
+    }
+
+    // Stream decode so the output buffer only grows as data is actually
+    // produced. Reading one byte past the limit detects overruns.
+    let decoder = zstd::stream::read::Decoder::with_buffer(data)
+        .map_err(|_| "error decompressing zstd data")?;
+    let mut output = Vec::new();
+    decoder
+        .take(limit + 1)
+        .read_to_end(&mut output)
+        .map_err(|_| "error decompressing zstd data")?;
+
+    match declared {
+        Some(size) if output.len() as u64 != size => {
+            Err("zstd data does not match its declared size")
+        }
+        None if output.len() as u64 > limit => Err("zstd data exceeds maximum decompressed size"),
+        _ => Ok(output),
+    }
+}
+
+/// Decompress data that was compressed with the specified codec.
+#[cfg_attr(not(all(feature = "zstd", feature = "lz4")), allow(unused_variables))]
+pub fn decompress_data(codec: CompressionCodec, data: &[u8]) -> Result<Vec<u8>, &'static str> {
+    match codec {
+        #[cfg(feature = "zstd")]
+        CompressionCodec::Zstd => decompress_zstd(data),
+        #[cfg(not(feature = "zstd"))]
+        CompressionCodec::Zstd => Err("zstd support is not enabled"),
+        #[cfg(feature = "lz4")]
+        CompressionCodec::Lz4 => {
+            if data.len() < 4 {
+                return Err("error decompressing lz4 data");
+            }
+
+            // LZ4 can't expand data by more than a factor of 255. Reject sizes
+            // beyond that before allocating an output buffer.
+            let size = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+            if size > (data.len() - 4).saturating_mul(LZ4_MAX_RATIO) {
+                return Err("error decompressing lz4 data");
+            }
+
+            lz4_flex::decompress_size_prepended(data).map_err(|_| "error decompressing lz4 data")
+        }
+        #[cfg(not(feature = "lz4"))]
+        CompressionCodec::Lz4 => Err("lz4 support is not enabled"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(all(feature = "zstd", feature = "lz4"))]
+    fn test_roundtrip() -> Result<(), &'static str> {
+        let data = b"import os\nimport sys\n".repeat(64);
+
+        for codec in [CompressionCodec::Zstd, CompressionCodec::Lz4] {
+            let compressed = compress_data(codec, &data)?;
+            assert!(compressed.len() < data.len());
+            assert_eq!(decompress_data(codec, &compressed)?, data);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(all(feature = "zstd", feature = "lz4"))]
+    fn test_decompress_garbage() {
+        assert_eq!(
+            decompress_data(CompressionCodec::Zstd, b"garbage"),
+            Err("error decompressing zstd data")
+        );
+        assert_eq!(
+            decompress_data(CompressionCodec::Lz4, b"\xff\xff\xff\xffgarbage"),
+            Err("error decompressing lz4 data")
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn test_zstd_declared_size_mismatch() -> Result<(), &'static str> {
+        let data = b"import os\nimport sys\n".repeat(64);
+        let mut compressed = compress_data(CompressionCodec::Zstd, &data)?;
+
+        // Inflate the declared frame content size. It follows the frame header
+        // descriptor and the window descriptor, which is absent for single
+        // segment frames. We never write a dictionary ID.
+        let descriptor = compressed[4];
+        let single_segment = descriptor & 0x20 != 0;
+        let width = match descriptor >> 6 {
+            0 if single_segment => 1,
+            0 => 0,
+            1 => 2,
+            2 => 4,
+            _ => 8,
+        };
+        assert!(width > 0);
+        let offset = if single_segment { 5 } else { 6 };
+        compressed[offset..offset + width].fill(0xff);
+
+        assert!(decompress_data(CompressionCodec::Zstd, &compressed).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_codec() {
+        assert_eq!(
+            CompressionPolicy::None.resolve_codec(ResourceField::InMemoryBytecode, 1024),
+            None
+        );
+        assert_eq!(
+            CompressionPolicy::Zstd.resolve_codec(ResourceField::InMemoryBytecode, 1024),
+            Some(CompressionCodec::Zstd)
+        );
+        assert_eq!(
+            CompressionPolicy::Zstd.resolve_codec(ResourceField::InMemoryBytecode, 10),
+            None
+        );
+        assert_eq!(
+            CompressionPolicy::Zstd.resolve_codec(ResourceField::Name, 1024),
+            None
+        );
+        assert_eq!(
+            CompressionPolicy::Adaptive.resolve_codec(ResourceField::InMemorySource, 1024),
+            Some(CompressionCodec::Zstd)
+        );
+        assert_eq!(
+            CompressionPolicy::Adaptive
+                .resolve_codec(ResourceField::InMemorySharedLibrary, 16 * 1024 * 1024),
+            Some(CompressionCodec::Lz4)
+        );
+    }
+}
@@ -0,0 +1,403 @@
+// Copyright 2022 Gregory Szorc.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/*! Integrity verification of packed resources data.
+
+Packed resources data can optionally be followed by an *integrity trailer*
+holding SHA-256 digests of the index data and of every blob section, plus an
+optional Ed25519 signature over those digests.
+
+The trailer is ignored by [crate::load_resources]. So data with a trailer
+remains readable by parsers that don't know about it.
+*/
+
+use {
+    crate::parser::load_resources,
+    anyhow::{anyhow, Result},
+    byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt},
+    ring::{
+        digest::{digest, SHA256, SHA256_OUTPUT_LEN},
+        signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519},
+    },
+    std::io::Cursor,
+};
+
+/// Magic bytes starting an integrity trailer.
+pub const INTEGRITY_TRAILER_MAGIC: &[u8] = b"pyembedI";
+
+/// Describes the digest algorithm used by an integrity trailer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IntegrityDigestAlgorithm {
+    Sha256 = 0x01,
+}
+
+/// Describes the signature algorithm used by an integrity trailer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IntegritySignatureAlgorithm {
+    None = 0x00,
+    Ed25519 = 0x01,
+}
+
+impl TryFrom<u8> for IntegritySignatureAlgorithm {
+    type Error = &'static str;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(Self::None),
+            0x01 => Ok(Self::Ed25519),
+            _ => Err("invalid integrity signature algorithm"),
+        }
+    }
+}
+
+/// A key used to sign packed resources data.
+#[derive(Debug)]
+pub struct PackedResourcesSigningKey {
+    key_pair: Ed25519KeyPair,
+}
+
+impl PackedResourcesSigningKey {
+    /// Construct an instance from a PKCS#8 document holding an Ed25519 key.
+    pub fn from_pkcs8(data: &[u8]) -> Result<Self> {
+        let key_pair = Ed25519KeyPair::from_pkcs8(data)
+            .map_err(|e| anyhow!("unable to parse Ed25519 PKCS#8 key: {}", e))?;
+
+        Ok(Self { key_pair })
+    }
+
+    /// The raw 32 byte Ed25519 public key corresponding to this key.
+    ///
+    /// This is the value readers need to verify signed data.
+    pub fn public_key(&self) -> &[u8] {
+        self.key_pair.public_key().as_ref()
+    }
+
+    fn sign(&self, message: &[u8]) -> Vec<u8> {
+        self.key_pair.sign(message).as_ref().to_vec()
+    }
+}
+
+/// A parsed integrity trailer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntegrityTrailer<'a> {
+    /// SHA-256 of the data preceding blob sections.
+    pub index_digest: &'a [u8],
+
+    /// SHA-256 of each blob section, in blob index order.
+    pub section_digests: Vec<&'a [u8]>,
+
+    /// The signature algorithm in use.
+    pub signature_algorithm: IntegritySignatureAlgorithm,
+
+    /// Raw signature data.
+    ///
+    /// Empty if the trailer isn't signed.
+    pub signature: &'a [u8],
+
+    /// The portion of the trailer covered by the signature.
+    signed_data: &'a [u8],
+}
+
+impl<'a> IntegrityTrailer<'a> {
+    /// Verify the trailer's signature against an Ed25519 public key.
+    pub fn verify_signature(&self, public_key: &[u8]) -> Result<(), &'static str> {
+        match self.signature_algorithm {
+            IntegritySignatureAlgorithm::None => Err("packed resources data is not signed"),
+            IntegritySignatureAlgorithm::Ed25519 => UnparsedPublicKey::new(&ED25519, public_key)
+                .verify(self.signed_data, self.signature)
+                .map_err(|_| "packed resources signature verification failed"),
+        }
+    }
+}
+
+/// Resolve ranges of data covered by trailer digests.
+///
+/// The first range is the data preceding blob sections. Subsequent ranges
+/// are blob sections. Also returns the offset where the trailer begins.
+fn resolve_digest_ranges(
+    data: &[u8],
+) -> Result<(Vec<std::ops::Range<usize>>, usize), &'static str> {
+    let resources = load_resources(data)?;
+
+    let blob_end = resources.blob_end_offset();
+    if blob_end > data.len() {
+        return Err("packed resources data is truncated");
+    }
+
+    let sections = resources.blob_section_ranges();
+    let index_end = sections.first().map(|r| r.start).unwrap_or(blob_end);
+
+    let ranges = std::iter::once(0..index_end).chain(sections).collect();
+
+    Ok((ranges, blob_end))
+}
+
+/// Parse the integrity trailer of packed resources data.
+///
+/// Returns `Ok(None)` if the data doesn't have a trailer.
+pub fn read_integrity_trailer(data: &[u8]) -> Result<Option<IntegrityTrailer<'_>>, &'static str> {
+    let (ranges, trailer_start) = resolve_digest_ranges(data)?;
+
+    if trailer_start == data.len() {
+        return Ok(None);
+    }
+
+    let trailer = &data[trailer_start..];
+    if !trailer.starts_with(INTEGRITY_TRAILER_MAGIC) {
+        return Err("unrecognized data following packed resources");
+    }
+
+    let mut reader = Cursor::new(trailer);
+    reader.set_position(INTEGRITY_TRAILER_MAGIC.len() as u64);
+
+    let digest_algorithm = reader
+        .read_u8()
+        .map_err(|_| "failed reading integrity digest algorithm")?;
+    if digest_algorithm != IntegrityDigestAlgorithm::Sha256 as u8 {
+        return Err("invalid integrity digest algorithm");
+    }
+
+    let mut digests = Vec::with_capacity(ranges.len());
+    for _ in 0..ranges.len() {
+        let start = reader.position() as usize;
+        let end = start + SHA256_OUTPUT_LEN;
+        if end > trailer.len() {
+            return Err("failed reading integrity digest");
+        }
+
+        digests.push(&trailer[start..end]);
+        reader.set_position(end as u64);
+    }
+
+    let signed_data = &trailer[0..reader.position() as usize];
+
+    let signature_algorithm = IntegritySignatureAlgorithm::try_from(
+        reader
+            .read_u8()
+            .map_err(|_| "failed reading integrity signature algorithm")?,
+    )?;
+    let signature_length = reader
+        .read_u32::<LittleEndian>()
+        .map_err(|_| "failed reading integrity signature length")?
+        as usize;
+
+    let start = reader.position() as usize;
+    if start + signature_length != trailer.len() {
+        return Err("integrity signature length mismatch");
+    }
+    let signature = &trailer[start..];
+
+    let index_digest = digests.remove(0);
+
+    Ok(Some(IntegrityTrailer {
+        index_digest,
+        section_digests: digests,
+        signature_algorithm,
+        signature,
+        signed_data,
+    }))
+}
+
+/// Verify the integrity of packed resources data.
+///
+/// The data must have an integrity trailer whose digests match the data.
+///
+/// If `public_key` is defined, the trailer must also carry a valid Ed25519
+/// signature made with the corresponding private key. The signature is
+/// verified before any data is hashed.
+pub fn verify_integrity(data: &[u8], public_key: Option<&[u8]>) -> Result<(), &'static str> {
+    let trailer =
+        read_integrity_trailer(data)?.ok_or("packed resources data has no integrity trailer")?;
+
+    if let Some(public_key) = public_key {
+        trailer.verify_signature(public_key)?;
+    }
+
+    let (ranges, _) = resolve_digest_ranges(data)?;
+
+    for (range, expected) in ranges
+        .into_iter()
+        .zip(std::iter::once(trailer.index_digest).chain(trailer.section_digests))
+    {
+        if digest(&SHA256, &data[range]).as_ref() != expected {
+            return Err("packed resources digest mismatch");
+        }
+    }
+
+    Ok(())
+}
+
+/// Append an integrity trailer to packed resources data.
+///
+/// `data` must hold complete packed resources data without a trailer. If
+/// `signing_key` is defined, the trailer is signed with it.
+pub fn write_integrity_trailer(
+    data: &mut Vec<u8>,
+    signing_key: Option<&PackedResourcesSigningKey>,
+) -> Result<()> {
+    let (ranges, trailer_start) = resolve_digest_ranges(data).map_err(|e| anyhow!(e))?;
+
+    if trailer_start != data.len() {
+        return Err(anyhow!(
+            "packed resources data already has data following it"
+        ));
+    }
+
+    let mut trailer = INTEGRITY_TRAILER_MAGIC.to_vec();
+    trailer.write_u8(IntegrityDigestAlgorithm::Sha256 as u8)?;
+    for range in ranges {
+        trailer.extend_from_slice(digest(&SHA256, &data[range]).as_ref());
+    }
+
+    let (algorithm, signature) = match signing_key {
+        Some(key) => (IntegritySignatureAlgorithm::Ed25519, key.sign(&trailer)),
+        None => (IntegritySignatureAlgorithm::None, vec![]),
+    };
+
+    trailer.write_u8(algorithm as u8)?;
+    trailer.write_u32::<LittleEndian>(signature.len() as u32)?;
+    trailer.extend_from_slice(&signature);
+
+    data.extend_from_slice(&trailer);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{resource::Resource, writer::write_packed_resources_v3},
+        ring::rand::SystemRandom,
+        std::borrow::Cow,
+    };
+
+    fn sample_data() -> Result<Vec<u8>> {
+        let resource = Resource {
+            name: Cow::from("foo"),
+            in_memory_source: Some(Cow::from(b"import os".to_vec())),
+            in_memory_bytecode: Some(Cow::from(b"bytecode".to_vec())),
+            ..Resource::default()
+        };
+
+        let mut data = vec![];
+        write_packed_resources_v3(&[resource], &mut data, None)?;
+
+        Ok(data)
+    }
+
+    fn signing_key() -> Result<PackedResourcesSigningKey> {
+        let pkcs8 =
+            Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).map_err(|e| anyhow!("{}", e))?;
+
+        PackedResourcesSigningKey::from_pkcs8(pkcs8.as_ref())
+    }
+
+    #[test]
+    fn test_no_trailer() -> Result<()> {
+        let data = sample_data()?;
+
+        assert_eq!(read_integrity_trailer(&data), Ok(None));
+        assert_eq!(
+            verify_integrity(&data, None),
+            Err("packed resources data has no integrity trailer")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsigned_trailer() -> Result<()> {
+        let original = sample_data()?;
+        let mut data = original.clone();
+        write_integrity_trailer(&mut data, None)?;
+
+        let trailer = read_integrity_trailer(&data).unwrap().unwrap();
+        assert_eq!(trailer.section_digests.len(), 3);
+        assert_eq!(
+            trailer.signature_algorithm,
+            IntegritySignatureAlgorithm::None
+        );
+        assert!(trailer.signature.is_empty());
+
+        assert_eq!(verify_integrity(&data, None), Ok(()));
+
+        let key = signing_key()?;
+        assert_eq!(
+            verify_integrity(&data, Some(key.public_key())),
+            Err("packed resources data is not signed")
+        );
+
+        // The parser ignores the trailer.
+        let resources = load_resources(&data)
+            .unwrap()
+            .collect::<Result<Vec<_>, &'static str>>()
+            .unwrap();
+        assert_eq!(resources.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_signed_trailer() -> Result<()> {
+        let key = signing_key()?;
+        let mut data = sample_data()?;
+        write_integrity_trailer(&mut data, Some(&key))?;
+
+        assert_eq!(verify_integrity(&data, Some(key.public_key())), Ok(()));
+
+        let other = signing_key()?;
+        assert_eq!(
+            verify_integrity(&data, Some(other.public_key())),
+            Err("packed resources signature verification failed")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tampered_data() -> Result<()> {
+        let key = signing_key()?;
+        let mut data = sample_data()?;
+        let original_length = data.len();
+        write_integrity_trailer(&mut data, Some(&key))?;
+
+        // Modify the last byte of blob data.
+        let mut tampered = data.clone();
+        tampered[original_length - 1] ^= 0xff;
+        assert_eq!(
+            verify_integrity(&tampered, Some(key.public_key())),
+            Err("packed resources digest mismatch")
+        );
+
+        // Modify a digest in the trailer.
+        let mut tampered = data.clone();
+        tampered[original_length + INTEGRITY_TRAILER_MAGIC.len() + 1] ^= 0xff;
+        assert_eq!(
+            verify_integrity(&tampered, Some(key.public_key())),
+            Err("packed resources signature verification failed")
+        );
+
+        // Truncate the signature.
+        assert_eq!(
+            verify_integrity(&data[0..data.len() - 1], Some(key.public_key())),
+            Err("integrity signature length mismatch")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_twice() -> Result<()> {
+        let mut data = sample_data()?;
+        write_integrity_trailer(&mut data, None)?;
+        assert!(write_integrity_trailer(&mut data, None).is_err());
+
+        Ok(())
+    }
+}
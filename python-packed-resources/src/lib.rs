@@ -29,14 +29,24 @@ it can be used outside the PyOxidizer project. See the aforementioned docs
 for the canonical specification of this format.
 */
 
+mod compression;
+#[cfg(feature = "integrity")]
+mod integrity;
 mod parser;
 mod resource;
 mod serialization;
 mod writer;
 
+#[cfg(feature = "integrity")]
+pub use crate::integrity::{
+    read_integrity_trailer, verify_integrity, write_integrity_trailer,
+    IntegritySignatureAlgorithm, IntegrityTrailer, PackedResourcesSigningKey,
+    INTEGRITY_TRAILER_MAGIC,
+};
 pub use crate::{
-    parser::{load_resources, ResourceParserIterator},
+    compression::{compress_data, decompress_data, CompressionPolicy},
+    parser::{load_resources, ResourceNameIndex, ResourceParserIterator},
     resource::Resource,
-    serialization::HEADER_V3,
-    writer::write_packed_resources_v3,
+    serialization::{CompressionCodec, ResourceField, HEADER_V3, HEADER_V4},
+    writer::{write_packed_resources_v3, write_packed_resources_v4},
 };
@@ -11,10 +11,18 @@
 use {
     crate::{
         resource::Resource,
-        serialization::{BlobInteriorPadding, BlobSectionField, ResourceField, HEADER_V3},
+        serialization::{
+            BlobInteriorPadding, BlobSectionField, CompressionCodec, ResourceField, HEADER_V3,
+            HEADER_V4,
+        },
     },
     byteorder::{LittleEndian, ReadBytesExt},
-    std::{borrow::Cow, collections::HashMap, io::Cursor, path::Path},
+    std::{
+        borrow::Cow,
+        collections::{BTreeMap, HashMap},
+        io::Cursor,
+        path::Path,
+    },
 };
 
 #[cfg(unix)]
@@ -48,6 +56,10 @@ pub struct ResourceParserIterator<'a> {
     blob_sections: [Option<BlobSectionReadState>; 256],
     claimed_resources_count: usize,
     read_resources_count: usize,
+    version_4: bool,
+    blob_start_offset: usize,
+    blob_section_lengths: Vec<usize>,
+    name_index: Option<ResourceNameIndex<'a>>,
 }
 
 impl<'a> ResourceParserIterator<'a> {
@@ -56,13 +68,46 @@ impl<'a> ResourceParserIterator<'a> {
         self.claimed_resources_count
     }
 
+    /// Resolve the ranges of each blob section within the original data.
+    ///
+    /// Ranges are relative to the start of the data passed to [load_resources],
+    /// including its 8 byte header. They are emitted in blob index order and
+    /// the first range starts where the index data ends.
+    pub fn blob_section_ranges(&self) -> Vec<std::ops::Range<usize>> {
+        let mut offset = self.blob_start_offset + 8;
+
+        self.blob_section_lengths
+            .iter()
+            .map(|length| {
+                let range = offset..offset + length;
+                offset += length;
+                range
+            })
+            .collect()
+    }
+
+    /// The offset within the original data where blob data ends.
+    ///
+    /// Any data past this offset isn't part of the resources data.
+    pub fn blob_end_offset(&self) -> usize {
+        self.blob_start_offset + 8 + self.blob_section_lengths.iter().sum::<usize>()
+    }
+
+    /// Obtain the sorted index of resource names, if the data has one.
+    ///
+    /// The index can be used to find and parse individual resources without
+    /// iterating.
+    pub fn name_index(&self) -> Option<&ResourceNameIndex<'a>> {
+        self.name_index.as_ref()
+    }
+
     /// Resolve a slice to an individual blob's data.
     ///
     /// This accepts a reference to the original blobs payload, an array of
     /// current blob section offsets, the resource field being accessed, and the
     /// length of the blob and returns a slice to that blob.
     fn resolve_blob_data(&mut self, resource_field: ResourceField, length: usize) -> &'a [u8] {
-        let mut state = self.blob_sections[resource_field as usize]
+        let state = self.blob_sections[resource_field as usize]
             .as_mut()
             .expect("blob state not found");
 
@@ -485,11 +530,239 @@ impl<'a> ResourceParserIterator<'a> {
                         std::str::from_utf8_unchecked(self.resolve_blob_data(field_type, l))
                     }));
                 }
+
+                ResourceField::NameIndex => {
+                    return Err("name index field not allowed in resource entry");
+                }
+
+                ResourceField::IsTombstone => {
+                    if !self.version_4 {
+                        return Err("tombstone requires packed resources version 4");
+                    }
+
+                    current_resource.is_tombstone = true;
+                }
+
+                ResourceField::FieldCompression => {
+                    if !self.version_4 {
+                        return Err("field compression requires packed resources version 4");
+                    }
+
+                    let field = self
+                        .reader
+                        .read_u8()
+                        .map_err(|_| "failed reading field compression resource field")?;
+                    let field = ResourceField::try_from(field)?;
+
+                    if !field.is_compressible() {
+                        return Err("field compression defined for incompressible field");
+                    }
+
+                    let codec = self
+                        .reader
+                        .read_u8()
+                        .map_err(|_| "failed reading field compression codec")?;
+                    let codec = CompressionCodec::try_from(codec)?;
+
+                    current_resource
+                        .field_compression
+                        .get_or_insert_with(BTreeMap::new)
+                        .insert(field, codec);
+                }
             }
         }
     }
 }
 
+/// A sorted index of resource names within packed resources data.
+///
+/// Each entry records where a resource's index entry and blob data begin. This
+/// allows a resource to be found via binary search and parsed on its own, without
+/// parsing the resources preceding it.
+///
+/// Entry offsets and names are validated when the index is loaded: names must be
+/// in bounds, valid UTF-8, and sorted. Lookups therefore only parse the record of
+/// the requested resource.
+#[derive(Clone, Debug)]
+pub struct ResourceNameIndex<'a> {
+    data: &'a [u8],
+    entries: &'a [u8],
+    entry_count: usize,
+    entry_length: usize,
+    /// Blob sections described by each entry, in blob index order.
+    ///
+    /// Holds the resource field, start offset, and interior padding of each section.
+    sections: Vec<(u8, usize, BlobInteriorPadding)>,
+    /// Offset within `sections` of the resource names section.
+    name_section: usize,
+    resources_index_offset: usize,
+    version_4: bool,
+}
+
+impl<'a> ResourceNameIndex<'a> {
+    /// The number of resources in the index.
+    pub fn len(&self) -> usize {
+        self.entry_count
+    }
+
+    /// Whether the index is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entry_count == 0
+    }
+
+    /// Obtain the raw entry at a position.
+    ///
+    /// The position must be less than `entry_count`, whose product with
+    /// `entry_length` was validated when the index was loaded.
+    fn entry(&self, index: usize) -> &'a [u8] {
+        let start = index * self.entry_length;
+
+        &self.entries[start..start + self.entry_length]
+    }
+
+    fn entry_u32(entry: &[u8], offset: usize) -> usize {
+        u32::from_le_bytes([
+            entry[offset],
+            entry[offset + 1],
+            entry[offset + 2],
+            entry[offset + 3],
+        ]) as usize
+    }
+
+    fn name_bytes(&self, index: usize) -> Result<&'a [u8], &'static str> {
+        let entry = self.entry(index);
+        let length = u16::from_le_bytes([entry[4], entry[5]]) as usize;
+
+        let start = self.sections[self.name_section]
+            .1
+            .checked_add(Self::entry_u32(entry, 6 + 4 * self.name_section))
+            .ok_or("name index entry out of bounds")?;
+        let end = start
+            .checked_add(length)
+            .ok_or("name index entry out of bounds")?;
+
+        self.data
+            .get(start..end)
+            .ok_or("name index entry out of bounds")
+    }
+
+    /// Validate names of all entries.
+    ///
+    /// Names must be in bounds, valid UTF-8, and strictly increasing so binary
+    /// search works.
+    fn validate_names(&self) -> Result<(), &'static str> {
+        let mut previous: Option<&[u8]> = None;
+
+        for index in 0..self.entry_count {
+            let name = self.name_bytes(index)?;
+
+            if std::str::from_utf8(name).is_err() {
+                return Err("name index entry is not valid UTF-8");
+            }
+
+            if let Some(previous) = previous {
+                if previous >= name {
+                    return Err("name index entries are not sorted");
+                }
+            }
+
+            previous = Some(name);
+        }
+
+        Ok(())
+    }
+
+    /// Obtain the name of the resource at a position in the index.
+    ///
+    /// Entries are sorted by name.
+    pub fn name(&self, index: usize) -> Result<&'a str, &'static str> {
+        if index >= self.entry_count {
+            return Err("name index position out of range");
+        }
+
+        std::str::from_utf8(self.name_bytes(index)?)
+            .map_err(|_| "name index entry is not valid UTF-8")
+    }
+
+    /// Find the position of a named resource in the index.
+    pub fn position(&self, name: &str) -> Result<Option<usize>, &'static str> {
+        let name = name.as_bytes();
+
+        let mut low = 0;
+        let mut high = self.entry_count;
+
+        while low < high {
+            let middle = low + (high - low) / 2;
+
+            match self.name_bytes(middle)?.cmp(name) {
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+                std::cmp::Ordering::Equal => return Ok(Some(middle)),
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Parse the resource at a position in the index.
+    pub fn resource(&self, index: usize) -> Result<Resource<'a, u8>, &'static str> {
+        if index >= self.entry_count {
+            return Err("name index position out of range");
+        }
+
+        let entry = self.entry(index);
+
+        let mut blob_sections = [None; 256];
+        for (i, (field, start, padding)) in self.sections.iter().enumerate() {
+            blob_sections[*field as usize] = Some(BlobSectionReadState {
+                offset: start
+                    .checked_add(Self::entry_u32(entry, 6 + 4 * i))
+                    .ok_or("name index entry out of bounds")?,
+                interior_padding: *padding,
+            });
+        }
+
+        let index_offset = self
+            .resources_index_offset
+            .checked_add(Self::entry_u32(entry, 0))
+            .ok_or("name index entry out of bounds")?;
+        if index_offset >= self.data.len() {
+            return Err("name index entry out of bounds");
+        }
+
+        let mut reader = Cursor::new(self.data);
+        reader.set_position(index_offset as u64);
+
+        let mut parser = ResourceParserIterator {
+            done: false,
+            data: self.data,
+            reader,
+            blob_sections,
+            claimed_resources_count: 1,
+            read_resources_count: 0,
+            version_4: self.version_4,
+            blob_start_offset: 0,
+            blob_section_lengths: vec![],
+            name_index: None,
+        };
+
+        match parser.parse_next()? {
+            Some(resource) if resource.name.as_bytes() == self.name_bytes(index)? => Ok(resource),
+            _ => Err("name index entry does not match resource"),
+        }
+    }
+
+    /// Find and parse a named resource.
+    ///
+    /// Returns `Ok(None)` if the resource isn't in the index.
+    pub fn find(&self, name: &str) -> Result<Option<Resource<'a, u8>>, &'static str> {
+        match self.position(name)? {
+            Some(index) => Ok(Some(self.resource(index)?)),
+            None => Ok(None),
+        }
+    }
+}
+
 impl<'a> Iterator for ResourceParserIterator<'a> {
     type Item = Result<Resource<'a, u8>, &'static str>;
 
@@ -521,13 +794,19 @@ pub fn load_resources<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>,
     let header = &data[0..8];
 
     if header == HEADER_V3 {
-        load_resources_v3(&data[8..])
+        load_resources_v3(&data[8..], false)
+    } else if header == HEADER_V4 {
+        // Version 4 is version 3 plus field compression and the name index.
+        load_resources_v3(&data[8..], true)
     } else {
         Err("unrecognized file format")
     }
 }
 
-fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &'static str> {
+fn load_resources_v3<'a>(
+    data: &'a [u8],
+    version_4: bool,
+) -> Result<ResourceParserIterator<'a>, &'static str> {
     let mut reader = Cursor::new(data);
 
     let blob_section_count = reader
@@ -628,18 +907,82 @@ fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &
     // Current offset from start of blobs data.
     let mut current_blob_offset = 0;
 
+    let mut name_index_range = None;
+    let mut indexed_sections = Vec::with_capacity(blob_sections.len());
+
     for section in &blob_sections {
         let section_start_offset = blob_start_offset + current_blob_offset;
+        let interior_padding = match section.interior_padding {
+            Some(padding) => padding,
+            None => BlobInteriorPadding::None,
+        };
+
+        if section.resource_field == u8::from(ResourceField::NameIndex) {
+            name_index_range =
+                Some(section_start_offset..section_start_offset + section.raw_payload_length);
+        } else {
+            indexed_sections.push((
+                section.resource_field,
+                section_start_offset,
+                interior_padding,
+            ));
+        }
+
         blob_offsets[section.resource_field as usize] = Some(BlobSectionReadState {
             offset: section_start_offset,
-            interior_padding: match section.interior_padding {
-                Some(padding) => padding,
-                None => BlobInteriorPadding::None,
-            },
+            interior_padding,
         });
         current_blob_offset += section.raw_payload_length;
     }
 
+    let name_index = if let Some(range) = name_index_range {
+        if !version_4 {
+            return Err("name index requires packed resources version 4");
+        }
+
+        let entries = data.get(range).ok_or("name index out of bounds")?;
+        if entries.len() < 4 {
+            return Err("name index too short");
+        }
+
+        let entry_count = u32::from_le_bytes([entries[0], entries[1], entries[2], entries[3]]);
+        let entry_count = entry_count as usize;
+        if entry_count != resources_count {
+            return Err("mismatch between name index count and resources count");
+        }
+
+        let entry_length = 4 + 2 + 4 * indexed_sections.len();
+        let entries = &entries[4..];
+        if Some(entries.len()) != entry_count.checked_mul(entry_length) {
+            return Err("name index length mismatch");
+        }
+
+        let name_section = indexed_sections
+            .iter()
+            .position(|(field, _, _)| *field == u8::from(ResourceField::Name));
+        let name_section = match name_section {
+            Some(position) => position,
+            None if entry_count == 0 => 0,
+            None => return Err("name index requires resource names blob section"),
+        };
+
+        let index = ResourceNameIndex {
+            data,
+            entries,
+            entry_count,
+            entry_length,
+            sections: indexed_sections,
+            name_section,
+            resources_index_offset: 1 + 4 + 4 + 4 + blob_index_length,
+            version_4,
+        };
+        index.validate_names()?;
+
+        Some(index)
+    } else {
+        None
+    };
+
     Ok(ResourceParserIterator {
         done: resources_index_length == 0 || resources_count == 0,
         data,
@@ -647,6 +990,13 @@ fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &
         blob_sections: blob_offsets,
         claimed_resources_count: resources_count,
         read_resources_count: 0,
+        version_4,
+        blob_start_offset,
+        blob_section_lengths: blob_sections
+            .iter()
+            .map(|section| section.raw_payload_length)
+            .collect(),
+        name_index,
     })
 }
 
@@ -655,8 +1005,10 @@ mod tests {
     use {
         super::*,
         crate::{
-            resource::Resource, serialization::BlobInteriorPadding,
-            writer::write_packed_resources_v3,
+            compression::CompressionPolicy,
+            resource::Resource,
+            serialization::BlobInteriorPadding,
+            writer::{write_packed_resources_v3, write_packed_resources_v4},
         },
     };
 
@@ -674,7 +1026,7 @@ mod tests {
         let res = load_resources(data);
         assert_eq!(res.err(), Some("unrecognized file format"));
 
-        let data = b"pyembed\x04";
+        let data = b"pyembed\x05";
         let res = load_resources(data);
         assert_eq!(res.err(), Some("unrecognized file format"));
     }
@@ -1405,6 +1757,8 @@ mod tests {
             file_executable: true,
             file_data_embedded: Some(Cow::from(b"file_data_embedded".to_vec())),
             file_data_utf8_relative_path: Some(Cow::from("file_data_utf8_relative_path")),
+            field_compression: None,
+            is_tombstone: false,
         };
 
         let mut data = Vec::new();
@@ -1547,4 +1901,312 @@ mod tests {
 
         assert_eq!(resources, loaded);
     }
+
+    #[test]
+    fn test_v4_uncompressed() {
+        let resources: Vec<Resource<u8>> = vec![Resource {
+            name: Cow::from("foo"),
+            is_python_module: true,
+            in_memory_source: Some(Cow::from(b"import io".to_vec())),
+            ..Resource::default()
+        }];
+
+        let mut data = Vec::new();
+        write_packed_resources_v4(&resources, &mut data, None, CompressionPolicy::None, false)
+            .unwrap();
+        assert_eq!(&data[0..8], b"pyembed\x04");
+
+        let loaded = load_resources(&data)
+            .unwrap()
+            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .unwrap();
+
+        assert_eq!(resources, loaded);
+    }
+
+    #[test]
+    #[cfg(all(feature = "zstd", feature = "lz4"))]
+    fn test_v4_compressed_fields() {
+        let source = b"import os\nimport sys\n".repeat(32);
+        let bytecode = b"fake bytecode ".repeat(32);
+        let resource_data = b"resource data ".repeat(32);
+
+        let mut package_resources = HashMap::new();
+        package_resources.insert(Cow::from("resource.txt"), Cow::from(resource_data.clone()));
+        package_resources.insert(Cow::from("tiny.txt"), Cow::from(b"tiny".to_vec()));
+
+        let resources: Vec<Resource<u8>> = vec![
+            Resource {
+                name: Cow::from("foo"),
+                is_python_module: true,
+                in_memory_source: Some(Cow::from(source.clone())),
+                in_memory_bytecode: Some(Cow::from(bytecode.clone())),
+                in_memory_package_resources: Some(package_resources),
+                ..Resource::default()
+            },
+            Resource {
+                name: Cow::from("bar"),
+                is_python_module: true,
+                in_memory_source: Some(Cow::from(b"import io".to_vec())),
+                ..Resource::default()
+            },
+        ];
+
+        for policy in [CompressionPolicy::Zstd, CompressionPolicy::Lz4] {
+            let mut data = Vec::new();
+            write_packed_resources_v4(&resources, &mut data, None, policy, false).unwrap();
+
+            let loaded = load_resources(&data)
+                .unwrap()
+                .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+                .unwrap();
+
+            assert_eq!(loaded.len(), 2);
+
+            let entry = &loaded[0];
+            let codec = policy
+                .resolve_codec(ResourceField::InMemorySource, source.len())
+                .unwrap();
+            assert_eq!(
+                entry.field_compression(ResourceField::InMemorySource),
+                Some(codec)
+            );
+            assert_eq!(
+                entry.field_compression(ResourceField::InMemoryBytecode),
+                Some(codec)
+            );
+            assert_eq!(
+                entry.field_compression(ResourceField::InMemoryResourcesData),
+                Some(codec)
+            );
+
+            let stored = entry.in_memory_source.as_ref().unwrap();
+            assert!(stored.len() < source.len());
+            assert_eq!(
+                entry
+                    .resolve_field_data(ResourceField::InMemorySource, stored)
+                    .unwrap()
+                    .as_ref(),
+                source.as_slice()
+            );
+
+            let decompressed = entry.to_decompressed().unwrap();
+            assert_eq!(decompressed.field_compression, None);
+            assert_eq!(decompressed, resources[0]);
+
+            // Small payloads aren't compressed.
+            let entry = &loaded[1];
+            assert_eq!(entry.field_compression, None);
+            assert_eq!(entry, &resources[1]);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn test_v3_rejects_compressed_fields() {
+        let mut resource = Resource {
+            name: Cow::from("foo"),
+            in_memory_source: Some(Cow::from(b"import io".to_vec())),
+            ..Resource::default()
+        };
+        resource
+            .compress_field(ResourceField::InMemorySource, CompressionCodec::Zstd)
+            .unwrap();
+
+        let mut data = Vec::new();
+        assert!(write_packed_resources_v3(&[resource.clone()], &mut data, None).is_err());
+
+        // Field compression in a version 3 payload is rejected by the parser.
+        let mut data = Vec::new();
+        write_packed_resources_v4(&[resource], &mut data, None, CompressionPolicy::None, false)
+            .unwrap();
+        data[7] = 0x03;
+
+        let mut res = load_resources(&data).unwrap();
+        assert_eq!(
+            res.next(),
+            Some(Err("field compression requires packed resources version 4"))
+        );
+    }
+
+    #[test]
+    fn test_tombstone() {
+        let resource = Resource {
+            name: Cow::from("foo"),
+            is_tombstone: true,
+            ..Resource::default()
+        };
+
+        let mut data = Vec::new();
+        assert!(
+            write_packed_resources_v3(std::slice::from_ref(&resource), &mut data, None).is_err()
+        );
+
+        let mut data = Vec::new();
+        write_packed_resources_v4(
+            std::slice::from_ref(&resource),
+            &mut data,
+            None,
+            CompressionPolicy::None,
+            false,
+        )
+        .unwrap();
+
+        let loaded = load_resources(&data)
+            .unwrap()
+            .collect::<Result<Vec<_>, &'static str>>()
+            .unwrap();
+        assert_eq!(loaded, vec![resource]);
+
+        // Tombstones in a version 3 payload are rejected by the parser.
+        data[7] = 0x03;
+
+        let mut res = load_resources(&data).unwrap();
+        assert_eq!(
+            res.next(),
+            Some(Err("tombstone requires packed resources version 4"))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn test_name_index() {
+        let mut package_resources = HashMap::new();
+        package_resources.insert(Cow::from("resource.txt"), Cow::from(b"data".to_vec()));
+
+        // Resources aren't written in name order, so the index must be sorted.
+        let resources: Vec<Resource<u8>> = vec![
+            Resource {
+                name: Cow::from("foo"),
+                is_python_module: true,
+                in_memory_source: Some(Cow::from(b"import io".to_vec())),
+                in_memory_bytecode: Some(Cow::from(b"foo bytecode".to_vec())),
+                ..Resource::default()
+            },
+            Resource {
+                name: Cow::from("bar"),
+                is_python_module: true,
+                is_python_package: true,
+                in_memory_package_resources: Some(package_resources),
+                ..Resource::default()
+            },
+            Resource {
+                name: Cow::from("baz"),
+                is_python_module: true,
+                in_memory_bytecode: Some(Cow::from(b"baz bytecode".repeat(64))),
+                ..Resource::default()
+            },
+        ];
+
+        for (padding, policy) in [
+            (None, CompressionPolicy::None),
+            (Some(BlobInteriorPadding::Null), CompressionPolicy::None),
+            (None, CompressionPolicy::Zstd),
+        ] {
+            let mut data = Vec::new();
+            write_packed_resources_v4(&resources, &mut data, padding, policy, true).unwrap();
+
+            let parser = load_resources(&data).unwrap();
+            let index = parser.name_index().unwrap().clone();
+
+            assert_eq!(index.len(), 3);
+            assert_eq!(index.name(0), Ok("bar"));
+            assert_eq!(index.name(1), Ok("baz"));
+            assert_eq!(index.name(2), Ok("foo"));
+            assert!(index.name(3).is_err());
+
+            assert_eq!(index.position("foo"), Ok(Some(2)));
+            assert_eq!(index.position("missing"), Ok(None));
+            assert_eq!(index.find("missing"), Ok(None));
+
+            // Parsing the index doesn't interfere with iteration.
+            let loaded = parser
+                .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+                .unwrap();
+            assert_eq!(loaded.len(), 3);
+
+            for resource in &resources {
+                let found = index.find(&resource.name).unwrap().unwrap();
+                assert_eq!(&found.to_decompressed().unwrap(), resource);
+            }
+        }
+    }
+
+    #[test]
+    fn test_name_index_version() {
+        let resources: Vec<Resource<u8>> = vec![Resource {
+            name: Cow::from("foo"),
+            ..Resource::default()
+        }];
+
+        let mut data = Vec::new();
+        write_packed_resources_v4(&resources, &mut data, None, CompressionPolicy::None, false)
+            .unwrap();
+        assert!(load_resources(&data).unwrap().name_index().is_none());
+
+        let mut data = Vec::new();
+        write_packed_resources_v4(&resources, &mut data, None, CompressionPolicy::None, true)
+            .unwrap();
+        data[7] = 0x03;
+        assert_eq!(
+            load_resources(&data).err(),
+            Some("name index requires packed resources version 4")
+        );
+    }
+
+    #[test]
+    fn test_name_index_invalid_utf8() {
+        let resources: Vec<Resource<u8>> = vec![Resource {
+            name: Cow::from("foo"),
+            ..Resource::default()
+        }];
+
+        let mut data = Vec::new();
+        write_packed_resources_v4(&resources, &mut data, None, CompressionPolicy::None, true)
+            .unwrap();
+
+        let offset = data.windows(3).position(|w| w == b"foo").unwrap();
+        data[offset] = 0xff;
+
+        assert_eq!(
+            load_resources(&data).err(),
+            Some("name index entry is not valid UTF-8")
+        );
+    }
+
+    #[test]
+    fn test_name_index_duplicate_names() {
+        let resource = Resource::<u8> {
+            name: Cow::from("foo"),
+            ..Resource::default()
+        };
+
+        let mut data = Vec::new();
+        assert!(write_packed_resources_v4(
+            &[resource.clone(), resource],
+            &mut data,
+            None,
+            CompressionPolicy::None,
+            true
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_name_index_name_too_long() {
+        let resources: Vec<Resource<u8>> = vec![Resource {
+            name: Cow::from("a".repeat(u16::MAX as usize + 1)),
+            ..Resource::default()
+        }];
+
+        let mut data = Vec::new();
+        let err =
+            write_packed_resources_v4(&resources, &mut data, None, CompressionPolicy::None, true)
+                .unwrap_err();
+
+        assert_eq!(
+            format!("{:#}", err),
+            "converting resource name length to u16: out of range integral type conversion attempted"
+        );
+    }
 }
@@ -6,7 +6,17 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::{borrow::Cow, collections::HashMap, path::Path};
+use {
+    crate::{
+        compression::{compress_data, decompress_data, CompressionPolicy},
+        serialization::{CompressionCodec, ResourceField},
+    },
+    std::{
+        borrow::Cow,
+        collections::{BTreeMap, HashMap},
+        path::Path,
+    },
+};
 
 /// Represents an indexed resource.
 ///
@@ -115,6 +125,20 @@ where
 
     /// Holds arbitrary file data in a relative path encoded in UTF-8.
     pub file_data_utf8_relative_path: Option<Cow<'a, str>>,
+
+    /// Compression codecs applied to data fields.
+    ///
+    /// If a field is present in this mapping, data for that field is stored
+    /// compressed with the given codec. For fields holding a mapping of names
+    /// to data (e.g. package resources), each data value is compressed
+    /// individually and names are stored as-is.
+    pub field_compression: Option<BTreeMap<ResourceField, CompressionCodec>>,
+
+    /// Whether this resource is a tombstone.
+    ///
+    /// A tombstone denotes that a resource with this name from a lower priority
+    /// source should be ignored. Tombstones carry no data of their own.
+    pub is_tombstone: bool,
 }
 
 impl<'a, X> Default for Resource<'a, X>
@@ -151,6 +175,8 @@ where
             file_executable: false,
             file_data_embedded: None,
             file_data_utf8_relative_path: None,
+            field_compression: None,
+            is_tombstone: false,
         }
     }
 }
@@ -170,12 +196,20 @@ where
 {
     /// Merge another resource into this one.
     ///
-    /// Fields from other will overwrite fields from self.
+    /// Fields from other will overwrite fields from self. The compression codec
+    /// of each data field follows the data, so a field replaced by other is
+    /// compressed only if it is compressed in other.
     pub fn merge_from(&mut self, other: Resource<'a, X>) -> Result<(), &'static str> {
         if self.name != other.name {
             return Err("resource names must be identical to perform a merge");
         }
 
+        let replaced_fields = COMPRESSIBLE_FIELDS
+            .iter()
+            .copied()
+            .filter(|field| other.has_field_data(*field))
+            .collect::<Vec<_>>();
+
         self.is_python_module |= other.is_python_module;
         self.is_python_builtin_extension_module |= other.is_python_builtin_extension_module;
         self.is_python_frozen_module |= other.is_python_frozen_module;
@@ -243,6 +277,28 @@ where
         if let Some(value) = other.file_data_utf8_relative_path {
             self.file_data_utf8_relative_path.replace(value);
         }
+        for field in replaced_fields {
+            match other
+                .field_compression
+                .as_ref()
+                .and_then(|fields| fields.get(&field))
+            {
+                Some(codec) => {
+                    self.field_compression
+                        .get_or_insert_with(BTreeMap::new)
+                        .insert(field, *codec);
+                }
+                None => {
+                    if let Some(fields) = self.field_compression.as_mut() {
+                        fields.remove(&field);
+                    }
+                }
+            }
+        }
+        if matches!(&self.field_compression, Some(fields) if fields.is_empty()) {
+            self.field_compression = None;
+        }
+        self.is_tombstone |= other.is_tombstone;
 
         Ok(())
     }
@@ -303,7 +359,7 @@ where
                 },
             ),
             in_memory_shared_library: self
-                .in_memory_source
+                .in_memory_shared_library
                 .as_ref()
                 .map(|value| Cow::Owned(value.clone().into_owned())),
             shared_library_dependency_names: self.shared_library_dependency_names.as_ref().map(
@@ -370,6 +426,270 @@ where
                 .file_data_utf8_relative_path
                 .as_ref()
                 .map(|value| Cow::Owned(value.clone().into_owned())),
+            field_compression: self.field_compression.clone(),
+            is_tombstone: self.is_tombstone,
+        }
+    }
+
+    /// Obtain the compression codec applied to a field, if any.
+    pub fn field_compression(&self, field: ResourceField) -> Option<CompressionCodec> {
+        self.field_compression
+            .as_ref()
+            .and_then(|fields| fields.get(&field).copied())
+    }
+
+    /// Whether any field in this resource holds compressed data.
+    pub fn has_compressed_fields(&self) -> bool {
+        matches!(&self.field_compression, Some(fields) if !fields.is_empty())
+    }
+
+    /// Whether a compressible field holds data.
+    fn has_field_data(&self, field: ResourceField) -> bool {
+        match field {
+            ResourceField::InMemorySource => self.in_memory_source.is_some(),
+            ResourceField::InMemoryBytecode => self.in_memory_bytecode.is_some(),
+            ResourceField::InMemoryBytecodeOpt1 => self.in_memory_bytecode_opt1.is_some(),
+            ResourceField::InMemoryBytecodeOpt2 => self.in_memory_bytecode_opt2.is_some(),
+            ResourceField::InMemoryExtensionModuleSharedLibrary => {
+                self.in_memory_extension_module_shared_library.is_some()
+            }
+            ResourceField::InMemoryResourcesData => self.in_memory_package_resources.is_some(),
+            ResourceField::InMemoryDistributionResource => {
+                self.in_memory_distribution_resources.is_some()
+            }
+            ResourceField::InMemorySharedLibrary => self.in_memory_shared_library.is_some(),
+            ResourceField::FileDataEmbedded => self.file_data_embedded.is_some(),
+            _ => false,
+        }
+    }
+}
+
+impl<'a> Resource<'a, u8> {
+    /// Resolve data belonging to a field, decompressing it if necessary.
+    ///
+    /// `data` is a value of `field` from this resource. If the field isn't
+    /// compressed, the data is returned as-is without copying.
+    pub fn resolve_field_data<'b>(
+        &self,
+        field: ResourceField,
+        data: &'b [u8],
+    ) -> Result<Cow<'b, [u8]>, &'static str> {
+        match self.field_compression(field) {
+            Some(codec) => Ok(Cow::Owned(decompress_data(codec, data)?)),
+            None => Ok(Cow::Borrowed(data)),
+        }
+    }
+
+    /// Compress data in a field using the specified codec.
+    ///
+    /// Existing field data is replaced by its compressed form. Fields that are
+    /// already compressed or that don't have data are left untouched.
+    pub fn compress_field(
+        &mut self,
+        field: ResourceField,
+        codec: CompressionCodec,
+    ) -> Result<(), &'static str> {
+        if !field.is_compressible() {
+            return Err("resource field cannot be compressed");
+        }
+
+        if self.field_compression(field).is_some() {
+            return Ok(());
+        }
+
+        let values = self.field_values_mut(field);
+        if values.is_empty() {
+            return Ok(());
         }
+
+        for value in values {
+            *value = Cow::Owned(compress_data(codec, value)?);
+        }
+
+        self.field_compression
+            .get_or_insert_with(BTreeMap::new)
+            .insert(field, codec);
+
+        Ok(())
+    }
+
+    /// Compress all data fields according to a compression policy.
+    ///
+    /// The codec for each field is chosen by the policy from the type and size of
+    /// the field's data. Compressed data is only retained if it is smaller than the
+    /// original.
+    pub fn compress_with_policy(&mut self, policy: CompressionPolicy) -> Result<(), &'static str> {
+        for field in COMPRESSIBLE_FIELDS {
+            if self.field_compression(*field).is_some() {
+                continue;
+            }
+
+            let mut values = self.field_values_mut(*field);
+            let length = values.iter().map(|value| value.len()).sum::<usize>();
+
+            let codec = match policy.resolve_codec(*field, length) {
+                Some(codec) => codec,
+                None => continue,
+            };
+
+            let compressed = values
+                .iter()
+                .map(|value| compress_data(codec, value))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            if compressed.iter().map(|value| value.len()).sum::<usize>() >= length {
+                continue;
+            }
+
+            for (value, data) in values.iter_mut().zip(compressed) {
+                **value = Cow::Owned(data);
+            }
+
+            self.field_compression
+                .get_or_insert_with(BTreeMap::new)
+                .insert(*field, codec);
+        }
+
+        Ok(())
+    }
+
+    /// Obtain a copy of this resource with all field data decompressed.
+    pub fn to_decompressed(&self) -> Result<Resource<'static, u8>, &'static str> {
+        let mut resource = self.to_owned();
+
+        if let Some(fields) = resource.field_compression.take() {
+            for (field, codec) in fields {
+                for value in resource.field_values_mut(field) {
+                    *value = Cow::Owned(decompress_data(codec, value)?);
+                }
+            }
+        }
+
+        Ok(resource)
+    }
+
+    /// Obtain mutable references to every data value in a compressible field.
+    fn field_values_mut(&mut self, field: ResourceField) -> Vec<&mut Cow<'a, [u8]>> {
+        match field {
+            ResourceField::InMemorySource => self.in_memory_source.iter_mut().collect(),
+            ResourceField::InMemoryBytecode => self.in_memory_bytecode.iter_mut().collect(),
+            ResourceField::InMemoryBytecodeOpt1 => {
+                self.in_memory_bytecode_opt1.iter_mut().collect()
+            }
+            ResourceField::InMemoryBytecodeOpt2 => {
+                self.in_memory_bytecode_opt2.iter_mut().collect()
+            }
+            ResourceField::InMemoryExtensionModuleSharedLibrary => self
+                .in_memory_extension_module_shared_library
+                .iter_mut()
+                .collect(),
+            ResourceField::InMemoryResourcesData => self
+                .in_memory_package_resources
+                .iter_mut()
+                .flat_map(|resources| resources.values_mut())
+                .collect(),
+            ResourceField::InMemoryDistributionResource => self
+                .in_memory_distribution_resources
+                .iter_mut()
+                .flat_map(|resources| resources.values_mut())
+                .collect(),
+            ResourceField::InMemorySharedLibrary => {
+                self.in_memory_shared_library.iter_mut().collect()
+            }
+            ResourceField::FileDataEmbedded => self.file_data_embedded.iter_mut().collect(),
+            _ => vec![],
+        }
+    }
+}
+
+/// Resource fields whose data can be compressed.
+const COMPRESSIBLE_FIELDS: &[ResourceField] = &[
+    ResourceField::InMemorySource,
+    ResourceField::InMemoryBytecode,
+    ResourceField::InMemoryBytecodeOpt1,
+    ResourceField::InMemoryBytecodeOpt2,
+    ResourceField::InMemoryExtensionModuleSharedLibrary,
+    ResourceField::InMemoryResourcesData,
+    ResourceField::InMemoryDistributionResource,
+    ResourceField::InMemorySharedLibrary,
+    ResourceField::FileDataEmbedded,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn test_merge_uncompressed_over_compressed() -> Result<(), &'static str> {
+        let source = b"import os\nimport sys\n".repeat(64);
+
+        let mut base = Resource::<u8> {
+            name: "foo".into(),
+            is_python_module: true,
+            in_memory_source: Some(source.clone().into()),
+            in_memory_bytecode: Some(source.clone().into()),
+            ..Default::default()
+        };
+        base.compress_field(ResourceField::InMemorySource, CompressionCodec::Zstd)?;
+        base.compress_field(ResourceField::InMemoryBytecode, CompressionCodec::Zstd)?;
+
+        let overlay = Resource::<u8> {
+            name: "foo".into(),
+            is_python_module: true,
+            in_memory_source: Some(b"import io".to_vec().into()),
+            ..Default::default()
+        };
+
+        base.merge_from(overlay)?;
+
+        assert_eq!(base.field_compression(ResourceField::InMemorySource), None);
+        assert_eq!(
+            base.field_compression(ResourceField::InMemoryBytecode),
+            Some(CompressionCodec::Zstd)
+        );
+
+        let resolved = base.to_decompressed()?;
+        assert_eq!(
+            resolved.in_memory_source,
+            Some(b"import io".to_vec().into())
+        );
+        assert_eq!(resolved.in_memory_bytecode, Some(source.into()));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn test_merge_compressed_over_uncompressed() -> Result<(), &'static str> {
+        let source = b"import os\nimport sys\n".repeat(64);
+
+        let mut base = Resource::<u8> {
+            name: "foo".into(),
+            is_python_module: true,
+            in_memory_source: Some(b"import io".to_vec().into()),
+            ..Default::default()
+        };
+
+        let mut overlay = Resource::<u8> {
+            name: "foo".into(),
+            is_python_module: true,
+            in_memory_source: Some(source.clone().into()),
+            ..Default::default()
+        };
+        overlay.compress_field(ResourceField::InMemorySource, CompressionCodec::Zstd)?;
+
+        base.merge_from(overlay)?;
+
+        assert_eq!(
+            base.field_compression(ResourceField::InMemorySource),
+            Some(CompressionCodec::Zstd)
+        );
+        assert_eq!(
+            base.to_decompressed()?.in_memory_source,
+            Some(source.into())
+        );
+
+        Ok(())
     }
 }
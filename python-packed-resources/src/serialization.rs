@@ -8,9 +8,12 @@
 
 /*! Declares the foundational data primitives inside packed resources data. */
 
-/// Header value for version 2 of resources payload.
+/// Header value for version 3 of resources payload.
 pub const HEADER_V3: &[u8] = b"pyembed\x03";
 
+/// Header value for version 4 of resources payload.
+pub const HEADER_V4: &[u8] = b"pyembed\x04";
+
 /// Defines interior padding mechanism between entries in blob sections.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum BlobInteriorPadding {
@@ -109,6 +112,9 @@ pub enum ResourceField {
     FileExecutable = 0x1c,
     FileDataEmbedded = 0x1d,
     FileDataUtf8RelativePath = 0x1e,
+    FieldCompression = 0x1f,
+    NameIndex = 0x20,
+    IsTombstone = 0x21,
 }
 
 impl From<ResourceField> for u8 {
@@ -144,6 +150,9 @@ impl From<ResourceField> for u8 {
             ResourceField::FileExecutable => 0x1c,
             ResourceField::FileDataEmbedded => 0x1d,
             ResourceField::FileDataUtf8RelativePath => 0x1e,
+            ResourceField::FieldCompression => 0x1f,
+            ResourceField::NameIndex => 0x20,
+            ResourceField::IsTombstone => 0x21,
             ResourceField::EndOfEntry => 0xff,
         }
     }
@@ -184,8 +193,69 @@ impl TryFrom<u8> for ResourceField {
             0x1c => Ok(ResourceField::FileExecutable),
             0x1d => Ok(ResourceField::FileDataEmbedded),
             0x1e => Ok(ResourceField::FileDataUtf8RelativePath),
+            0x1f => Ok(ResourceField::FieldCompression),
+            0x20 => Ok(ResourceField::NameIndex),
+            0x21 => Ok(ResourceField::IsTombstone),
             0xff => Ok(ResourceField::EndOfEntry),
             _ => Err("invalid field type"),
         }
     }
 }
+
+impl ResourceField {
+    /// Whether data for this field can be stored compressed.
+    ///
+    /// Only fields holding raw binary payloads are compressible. Names and
+    /// paths are always stored as-is so they can be referenced without copying.
+    pub fn is_compressible(&self) -> bool {
+        matches!(
+            self,
+            ResourceField::InMemorySource
+                | ResourceField::InMemoryBytecode
+                | ResourceField::InMemoryBytecodeOpt1
+                | ResourceField::InMemoryBytecodeOpt2
+                | ResourceField::InMemoryExtensionModuleSharedLibrary
+                | ResourceField::InMemoryResourcesData
+                | ResourceField::InMemoryDistributionResource
+                | ResourceField::InMemorySharedLibrary
+                | ResourceField::FileDataEmbedded
+        )
+    }
+}
+
+/// Describes a compression codec applied to resource field data.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub enum CompressionCodec {
+    /// Zstandard.
+    ///
+    /// Data is a single zstd frame with the content size recorded in the
+    /// frame header.
+    Zstd = 0x01,
+
+    /// LZ4.
+    ///
+    /// Data is a single LZ4 block prefixed by a little-endian `u32` holding
+    /// the decompressed size.
+    Lz4 = 0x02,
+}
+
+impl From<CompressionCodec> for u8 {
+    fn from(codec: CompressionCodec) -> Self {
+        match codec {
+            CompressionCodec::Zstd => 0x01,
+            CompressionCodec::Lz4 => 0x02,
+        }
+    }
+}
+
+impl TryFrom<u8> for CompressionCodec {
+    type Error = &'static str;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(CompressionCodec::Zstd),
+            0x02 => Ok(CompressionCodec::Lz4),
+            _ => Err("invalid compression codec"),
+        }
+    }
+}
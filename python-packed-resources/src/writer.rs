@@ -10,8 +10,11 @@
 
 use {
     crate::{
+        compression::CompressionPolicy,
         resource::Resource,
-        serialization::{BlobInteriorPadding, BlobSectionField, ResourceField, HEADER_V3},
+        serialization::{
+            BlobInteriorPadding, BlobSectionField, ResourceField, HEADER_V3, HEADER_V4,
+        },
     },
     anyhow::{anyhow, Context, Result},
     byteorder::{LittleEndian, WriteBytesExt},
@@ -115,9 +118,11 @@ where
 {
     /// Whether the resource is meaningful.
     ///
-    /// The resource is meaningful if it has data attached or is a package.
+    /// The resource is meaningful if it has data attached, is a package, or
+    /// is a tombstone.
     pub fn is_meaningful(&self) -> bool {
-        self.is_python_package
+        self.is_tombstone
+            || self.is_python_package
             || self.is_python_namespace_package
             || self.in_memory_source.is_some()
             || self.in_memory_bytecode.is_some()
@@ -264,6 +269,15 @@ where
             index += 5;
         }
 
+        if let Some(fields) = &self.field_compression {
+            // Field + resource field value + codec value.
+            index += 3 * fields.len();
+        }
+
+        if self.is_tombstone {
+            index += 1;
+        }
+
         // End of index entry.
         index += 1;
 
@@ -426,6 +440,9 @@ where
                     0
                 }
             }
+            ResourceField::FieldCompression => 0,
+            ResourceField::NameIndex => 0,
+            ResourceField::IsTombstone => 0,
         }
     }
 
@@ -575,6 +592,9 @@ where
                     0
                 }
             }
+            ResourceField::FieldCompression => 0,
+            ResourceField::NameIndex => 0,
+            ResourceField::IsTombstone => 0,
         };
 
         let overhead = match padding {
@@ -853,6 +873,22 @@ where
                 .context("writing file_data_utf_relative_path field")?;
         }
 
+        if let Some(fields) = &self.field_compression {
+            for (field, codec) in fields {
+                dest.write_u8(ResourceField::FieldCompression.into())
+                    .context("writing field compression field")?;
+                dest.write_u8((*field).into())
+                    .context("writing field compression resource field")?;
+                dest.write_u8((*codec).into())
+                    .context("writing field compression codec")?;
+            }
+        }
+
+        if self.is_tombstone {
+            dest.write_u8(ResourceField::IsTombstone.into())
+                .context("writing is_tombstone field")?;
+        }
+
         dest.write_u8(ResourceField::EndOfEntry.into())
             .map_err(|_| anyhow!("error writing end of index entry"))?;
 
@@ -861,11 +897,141 @@ where
 }
 
 /// Write packed resources data, version 3.
-#[allow(clippy::cognitive_complexity)]
+///
+/// Version 3 cannot represent compressed field data or tombstones. An error
+/// occurs if any resource has compressed fields or is a tombstone.
 pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
     resources: &[T],
     dest: &mut W,
     interior_padding: Option<BlobInteriorPadding>,
+) -> Result<()> {
+    if let Some(resource) = resources
+        .iter()
+        .find(|r| r.as_ref().has_compressed_fields())
+    {
+        return Err(anyhow!(
+            "resource {} has compressed fields, which require packed resources version 4",
+            resource.as_ref().name
+        ));
+    }
+
+    if let Some(resource) = resources.iter().find(|r| r.as_ref().is_tombstone) {
+        return Err(anyhow!(
+            "resource {} is a tombstone, which requires packed resources version 4",
+            resource.as_ref().name
+        ));
+    }
+
+    write_packed_resources(HEADER_V3, resources, dest, interior_padding, false)
+}
+
+/// Write packed resources data, version 4.
+///
+/// Field data is compressed according to `compression`. The policy chooses a codec
+/// for each field of each resource. Fields that are already compressed are written
+/// as-is.
+///
+/// If `name_index` is true, a sorted index of resource names is written so
+/// individual resources can be found and parsed without parsing the resources
+/// preceding them. Resource names must be unique for the index to be written.
+pub fn write_packed_resources_v4<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
+    resources: &[T],
+    dest: &mut W,
+    interior_padding: Option<BlobInteriorPadding>,
+    compression: CompressionPolicy,
+    name_index: bool,
+) -> Result<()> {
+    if compression == CompressionPolicy::None {
+        return write_packed_resources(HEADER_V4, resources, dest, interior_padding, name_index);
+    }
+
+    let resources = resources
+        .iter()
+        .map(|resource| {
+            let mut resource = resource.as_ref().clone();
+            resource
+                .compress_with_policy(compression)
+                .map_err(|e| anyhow!("compressing resource {}: {}", resource.name, e))?;
+
+            Ok(resource)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    write_packed_resources(HEADER_V4, &resources, dest, interior_padding, name_index)
+}
+
+/// Compute the content of the name index blob section.
+///
+/// `sections` are the blob sections holding resource data, in blob index order.
+fn name_index_data<'a, T: AsRef<Resource<'a, u8>>>(
+    resources: &[T],
+    sections: &[&BlobSection],
+    padding: BlobInteriorPadding,
+) -> Result<Vec<u8>> {
+    let mut entries = Vec::with_capacity(resources.len());
+
+    // Offset of the current resource's entry in the resources index.
+    let mut index_offset = 0;
+    // Offsets of the current resource's data within each blob section.
+    let mut section_offsets = vec![0; sections.len()];
+
+    for resource in resources {
+        let resource = resource.as_ref();
+
+        entries.push((
+            resource.name.as_ref(),
+            index_offset,
+            section_offsets.clone(),
+        ));
+
+        index_offset += resource.index_v1_length();
+
+        for (offset, section) in section_offsets.iter_mut().zip(sections) {
+            *offset += resource.field_blob_length(section.resource_field)
+                + resource.field_blob_interior_padding_length(section.resource_field, padding);
+        }
+    }
+
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+
+    if let Some(pair) = entries.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+        return Err(anyhow!(
+            "cannot write name index: duplicate resource name {}",
+            pair[0].0
+        ));
+    }
+
+    let mut data = Vec::with_capacity(4 + entries.len() * (4 + 2 + 4 * sections.len()));
+
+    data.write_u32::<LittleEndian>(
+        u32::try_from(entries.len()).context("converting name index entry count to u32")?,
+    )?;
+
+    for (name, index_offset, section_offsets) in entries {
+        data.write_u32::<LittleEndian>(
+            u32::try_from(index_offset).context("converting name index entry offset to u32")?,
+        )?;
+        data.write_u16::<LittleEndian>(
+            u16::try_from(name.len()).context("converting resource name length to u16")?,
+        )?;
+
+        for offset in section_offsets {
+            data.write_u32::<LittleEndian>(
+                u32::try_from(offset).context("blob section too large for name index")?,
+            )?;
+        }
+    }
+
+    Ok(data)
+}
+
+#[allow(clippy::cognitive_complexity)]
+fn write_packed_resources<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
+    header: &[u8],
+    resources: &[T],
+    dest: &mut W,
+    interior_padding: Option<BlobInteriorPadding>,
+    name_index: bool,
 ) -> Result<()> {
     let mut blob_sections = BTreeMap::new();
 
@@ -999,12 +1165,35 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
         );
     }
 
+    // The name index is the last blob section. It describes all blob sections
+    // preceding it.
+    let name_index = if name_index {
+        let data = name_index_data(
+            resources,
+            &blob_sections.values().collect::<Vec<_>>(),
+            interior_padding.unwrap_or(BlobInteriorPadding::None),
+        )?;
+
+        blob_sections.insert(
+            ResourceField::NameIndex,
+            BlobSection {
+                resource_field: ResourceField::NameIndex,
+                raw_payload_length: data.len(),
+                interior_padding: None,
+            },
+        );
+
+        Some(data)
+    } else {
+        None
+    };
+
     for section in blob_sections.values() {
         blob_section_count += 1;
         blob_index_length += section.index_v1_length();
     }
 
-    dest.write_all(HEADER_V3)?;
+    dest.write_all(header)?;
 
     dest.write_u8(blob_section_count)?;
     dest.write_u32::<LittleEndian>(blob_index_length as u32)?;
@@ -1176,6 +1365,10 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
         }
     }
 
+    if let Some(data) = name_index {
+        dest.write_all(&data)?;
+    }
+
     Ok(())
 }
 